@@ -0,0 +1,515 @@
+use crate::render::{Agent, Renderer};
+use ash::{vk, Device};
+use compute_context::barrier::{self, Access};
+use compute_context::blit::{self, Letterbox};
+use compute_context::buffer::Buffer;
+use compute_context::commands::CommandPool;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::presenter::Presenter;
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use std::{mem, slice};
+use winit::window::Window;
+
+// Number of agents simulated, each checking every other one per step
+const AGENT_COUNT: u32 = 8192;
+
+// Must match local_size_x in boids.comp
+const WORKGROUP_SIZE: u32 = 64;
+
+// Seed of the starting positions and headings, which any value scatters differently
+const SEED: u32 = 0x9e37_79b9;
+
+// Speed agents start at, within the limits boids.comp keeps them to
+const START_SPEED: f32 = 0.3;
+
+// The world is the square from -1 to 1, drawn at this resolution in the middle of the window
+const WORLD_SIZE: u32 = 1024;
+
+// Color around the world when the window isn't square, kept apart from its background
+const BAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+// Two vec2s of f32s, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Agent {}
+
+// Laid out to match the compute shader's push constant block
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Step {
+    count: u32,
+    dt: f32,
+    target: [f32; 2],
+    attraction: f32,
+}
+
+// u32 and f32s, each 4 byte aligned, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Step {}
+
+// Window's Vulkan state and the flock drawn with it
+pub struct Boids {
+    flock: Flock,
+    renderer: Renderer,
+    presenter: Presenter,
+    context: ComputeContext,
+    letterbox: Letterbox,
+    // Cursor in world coordinates, and how strongly it pulls the flock
+    target: [f32; 2],
+    attraction: f32,
+}
+
+impl Boids {
+    pub fn new(window: &Window) -> Result<Boids, ComputeError> {
+        let size = window.inner_size();
+        let context = ComputeContext::new(window, "Boids")?;
+        let mut presenter = Presenter::new(&context, size.width, size.height)?;
+        let device = context.device();
+
+        let mut renderer =
+            Boids::create_renderer(&context).inspect_err(|_| presenter.destroy(device))?;
+        let flock =
+            Flock::new(&context, AGENT_COUNT, presenter.frames_in_flight()).inspect_err(|_| {
+                renderer.destroy(device);
+                presenter.destroy(device);
+            })?;
+
+        Ok(Boids {
+            flock,
+            renderer,
+            presenter,
+            context,
+            letterbox: Letterbox {
+                bar_color: BAR_COLOR,
+                ..Letterbox::new(WORLD_SIZE, WORLD_SIZE)
+            },
+            target: [0.0, 0.0],
+            attraction: 0.0,
+        })
+    }
+
+    // Rebuilds the swapchain for the new window size
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), ComputeError> {
+        self.presenter.resize(&self.context, width, height)
+    }
+
+    // Moves the point the flock is drawn to, from a cursor position in the window's physical pixels
+    pub fn set_cursor(&mut self, window: &Window, x: f64, y: f64) {
+        let size = window.inner_size();
+        let area = self.letterbox.fit(vk::Extent2D {
+            width: size.width,
+            height: size.height,
+        });
+        self.target = window_to_world(x, y, area);
+    }
+
+    // Pull towards the cursor per unit of distance, where negative values push the flock away and 0 lets it be
+    pub fn set_attraction(&mut self, attraction: f32) {
+        self.attraction = attraction;
+    }
+
+    // Advances the flock by a step of dt seconds on the compute queue and draws it
+    pub fn draw(&mut self, dt: f32) -> Result<(), ComputeError> {
+        let flock = &mut self.flock;
+        let renderer = &self.renderer;
+        let letterbox = &self.letterbox;
+        let slot = self.presenter.current_frame();
+        let step = Step {
+            count: flock.count,
+            dt,
+            target: self.target,
+            attraction: self.attraction,
+        };
+
+        // The draw reads the instances as vertices, which the compute submission writes
+        let waits = [(
+            flock.steps_finished[slot],
+            vk::PipelineStageFlags::VERTEX_INPUT,
+        )];
+        let mut submitted = Ok(());
+        self.presenter
+            .draw_waiting(&self.context, &waits, |device, command_buffer, frame| {
+                submitted = flock.submit_step(device, slot, &step);
+                renderer.record_draw(
+                    device,
+                    command_buffer,
+                    flock.instances[slot].handle(),
+                    flock.count,
+                );
+                blit::record_letterboxed_blit(
+                    device,
+                    command_buffer,
+                    renderer.image(),
+                    renderer.extent(),
+                    frame,
+                    letterbox,
+                    vk::Filter::LINEAR,
+                );
+            })?;
+
+        submitted
+    }
+
+    fn create_renderer(context: &ComputeContext) -> Result<Renderer, ComputeError> {
+        let compiler = ShaderCompiler::new()?;
+        let vertex_code = compiler.compile(
+            include_str!("shaders/boids.vert"),
+            ShaderKind::Vertex,
+            "boids.vert",
+            "boids vertex",
+        )?;
+        let fragment_code = compiler.compile(
+            include_str!("shaders/boids.frag"),
+            ShaderKind::Fragment,
+            "boids.frag",
+            "boids fragment",
+        )?;
+
+        Renderer::new(
+            context,
+            vk::Extent2D {
+                width: WORLD_SIZE,
+                height: WORLD_SIZE,
+            },
+            &vertex_code,
+            &fragment_code,
+        )
+    }
+}
+
+impl Drop for Boids {
+    fn drop(&mut self) {
+        // Both queues may still be using the flock - if the device was lost there is nothing left to wait for
+        let device = self.context.device();
+        unsafe { device.device_wait_idle().ok() };
+        self.flock.destroy(device);
+        self.renderer.destroy(device);
+        self.presenter.destroy(device);
+    }
+}
+
+// Agents stepped on the compute queue while the graphics queue draws earlier frames, as async compute
+// The simulation ping-pongs between two storage buffers only the compute queue touches, and each frame's last state is
+// copied into that frame in flight's own instance buffer, so no step can overwrite what a frame still being drawn reads
+// The graphics submission waits on a semaphore for the copy, and the frame's fence, which the next compute submission
+// for the same slot comes after, covers the other direction
+struct Flock {
+    count: u32,
+    // Simulation state, exclusive to the compute family
+    agents: Vec<Buffer>,
+    // Pipeline i reads agents[i] and writes the other buffer, so neither descriptor set ever changes
+    steps: Vec<ComputePipeline<Step>>,
+    // Index of the agents buffer holding the latest state
+    current: usize,
+    // Vertex buffer per frame in flight, shared concurrently by the compute and graphics families
+    instances: Vec<Buffer>,
+    // Signalled by each frame in flight's compute submission, waited on by its draw
+    steps_finished: Vec<vk::Semaphore>,
+    // Command buffer per frame in flight on the compute family, None until created
+    command_pool: Option<CommandPool>,
+    queue: vk::Queue,
+}
+
+impl Flock {
+    fn new(
+        context: &ComputeContext,
+        count: u32,
+        frames_in_flight: usize,
+    ) -> Result<Flock, ComputeError> {
+        let mut flock = Flock {
+            count,
+            agents: Vec::with_capacity(2),
+            steps: Vec::with_capacity(2),
+            current: 0,
+            instances: Vec::with_capacity(frames_in_flight),
+            steps_finished: Vec::with_capacity(frames_in_flight),
+            command_pool: None,
+            queue: context.compute_queue(),
+        };
+
+        // Whatever was created before a failure is held by the flock, so it can be cleaned up as a whole
+        flock
+            .create(context, frames_in_flight)
+            .inspect_err(|_| flock.destroy(context.device()))?;
+
+        Ok(flock)
+    }
+
+    // Submits a step to the compute queue followed by a copy of the result into slot's instances, signalling
+    // steps_finished[slot] once done
+    // The previous frame in slot must have finished, which drawing it waited for
+    fn submit_step(
+        &mut self,
+        device: &Device,
+        slot: usize,
+        step: &Step,
+    ) -> Result<(), ComputeError> {
+        let command_pool = self.command_pool.as_ref().unwrap();
+        let current = self.current;
+        let next = 1 - current;
+
+        let command_buffer = command_pool.record(device, slot, |command_buffer| {
+            // The step before this one read next and copied from it, both of which have to finish first
+            barrier::record_memory_barrier(
+                device,
+                command_buffer,
+                Access::COMPUTE_READ.and(Access::TRANSFER_READ),
+                Access::COMPUTE_WRITE,
+            );
+
+            self.steps[current].dispatch(
+                device,
+                command_buffer,
+                [pipeline::group_count(step.count, WORKGROUP_SIZE), 1, 1],
+                step,
+            );
+
+            // Read by the copy into the instances, and by the next step
+            barrier::record_memory_barrier(
+                device,
+                command_buffer,
+                Access::COMPUTE_WRITE,
+                Access::TRANSFER_READ.and(Access::COMPUTE_READ),
+            );
+
+            let copy = vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size: self.instances[slot].size(),
+            };
+            unsafe {
+                device.cmd_copy_buffer(
+                    command_buffer,
+                    self.agents[next].handle(),
+                    self.instances[slot].handle(),
+                    slice::from_ref(&copy),
+                )
+            };
+        })?;
+
+        let signal_semaphores = [self.steps_finished[slot]];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(slice::from_ref(&command_buffer))
+            .signal_semaphores(&signal_semaphores);
+        unsafe {
+            device.queue_submit(self.queue, slice::from_ref(&submit_info), vk::Fence::null())?
+        };
+
+        self.current = next;
+        Ok(())
+    }
+
+    // Destroys the pipelines, buffers, semaphores, and command pool - must be called before the context is dropped
+    fn destroy(&mut self, device: &Device) {
+        for step in self.steps.iter_mut() {
+            step.destroy(device);
+        }
+        for buffer in self.agents.iter_mut().chain(self.instances.iter_mut()) {
+            buffer.destroy(device);
+        }
+        for semaphore in self.steps_finished.iter() {
+            unsafe { device.destroy_semaphore(*semaphore, None) };
+        }
+        if let Some(mut command_pool) = self.command_pool.take() {
+            command_pool.destroy(device);
+        }
+        self.steps.clear();
+        self.agents.clear();
+        self.instances.clear();
+        self.steps_finished.clear();
+    }
+
+    fn create(
+        &mut self,
+        context: &ComputeContext,
+        frames_in_flight: usize,
+    ) -> Result<(), ComputeError> {
+        let device = context.device();
+        let size = (self.count as usize * mem::size_of::<Agent>()) as vk::DeviceSize;
+
+        for _ in 0..2 {
+            self.agents.push(Buffer::new(
+                context,
+                size,
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?);
+        }
+
+        // Concurrent, as a new copy lands in each every few frames, which would otherwise need an ownership transfer
+        // each time
+        let families = [context.queue_family_index(), context.compute_family_index()];
+        for _ in 0..frames_in_flight {
+            self.instances.push(Buffer::new_shared(
+                context,
+                size,
+                vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                &families,
+            )?);
+            self.steps_finished.push(unsafe {
+                device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?
+            });
+        }
+
+        self.command_pool = Some(CommandPool::new(
+            device,
+            context.compute_family_index(),
+            frames_in_flight as u32,
+        )?);
+
+        let code = ShaderCompiler::new()?.compile(
+            include_str!("shaders/boids.comp"),
+            ShaderKind::Compute,
+            "boids.comp",
+            "boids",
+        )?;
+
+        for (current, next) in [(0, 1), (1, 0)].iter() {
+            let bindings = [
+                Binding::StorageBuffer(self.agents[*current].handle()),
+                Binding::StorageBuffer(self.agents[*next].handle()),
+            ];
+            self.steps
+                .push(ComputePipeline::new(device, &code, &bindings)?);
+        }
+
+        self.seed(context)
+    }
+
+    // Uploads the starting flock to the current agents buffer through a staging buffer, on the compute queue
+    fn seed(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let device = context.device();
+        let agents = spawn(self.count, SEED);
+
+        let mut staging_buffer = Buffer::new(
+            context,
+            self.agents[self.current].size(),
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let destination = &self.agents[self.current];
+        let result = staging_buffer.write(device, &agents).and_then(|()| {
+            self.command_pool
+                .as_ref()
+                .unwrap()
+                .submit_once(device, self.queue, |command_buffer| {
+                    let copy = vk::BufferCopy {
+                        src_offset: 0,
+                        dst_offset: 0,
+                        size: destination.size(),
+                    };
+                    unsafe {
+                        device.cmd_copy_buffer(
+                            command_buffer,
+                            staging_buffer.handle(),
+                            destination.handle(),
+                            slice::from_ref(&copy),
+                        )
+                    };
+
+                    // Makes the upload visible to the first step
+                    barrier::record_memory_barrier(
+                        device,
+                        command_buffer,
+                        Access::TRANSFER_WRITE,
+                        Access::COMPUTE_READ,
+                    );
+                })
+        });
+
+        // The submission is waited on, so the staging buffer is no longer in use either way
+        staging_buffer.destroy(device);
+        result
+    }
+}
+
+// count agents scattered over the world from seed, all at START_SPEED in random directions
+fn spawn(count: u32, seed: u32) -> Vec<Agent> {
+    // Uniform value in [0, 1) from the index and which of its values is wanted
+    let random = |index: u32, value: u32| {
+        (hash(hash(index.wrapping_mul(3).wrapping_add(value)) ^ seed) >> 8) as f32 / 16_777_216.0
+    };
+
+    (0..count)
+        .map(|index| {
+            let angle = random(index, 2) * std::f32::consts::TAU;
+            Agent {
+                position: [random(index, 0) * 2.0 - 1.0, random(index, 1) * 2.0 - 1.0],
+                velocity: [angle.cos() * START_SPEED, angle.sin() * START_SPEED],
+            }
+        })
+        .collect()
+}
+
+// Integer hash scattering the starting flock, so it doesn't depend on a random number crate
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+// World coordinates from -1 to 1 of a point in the window, given the letterboxed area the world is drawn in
+// Vulkan's y points down, as the window's does, so neither axis is flipped
+fn window_to_world(x: f64, y: f64, area: vk::Rect2D) -> [f32; 2] {
+    let to_world = |position: f64, offset: i32, length: u32| {
+        ((position - offset as f64) / length.max(1) as f64 * 2.0 - 1.0) as f32
+    };
+
+    [
+        to_world(x, area.offset.x, area.extent.width),
+        to_world(y, area.offset.y, area.extent.height),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+
+    #[test]
+    fn corners_of_the_area_are_corners_of_the_world() {
+        let area = area(100, 0, 600, 600);
+
+        assert_eq!(window_to_world(100.0, 0.0, area), [-1.0, -1.0]);
+        assert_eq!(window_to_world(700.0, 600.0, area), [1.0, 1.0]);
+        assert_eq!(window_to_world(400.0, 300.0, area), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn bars_are_outside_the_world() {
+        let [x, _] = window_to_world(50.0, 300.0, area(100, 0, 600, 600));
+
+        assert!(x < -1.0);
+    }
+
+    #[test]
+    fn spawned_agents_start_inside_the_world_at_the_same_speed() {
+        let agents = spawn(1000, SEED);
+
+        assert_eq!(agents.len(), 1000);
+        for agent in agents.iter() {
+            assert!(agent.position.iter().all(|p| (-1.0..1.0).contains(p)));
+            let speed = agent.velocity[0].hypot(agent.velocity[1]);
+            assert!((speed - START_SPEED).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn spawning_depends_on_the_seed() {
+        assert_eq!(spawn(16, SEED), spawn(16, SEED));
+        assert_ne!(spawn(16, SEED), spawn(16, SEED + 1));
+    }
+}
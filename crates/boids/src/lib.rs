@@ -0,0 +1,96 @@
+mod flock;
+mod render;
+
+use compute_context::compute_errors::ComputeError;
+use flock::Boids;
+use std::time::Instant;
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, MouseButton, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// Longest step taken, so a stalled frame (e.g. while the window is dragged) doesn't fling the flock across the world
+const MAX_STEP_SECONDS: f32 = 1.0 / 30.0;
+
+// Pull of the cursor on the flock while the left button is held, pushing it away instead while the right one is
+const ATTRACTION: f32 = 1.5;
+
+pub fn run() {
+    let event_loop = EventLoop::new();
+
+    // Creates a window using a WindowBuilder
+    let window = WindowBuilder::new()
+        .with_title("Boids")
+        .with_inner_size(LogicalSize::new(800, 800))
+        .build(&event_loop)
+        .expect("Could not create a window!");
+
+    // Creates all the vulkan state, including the buffers holding the flock
+    let mut boids = match Boids::new(&window) {
+        Ok(boids) => boids,
+        Err(error) => {
+            eprintln!("Vulkan error: {}", error);
+            return;
+        }
+    };
+
+    let mut last_frame = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        // Continually runs the event loop - presentation is paced by FIFO
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                println!("Close button was pressed");
+                *control_flow = ControlFlow::Exit;
+            }
+            // A minimized window has a zero sized surface, so the old swapchain is kept until it is restored
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } if size.width > 0 && size.height > 0 => {
+                exit_on_error(boids.resize(size.width, size.height), control_flow)
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => boids.set_cursor(&window, position.x, position.y),
+            // Holding a button pulls the flock towards the cursor or pushes it away, until it is let go
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => match (state, button) {
+                (ElementState::Pressed, MouseButton::Left) => boids.set_attraction(ATTRACTION),
+                (ElementState::Pressed, MouseButton::Right) => boids.set_attraction(-ATTRACTION),
+                (ElementState::Released, _) => boids.set_attraction(0.0),
+                _ => (),
+            },
+            // Steps the simulation by the time since the last frame and presents it
+            Event::MainEventsCleared => {
+                let size = window.inner_size();
+                let now = Instant::now();
+                let dt = (now - last_frame).as_secs_f32().min(MAX_STEP_SECONDS);
+                last_frame = now;
+
+                if size.width > 0 && size.height > 0 {
+                    exit_on_error(boids.draw(dt), control_flow);
+                }
+            }
+            _ => (),
+        }
+    })
+}
+
+// Reports a Vulkan failure and exits, as the simulation can't carry on without it
+fn exit_on_error(result: Result<(), ComputeError>, control_flow: &mut ControlFlow) {
+    if let Err(error) = result {
+        eprintln!("Vulkan error: {}", error);
+        *control_flow = ControlFlow::Exit;
+    }
+}
@@ -0,0 +1,375 @@
+use ash::{vk, Device};
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use std::{ffi::CString, mem, slice};
+
+// SRGB, so the fragment shader's linear colors are encoded for display the same way the swapchain would encode them
+const FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+// Color of the world behind the flock
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// Agents read as per instance vertex attributes by boids.vert, which turns each into a triangle facing its velocity
+// Laid out to match struct Agent in boids.comp
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Agent {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+}
+
+// Color image the flock is drawn into at the world's resolution, ready to be letterboxed into the window
+// Presented frames are only ever written by transfers, so the flock is drawn here first and blitted from GENERAL layout
+// The render pass orders each draw after the previous frame's blit and before the next one, so frames in flight can
+// share the one image
+pub struct Renderer {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    extent: vk::Extent2D,
+}
+
+impl Renderer {
+    // Creates a target of extent and a pipeline drawing agents with the compiled boids.vert and boids.frag
+    pub fn new(
+        context: &ComputeContext,
+        extent: vk::Extent2D,
+        vertex_code: &[u32],
+        fragment_code: &[u32],
+    ) -> Result<Renderer, ComputeError> {
+        let mut renderer = Renderer {
+            image: vk::Image::null(),
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            render_pass: vk::RenderPass::null(),
+            framebuffer: vk::Framebuffer::null(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            extent,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        renderer
+            .create(context, vertex_code, fragment_code)
+            .inspect_err(|_| renderer.destroy(context.device()))?;
+
+        Ok(renderer)
+    }
+
+    // Image holding the last frame drawn, in GENERAL layout with the draw's writes visible to transfers
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Records clearing the image and drawing count agents from instances as one triangle each
+    pub fn record_draw(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        instances: vk::Buffer,
+        count: u32,
+    ) {
+        let clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: BACKGROUND_COLOR,
+            },
+        };
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D::default(),
+                extent: self.extent,
+            })
+            .clear_values(slice::from_ref(&clear_value));
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, slice::from_ref(&instances), &[0]);
+            device.cmd_draw(command_buffer, 3, count, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    // Destroys the pipeline, render pass, and image - the device must be done with them
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+        self.pipeline = vk::Pipeline::null();
+        self.layout = vk::PipelineLayout::null();
+        self.framebuffer = vk::Framebuffer::null();
+        self.render_pass = vk::RenderPass::null();
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self.memory = vk::DeviceMemory::null();
+    }
+
+    fn create(
+        &mut self,
+        context: &ComputeContext,
+        vertex_code: &[u32],
+        fragment_code: &[u32],
+    ) -> Result<(), ComputeError> {
+        self.create_image(context)?;
+        self.create_render_pass(context.device())?;
+
+        let attachments = [self.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(self.render_pass)
+            .attachments(&attachments)
+            .width(self.extent.width)
+            .height(self.extent.height)
+            .layers(1);
+        self.framebuffer = unsafe {
+            context
+                .device()
+                .create_framebuffer(&framebuffer_info, None)?
+        };
+
+        self.create_pipeline(context.device(), vertex_code, fragment_code)
+    }
+
+    fn create_image(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let device = context.device();
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(FORMAT)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        let requirements = unsafe { device.get_image_memory_requirements(self.image) };
+        self.memory =
+            context.allocate_memory(&requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+        unsafe { device.bind_image_memory(self.image, self.memory, 0)? };
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(FORMAT)
+            .subresource_range(compute_context::barrier::color_range());
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        Ok(())
+    }
+
+    // Single subpass clearing and drawing to the image, which is left in GENERAL for blitting from
+    fn create_render_pass(&mut self, device: &Device) -> Result<(), ComputeError> {
+        // The old contents are cleared anyway, so they are discarded
+        let attachment = vk::AttachmentDescription::builder()
+            .format(FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::GENERAL);
+        let color_reference = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(slice::from_ref(&color_reference));
+
+        let dependencies = [
+            // The previous frame's blit has to finish reading the image before it is cleared
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dependency_flags: vk::DependencyFlags::empty(),
+            },
+            // This frame's blit reads what was drawn
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                dependency_flags: vk::DependencyFlags::empty(),
+            },
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(slice::from_ref(&attachment))
+            .subpasses(slice::from_ref(&subpass))
+            .dependencies(&dependencies);
+        self.render_pass = unsafe { device.create_render_pass(&render_pass_info, None)? };
+
+        Ok(())
+    }
+
+    fn create_pipeline(
+        &mut self,
+        device: &Device,
+        vertex_code: &[u32],
+        fragment_code: &[u32],
+    ) -> Result<(), ComputeError> {
+        // Neither module is needed once the pipeline is made, so both go whether or not that worked
+        let vertex_module = create_shader_module(device, vertex_code)?;
+        let fragment_module = create_shader_module(device, fragment_code)
+            .inspect_err(|_| unsafe { device.destroy_shader_module(vertex_module, None) })?;
+
+        let result = self.create_pipeline_from(device, vertex_module, fragment_module);
+
+        unsafe {
+            device.destroy_shader_module(fragment_module, None);
+            device.destroy_shader_module(vertex_module, None);
+        }
+        result
+    }
+
+    fn create_pipeline_from(
+        &mut self,
+        device: &Device,
+        vertex_module: vk::ShaderModule,
+        fragment_module: vk::ShaderModule,
+    ) -> Result<(), ComputeError> {
+        let entry_name = CString::new("main").unwrap();
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vertex_module)
+                .name(&entry_name)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fragment_module)
+                .name(&entry_name)
+                .build(),
+        ];
+
+        // Agents are read once per instance, with their position at location 0 and velocity at location 1
+        let binding = vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: mem::size_of::<Agent>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        };
+        let attributes = [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: 0,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: mem::size_of::<[f32; 2]>() as u32,
+            },
+        ];
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(slice::from_ref(&binding))
+            .vertex_attribute_descriptions(&attributes);
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        // The whole image shows the world from -1 to 1, and never changes size
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.extent.width as f32,
+            height: self.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: self.extent,
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice::from_ref(&viewport))
+            .scissors(slice::from_ref(&scissor));
+
+        // Triangles face whichever way their agent heads, so none are culled
+        let rasterization = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1.0);
+
+        let multisample = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+        let blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all());
+        let color_blend = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice::from_ref(&blend_attachment));
+
+        self.layout = unsafe {
+            device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo::default(), None)?
+        };
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization)
+            .multisample_state(&multisample)
+            .color_blend_state(&color_blend)
+            .layout(self.layout)
+            .render_pass(self.render_pass)
+            .subpass(0);
+
+        self.pipeline = unsafe {
+            device
+                .create_graphics_pipelines(
+                    vk::PipelineCache::null(),
+                    slice::from_ref(&pipeline_info),
+                    None,
+                )
+                .map_err(|(_, result)| result)?[0]
+        };
+
+        Ok(())
+    }
+}
+
+fn create_shader_module(device: &Device, code: &[u32]) -> Result<vk::ShaderModule, ComputeError> {
+    let shader_module_info = vk::ShaderModuleCreateInfo::builder().code(code);
+
+    Ok(unsafe { device.create_shader_module(&shader_module_info, None)? })
+}
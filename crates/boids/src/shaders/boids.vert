@@ -0,0 +1,29 @@
+#version 460
+
+// Read per instance, so every triangle is one agent
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 velocity;
+
+layout(location = 0) out vec3 fragColor;
+
+// Half the length of each agent, in the [-1, 1] world the letterboxed viewport shows
+const float AGENT_SIZE = 0.012;
+
+// Arrowhead pointing along +x, rotated to face the agent's heading
+const vec2 SHAPE[3] = vec2[](
+    vec2(1.0, 0.0),
+    vec2(-0.7, 0.5),
+    vec2(-0.7, -0.5)
+);
+
+void main() {
+    float speed = length(velocity);
+    vec2 heading = speed > 0.0 ? velocity / speed : vec2(1.0, 0.0);
+
+    vec2 corner = SHAPE[gl_VertexIndex] * AGENT_SIZE;
+    vec2 rotated = vec2(corner.x * heading.x - corner.y * heading.y, corner.x * heading.y + corner.y * heading.x);
+
+    gl_Position = vec4(position + rotated, 0.5, 1.0);
+    // Colored by heading, so flocking together shows up as patches of one color
+    fragColor = 0.55 + 0.45 * vec3(heading.x, heading.y, -heading.x);
+}
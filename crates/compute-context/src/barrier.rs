@@ -0,0 +1,128 @@
+use ash::{vk, Device};
+use std::slice;
+
+// Pipeline stages and the accesses made in them, on one side of a barrier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+}
+
+impl Access {
+    // Nothing before the barrier, e.g. when an image's previous contents are discarded
+    pub const NONE: Access = Access::new(
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::AccessFlags::empty(),
+    );
+
+    pub const COMPUTE_READ: Access = Access::new(
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::AccessFlags::SHADER_READ,
+    );
+
+    pub const COMPUTE_WRITE: Access = Access::new(
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::AccessFlags::SHADER_WRITE,
+    );
+
+    pub const TRANSFER_READ: Access = Access::new(
+        vk::PipelineStageFlags::TRANSFER,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+
+    pub const TRANSFER_WRITE: Access = Access::new(
+        vk::PipelineStageFlags::TRANSFER,
+        vk::AccessFlags::TRANSFER_WRITE,
+    );
+
+    pub const HOST_READ: Access =
+        Access::new(vk::PipelineStageFlags::HOST, vk::AccessFlags::HOST_READ);
+
+    pub const fn new(stage: vk::PipelineStageFlags, access: vk::AccessFlags) -> Access {
+        Access { stage, access }
+    }
+
+    // Both sets of stages and accesses, for a barrier covering either
+    pub fn and(self, other: Access) -> Access {
+        Access::new(self.stage | other.stage, self.access | other.access)
+    }
+}
+
+// Records a barrier ordering the accesses in to after those in from, for every resource on the queue
+// Writes in from are made visible to to, and when from only reads, to only waits for the reads to finish
+pub fn record_memory_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    from: Access,
+    to: Access,
+) {
+    let barrier = vk::MemoryBarrier::builder()
+        .src_access_mask(from.access)
+        .dst_access_mask(to.access);
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            from.stage,
+            to.stage,
+            vk::DependencyFlags::empty(),
+            slice::from_ref(&barrier),
+            &[],
+            &[],
+        )
+    };
+}
+
+// Records a barrier moving the only mip level and layer of a color image from layouts[0] to layouts[1], ordering the
+// accesses in to after those in from
+pub fn record_image_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    layouts: [vk::ImageLayout; 2],
+    from: Access,
+    to: Access,
+) {
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(from.access)
+        .dst_access_mask(to.access)
+        .old_layout(layouts[0])
+        .new_layout(layouts[1])
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(color_range());
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            from.stage,
+            to.stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            slice::from_ref(&barrier),
+        )
+    };
+}
+
+// The only mip level and layer of a color image, such as a storage image or a swapchain image
+pub fn color_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+// Same as color_range(), for copies and blits
+pub fn color_layers() -> vk::ImageSubresourceLayers {
+    vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level: 0,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
@@ -0,0 +1,237 @@
+use crate::barrier::{self, Access};
+use crate::presenter::Frame;
+use ash::{vk, Device};
+use std::slice;
+
+// Fixed resolution image drawn centred in the window, with bars filling the rest, so a compute shader's output keeps
+// its aspect ratio however the window is sized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Letterbox {
+    // Resolution of the image in pixels, whose aspect ratio is kept
+    pub width: u32,
+    pub height: u32,
+    // Only scales by whole multiples, keeping pixels sharp - windows too small for 1x scale down to fit instead
+    pub integer_scale: bool,
+    // Color of the bars left around the letterboxed area
+    pub bar_color: [f32; 4],
+}
+
+impl Letterbox {
+    pub fn new(width: u32, height: u32) -> Letterbox {
+        Letterbox {
+            width,
+            height,
+            integer_scale: false,
+            bar_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    // Largest area with the image's aspect ratio that fits in extent, centred in it
+    pub fn fit(&self, extent: vk::Extent2D) -> vk::Rect2D {
+        let scale = (extent.width / self.width).min(extent.height / self.height);
+        let (width, height) = if self.integer_scale && scale >= 1 {
+            (self.width * scale, self.height * scale)
+        } else if extent.width as u64 * self.height as u64
+            > extent.height as u64 * self.width as u64
+        {
+            // The window is wider than the image, so bars go at the sides
+            (
+                scale_rounded(extent.height, self.width, self.height),
+                extent.height,
+            )
+        } else {
+            (
+                extent.width,
+                scale_rounded(extent.width, self.height, self.width),
+            )
+        };
+
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: ((extent.width - width) / 2) as i32,
+                y: ((extent.height - height) / 2) as i32,
+            },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+}
+
+// Records clearing frame's image to the letterbox's bar color and blitting the whole of source, of size
+// source_extent, onto the letterboxed area, leaving the frame ready to present
+// source must be in GENERAL layout, with its last writes already made visible to TRANSFER_READ
+pub fn record_letterboxed_blit(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    source: vk::Image,
+    source_extent: vk::Extent2D,
+    frame: &Frame,
+    letterbox: &Letterbox,
+    filter: vk::Filter,
+) {
+    let area = letterbox.fit(frame.extent);
+
+    // Frames wait for their image to be acquired at the transfer stage, which the first barrier follows on from
+    barrier::record_image_barrier(
+        device,
+        command_buffer,
+        frame.image,
+        [
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        ],
+        Access::new(vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::empty()),
+        Access::TRANSFER_WRITE,
+    );
+
+    unsafe {
+        device.cmd_clear_color_image(
+            command_buffer,
+            frame.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &vk::ClearColorValue {
+                float32: letterbox.bar_color,
+            },
+            slice::from_ref(&barrier::color_range()),
+        )
+    };
+
+    // The blit overwrites part of what was cleared
+    barrier::record_image_barrier(
+        device,
+        command_buffer,
+        frame.image,
+        [
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        ],
+        Access::TRANSFER_WRITE,
+        Access::TRANSFER_WRITE,
+    );
+
+    unsafe {
+        device.cmd_blit_image(
+            command_buffer,
+            source,
+            vk::ImageLayout::GENERAL,
+            frame.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            slice::from_ref(&blit_region(source_extent, area)),
+            filter,
+        )
+    };
+
+    // Presenting is ordered by the semaphore the submission signals
+    barrier::record_image_barrier(
+        device,
+        command_buffer,
+        frame.image,
+        [
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        ],
+        Access::TRANSFER_WRITE,
+        Access::new(
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::AccessFlags::empty(),
+        ),
+    );
+}
+
+// value * numerator / denominator, rounded to the nearest whole number
+fn scale_rounded(value: u32, numerator: u32, denominator: u32) -> u32 {
+    let denominator = denominator as u64;
+    ((value as u64 * numerator as u64 + denominator / 2) / denominator) as u32
+}
+
+// Blit stretching the whole of an image of size source onto area of the target image
+fn blit_region(source: vk::Extent2D, area: vk::Rect2D) -> vk::ImageBlit {
+    vk::ImageBlit {
+        src_subresource: barrier::color_layers(),
+        src_offsets: [
+            vk::Offset3D { x: 0, y: 0, z: 0 },
+            vk::Offset3D {
+                x: source.width as i32,
+                y: source.height as i32,
+                z: 1,
+            },
+        ],
+        dst_subresource: barrier::color_layers(),
+        dst_offsets: [
+            vk::Offset3D {
+                x: area.offset.x,
+                y: area.offset.y,
+                z: 0,
+            },
+            vk::Offset3D {
+                x: area.offset.x + area.extent.width as i32,
+                y: area.offset.y + area.extent.height as i32,
+                z: 1,
+            },
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: extent(width, height),
+        }
+    }
+
+    fn offset(x: i32, y: i32, z: i32) -> vk::Offset3D {
+        vk::Offset3D { x, y, z }
+    }
+
+    #[test]
+    fn matching_aspect_ratio_fills_the_window() {
+        assert_eq!(
+            Letterbox::new(960, 540).fit(extent(1920, 1080)),
+            rect(0, 0, 1920, 1080)
+        );
+    }
+
+    #[test]
+    fn wider_windows_are_pillarboxed() {
+        assert_eq!(
+            Letterbox::new(640, 480).fit(extent(1920, 1080)),
+            rect(240, 0, 1440, 1080)
+        );
+    }
+
+    #[test]
+    fn taller_windows_are_letterboxed() {
+        assert_eq!(
+            Letterbox::new(1920, 1080).fit(extent(800, 600)),
+            rect(0, 75, 800, 450)
+        );
+    }
+
+    #[test]
+    fn integer_scale_uses_whole_multiples() {
+        let letterbox = Letterbox {
+            integer_scale: true,
+            ..Letterbox::new(256, 256)
+        };
+
+        assert_eq!(letterbox.fit(extent(1000, 700)), rect(244, 94, 512, 512));
+        assert_eq!(letterbox.fit(extent(256, 256)), rect(0, 0, 256, 256));
+        // Too small for 1x, so it scales down like any other letterbox
+        assert_eq!(letterbox.fit(extent(200, 100)), rect(50, 0, 100, 100));
+    }
+
+    #[test]
+    fn blits_read_the_whole_source() {
+        let blit = blit_region(extent(256, 256), rect(10, 20, 30, 40));
+
+        assert_eq!(blit.src_offsets, [offset(0, 0, 0), offset(256, 256, 1)]);
+        assert_eq!(blit.dst_offsets, [offset(10, 20, 0), offset(40, 60, 1)]);
+    }
+}
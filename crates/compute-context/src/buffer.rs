@@ -0,0 +1,151 @@
+use crate::compute_errors::ComputeError;
+use crate::context::ComputeContext;
+use crate::pod::{self, Pod};
+use ash::{vk, Device};
+use std::{mem, ptr};
+
+// Buffer with a block of memory of its own
+// Buffers created with HOST_VISIBLE | HOST_COHERENT memory can be written and read by the host through write() and
+// read(), with no flushes needed
+pub struct Buffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    properties: vk::MemoryPropertyFlags,
+}
+
+impl Buffer {
+    // Creates a buffer used by a single queue family at a time
+    pub fn new(
+        context: &ComputeContext,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<Buffer, ComputeError> {
+        Buffer::new_shared(context, size, usage, properties, &[])
+    }
+
+    // Creates a buffer that queue_families can all use at once without transferring ownership, e.g. one written on
+    // the compute queue and read on the graphics queue
+    // It is exclusive to one family anyway when queue_families doesn't hold two different ones
+    pub fn new_shared(
+        context: &ComputeContext,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+        queue_families: &[u32],
+    ) -> Result<Buffer, ComputeError> {
+        let mut buffer = Buffer {
+            buffer: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            size,
+            properties,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        buffer
+            .create(context, usage, queue_families)
+            .inspect_err(|_| buffer.destroy(context.device()))?;
+
+        Ok(buffer)
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    // Copies data to the start of the buffer, which must be host visible and coherent and have room for it
+    // The GPU must not be using the buffer
+    pub fn write<T: Pod>(&mut self, device: &Device, data: &[T]) -> Result<(), ComputeError> {
+        let bytes = pod::slice_bytes(data);
+        assert!(
+            bytes.len() as vk::DeviceSize <= self.size,
+            "Data does not fit in the buffer!"
+        );
+
+        let mapped = self.map(device)?;
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), mapped, bytes.len());
+            device.unmap_memory(self.memory);
+        }
+
+        Ok(())
+    }
+
+    // Copies the whole buffer out as values of T, which must be host visible and coherent
+    // The GPU's writes must have been made visible to HOST_READ, and finished
+    pub fn read<T: Pod>(&self, device: &Device) -> Result<Vec<T>, ComputeError> {
+        let len = self.size as usize / mem::size_of::<T>();
+        let mut values = Vec::with_capacity(len);
+
+        let mapped = self.map(device)?;
+        unsafe {
+            // Pod allows any bit pattern, so whatever the GPU wrote is a valid T
+            ptr::copy_nonoverlapping(mapped as *const T, values.as_mut_ptr(), len);
+            values.set_len(len);
+            device.unmap_memory(self.memory);
+        }
+
+        Ok(values)
+    }
+
+    // Destroys the buffer and frees its memory
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+        self.buffer = vk::Buffer::null();
+        self.memory = vk::DeviceMemory::null();
+    }
+
+    fn create(
+        &mut self,
+        context: &ComputeContext,
+        usage: vk::BufferUsageFlags,
+        queue_families: &[u32],
+    ) -> Result<(), ComputeError> {
+        let device = context.device();
+        let mut families = queue_families.to_vec();
+        families.sort_unstable();
+        families.dedup();
+
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(self.size)
+            .usage(usage);
+        // Concurrent buffers must list every family using them, where exclusive ones list none
+        let buffer_info = if families.len() > 1 {
+            buffer_info
+                .sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&families)
+        } else {
+            buffer_info.sharing_mode(vk::SharingMode::EXCLUSIVE)
+        };
+
+        self.buffer = unsafe { device.create_buffer(&buffer_info, None)? };
+
+        let requirements = unsafe { device.get_buffer_memory_requirements(self.buffer) };
+        self.memory = context.allocate_memory(&requirements, self.properties)?;
+        unsafe { device.bind_buffer_memory(self.buffer, self.memory, 0)? };
+
+        Ok(())
+    }
+
+    // Maps the whole buffer, which unmap_memory() must be called on once done with
+    fn map(&self, device: &Device) -> Result<*mut u8, ComputeError> {
+        assert!(
+            self.properties.contains(
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+            ),
+            "Only host visible and coherent buffers can be mapped!"
+        );
+
+        let mapped =
+            unsafe { device.map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty())? };
+        Ok(mapped as *mut u8)
+    }
+}
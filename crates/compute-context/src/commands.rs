@@ -0,0 +1,120 @@
+use crate::compute_errors::ComputeError;
+use ash::{vk, Device};
+use std::slice;
+
+// Command pool on a queue family, with buffer_count primary command buffers re-recorded as needed (e.g. one per frame
+// in flight), and temporary ones for work submitted and waited for on the spot
+pub struct CommandPool {
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+impl CommandPool {
+    pub fn new(
+        device: &Device,
+        queue_family_index: u32,
+        buffer_count: u32,
+    ) -> Result<CommandPool, ComputeError> {
+        // Buffers are re-recorded every frame, so each must be resettable on its own
+        let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
+            .queue_family_index(queue_family_index)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+
+        let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+
+        let command_buffers = if buffer_count > 0 {
+            let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(buffer_count);
+
+            // Destroying the pool is all the cleanup needed, as nothing was allocated from it
+            unsafe { device.allocate_command_buffers(&command_buffer_allocate_info) }
+                .inspect_err(|_| unsafe { device.destroy_command_pool(command_pool, None) })?
+        } else {
+            Vec::new()
+        };
+
+        Ok(CommandPool {
+            command_pool,
+            command_buffers,
+        })
+    }
+
+    // Resets and re-records the command buffer at index, with record filling in the commands
+    // The buffer must not be pending execution, so the previous submission using it has to have finished
+    pub fn record<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        device: &Device,
+        index: usize,
+        record: F,
+    ) -> Result<vk::CommandBuffer, ComputeError> {
+        let command_buffer = self.command_buffers[index];
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        unsafe {
+            device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+            device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
+
+        record(command_buffer);
+
+        unsafe { device.end_command_buffer(command_buffer)? };
+
+        Ok(command_buffer)
+    }
+
+    // Records a temporary command buffer, submits it to queue, and waits for it to finish - meant for setup work and
+    // readbacks rather than anything done every frame
+    pub fn submit_once<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        device: &Device,
+        queue: vk::Queue,
+        record: F,
+    ) -> Result<(), ComputeError> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+
+        let command_buffer =
+            unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? }[0];
+
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }
+            .inspect_err(|_| self.free(device, command_buffer))?;
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        let submit_info =
+            vk::SubmitInfo::builder().command_buffers(slice::from_ref(&command_buffer));
+
+        let result = unsafe {
+            device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                .map(|()| record(command_buffer))
+                .and_then(|()| device.end_command_buffer(command_buffer))
+                .and_then(|()| device.queue_submit(queue, slice::from_ref(&submit_info), fence))
+                .and_then(|()| device.wait_for_fences(slice::from_ref(&fence), true, u64::MAX))
+        };
+
+        // Freed whether or not the submission succeeded, as nothing else refers to them
+        unsafe { device.destroy_fence(fence, None) };
+        self.free(device, command_buffer);
+
+        result.map_err(ComputeError::from)
+    }
+
+    // Destroys the pool, which frees its command buffers - none of them may be pending execution
+    pub fn destroy(&mut self, device: &Device) {
+        self.command_buffers.clear();
+
+        unsafe { device.destroy_command_pool(self.command_pool, None) };
+        self.command_pool = vk::CommandPool::null();
+    }
+
+    fn free(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        unsafe { device.free_command_buffers(self.command_pool, slice::from_ref(&command_buffer)) };
+    }
+}
@@ -0,0 +1,52 @@
+use ash::vk;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ComputeError {
+    #[error("Failed to load the Vulkan library: {0}")]
+    Loading(#[from] ash::LoadingError),
+    #[error("Failed to create the Vulkan instance: {0}")]
+    InstanceCreation(vk::Result),
+    #[error("Failed to load Vulkan instance functions: {}", .0.join(", "))]
+    InstanceLoading(Vec<&'static str>),
+    #[error("Failed to create the window surface: {0}")]
+    SurfaceCreation(vk::Result),
+    #[error("No GPU has a queue family for both graphics and compute work, that can present to the window if there is one")]
+    NoSuitableDevice,
+    #[error("Failed to create the logical device: {0}")]
+    DeviceCreation(vk::Result),
+    #[error("The surface supports no formats")]
+    NoSurfaceFormats,
+    #[error("The surface's images can't be blitted to")]
+    TransferDstUnsupported,
+    #[error("The window surface was lost")]
+    SurfaceLost,
+    #[error("No memory type with {0:?}")]
+    NoSuitableMemoryType(vk::MemoryPropertyFlags),
+    #[error("Failed to start the shader compiler")]
+    ShaderCompilerCreation,
+    #[error("Failed to compile {name} shader:\n{log}")]
+    ShaderCompilation { name: &'static str, log: String },
+    #[error("Vulkan call failed: {0}")]
+    Vulkan(#[from] vk::Result),
+}
+
+// Entry::create_instance reports functions it could not load apart from a failed vkCreateInstance
+impl From<ash::InstanceError> for ComputeError {
+    fn from(error: ash::InstanceError) -> ComputeError {
+        match error {
+            ash::InstanceError::LoadError(functions) => ComputeError::InstanceLoading(functions),
+            ash::InstanceError::VkError(result) => ComputeError::InstanceCreation(result),
+        }
+    }
+}
+
+impl ComputeError {
+    // Sorts results from acquiring and presenting, as a lost surface needs telling apart from other failures
+    pub fn from_swapchain(result: vk::Result) -> ComputeError {
+        match result {
+            vk::Result::ERROR_SURFACE_LOST_KHR => ComputeError::SurfaceLost,
+            _ => ComputeError::Vulkan(result),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use crate::compute_errors::ComputeError;
+use ash::{
+    extensions::khr::{Surface, Swapchain},
+    vk, Device, Entry, Instance,
+};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
+use winit::window::Window;
+
+// Instance, device, and queue the compute examples run on, along with the window's surface if there is one
+// Compute shaders, blits, and presentation all go through a single queue from a family supporting both graphics and
+// compute, which every device with graphics has, so nothing needs to move between queue families
+// Examples overlapping compute with graphics work (async compute) can also submit to compute_queue(), which comes from a
+// compute only family where the device has one
+pub struct ComputeContext {
+    _entry: Entry,
+    instance: Instance,
+    surface: Surface,
+    surface_khr: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    device: Device,
+    queue_family_index: u32,
+    queue: vk::Queue,
+    compute_family_index: u32,
+    compute_queue: vk::Queue,
+}
+
+impl ComputeContext {
+    // Creates a context presenting to window, with application_name reported to the driver
+    pub fn new(window: &Window, application_name: &str) -> Result<ComputeContext, ComputeError> {
+        // Creates Entry and Instance, with the extensions the window's surface needs
+        let surface_extensions = ash_window::enumerate_required_extensions(window)?;
+        let extension_names_raw: Vec<*const c_char> = surface_extensions
+            .iter()
+            .map(|extension| extension.as_ptr())
+            .collect();
+        let (entry, instance) =
+            ComputeContext::create_instance(application_name, &extension_names_raw)?;
+
+        // Creates vk::SurfaceKHR and Surface
+        let surface = Surface::new(&entry, &instance);
+        let surface_khr = unsafe { ash_window::create_surface(&entry, &instance, window, None) }
+            .map_err(ComputeError::SurfaceCreation)
+            .inspect_err(|_| unsafe { instance.destroy_instance(None) })?;
+
+        ComputeContext::create(entry, instance, surface, surface_khr)
+    }
+
+    // Creates a context with no window, for work that is only read back, such as tests and offline renders
+    // The surface is null, so nothing can be presented
+    pub fn new_headless(application_name: &str) -> Result<ComputeContext, ComputeError> {
+        let (entry, instance) = ComputeContext::create_instance(application_name, &[])?;
+        let surface = Surface::new(&entry, &instance);
+
+        ComputeContext::create(entry, instance, surface, vk::SurfaceKHR::null())
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    pub fn surface(&self) -> &Surface {
+        &self.surface
+    }
+
+    pub fn surface_khr(&self) -> vk::SurfaceKHR {
+        self.surface_khr
+    }
+
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.physical_device
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    // Family of queue(), supporting graphics, compute, transfers, and presenting to the surface if there is one
+    pub fn queue_family_index(&self) -> u32 {
+        self.queue_family_index
+    }
+
+    pub fn queue(&self) -> vk::Queue {
+        self.queue
+    }
+
+    // Family of compute_queue(), which is queue_family_index() on devices without a compute only family
+    pub fn compute_family_index(&self) -> u32 {
+        self.compute_family_index
+    }
+
+    // Queue for compute work that can run alongside the work on queue(), or queue() itself on devices without a
+    // compute only family
+    pub fn compute_queue(&self) -> vk::Queue {
+        self.compute_queue
+    }
+
+    // Index of the first memory type allowed by type_bits (from vk::MemoryRequirements) that has properties
+    pub fn memory_type_index(
+        &self,
+        type_bits: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<u32, ComputeError> {
+        self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .position(|(index, memory_type)| {
+                type_bits & (1 << index) != 0 && memory_type.property_flags.contains(properties)
+            })
+            .map(|index| index as u32)
+            .ok_or(ComputeError::NoSuitableMemoryType(properties))
+    }
+
+    // Allocates a block of memory of its own for a resource with requirements, with properties
+    // The examples only create a handful of resources each, so they don't need suballocation
+    pub fn allocate_memory(
+        &self,
+        requirements: &vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<vk::DeviceMemory, ComputeError> {
+        let memory_type_index =
+            self.memory_type_index(requirements.memory_type_bits, properties)?;
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+
+        Ok(unsafe { self.device.allocate_memory(&allocate_info, None)? })
+    }
+
+    // Creates an ash Instance, which is a light wrapper around a vk::Instance, with extension_names enabled
+    fn create_instance(
+        application_name: &str,
+        extension_names: &[*const c_char],
+    ) -> Result<(Entry, Instance), ComputeError> {
+        let entry = unsafe { Entry::new()? };
+
+        // Loads names into CStrings
+        let application_name =
+            CString::new(application_name).expect("Application names must not contain nul bytes!");
+        let engine_name = CString::new("Compute Context").unwrap();
+
+        let app_info = vk::ApplicationInfo::builder()
+            .application_name(&application_name)
+            .application_version(vk::make_api_version(0, 1, 0, 0))
+            .engine_name(&engine_name)
+            .engine_version(vk::make_api_version(0, 1, 0, 0))
+            .api_version(vk::make_api_version(0, 1, 0, 0));
+
+        let create_info = vk::InstanceCreateInfo::builder()
+            .application_info(&app_info)
+            .enabled_extension_names(extension_names);
+
+        let instance = unsafe { entry.create_instance(&create_info, None)? };
+
+        Ok((entry, instance))
+    }
+
+    // Creates the device for surface_khr, which is null for headless contexts, taking over the instance and surface so
+    // they are destroyed if it fails
+    fn create(
+        entry: Entry,
+        instance: Instance,
+        surface: Surface,
+        surface_khr: vk::SurfaceKHR,
+    ) -> Result<ComputeContext, ComputeError> {
+        let created = ComputeContext::pick_physical_device(&instance, &surface, surface_khr)
+            .and_then(|(physical_device, queue_family_index)| {
+                let compute_family_index = ComputeContext::pick_compute_family(
+                    &instance,
+                    physical_device,
+                    queue_family_index,
+                );
+                let device = ComputeContext::create_logical_device(
+                    &instance,
+                    physical_device,
+                    [queue_family_index, compute_family_index],
+                    surface_khr != vk::SurfaceKHR::null(),
+                )?;
+                Ok((
+                    physical_device,
+                    queue_family_index,
+                    compute_family_index,
+                    device,
+                ))
+            });
+
+        let (physical_device, queue_family_index, compute_family_index, device) = created
+            .inspect_err(|_| unsafe {
+                surface.destroy_surface(surface_khr, None);
+                instance.destroy_instance(None);
+            })?;
+
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        let compute_queue = unsafe { device.get_device_queue(compute_family_index, 0) };
+
+        Ok(ComputeContext {
+            _entry: entry,
+            instance,
+            surface,
+            surface_khr,
+            physical_device,
+            memory_properties,
+            device,
+            queue_family_index,
+            queue,
+            compute_family_index,
+            compute_queue,
+        })
+    }
+
+    // First device with a queue family for graphics and compute that can present to surface_khr, along with that
+    // family - with a null surface_khr, presenting isn't needed
+    fn pick_physical_device(
+        instance: &Instance,
+        surface: &Surface,
+        surface_khr: vk::SurfaceKHR,
+    ) -> Result<(vk::PhysicalDevice, u32), ComputeError> {
+        let present = surface_khr != vk::SurfaceKHR::null();
+        let physical_devices = unsafe { instance.enumerate_physical_devices()? };
+
+        for physical_device in physical_devices {
+            if present && !ComputeContext::supports_swapchain(instance, physical_device)? {
+                continue;
+            }
+
+            let queue_families =
+                unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+            for (index, family) in queue_families.iter().enumerate() {
+                let index = index as u32;
+                let supports_work = family.queue_count > 0
+                    && family
+                        .queue_flags
+                        .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE);
+                if !supports_work {
+                    continue;
+                }
+
+                let supports_present = !present
+                    || unsafe {
+                        surface.get_physical_device_surface_support(
+                            physical_device,
+                            index,
+                            surface_khr,
+                        )?
+                    };
+                if supports_present {
+                    return Ok((physical_device, index));
+                }
+            }
+        }
+
+        Err(ComputeError::NoSuitableDevice)
+    }
+
+    // First family with compute but not graphics support on physical_device, or fallback if there is none
+    fn pick_compute_family(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        fallback: u32,
+    ) -> u32 {
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+
+        queue_families
+            .iter()
+            .position(|family| {
+                family.queue_count > 0
+                    && family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                    && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+            })
+            .map_or(fallback, |index| index as u32)
+    }
+
+    fn supports_swapchain(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<bool, ComputeError> {
+        let extensions =
+            unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+
+        Ok(extensions.iter().any(|extension| {
+            let name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+            name == Swapchain::name()
+        }))
+    }
+
+    // Creates a Device with a queue from each of families, which is only created once if both are the same, and the
+    // swapchain extension if it will present
+    fn create_logical_device(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        families: [u32; 2],
+        present: bool,
+    ) -> Result<Device, ComputeError> {
+        let queue_priorities = [1.0];
+        let family_count = if families[0] == families[1] { 1 } else { 2 };
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = families[..family_count]
+            .iter()
+            .map(|family| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(*family)
+                    .queue_priorities(&queue_priorities)
+                    .build()
+            })
+            .collect();
+
+        let extension_names_raw = [Swapchain::name().as_ptr()];
+        let device_create_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(if present {
+                &extension_names_raw
+            } else {
+                &[]
+            });
+
+        unsafe { instance.create_device(physical_device, &device_create_info, None) }
+            .map_err(ComputeError::DeviceCreation)
+    }
+}
+
+impl Drop for ComputeContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+            // Does nothing for headless contexts, whose surface is null
+            self.surface.destroy_surface(self.surface_khr, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// Vulkan setup, resources, and presentation shared by the compute examples, which dispatch compute shaders over
+// storage images and buffers and either blit the results to a window or read them back
+//
+// Resources hold no device reference, so their owners call destroy() on them before the ComputeContext is dropped.
+pub mod barrier;
+pub mod blit;
+pub mod buffer;
+pub mod commands;
+pub mod compute_errors;
+pub mod context;
+pub mod pipeline;
+pub mod pod;
+pub mod presenter;
+pub mod readback;
+pub mod shaders;
+pub mod storage_image;
@@ -0,0 +1,285 @@
+use crate::compute_errors::ComputeError;
+use crate::pod::{self, Pod};
+use ash::{vk, Device};
+use std::{ffi::CString, marker::PhantomData, mem, slice};
+
+// Number of workgroups of local_size invocations needed to cover invocations
+pub fn group_count(invocations: u32, local_size: u32) -> u32 {
+    invocations.div_ceil(local_size)
+}
+
+// Resource bound to a compute shader in set 0, at the binding matching its position in the list of bindings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    // View of an image with STORAGE usage, which must be in GENERAL layout when dispatched
+    StorageImage(vk::ImageView),
+    // Whole of a buffer with STORAGE_BUFFER usage
+    StorageBuffer(vk::Buffer),
+}
+
+impl Binding {
+    fn descriptor_type(&self) -> vk::DescriptorType {
+        match self {
+            Binding::StorageImage(_) => vk::DescriptorType::STORAGE_IMAGE,
+            Binding::StorageBuffer(_) => vk::DescriptorType::STORAGE_BUFFER,
+        }
+    }
+}
+
+// Size of push constants of type P, checked when the pipeline type is used rather than when a dispatch is recorded
+struct PushConstantSize<P>(PhantomData<P>);
+
+impl<P> PushConstantSize<P> {
+    const SIZE: u32 = {
+        let size = mem::size_of::<P>();
+        assert!(size > 0, "Push constants must not be empty!");
+        assert!(
+            size % 4 == 0,
+            "Push constants must be a multiple of 4 bytes!"
+        );
+        // The least every device supports
+        assert!(size <= 128, "Push constants must fit in 128 bytes!");
+        size as u32
+    };
+}
+
+// Compute pipeline built from a SPIR-V compute shader, along with the descriptor set holding its bindings and push
+// constants of type P, which must match the shader's push constant block
+// The bindings are fixed at creation, so resources that swap roles (such as ping-pong images) get a pipeline per
+// arrangement rather than updating a descriptor set that frames in flight may still be using
+pub struct ComputePipeline<P: Pod> {
+    shader_module: vk::ShaderModule,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    push_constants: PhantomData<P>,
+}
+
+impl<P: Pod> ComputePipeline<P> {
+    // Creates a pipeline running code's main function, with bindings in set 0
+    pub fn new(
+        device: &Device,
+        code: &[u32],
+        bindings: &[Binding],
+    ) -> Result<ComputePipeline<P>, ComputeError> {
+        let mut pipeline = ComputePipeline {
+            shader_module: vk::ShaderModule::null(),
+            descriptor_set_layout: vk::DescriptorSetLayout::null(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set: vk::DescriptorSet::null(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            push_constants: PhantomData,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        pipeline
+            .create(device, code, bindings)
+            .inspect_err(|_| pipeline.destroy(device))?;
+
+        Ok(pipeline)
+    }
+
+    // Records binding the pipeline and dispatching group_count workgroups with push_constants
+    // Barriers ordering the dispatch against other work on the resources are up to the caller
+    pub fn dispatch(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        group_count: [u32; 3],
+        push_constants: &P,
+    ) {
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.layout,
+                0,
+                slice::from_ref(&self.descriptor_set),
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                pod::bytes_of(push_constants),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                group_count[0],
+                group_count[1],
+                group_count[2],
+            );
+        }
+    }
+
+    // Destroys the pipeline, its layouts, descriptor pool, and shader module
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            device.destroy_shader_module(self.shader_module, None);
+        }
+        self.pipeline = vk::Pipeline::null();
+        self.layout = vk::PipelineLayout::null();
+        self.descriptor_pool = vk::DescriptorPool::null();
+        self.descriptor_set = vk::DescriptorSet::null();
+        self.descriptor_set_layout = vk::DescriptorSetLayout::null();
+        self.shader_module = vk::ShaderModule::null();
+    }
+
+    fn create(
+        &mut self,
+        device: &Device,
+        code: &[u32],
+        bindings: &[Binding],
+    ) -> Result<(), ComputeError> {
+        let shader_module_info = vk::ShaderModuleCreateInfo::builder().code(code);
+        self.shader_module = unsafe { device.create_shader_module(&shader_module_info, None)? };
+
+        let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(index as u32)
+                    .descriptor_type(binding.descriptor_type())
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build()
+            })
+            .collect();
+        let descriptor_set_layout_info =
+            vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        self.descriptor_set_layout =
+            unsafe { device.create_descriptor_set_layout(&descriptor_set_layout_info, None)? };
+
+        self.create_descriptor_set(device, bindings)?;
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: PushConstantSize::<P>::SIZE,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(slice::from_ref(&self.descriptor_set_layout))
+            .push_constant_ranges(slice::from_ref(&push_constant_range));
+        self.layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        let entry_name = CString::new("main").unwrap();
+        let stage_info = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(self.shader_module)
+            .name(&entry_name);
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(*stage_info)
+            .layout(self.layout);
+
+        self.pipeline = unsafe {
+            device
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    slice::from_ref(&pipeline_info),
+                    None,
+                )
+                .map_err(|(_, result)| result)?[0]
+        };
+
+        Ok(())
+    }
+
+    // Creates a pool with room for exactly one set of bindings, and the set pointing at them
+    fn create_descriptor_set(
+        &mut self,
+        device: &Device,
+        bindings: &[Binding],
+    ) -> Result<(), ComputeError> {
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = bindings
+            .iter()
+            .map(|binding| vk::DescriptorPoolSize {
+                ty: binding.descriptor_type(),
+                descriptor_count: 1,
+            })
+            .collect();
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        self.descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None)? };
+
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(slice::from_ref(&self.descriptor_set_layout));
+        self.descriptor_set = unsafe { device.allocate_descriptor_sets(&allocate_info)? }[0];
+
+        // Infos are built up front, as the writes point into them - each binding only uses the one of its kind
+        let image_infos: Vec<vk::DescriptorImageInfo> = bindings
+            .iter()
+            .map(|binding| vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: match *binding {
+                    Binding::StorageImage(image_view) => image_view,
+                    _ => vk::ImageView::null(),
+                },
+                image_layout: vk::ImageLayout::GENERAL,
+            })
+            .collect();
+        let buffer_infos: Vec<vk::DescriptorBufferInfo> = bindings
+            .iter()
+            .map(|binding| vk::DescriptorBufferInfo {
+                buffer: match *binding {
+                    Binding::StorageBuffer(buffer) => buffer,
+                    _ => vk::Buffer::null(),
+                },
+                offset: 0,
+                range: vk::WHOLE_SIZE,
+            })
+            .collect();
+
+        let writes: Vec<vk::WriteDescriptorSet> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_set(self.descriptor_set)
+                    .dst_binding(index as u32)
+                    .descriptor_type(binding.descriptor_type());
+
+                match binding {
+                    Binding::StorageImage(_) => write
+                        .image_info(slice::from_ref(&image_infos[index]))
+                        .build(),
+                    Binding::StorageBuffer(_) => write
+                        .buffer_info(slice::from_ref(&buffer_infos[index]))
+                        .build(),
+                }
+            })
+            .collect();
+
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_counts_cover_every_invocation() {
+        assert_eq!(group_count(1, 16), 1);
+        assert_eq!(group_count(16, 16), 1);
+        assert_eq!(group_count(17, 16), 2);
+        assert_eq!(group_count(0, 16), 0);
+    }
+}
@@ -0,0 +1,59 @@
+use std::{mem, slice};
+
+/// Plain data that can be handed to the GPU as bytes, such as push constants and buffer contents
+///
+/// # Safety
+///
+/// Implementors must have no padding bytes, as reading padding is undefined behaviour, and every bit pattern must be a
+/// valid value, as data read back from the GPU is taken as is. In practice that means #[repr(C)] structs of integers,
+/// floats, and arrays of them, laid out so no field needs padding before it.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+// Bytes of value, e.g. for vkCmdPushConstants
+pub fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    slice_bytes(slice::from_ref(value))
+}
+
+// Bytes of every value in values, back to back
+pub fn slice_bytes<T: Pod>(values: &[T]) -> &[u8] {
+    // Pod rules out padding, so every byte is initialised
+    unsafe { slice::from_raw_parts(values.as_ptr() as *const u8, mem::size_of_val(values)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Pair {
+        first: u32,
+        second: f32,
+    }
+
+    unsafe impl Pod for Pair {}
+
+    #[test]
+    fn structs_are_laid_out_field_by_field() {
+        let pair = Pair {
+            first: 1,
+            second: 2.0,
+        };
+
+        let mut expected = 1u32.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&2.0f32.to_ne_bytes());
+        assert_eq!(bytes_of(&pair), &expected[..]);
+    }
+
+    #[test]
+    fn slices_are_packed_back_to_back() {
+        assert_eq!(slice_bytes(&[[1u32, 2], [3, 4]]).len(), 16);
+        assert!(slice_bytes::<u32>(&[]).is_empty());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::commands::CommandPool;
+use crate::compute_errors::ComputeError;
+use crate::context::ComputeContext;
+use ash::{extensions::khr::Swapchain, vk, Device};
+use std::slice;
+
+// Frames that can be recorded and submitted before waiting on the GPU, so the CPU works on one while the GPU works on
+// the other
+const FRAMES_IN_FLIGHT: usize = 2;
+
+// Swapchain image a frame is drawn into, which starts in UNDEFINED layout as its old contents are discarded, and must
+// be left in PRESENT_SRC_KHR layout
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub image: vk::Image,
+    pub extent: vk::Extent2D,
+}
+
+// Swapchain for the context's surface, along with the synchronization and command buffers for drawing frames to it
+// Frames are filled by transfers (e.g. blitting a compute shader's output), so the images only need TRANSFER_DST usage
+pub struct Presenter {
+    swapchain: Swapchain,
+    swapchain_khr: vk::SwapchainKHR,
+    images: Vec<vk::Image>,
+    extent: vk::Extent2D,
+    // Size of the window, used when the surface leaves the extent up to the swapchain
+    window_extent: vk::Extent2D,
+    // Signalled once the image for each frame in flight is acquired
+    image_available: Vec<vk::Semaphore>,
+    // Signalled once the frame drawn to each swapchain image is finished, which presenting it waits on
+    // These are per image rather than per frame in flight, as a semaphore waited on by a present is only known to be
+    // free again once that image is acquired again
+    render_finished: Vec<vk::Semaphore>,
+    // Signalled once each frame in flight's command buffer has finished executing
+    in_flight: Vec<vk::Fence>,
+    command_pool: Option<CommandPool>,
+    current_frame: usize,
+}
+
+impl Presenter {
+    // Creates a swapchain for a window of width by height pixels
+    pub fn new(
+        context: &ComputeContext,
+        width: u32,
+        height: u32,
+    ) -> Result<Presenter, ComputeError> {
+        let mut presenter = Presenter {
+            swapchain: Swapchain::new(context.instance(), context.device()),
+            swapchain_khr: vk::SwapchainKHR::null(),
+            images: Vec::new(),
+            extent: vk::Extent2D::default(),
+            window_extent: vk::Extent2D { width, height },
+            image_available: Vec::new(),
+            render_finished: Vec::new(),
+            in_flight: Vec::new(),
+            command_pool: None,
+            current_frame: 0,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        presenter
+            .create(context)
+            .inspect_err(|_| presenter.destroy(context.device()))?;
+
+        Ok(presenter)
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Index of the frame in flight the next draw uses, for resources kept per frame in flight
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        FRAMES_IN_FLIGHT
+    }
+
+    // Waits for the next frame in flight to be free, acquires a swapchain image, has record fill a command buffer
+    // drawing into it, then submits and presents it
+    // The swapchain is recreated when it no longer matches the surface, in which case the frame may be skipped
+    pub fn draw<F: FnOnce(&Device, vk::CommandBuffer, &Frame)>(
+        &mut self,
+        context: &ComputeContext,
+        record: F,
+    ) -> Result<(), ComputeError> {
+        self.draw_waiting(context, &[], record)
+    }
+
+    // Same as draw(), with the frame's submission also waiting on each semaphore in waits at its stage, e.g. for work
+    // on another queue that the frame reads
+    // record is only called once the frame's previous submission has finished, and before the frame is submitted, so
+    // it can submit the work signalling those semaphores - if the frame is skipped, record isn't called at all
+    pub fn draw_waiting<F: FnOnce(&Device, vk::CommandBuffer, &Frame)>(
+        &mut self,
+        context: &ComputeContext,
+        waits: &[(vk::Semaphore, vk::PipelineStageFlags)],
+        record: F,
+    ) -> Result<(), ComputeError> {
+        let device = context.device();
+        let fence = self.in_flight[self.current_frame];
+
+        unsafe { device.wait_for_fences(slice::from_ref(&fence), true, u64::MAX)? };
+
+        // Suboptimal swapchains can still be drawn to, and are recreated after presenting
+        let acquired = unsafe {
+            self.swapchain.acquire_next_image(
+                self.swapchain_khr,
+                u64::MAX,
+                self.image_available[self.current_frame],
+                vk::Fence::null(),
+            )
+        };
+        let image_index = match acquired {
+            Ok((image_index, _)) => image_index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate(context),
+            Err(result) => return Err(ComputeError::from_swapchain(result)),
+        };
+
+        // Only reset once something is sure to be submitted, so waiting on the fence next time can't hang
+        unsafe { device.reset_fences(slice::from_ref(&fence))? };
+
+        let frame = Frame {
+            image: self.images[image_index as usize],
+            extent: self.extent,
+        };
+        let command_buffer = self.command_pool.as_ref().unwrap().record(
+            device,
+            self.current_frame,
+            |command_buffer| record(device, command_buffer, &frame),
+        )?;
+
+        // Frames are drawn to with transfers, which can't start until the image is acquired
+        let (mut wait_semaphores, mut wait_stages): (Vec<_>, Vec<_>) = waits.iter().copied().unzip();
+        wait_semaphores.push(self.image_available[self.current_frame]);
+        wait_stages.push(vk::PipelineStageFlags::TRANSFER);
+        let render_finished = self.render_finished[image_index as usize];
+        let submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(slice::from_ref(&command_buffer))
+            .signal_semaphores(slice::from_ref(&render_finished));
+
+        unsafe { device.queue_submit(context.queue(), slice::from_ref(&submit_info), fence)? };
+
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(slice::from_ref(&render_finished))
+            .swapchains(slice::from_ref(&self.swapchain_khr))
+            .image_indices(slice::from_ref(&image_index));
+
+        self.current_frame = (self.current_frame + 1) % FRAMES_IN_FLIGHT;
+
+        match unsafe { self.swapchain.queue_present(context.queue(), &present_info) } {
+            Ok(false) => Ok(()),
+            Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.recreate(context),
+            Err(result) => Err(ComputeError::from_swapchain(result)),
+        }
+    }
+
+    // Recreates the swapchain for a window resized to width by height pixels
+    pub fn resize(
+        &mut self,
+        context: &ComputeContext,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ComputeError> {
+        self.window_extent = vk::Extent2D { width, height };
+        self.recreate(context)
+    }
+
+    // Destroys the swapchain, synchronization objects, and command buffers - the device must be idle
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            for semaphore in self.image_available.drain(..) {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for semaphore in self.render_finished.drain(..) {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for fence in self.in_flight.drain(..) {
+                device.destroy_fence(fence, None);
+            }
+        }
+
+        if let Some(mut command_pool) = self.command_pool.take() {
+            command_pool.destroy(device);
+        }
+
+        unsafe { self.swapchain.destroy_swapchain(self.swapchain_khr, None) };
+        self.swapchain_khr = vk::SwapchainKHR::null();
+        self.images.clear();
+    }
+
+    fn create(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let device = context.device();
+
+        self.command_pool = Some(CommandPool::new(
+            device,
+            context.queue_family_index(),
+            FRAMES_IN_FLIGHT as u32,
+        )?);
+
+        // Fences start signalled, so the first wait on each frame returns straight away
+        let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
+        for _ in 0..FRAMES_IN_FLIGHT {
+            self.image_available.push(unsafe {
+                device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?
+            });
+            self.in_flight
+                .push(unsafe { device.create_fence(&fence_info, None)? });
+        }
+
+        self.create_swapchain(context)
+    }
+
+    // Waits for the device to go idle and replaces the swapchain with one matching the surface
+    fn recreate(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        unsafe { context.device().device_wait_idle()? };
+
+        self.create_swapchain(context)
+    }
+
+    // Creates a swapchain for the surface, replacing the current one if there is one
+    // Nothing is created while the window is minimized, as swapchains can't be empty, so the last one is kept until
+    // the window is restored and resized
+    fn create_swapchain(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let surface = context.surface();
+        let physical_device = context.physical_device();
+
+        let capabilities = unsafe {
+            surface
+                .get_physical_device_surface_capabilities(physical_device, context.surface_khr())?
+        };
+        if !capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_DST)
+        {
+            return Err(ComputeError::TransferDstUnsupported);
+        }
+
+        let formats = unsafe {
+            surface.get_physical_device_surface_formats(physical_device, context.surface_khr())?
+        };
+        let format = choose_format(&formats).ok_or(ComputeError::NoSurfaceFormats)?;
+
+        let extent = choose_extent(&capabilities, self.window_extent);
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(());
+        }
+
+        // One more than the minimum, so acquiring doesn't have to wait on the driver, within any maximum (0 is none)
+        let mut image_count = capabilities.min_image_count + 1;
+        if capabilities.max_image_count > 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(context.surface_khr())
+            .min_image_count(image_count)
+            .image_format(format.format)
+            .image_color_space(format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(vk::ImageUsageFlags::TRANSFER_DST)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            // FIFO is the only present mode every device supports, and caps the frame rate at the refresh rate
+            .present_mode(vk::PresentModeKHR::FIFO)
+            .clipped(true)
+            .old_swapchain(self.swapchain_khr);
+
+        let swapchain_khr = unsafe {
+            self.swapchain
+                .create_swapchain(&swapchain_create_info, None)?
+        };
+
+        // The old swapchain is retired by creating the new one, and nothing is using it once the device is idle
+        unsafe { self.swapchain.destroy_swapchain(self.swapchain_khr, None) };
+        self.swapchain_khr = swapchain_khr;
+        self.extent = extent;
+
+        // The old images went with the old swapchain, so none are kept if the new ones can't be fetched
+        self.images = match unsafe { self.swapchain.get_swapchain_images(swapchain_khr) } {
+            Ok(images) => images,
+            Err(result) => {
+                self.images.clear();
+                return Err(result.into());
+            }
+        };
+
+        self.create_render_finished(context.device())
+    }
+
+    // Makes sure there is a render finished semaphore for every swapchain image
+    fn create_render_finished(&mut self, device: &Device) -> Result<(), ComputeError> {
+        while self.render_finished.len() > self.images.len() {
+            let semaphore = self.render_finished.pop().unwrap();
+            unsafe { device.destroy_semaphore(semaphore, None) };
+        }
+
+        while self.render_finished.len() < self.images.len() {
+            self.render_finished.push(unsafe {
+                device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// B8G8R8A8_SRGB when the surface supports it, so blits from linear images are encoded for display, and otherwise the
+// surface's first format
+fn choose_format(formats: &[vk::SurfaceFormatKHR]) -> Option<vk::SurfaceFormatKHR> {
+    formats
+        .iter()
+        .find(|format| {
+            format.format == vk::Format::B8G8R8A8_SRGB
+                && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        })
+        .or_else(|| formats.first())
+        .copied()
+}
+
+// The surface's extent, or the window's size within the surface's limits when the surface leaves it to the swapchain
+fn choose_extent(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    window_extent: vk::Extent2D,
+) -> vk::Extent2D {
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+
+    vk::Extent2D {
+        width: window_extent.width.clamp(
+            capabilities.min_image_extent.width,
+            capabilities.max_image_extent.width,
+        ),
+        height: window_extent.height.clamp(
+            capabilities.min_image_extent.height,
+            capabilities.max_image_extent.height,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        }
+    }
+
+    fn extent(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    #[test]
+    fn srgb_formats_are_preferred() {
+        let formats = [
+            surface_format(vk::Format::B8G8R8A8_UNORM),
+            surface_format(vk::Format::B8G8R8A8_SRGB),
+        ];
+
+        assert_eq!(
+            choose_format(&formats),
+            Some(surface_format(vk::Format::B8G8R8A8_SRGB))
+        );
+        assert_eq!(
+            choose_format(&formats[..1]),
+            Some(surface_format(vk::Format::B8G8R8A8_UNORM))
+        );
+        assert_eq!(choose_format(&[]), None);
+    }
+
+    #[test]
+    fn window_sizes_are_clamped_when_the_surface_has_no_extent() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            current_extent: extent(u32::MAX, u32::MAX),
+            min_image_extent: extent(1, 1),
+            max_image_extent: extent(1024, 1024),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            choose_extent(&capabilities, extent(800, 2000)),
+            extent(800, 1024)
+        );
+    }
+
+    #[test]
+    fn surface_extents_are_used_as_is() {
+        let capabilities = vk::SurfaceCapabilitiesKHR {
+            current_extent: extent(640, 480),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            choose_extent(&capabilities, extent(800, 600)),
+            extent(640, 480)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::barrier::{self, Access};
+use crate::buffer::Buffer;
+use crate::compute_errors::ComputeError;
+use crate::context::ComputeContext;
+use crate::storage_image::StorageImage;
+use ash::{vk, Device};
+use std::slice;
+
+// Host visible buffer a storage image is copied into, so its texels can be read on the CPU, e.g. to save them
+pub struct Readback {
+    buffer: Buffer,
+    image: vk::Image,
+    extent: vk::Extent2D,
+}
+
+impl Readback {
+    // Creates a buffer with room for the whole of image, whose texels are texel_size bytes each
+    pub fn new(
+        context: &ComputeContext,
+        image: &StorageImage,
+        texel_size: vk::DeviceSize,
+    ) -> Result<Readback, ComputeError> {
+        let extent = image.extent();
+        let size = extent.width as vk::DeviceSize * extent.height as vk::DeviceSize * texel_size;
+        let buffer = Buffer::new(
+            context,
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        Ok(Readback {
+            buffer,
+            image: image.image(),
+            extent,
+        })
+    }
+
+    // Records copying the image into the buffer, after the accesses in after (usually the compute shader's writes)
+    // The image must be in GENERAL layout, which it stays in
+    pub fn record_copy(&self, device: &Device, command_buffer: vk::CommandBuffer, after: Access) {
+        barrier::record_memory_barrier(device, command_buffer, after, Access::TRANSFER_READ);
+
+        // Rows are tightly packed, from top to bottom
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: barrier::color_layers(),
+            image_offset: vk::Offset3D::default(),
+            image_extent: vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+        };
+
+        unsafe {
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                self.image,
+                vk::ImageLayout::GENERAL,
+                self.buffer.handle(),
+                slice::from_ref(&region),
+            )
+        };
+
+        barrier::record_memory_barrier(
+            device,
+            command_buffer,
+            Access::TRANSFER_WRITE,
+            Access::HOST_READ,
+        );
+    }
+
+    // Texels copied by the last record_copy(), whose submission must have finished
+    pub fn read(&self, device: &Device) -> Result<Vec<u8>, ComputeError> {
+        self.buffer.read(device)
+    }
+
+    // Destroys the buffer
+    pub fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::compute_errors::ComputeError;
+use shaderc::{CompileOptions, Compiler};
+
+// Re-exported so the examples don't need shaderc as a dependency of their own
+pub use shaderc::ShaderKind;
+
+// Compiles GLSL to SPIR-V for Vulkan with shaderc, so the examples can keep their shaders as sources next to the code
+// using them rather than checking in SPIR-V
+pub struct ShaderCompiler {
+    compiler: Compiler,
+    options: CompileOptions<'static>,
+}
+
+impl ShaderCompiler {
+    pub fn new() -> Result<ShaderCompiler, ComputeError> {
+        let compiler = Compiler::new().ok_or(ComputeError::ShaderCompilerCreation)?;
+        let mut options = CompileOptions::new().ok_or(ComputeError::ShaderCompilerCreation)?;
+        options.set_target_env(
+            shaderc::TargetEnv::Vulkan,
+            shaderc::EnvVersion::Vulkan1_0 as u32,
+        );
+
+        Ok(ShaderCompiler { compiler, options })
+    }
+
+    // Compiles source (e.g. from include_str!) as a shader of kind, with file_name standing in for its path in the
+    // compiler log and name identifying it in errors
+    // Failures to compile come back as ComputeError::ShaderCompilation holding the compiler log, and warnings are
+    // printed
+    pub fn compile(
+        &self,
+        source: &str,
+        kind: ShaderKind,
+        file_name: &str,
+        name: &'static str,
+    ) -> Result<Vec<u32>, ComputeError> {
+        let artifact = self
+            .compiler
+            .compile_into_spirv(source, kind, file_name, "main", Some(&self.options))
+            .map_err(|error| ComputeError::ShaderCompilation {
+                name,
+                log: match error {
+                    shaderc::Error::CompilationError(_, log) => log,
+                    error => error.to_string(),
+                },
+            })?;
+
+        if artifact.get_num_warnings() > 0 {
+            eprintln!(
+                "Warnings compiling {} shader:\n{}",
+                name,
+                artifact.get_warning_messages()
+            );
+        }
+
+        Ok(artifact.as_binary().to_vec())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::barrier;
+use crate::compute_errors::ComputeError;
+use crate::context::ComputeContext;
+use ash::{vk, Device};
+
+// 2D color image written by compute shaders through a Binding::StorageImage, such as a simulation grid or a traced
+// picture to be blitted or read back
+// It starts in UNDEFINED layout, and is expected to be moved to GENERAL once and kept there, as storage writes, blits
+// from it, and copies out of it all work in GENERAL
+pub struct StorageImage {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    format: vk::Format,
+    extent: vk::Extent2D,
+}
+
+impl StorageImage {
+    // Creates an image of format, which must support STORAGE_IMAGE, with usage on top of STORAGE for whatever else
+    // reads or writes it (e.g. TRANSFER_SRC to blit it or read it back)
+    pub fn new(
+        context: &ComputeContext,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<StorageImage, ComputeError> {
+        let mut image = StorageImage {
+            image: vk::Image::null(),
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            format,
+            extent,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        image
+            .create(context, usage)
+            .inspect_err(|_| image.destroy(context.device()))?;
+
+        Ok(image)
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Destroys the view and image and frees their memory
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self.memory = vk::DeviceMemory::null();
+    }
+
+    fn create(
+        &mut self,
+        context: &ComputeContext,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<(), ComputeError> {
+        let device = context.device();
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.format)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::STORAGE | usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        let requirements = unsafe { device.get_image_memory_requirements(self.image) };
+        self.memory =
+            context.allocate_memory(&requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+        unsafe { device.bind_image_memory(self.image, self.memory, 0)? };
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(self.format)
+            .subresource_range(barrier::color_range());
+
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        Ok(())
+    }
+}
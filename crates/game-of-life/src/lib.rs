@@ -0,0 +1,92 @@
+mod life;
+
+use compute_context::compute_errors::ComputeError;
+use life::GameOfLife;
+use std::time::{Duration, Instant};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// Generations simulated per second, however fast frames are presented
+const GENERATIONS_PER_SECOND: u32 = 30;
+
+// Most generations caught up on in one frame, so a long stall (e.g. while the window is dragged) is skipped over rather
+// than simulated all at once
+const MAX_GENERATIONS_PER_FRAME: u32 = 4;
+
+pub fn run() {
+    let event_loop = EventLoop::new();
+
+    // Creates a window using a WindowBuilder
+    let window = WindowBuilder::new()
+        .with_title("Game of Life")
+        .with_inner_size(LogicalSize::new(768, 768))
+        .build(&event_loop)
+        .expect("Could not create a window!");
+
+    // Creates all the vulkan state, including the two storage images holding the generations
+    let mut life = match GameOfLife::new(&window) {
+        Ok(life) => life,
+        Err(error) => {
+            eprintln!("Vulkan error: {}", error);
+            return;
+        }
+    };
+
+    let generation_time = Duration::from_secs(1) / GENERATIONS_PER_SECOND;
+    let mut last_frame = Instant::now();
+    // Time passed that hasn't been simulated yet, always less than a generation after each frame
+    let mut unsimulated = Duration::default();
+
+    event_loop.run(move |event, _, control_flow| {
+        // Continually runs the event loop - presentation is paced by FIFO
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                println!("Close button was pressed");
+                *control_flow = ControlFlow::Exit;
+            }
+            // A minimized window has a zero sized surface, so the old swapchain is kept until it is restored
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } if size.width > 0 && size.height > 0 => {
+                exit_on_error(life.resize(size.width, size.height), control_flow)
+            }
+            // Steps the simulation and presents it
+            Event::MainEventsCleared => {
+                let size = window.inner_size();
+                let now = Instant::now();
+                unsimulated += now - last_frame;
+                last_frame = now;
+
+                let mut generations = 0;
+                while unsimulated >= generation_time {
+                    unsimulated -= generation_time;
+                    generations += 1;
+                }
+                generations = generations.min(MAX_GENERATIONS_PER_FRAME);
+
+                if size.width > 0 && size.height > 0 {
+                    exit_on_error(life.draw(generations), control_flow);
+                }
+            }
+            _ => (),
+        }
+    })
+}
+
+// Reports a Vulkan failure and exits, as the simulation can't carry on without it
+fn exit_on_error(result: Result<(), ComputeError>, control_flow: &mut ControlFlow) {
+    if let Err(error) = result {
+        eprintln!("Vulkan error: {}", error);
+        *control_flow = ControlFlow::Exit;
+    }
+}
@@ -0,0 +1,241 @@
+use ash::{vk, Device};
+use compute_context::barrier::{self, Access};
+use compute_context::blit::{self, Letterbox};
+use compute_context::commands::CommandPool;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::presenter::{Frame, Presenter};
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use compute_context::storage_image::StorageImage;
+use winit::window::Window;
+
+// Size of the simulation grid - each cell is one texel of a storage image
+const GRID_SIZE: u32 = 256;
+
+// Must match local_size_x and local_size_y in life.comp
+const WORKGROUP_SIZE: u32 = 16;
+
+// Both generations use the same format, which must support storage and blitting
+const GRID_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+// Seed of the starting population, which any non-zero value scatters differently
+const SEED: u32 = 0x9e37_79b9;
+
+// Color around the grid when the window isn't a whole multiple of it, kept apart from the black of dead cells
+const BAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+// Laid out to match the shader's push constant block
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Step {
+    seed: u32,
+}
+
+// A single u32, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Step {}
+
+// Window's Vulkan state and the simulation drawn with it
+pub struct GameOfLife {
+    life: Life,
+    presenter: Presenter,
+    context: ComputeContext,
+}
+
+impl GameOfLife {
+    pub fn new(window: &Window) -> Result<GameOfLife, ComputeError> {
+        let size = window.inner_size();
+        let context = ComputeContext::new(window, "Game of Life")?;
+        let mut presenter = Presenter::new(&context, size.width, size.height)?;
+        let life = Life::new(&context).inspect_err(|_| presenter.destroy(context.device()))?;
+
+        Ok(GameOfLife {
+            life,
+            presenter,
+            context,
+        })
+    }
+
+    // Rebuilds the swapchain for the new window size
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), ComputeError> {
+        self.presenter.resize(&self.context, width, height)
+    }
+
+    // Advances the simulation by generations and presents the latest one
+    pub fn draw(&mut self, generations: u32) -> Result<(), ComputeError> {
+        let life = &mut self.life;
+
+        self.presenter
+            .draw(&self.context, |device, command_buffer, frame| {
+                for _ in 0..generations {
+                    life.record_step(device, command_buffer, 0);
+                }
+                life.record_blit(device, command_buffer, frame);
+            })
+    }
+}
+
+impl Drop for GameOfLife {
+    fn drop(&mut self) {
+        // Frames may still be reading the grids - if the device was lost there is nothing left to wait for
+        let device = self.context.device();
+        unsafe { device.device_wait_idle().ok() };
+        self.life.destroy(device);
+        self.presenter.destroy(device);
+    }
+}
+
+// Ping-pong pair of grids, stepped by a compute shader and blitted onto each frame
+// Every step and blit is recorded into the frame's command buffer on the one queue, so frames in flight are kept apart
+// by barriers within the queue rather than by waiting on the CPU
+struct Life {
+    grids: Vec<StorageImage>,
+    // Pipeline i reads grid i and writes the other one, so neither descriptor set changes while frames are in flight
+    steps: Vec<ComputePipeline<Step>>,
+    // Index of the grid holding the most recent generation
+    current: usize,
+    letterbox: Letterbox,
+}
+
+impl Life {
+    fn new(context: &ComputeContext) -> Result<Life, ComputeError> {
+        let mut life = Life {
+            grids: Vec::with_capacity(2),
+            steps: Vec::with_capacity(2),
+            current: 0,
+            // Whole multiples keep every cell the same number of pixels across
+            letterbox: Letterbox {
+                integer_scale: true,
+                bar_color: BAR_COLOR,
+                ..Letterbox::new(GRID_SIZE, GRID_SIZE)
+            },
+        };
+
+        // Whatever was created before a failure is in the lists, so it can be cleaned up as a whole
+        life.create(context)
+            .inspect_err(|_| life.destroy(context.device()))?;
+
+        Ok(life)
+    }
+
+    // Records a dispatch computing the next generation into the other grid, which becomes the current one
+    // A non-zero seed fills it with a random population instead
+    fn record_step(&mut self, device: &Device, command_buffer: vk::CommandBuffer, seed: u32) {
+        // The last step's generation is read, and the grid written was last read by a step or a blit that must finish
+        // first
+        barrier::record_memory_barrier(
+            device,
+            command_buffer,
+            Access::COMPUTE_WRITE.and(Access::new(
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::empty(),
+            )),
+            Access::COMPUTE_READ.and(Access::COMPUTE_WRITE),
+        );
+
+        let group_count = pipeline::group_count(GRID_SIZE, WORKGROUP_SIZE);
+        self.steps[self.current].dispatch(
+            device,
+            command_buffer,
+            [group_count, group_count, 1],
+            &Step { seed },
+        );
+
+        // The latest generation may be blitted next
+        barrier::record_memory_barrier(
+            device,
+            command_buffer,
+            Access::COMPUTE_WRITE,
+            Access::TRANSFER_READ,
+        );
+
+        self.current = 1 - self.current;
+    }
+
+    // Records clearing the frame's image to the bar color and blitting the current grid onto the letterboxed area
+    fn record_blit(&self, device: &Device, command_buffer: vk::CommandBuffer, frame: &Frame) {
+        let grid = &self.grids[self.current];
+
+        // Nearest filtering keeps the cells sharp when scaled up
+        blit::record_letterboxed_blit(
+            device,
+            command_buffer,
+            grid.image(),
+            grid.extent(),
+            frame,
+            &self.letterbox,
+            vk::Filter::NEAREST,
+        );
+    }
+
+    // Destroys the pipelines and grids - must be called before the context is dropped
+    fn destroy(&mut self, device: &Device) {
+        for step in self.steps.iter_mut() {
+            step.destroy(device);
+        }
+        for grid in self.grids.iter_mut() {
+            grid.destroy(device);
+        }
+        self.steps.clear();
+        self.grids.clear();
+    }
+
+    fn create(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let extent = vk::Extent2D {
+            width: GRID_SIZE,
+            height: GRID_SIZE,
+        };
+
+        for _ in 0..2 {
+            self.grids.push(StorageImage::new(
+                context,
+                GRID_FORMAT,
+                extent,
+                vk::ImageUsageFlags::TRANSFER_SRC,
+            )?);
+        }
+
+        let code = ShaderCompiler::new()?.compile(
+            include_str!("shaders/life.comp"),
+            ShaderKind::Compute,
+            "life.comp",
+            "life",
+        )?;
+
+        for (current, next) in [(0, 1), (1, 0)].iter() {
+            let bindings = [
+                Binding::StorageImage(self.grids[*current].view()),
+                Binding::StorageImage(self.grids[*next].view()),
+            ];
+            self.steps
+                .push(ComputePipeline::new(context.device(), &code, &bindings)?);
+        }
+
+        self.seed(context)
+    }
+
+    // Moves both grids to GENERAL, where they stay, and fills one with the starting population
+    fn seed(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let device = context.device();
+        let mut command_pool = CommandPool::new(device, context.queue_family_index(), 0)?;
+
+        let result = command_pool.submit_once(device, context.queue(), |command_buffer| {
+            for grid in self.grids.iter() {
+                barrier::record_image_barrier(
+                    device,
+                    command_buffer,
+                    grid.image(),
+                    [vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL],
+                    Access::NONE,
+                    Access::COMPUTE_READ.and(Access::COMPUTE_WRITE),
+                );
+            }
+
+            self.record_step(device, command_buffer, SEED);
+        });
+
+        command_pool.destroy(device);
+        result
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    game_of_life::run();
+}
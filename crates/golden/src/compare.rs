@@ -0,0 +1,206 @@
+use crate::image::Image;
+
+// Largest possible YIQ distance, between black and white
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+// How different two images may be before a golden test fails
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    // Per pixel perceptual distance (0 to 1) below which pixels count as equal, absorbing rounding differences between GPUs
+    pub pixel_threshold: f32,
+    // Fraction (0 to 1) of pixels allowed to exceed pixel_threshold, absorbing isolated edge and precision differences
+    pub max_differing_fraction: f32,
+}
+
+impl Tolerance {
+    pub fn exact() -> Tolerance {
+        Tolerance {
+            pixel_threshold: 0.0,
+            max_differing_fraction: 0.0,
+        }
+    }
+
+    pub fn max_differing_pixels(&self, total_pixels: usize) -> usize {
+        (total_pixels as f32 * self.max_differing_fraction) as usize
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        Tolerance {
+            pixel_threshold: 0.1,
+            max_differing_fraction: 0.001,
+        }
+    }
+}
+
+pub struct Comparison {
+    pub passed: bool,
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    // Faded copy of the expected image with differing pixels marked red, None if the sizes don't match
+    pub diff: Option<Image>,
+}
+
+// Compares two images pixel by pixel in YIQ space, which weights differences roughly by how visible they are
+pub fn compare(expected: &Image, actual: &Image, tolerance: &Tolerance) -> Comparison {
+    let total_pixels = (expected.width * expected.height) as usize;
+
+    if expected.width != actual.width || expected.height != actual.height {
+        return Comparison {
+            passed: false,
+            differing_pixels: total_pixels,
+            total_pixels,
+            diff: None,
+        };
+    }
+
+    let max_delta = MAX_YIQ_DELTA * tolerance.pixel_threshold * tolerance.pixel_threshold;
+    let mut diff_pixels = Vec::with_capacity(expected.pixels.len());
+    let mut differing_pixels = 0;
+
+    for (expected, actual) in expected
+        .pixels
+        .chunks_exact(4)
+        .zip(actual.pixels.chunks_exact(4))
+    {
+        let delta = yiq_delta(expected, actual);
+
+        if delta > max_delta {
+            differing_pixels += 1;
+            diff_pixels.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // Fades matching pixels towards white so the differences stand out
+            let luma = luma(expected);
+            let faded = (255.0 - (255.0 - luma) * 0.1) as u8;
+            diff_pixels.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    Comparison {
+        passed: differing_pixels <= tolerance.max_differing_pixels(total_pixels),
+        differing_pixels,
+        total_pixels,
+        diff: Some(Image::new(expected.width, expected.height, diff_pixels)),
+    }
+}
+
+// Squared perceptual distance between two RGBA8 pixels, both composited over white first
+fn yiq_delta(a: &[u8], b: &[u8]) -> f32 {
+    let (r1, g1, b1) = composite_over_white(a);
+    let (r2, g2, b2) = composite_over_white(b);
+
+    let y = rgb_to_y(r1, g1, b1) - rgb_to_y(r2, g2, b2);
+    let i = rgb_to_i(r1, g1, b1) - rgb_to_i(r2, g2, b2);
+    let q = rgb_to_q(r1, g1, b1) - rgb_to_q(r2, g2, b2);
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn composite_over_white(pixel: &[u8]) -> (f32, f32, f32) {
+    let alpha = pixel[3] as f32 / 255.0;
+    let blend = |channel: u8| 255.0 + (channel as f32 - 255.0) * alpha;
+
+    (blend(pixel[0]), blend(pixel[1]), blend(pixel[2]))
+}
+
+fn luma(pixel: &[u8]) -> f32 {
+    let (r, g, b) = composite_over_white(pixel);
+    rgb_to_y(r, g, b)
+}
+
+fn rgb_to_y(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23
+}
+
+fn rgb_to_i(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9
+}
+
+fn rgb_to_q(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // width by height image filled with one RGBA8 color
+    fn filled(width: u32, height: u32, color: [u8; 4]) -> Image {
+        let pixels = color
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    #[test]
+    fn identical_images_pass_exactly() {
+        let image = filled(4, 4, [10, 200, 30, 255]);
+        let comparison = compare(&image, &image, &Tolerance::exact());
+
+        assert!(comparison.passed);
+        assert_eq!(comparison.differing_pixels, 0);
+        assert_eq!(comparison.total_pixels, 16);
+    }
+
+    #[test]
+    fn size_mismatches_fail_without_a_diff() {
+        let expected = filled(4, 4, [0, 0, 0, 255]);
+        let actual = filled(4, 2, [0, 0, 0, 255]);
+        let comparison = compare(&expected, &actual, &Tolerance::default());
+
+        assert!(!comparison.passed);
+        assert_eq!(comparison.differing_pixels, 16);
+        assert!(comparison.diff.is_none());
+    }
+
+    #[test]
+    fn rounding_differences_are_within_the_default_threshold() {
+        let expected = filled(4, 4, [100, 100, 100, 255]);
+        let actual = filled(4, 4, [101, 99, 100, 255]);
+
+        assert!(compare(&expected, &actual, &Tolerance::default()).passed);
+        assert!(!compare(&expected, &actual, &Tolerance::exact()).passed);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted_against_the_allowed_fraction() {
+        let expected = filled(10, 10, [0, 0, 0, 255]);
+        let mut actual = expected.clone();
+        actual.pixels[..4].copy_from_slice(&[255, 255, 255, 255]);
+
+        let strict = compare(&expected, &actual, &Tolerance::default());
+        assert!(!strict.passed);
+        assert_eq!(strict.differing_pixels, 1);
+
+        let lenient = Tolerance {
+            max_differing_fraction: 0.01,
+            ..Tolerance::default()
+        };
+        assert!(compare(&expected, &actual, &lenient).passed);
+    }
+
+    #[test]
+    fn diff_marks_differing_pixels_red() {
+        let expected = filled(2, 1, [0, 0, 0, 255]);
+        let mut actual = expected.clone();
+        actual.pixels[4..].copy_from_slice(&[255, 255, 255, 255]);
+
+        let diff = compare(&expected, &actual, &Tolerance::default())
+            .diff
+            .unwrap();
+        assert_eq!(diff.pixel(1, 0), [255, 0, 0, 255]);
+        assert_ne!(diff.pixel(0, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn transparent_pixels_compare_as_white() {
+        let expected = filled(1, 1, [255, 255, 255, 255]);
+        let actual = filled(1, 1, [0, 0, 0, 0]);
+
+        assert!(compare(&expected, &actual, &Tolerance::exact()).passed);
+    }
+}
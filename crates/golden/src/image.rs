@@ -0,0 +1,81 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+// Tightly packed RGBA8 image, the layout headless renderers read back into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Image {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "Image pixels must be tightly packed RGBA8!"
+        );
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[offset],
+            self.pixels[offset + 1],
+            self.pixels[offset + 2],
+            self.pixels[offset + 3],
+        ]
+    }
+}
+
+// Reads a PNG, expanding grayscale, RGB, and palette images to RGBA8
+pub fn load_png(path: &Path) -> Image {
+    let file = File::open(path).expect("Could not open the PNG file!");
+
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let (info, mut reader) = decoder.read_info().expect("Could not read the PNG header!");
+
+    let mut buffer = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer)
+        .expect("Could not read the PNG image data!");
+
+    let pixels = match info.color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|ga| vec![ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|g| vec![*g, *g, *g, 255]).collect(),
+        png::ColorType::Indexed => panic!("Palette PNGs should have been expanded!"),
+    };
+
+    Image::new(info.width, info.height, pixels)
+}
+
+pub fn save_png(path: &Path, image: &Image) {
+    let file = File::create(path).expect("Could not create the PNG file!");
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .expect("Could not write the PNG header!");
+    writer
+        .write_image_data(&image.pixels)
+        .expect("Could not write the PNG image data!");
+}
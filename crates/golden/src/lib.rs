@@ -0,0 +1,115 @@
+mod compare;
+mod image;
+
+pub use compare::{compare, Comparison, Tolerance};
+pub use image::{load_png, save_png, Image};
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// Set to regenerate every reference image from the current output instead of comparing against it
+const UPDATE_VARIABLE: &str = "UPDATE_GOLDEN";
+
+// Compares rendered scenes against reference PNGs checked in under reference_dir
+//
+// A missing reference fails the check, so new scenes are added by running once with UPDATE_GOLDEN=1 and committing
+// the written PNG. On a mismatch the actual and diff images are written to failure_dir before panicking.
+pub struct GoldenTest {
+    reference_dir: PathBuf,
+    failure_dir: PathBuf,
+    tolerance: Tolerance,
+}
+
+impl GoldenTest {
+    pub fn new<P: AsRef<Path>>(reference_dir: P) -> GoldenTest {
+        GoldenTest {
+            reference_dir: reference_dir.as_ref().to_path_buf(),
+            failure_dir: env::temp_dir().join("golden-failures"),
+            tolerance: Tolerance::default(),
+        }
+    }
+
+    pub fn with_failure_dir<P: AsRef<Path>>(mut self, failure_dir: P) -> GoldenTest {
+        self.failure_dir = failure_dir.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Tolerance) -> GoldenTest {
+        self.tolerance = tolerance;
+        self
+    }
+
+    // Checks a rendered scene against reference_dir/<name>.png, panicking with the paths of the written images on failure
+    pub fn check(&self, name: &str, actual: &Image) {
+        let reference_path = self.reference_dir.join(format!("{}.png", name));
+
+        if env::var_os(UPDATE_VARIABLE).is_some() {
+            fs::create_dir_all(&self.reference_dir)
+                .expect("Could not create the reference image directory!");
+            save_png(&reference_path, actual);
+            println!("Wrote reference image {}", reference_path.display());
+            return;
+        }
+
+        if !reference_path.exists() {
+            panic!(
+                "Golden image {} has no reference at {}\nRerun with {}=1 to write it from the current output",
+                name,
+                reference_path.display(),
+                UPDATE_VARIABLE
+            );
+        }
+
+        let expected = load_png(&reference_path);
+        let comparison = compare(&expected, actual, &self.tolerance);
+
+        if comparison.passed {
+            return;
+        }
+
+        fs::create_dir_all(&self.failure_dir)
+            .expect("Could not create the golden failure directory!");
+
+        let actual_path = self.failure_dir.join(format!("{}.actual.png", name));
+        save_png(&actual_path, actual);
+
+        let mut message = format!(
+            "Golden image {} does not match {} ({} of {} pixels differ, {} allowed)\nActual: {}",
+            name,
+            reference_path.display(),
+            comparison.differing_pixels,
+            comparison.total_pixels,
+            self.tolerance.max_differing_pixels(comparison.total_pixels),
+            actual_path.display()
+        );
+
+        if let Some(diff) = &comparison.diff {
+            let diff_path = self.failure_dir.join(format!("{}.diff.png", name));
+            save_png(&diff_path, diff);
+            message += &format!("\nDiff: {}", diff_path.display());
+        }
+
+        message += &format!(
+            "\nRerun with {}=1 to accept the new output",
+            UPDATE_VARIABLE
+        );
+
+        panic!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "has no reference")]
+    fn missing_references_fail() {
+        let reference_dir = env::temp_dir().join("golden-missing-references");
+        let golden = GoldenTest::new(&reference_dir);
+
+        golden.check("missing", &Image::new(1, 1, vec![0, 0, 0, 255]));
+    }
+}
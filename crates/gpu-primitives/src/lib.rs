@@ -0,0 +1,4 @@
+// Reusable compute passes over storage buffers, recorded into the caller's command buffers, as building blocks for
+// work like particle compaction and GPU culling
+pub mod prefix_sum;
+pub mod radix_sort;
@@ -0,0 +1,227 @@
+use ash::{vk, Device};
+use compute_context::barrier::{self, Access};
+use compute_context::buffer::Buffer;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use std::mem;
+
+// Values scanned by each workgroup - must match local_size_x in prefix_sum.comp and prefix_sum_add.comp
+const BLOCK_SIZE: u32 = 256;
+
+// Laid out to match the push constant blocks of both shaders, where prefix_sum_add.comp only reads count
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Level {
+    count: u32,
+    write_sums: u32,
+}
+
+// Two u32s, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Level {}
+
+// Replaces the u32 values in a storage buffer with their exclusive prefix sum (each value becomes the wrapping sum of
+// the values before it), e.g. to turn per-item counts into output offsets for compaction
+// Every block of values is scanned on its own, and their totals are scanned the same way one level up until a single
+// block is left, after which each level's blocks are offset by the scanned totals of the level above
+// The buffer is fixed at creation, as the pipelines' descriptor sets point at it
+pub struct PrefixSum {
+    // Buffer being scanned, which the caller owns
+    buffer: vk::Buffer,
+    // Number of values at each level, starting with the buffer being scanned
+    lengths: Vec<u32>,
+    // Block totals of each level but the last, which the level above scans in place
+    sums: Vec<Buffer>,
+    // Pipeline i scans level i, writing its block totals to sums[i] unless it is the last level
+    scans: Vec<ComputePipeline<Level>>,
+    // Pipeline i offsets level i by sums[i]
+    adds: Vec<ComputePipeline<Level>>,
+}
+
+impl PrefixSum {
+    // Creates the pipelines scanning the first len values of buffer, which needs STORAGE_BUFFER usage
+    pub fn new(
+        context: &ComputeContext,
+        buffer: vk::Buffer,
+        len: u32,
+    ) -> Result<PrefixSum, ComputeError> {
+        let lengths = level_lengths(len);
+        let mut prefix_sum = PrefixSum {
+            buffer,
+            sums: Vec::with_capacity(lengths.len()),
+            scans: Vec::with_capacity(lengths.len()),
+            adds: Vec::with_capacity(lengths.len()),
+            lengths,
+        };
+
+        // Whatever was created before a failure is in the lists, so it can be cleaned up as a whole
+        prefix_sum
+            .create(context)
+            .inspect_err(|_| prefix_sum.destroy(context.device()))?;
+
+        Ok(prefix_sum)
+    }
+
+    // Number of values scanned
+    pub fn len(&self) -> u32 {
+        self.lengths.first().copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    // Records the scan into command_buffer, making the result visible to reader
+    // Earlier writes to the buffer must already be visible to compute shaders
+    pub fn record(&self, device: &Device, command_buffer: vk::CommandBuffer, reader: Access) {
+        let level_count = self.lengths.len();
+        let dispatch_count = self.scans.len() + self.adds.len();
+        let mut dispatched = 0;
+
+        // Everything is read again by a later dispatch apart from what the last one writes
+        let mut dispatch = |pipeline: &ComputePipeline<Level>, level: usize| {
+            let push_constants = Level {
+                count: self.lengths[level],
+                write_sums: (level + 1 < level_count) as u32,
+            };
+            pipeline.dispatch(
+                device,
+                command_buffer,
+                [pipeline::group_count(self.lengths[level], BLOCK_SIZE), 1, 1],
+                &push_constants,
+            );
+
+            dispatched += 1;
+            let next = if dispatched == dispatch_count {
+                reader
+            } else {
+                Access::COMPUTE_READ.and(Access::COMPUTE_WRITE)
+            };
+            barrier::record_memory_barrier(device, command_buffer, Access::COMPUTE_WRITE, next);
+        };
+
+        // Scans go up the levels, each leaving its block totals for the next
+        for (level, scan) in self.scans.iter().enumerate() {
+            dispatch(scan, level);
+        }
+
+        // Offsets come back down, as each level's totals are only final once the level above has been offset
+        for (level, add) in self.adds.iter().enumerate().rev() {
+            dispatch(add, level);
+        }
+    }
+
+    // Destroys the pipelines and block total buffers - must be called before the context is dropped
+    pub fn destroy(&mut self, device: &Device) {
+        for pipeline in self.scans.iter_mut().chain(self.adds.iter_mut()) {
+            pipeline.destroy(device);
+        }
+        for sums in self.sums.iter_mut() {
+            sums.destroy(device);
+        }
+        self.scans.clear();
+        self.adds.clear();
+        self.sums.clear();
+    }
+
+    fn create(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        if self.lengths.is_empty() {
+            return Ok(());
+        }
+
+        for length in self.lengths.iter().skip(1) {
+            self.sums.push(Buffer::new(
+                context,
+                (*length as usize * mem::size_of::<u32>()) as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?);
+        }
+
+        let compiler = ShaderCompiler::new()?;
+        let scan_code = compiler.compile(
+            include_str!("shaders/prefix_sum.comp"),
+            ShaderKind::Compute,
+            "prefix_sum.comp",
+            "prefix sum",
+        )?;
+        let add_code = compiler.compile(
+            include_str!("shaders/prefix_sum_add.comp"),
+            ShaderKind::Compute,
+            "prefix_sum_add.comp",
+            "prefix sum add",
+        )?;
+
+        let device = context.device();
+        for level in 0..self.lengths.len() {
+            let data = self.level_buffer(level);
+            // The last level has no totals to write, so its unused binding points back at its own values
+            let sums = self.sums.get(level).map_or(data, Buffer::handle);
+            let bindings = [Binding::StorageBuffer(data), Binding::StorageBuffer(sums)];
+
+            self.scans
+                .push(ComputePipeline::new(device, &scan_code, &bindings)?);
+            if level < self.sums.len() {
+                self.adds
+                    .push(ComputePipeline::new(device, &add_code, &bindings)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Buffer holding the values of level, which the scan of level - 1 wrote the block totals of
+    fn level_buffer(&self, level: usize) -> vk::Buffer {
+        match level {
+            0 => self.buffer,
+            _ => self.sums[level - 1].handle(),
+        }
+    }
+}
+
+// Number of values at each level of the scan of len values, from len itself up to the level fitting in one block
+fn level_lengths(len: u32) -> Vec<u32> {
+    let mut lengths = Vec::new();
+    let mut length = len;
+
+    while length > 0 {
+        lengths.push(length);
+        if length <= BLOCK_SIZE {
+            break;
+        }
+        length = pipeline::group_count(length, BLOCK_SIZE);
+    }
+
+    lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_blocks_need_one_level() {
+        assert_eq!(level_lengths(1), vec![1]);
+        assert_eq!(level_lengths(BLOCK_SIZE), vec![BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn levels_hold_one_total_per_block_below() {
+        assert_eq!(level_lengths(BLOCK_SIZE + 1), vec![BLOCK_SIZE + 1, 2]);
+        assert_eq!(
+            level_lengths(BLOCK_SIZE * BLOCK_SIZE),
+            vec![BLOCK_SIZE * BLOCK_SIZE, BLOCK_SIZE]
+        );
+        assert_eq!(
+            level_lengths(BLOCK_SIZE * BLOCK_SIZE + 1),
+            vec![BLOCK_SIZE * BLOCK_SIZE + 1, BLOCK_SIZE + 1, 2]
+        );
+    }
+
+    #[test]
+    fn empty_buffers_have_no_levels() {
+        assert!(level_lengths(0).is_empty());
+    }
+}
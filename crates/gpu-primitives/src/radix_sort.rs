@@ -0,0 +1,242 @@
+use crate::prefix_sum::PrefixSum;
+use ash::{vk, Device};
+use compute_context::barrier::{self, Access};
+use compute_context::buffer::Buffer;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use std::mem;
+
+// Keys counted and scattered by each workgroup - must match local_size_x in radix_count.comp and radix_scatter.comp
+const BLOCK_SIZE: u32 = 256;
+
+// Bits of the key sorted on by each pass, giving 16 buckets
+const DIGIT_BITS: u32 = 4;
+const RADIX: u32 = 1 << DIGIT_BITS;
+
+// Enough passes to cover a u32 key, which is even, so the result ends up back in the caller's buffers
+const PASSES: u32 = 32 / DIGIT_BITS;
+
+// Laid out to match the push constant blocks of both shaders, where radix_count.comp doesn't read has_values
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Pass {
+    count: u32,
+    shift: u32,
+    has_values: u32,
+}
+
+// Three u32s, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Pass {}
+
+// Stable least significant digit radix sort of u32 keys in a storage buffer, optionally moving a u32 value along with
+// each key, e.g. to order particle indices by depth or cell
+// Each pass counts the keys per block with each digit, scans the counts into output offsets with a PrefixSum, and
+// scatters the keys to them, ping-ponging with scratch buffers of the same size
+// The buffers are fixed at creation, as the pipelines' descriptor sets point at them
+pub struct RadixSort {
+    len: u32,
+    has_values: bool,
+    // Keys and values are sorted back and forth between the caller's buffers and these
+    scratch_keys: Option<Buffer>,
+    scratch_values: Option<Buffer>,
+    // Digit counts of each block, laid out digit major so their prefix sum gives each block's output offsets
+    histogram: Option<Buffer>,
+    offsets: Option<PrefixSum>,
+    // Pipeline i counts the keys in buffer i, where buffer 0 is the caller's and buffer 1 the scratch one
+    counts: Vec<ComputePipeline<Pass>>,
+    // Pipeline i scatters from buffer i to the other one
+    scatters: Vec<ComputePipeline<Pass>>,
+    // Handles of the caller's and scratch buffers, in that order
+    keys: [vk::Buffer; 2],
+    values: [vk::Buffer; 2],
+}
+
+impl RadixSort {
+    // Creates the pipelines sorting the first len keys of keys, and the values along with them if given
+    // Both buffers need STORAGE_BUFFER usage and room for len u32s
+    pub fn new(
+        context: &ComputeContext,
+        keys: vk::Buffer,
+        values: Option<vk::Buffer>,
+        len: u32,
+    ) -> Result<RadixSort, ComputeError> {
+        let mut sort = RadixSort {
+            len,
+            has_values: values.is_some(),
+            scratch_keys: None,
+            scratch_values: None,
+            histogram: None,
+            offsets: None,
+            counts: Vec::with_capacity(2),
+            scatters: Vec::with_capacity(2),
+            keys: [keys, vk::Buffer::null()],
+            // Without values, the values bindings point at the keys, and the shader never touches them
+            values: [values.unwrap_or(keys), vk::Buffer::null()],
+        };
+
+        // Whatever was created before a failure is held by the sort, so it can be cleaned up as a whole
+        sort.create(context)
+            .inspect_err(|_| sort.destroy(context.device()))?;
+
+        Ok(sort)
+    }
+
+    // Number of keys sorted
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Records the sort into command_buffer, making the sorted keys and values visible to reader
+    // Earlier writes to the keys and values must already be visible to compute shaders
+    pub fn record(&self, device: &Device, command_buffer: vk::CommandBuffer, reader: Access) {
+        let offsets = match &self.offsets {
+            Some(offsets) => offsets,
+            None => return,
+        };
+        let compute = Access::COMPUTE_READ.and(Access::COMPUTE_WRITE);
+        let group_count = [pipeline::group_count(self.len, BLOCK_SIZE), 1, 1];
+
+        for pass in 0..PASSES {
+            let source = (pass % 2) as usize;
+            let push_constants = Pass {
+                count: self.len,
+                shift: pass * DIGIT_BITS,
+                has_values: self.has_values as u32,
+            };
+
+            self.counts[source].dispatch(device, command_buffer, group_count, &push_constants);
+            barrier::record_memory_barrier(device, command_buffer, Access::COMPUTE_WRITE, compute);
+
+            offsets.record(device, command_buffer, compute);
+
+            self.scatters[source].dispatch(device, command_buffer, group_count, &push_constants);
+
+            // Only the last pass writes the final order, which goes to the caller's reader
+            let next = if pass + 1 == PASSES { reader } else { compute };
+            barrier::record_memory_barrier(device, command_buffer, Access::COMPUTE_WRITE, next);
+        }
+    }
+
+    // Destroys the pipelines and scratch buffers - must be called before the context is dropped
+    pub fn destroy(&mut self, device: &Device) {
+        for pipeline in self.counts.iter_mut().chain(self.scatters.iter_mut()) {
+            pipeline.destroy(device);
+        }
+        self.counts.clear();
+        self.scatters.clear();
+
+        if let Some(mut offsets) = self.offsets.take() {
+            offsets.destroy(device);
+        }
+        for buffer in [
+            &mut self.histogram,
+            &mut self.scratch_values,
+            &mut self.scratch_keys,
+        ]
+        .iter_mut()
+        {
+            if let Some(mut buffer) = buffer.take() {
+                buffer.destroy(device);
+            }
+        }
+    }
+
+    fn create(&mut self, context: &ComputeContext) -> Result<(), ComputeError> {
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        let storage_buffer = |size: vk::DeviceSize| {
+            Buffer::new(
+                context,
+                size,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        };
+        let key_size = (self.len as usize * mem::size_of::<u32>()) as vk::DeviceSize;
+
+        let scratch_keys = storage_buffer(key_size)?;
+        self.keys[1] = scratch_keys.handle();
+        self.values[1] = self.keys[1];
+        self.scratch_keys = Some(scratch_keys);
+
+        if self.has_values {
+            let scratch_values = storage_buffer(key_size)?;
+            self.values[1] = scratch_values.handle();
+            self.scratch_values = Some(scratch_values);
+        }
+
+        let histogram_len = histogram_len(self.len);
+        let histogram =
+            storage_buffer((histogram_len as usize * mem::size_of::<u32>()) as vk::DeviceSize)?;
+        let histogram_handle = histogram.handle();
+        self.histogram = Some(histogram);
+        self.offsets = Some(PrefixSum::new(context, histogram_handle, histogram_len)?);
+
+        let compiler = ShaderCompiler::new()?;
+        let count_code = compiler.compile(
+            include_str!("shaders/radix_count.comp"),
+            ShaderKind::Compute,
+            "radix_count.comp",
+            "radix count",
+        )?;
+        let scatter_code = compiler.compile(
+            include_str!("shaders/radix_scatter.comp"),
+            ShaderKind::Compute,
+            "radix_scatter.comp",
+            "radix scatter",
+        )?;
+
+        let device = context.device();
+        for source in 0..2 {
+            let destination = 1 - source;
+
+            self.counts.push(ComputePipeline::new(
+                device,
+                &count_code,
+                &[
+                    Binding::StorageBuffer(self.keys[source]),
+                    Binding::StorageBuffer(histogram_handle),
+                ],
+            )?);
+            self.scatters.push(ComputePipeline::new(
+                device,
+                &scatter_code,
+                &[
+                    Binding::StorageBuffer(self.keys[source]),
+                    Binding::StorageBuffer(self.values[source]),
+                    Binding::StorageBuffer(self.keys[destination]),
+                    Binding::StorageBuffer(self.values[destination]),
+                    Binding::StorageBuffer(histogram_handle),
+                ],
+            )?);
+        }
+
+        Ok(())
+    }
+}
+
+// Number of digit counts in the histogram for len keys, one per digit for every block
+fn histogram_len(len: u32) -> u32 {
+    RADIX * pipeline::group_count(len, BLOCK_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_count_every_digit_per_block() {
+        assert_eq!(histogram_len(1), RADIX);
+        assert_eq!(histogram_len(BLOCK_SIZE), RADIX);
+        assert_eq!(histogram_len(BLOCK_SIZE + 1), 2 * RADIX);
+    }
+}
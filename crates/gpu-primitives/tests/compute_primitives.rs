@@ -0,0 +1,179 @@
+// Runs the compute primitives on the GPU and checks them against the same operations done on the CPU
+use ash::vk;
+use compute_context::barrier::Access;
+use compute_context::buffer::Buffer;
+use compute_context::commands::CommandPool;
+use compute_context::context::ComputeContext;
+use gpu_primitives::prefix_sum::PrefixSum;
+use gpu_primitives::radix_sort::RadixSort;
+use std::mem;
+
+// Lengths around the block size, and large enough for the prefix sum to need three levels
+const LENGTHS: [usize; 6] = [1, 255, 256, 257, 5000, 70_000];
+
+// Headless context, or None when there is no Vulkan device to run on, in which case the test is skipped
+fn headless_context() -> Option<ComputeContext> {
+    match ComputeContext::new_headless("GPU primitives tests") {
+        Ok(context) => Some(context),
+        Err(error) => {
+            eprintln!("Skipping, as no Vulkan device is available: {}", error);
+            None
+        }
+    }
+}
+
+// Host visible storage buffer holding data, which the host reads back through read()
+fn mapped_buffer(context: &ComputeContext, data: &[u32]) -> Buffer {
+    let mut buffer = Buffer::new(
+        context,
+        mem::size_of_val(data) as vk::DeviceSize,
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )
+    .expect("Could not create a buffer!");
+    buffer
+        .write(context.device(), data)
+        .expect("Could not write the buffer!");
+    buffer
+}
+
+fn read(context: &ComputeContext, buffer: &Buffer) -> Vec<u32> {
+    buffer
+        .read(context.device())
+        .expect("Could not read the buffer!")
+}
+
+// Records commands and waits for them, for results read back by the host
+fn submit<F: FnOnce(vk::CommandBuffer, Access)>(context: &ComputeContext, record: F) {
+    let device = context.device();
+
+    let mut command_pool = CommandPool::new(device, context.queue_family_index(), 0)
+        .expect("Could not create a command pool!");
+    command_pool
+        .submit_once(device, context.queue(), |command_buffer| {
+            record(command_buffer, Access::HOST_READ)
+        })
+        .expect("Could not submit the commands!");
+    command_pool.destroy(device);
+}
+
+// Small pseudo random values, so the sums stay well clear of wrapping
+fn random_values(len: usize, seed: u32, max: u32) -> Vec<u32> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % max
+        })
+        .collect()
+}
+
+fn exclusive_prefix_sum(values: &[u32]) -> Vec<u32> {
+    values
+        .iter()
+        .scan(0u32, |sum, value| {
+            let before = *sum;
+            *sum = sum.wrapping_add(*value);
+            Some(before)
+        })
+        .collect()
+}
+
+#[test]
+fn prefix_sums_match_the_cpu() {
+    let context = match headless_context() {
+        Some(context) => context,
+        None => return,
+    };
+    let device = context.device();
+
+    for (index, len) in LENGTHS.iter().enumerate() {
+        let values = random_values(*len, 0x1234_5678 + index as u32, 16);
+        let mut buffer = mapped_buffer(&context, &values);
+        let mut prefix_sum = PrefixSum::new(&context, buffer.handle(), *len as u32)
+            .expect("Could not create the prefix sum!");
+
+        submit(&context, |command_buffer, host| {
+            prefix_sum.record(device, command_buffer, host)
+        });
+
+        assert_eq!(
+            read(&context, &buffer),
+            exclusive_prefix_sum(&values),
+            "Prefix sum of {} values differs",
+            len
+        );
+
+        prefix_sum.destroy(device);
+        buffer.destroy(device);
+    }
+}
+
+#[test]
+fn radix_sorts_match_the_cpu() {
+    let context = match headless_context() {
+        Some(context) => context,
+        None => return,
+    };
+    let device = context.device();
+
+    for (index, len) in LENGTHS.iter().enumerate() {
+        // Few distinct keys, so stability is checked through the values
+        let max_key = if index % 2 == 0 { u32::MAX } else { 64 };
+        let keys = random_values(*len, 0x9e37_79b9 + index as u32, max_key);
+        let values: Vec<u32> = (0..*len as u32).collect();
+
+        let mut key_buffer = mapped_buffer(&context, &keys);
+        let mut value_buffer = mapped_buffer(&context, &values);
+        let mut sort = RadixSort::new(
+            &context,
+            key_buffer.handle(),
+            Some(value_buffer.handle()),
+            *len as u32,
+        )
+        .expect("Could not create the radix sort!");
+
+        submit(&context, |command_buffer, host| {
+            sort.record(device, command_buffer, host)
+        });
+
+        // sort_by_key is stable, as the GPU sort must be
+        let mut expected: Vec<(u32, u32)> = keys.iter().copied().zip(values).collect();
+        expected.sort_by_key(|(key, _)| *key);
+        let sorted: Vec<(u32, u32)> = read(&context, &key_buffer)
+            .into_iter()
+            .zip(read(&context, &value_buffer))
+            .collect();
+        assert_eq!(sorted, expected, "Radix sort of {} keys differs", len);
+
+        sort.destroy(device);
+        value_buffer.destroy(device);
+        key_buffer.destroy(device);
+    }
+}
+
+#[test]
+fn radix_sorts_work_without_values() {
+    let context = match headless_context() {
+        Some(context) => context,
+        None => return,
+    };
+    let device = context.device();
+
+    let mut keys = random_values(1000, 42, u32::MAX);
+    let mut buffer = mapped_buffer(&context, &keys);
+    let mut sort = RadixSort::new(&context, buffer.handle(), None, keys.len() as u32)
+        .expect("Could not create the radix sort!");
+
+    submit(&context, |command_buffer, host| {
+        sort.record(device, command_buffer, host)
+    });
+
+    keys.sort_unstable();
+    assert_eq!(read(&context, &buffer), keys);
+
+    sort.destroy(device);
+    buffer.destroy(device);
+}
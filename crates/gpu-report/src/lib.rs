@@ -0,0 +1,23 @@
+mod report;
+
+use std::env;
+use winit::{event_loop::EventLoop, window::WindowBuilder};
+
+// Prints everything Vulkan reports about the machine's GPUs, to be attached to bug reports
+//
+// Surface capabilities need a window, so a hidden one is created unless --no-surface is passed (e.g. over SSH).
+pub fn run() {
+    if env::args().any(|arg| arg == "--no-surface") {
+        report::print_report(None);
+        return;
+    }
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("GPU Report")
+        .with_visible(false)
+        .build(&event_loop)
+        .expect("Could not create a window! Pass --no-surface to skip surface capabilities");
+
+    report::print_report(Some(&window));
+}
@@ -0,0 +1,3 @@
+fn main() {
+    gpu_report::run();
+}
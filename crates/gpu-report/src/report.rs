@@ -0,0 +1,326 @@
+use ash::{extensions::khr::Surface, vk, Entry, Instance};
+use std::ffi::{CStr, CString};
+use winit::window::Window;
+
+const BAD_ERROR: &str = "Something went incredibly wrong!";
+
+pub fn print_report(window: Option<&Window>) {
+    let entry = unsafe { Entry::new().expect("Could not load the Vulkan library!") };
+
+    print_instance(&entry);
+
+    let instance = create_instance(&entry, window);
+
+    // The surface is only used to query capabilities, nothing is ever presented to it
+    let surface = window.map(|window| {
+        let surface_khr = unsafe {
+            ash_window::create_surface(&entry, &instance, window, None)
+                .expect("Unsupported platform!")
+        };
+        (surface_khr, Surface::new(&entry, &instance))
+    });
+
+    let physical_devices = unsafe { instance.enumerate_physical_devices().expect(BAD_ERROR) };
+
+    if physical_devices.is_empty() {
+        println!("No Vulkan devices found!");
+    }
+
+    for (index, physical_device) in physical_devices.iter().enumerate() {
+        print_device(&instance, *physical_device, index);
+        print_queue_families(&instance, *physical_device, surface.as_ref());
+        print_memory(&instance, *physical_device);
+        print_device_extensions(&instance, *physical_device);
+
+        if let Some((surface_khr, surface)) = &surface {
+            print_surface(surface, *surface_khr, *physical_device);
+        }
+    }
+
+    unsafe {
+        if let Some((surface_khr, surface)) = surface {
+            surface.destroy_surface(surface_khr, None);
+        }
+        instance.destroy_instance(None);
+    }
+}
+
+// Creates an instance with just the extensions needed to create a surface for the window
+fn create_instance(entry: &Entry, window: Option<&Window>) -> Instance {
+    let extension_names_raw = match window {
+        Some(window) => ash_window::enumerate_required_extensions(window)
+            .expect("Unsupported platform!")
+            .iter()
+            .map(|ext| ext.as_ptr())
+            .collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+
+    let application_name = CString::new("GPU Report").unwrap();
+
+    let app_info = vk::ApplicationInfo::builder()
+        .application_name(&application_name)
+        .application_version(vk::make_api_version(0, 1, 0, 0))
+        .api_version(vk::make_api_version(0, 1, 0, 0));
+
+    let create_info = vk::InstanceCreateInfo::builder()
+        .application_info(&app_info)
+        .enabled_extension_names(&extension_names_raw);
+
+    unsafe { entry.create_instance(&create_info, None).expect(BAD_ERROR) }
+}
+
+fn print_instance(entry: &Entry) {
+    // Vulkan 1.0 loaders don't have vkEnumerateInstanceVersion
+    let version = entry
+        .try_enumerate_instance_version()
+        .expect(BAD_ERROR)
+        .unwrap_or_else(|| vk::make_api_version(0, 1, 0, 0));
+
+    println!("Instance version: {}", format_version(version));
+
+    let mut extensions = entry
+        .enumerate_instance_extension_properties()
+        .expect(BAD_ERROR);
+    extensions.sort_by(|a, b| extension_name(a).cmp(extension_name(b)));
+
+    println!("Instance extensions ({}):", extensions.len());
+    for extension in &extensions {
+        println!(
+            "    {:<48} v{}",
+            extension_name(extension).to_string_lossy(),
+            extension.spec_version
+        );
+    }
+}
+
+fn print_device(instance: &Instance, physical_device: vk::PhysicalDevice, index: usize) {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let device_name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+
+    println!();
+    println!(
+        "==== Device {}: {} ====",
+        index,
+        device_name.to_string_lossy()
+    );
+    println!("    {:<20} {:?}", "Type", properties.device_type);
+    println!(
+        "    {:<20} {}",
+        "API version",
+        format_version(properties.api_version)
+    );
+    // Driver versions are vendor specific, so the raw value is printed for the vendor to decode
+    println!(
+        "    {:<20} {:#x}",
+        "Driver version", properties.driver_version
+    );
+    println!("    {:<20} {:#06x}", "Vendor ID", properties.vendor_id);
+    println!("    {:<20} {:#06x}", "Device ID", properties.device_id);
+    println!(
+        "    {:<20} {}",
+        "Max image size", properties.limits.max_image_dimension2_d
+    );
+    println!(
+        "    {:<20} {}",
+        "Max push constants", properties.limits.max_push_constants_size
+    );
+}
+
+fn print_queue_families(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<&(vk::SurfaceKHR, Surface)>,
+) {
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+
+    println!();
+    println!("  Queue families:");
+    println!(
+        "    {:<6} {:<6} {:<10} {:<8} Flags",
+        "Index", "Count", "Timestamp", "Present"
+    );
+
+    for (index, queue_family) in queue_families.iter().enumerate() {
+        let present = match surface {
+            Some((surface_khr, surface)) => unsafe {
+                let supported = surface
+                    .get_physical_device_surface_support(
+                        physical_device,
+                        index as u32,
+                        *surface_khr,
+                    )
+                    .expect(BAD_ERROR);
+                if supported {
+                    "yes"
+                } else {
+                    "no"
+                }
+            },
+            None => "-",
+        };
+
+        println!(
+            "    {:<6} {:<6} {:<10} {:<8} {:?}",
+            index,
+            queue_family.queue_count,
+            format!("{} bits", queue_family.timestamp_valid_bits),
+            present,
+            queue_family.queue_flags
+        );
+    }
+}
+
+fn print_memory(instance: &Instance, physical_device: vk::PhysicalDevice) {
+    let memory = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    println!();
+    println!("  Memory heaps:");
+    println!("    {:<6} {:>12} Flags", "Index", "Size");
+
+    for (index, heap) in memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+    {
+        println!(
+            "    {:<6} {:>8} MiB {:?}",
+            index,
+            heap.size / (1024 * 1024),
+            heap.flags
+        );
+    }
+
+    println!();
+    println!("  Memory types:");
+    println!("    {:<6} {:<6} Properties", "Index", "Heap");
+
+    for (index, memory_type) in memory.memory_types[..memory.memory_type_count as usize]
+        .iter()
+        .enumerate()
+    {
+        println!(
+            "    {:<6} {:<6} {:?}",
+            index, memory_type.heap_index, memory_type.property_flags
+        );
+    }
+}
+
+fn print_device_extensions(instance: &Instance, physical_device: vk::PhysicalDevice) {
+    let mut extensions = unsafe {
+        instance
+            .enumerate_device_extension_properties(physical_device)
+            .expect(BAD_ERROR)
+    };
+    extensions.sort_by(|a, b| extension_name(a).cmp(extension_name(b)));
+
+    println!();
+    println!("  Device extensions ({}):", extensions.len());
+    for extension in &extensions {
+        println!(
+            "    {:<48} v{}",
+            extension_name(extension).to_string_lossy(),
+            extension.spec_version
+        );
+    }
+}
+
+fn print_surface(
+    surface: &Surface,
+    surface_khr: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+) {
+    let (capabilities, formats, present_modes) = unsafe {
+        (
+            surface
+                .get_physical_device_surface_capabilities(physical_device, surface_khr)
+                .expect(BAD_ERROR),
+            surface
+                .get_physical_device_surface_formats(physical_device, surface_khr)
+                .expect(BAD_ERROR),
+            surface
+                .get_physical_device_surface_present_modes(physical_device, surface_khr)
+                .expect(BAD_ERROR),
+        )
+    };
+
+    println!();
+    println!("  Surface capabilities:");
+    println!(
+        "    {:<20} {} to {}",
+        "Image count",
+        capabilities.min_image_count,
+        // A max image count of 0 means there is no upper limit
+        if capabilities.max_image_count == 0 {
+            String::from("unlimited")
+        } else {
+            capabilities.max_image_count.to_string()
+        }
+    );
+    println!(
+        "    {:<20} {}",
+        "Current extent",
+        // u32::MAX means the swapchain decides the surface size
+        if capabilities.current_extent.width == u32::MAX {
+            String::from("decided by swapchain")
+        } else {
+            format_extent(capabilities.current_extent)
+        }
+    );
+    println!(
+        "    {:<20} {} to {}",
+        "Extent range",
+        format_extent(capabilities.min_image_extent),
+        format_extent(capabilities.max_image_extent)
+    );
+    println!(
+        "    {:<20} {}",
+        "Max array layers", capabilities.max_image_array_layers
+    );
+    println!(
+        "    {:<20} {:?}",
+        "Transforms", capabilities.supported_transforms
+    );
+    println!(
+        "    {:<20} {:?}",
+        "Composite alpha", capabilities.supported_composite_alpha
+    );
+    println!(
+        "    {:<20} {:?}",
+        "Usage", capabilities.supported_usage_flags
+    );
+
+    println!();
+    println!("  Surface formats:");
+    println!("    {:<32} Color space", "Format");
+    for format in &formats {
+        println!(
+            "    {:<32} {:?}",
+            format!("{:?}", format.format),
+            format.color_space
+        );
+    }
+
+    println!();
+    println!("  Present modes:");
+    for present_mode in &present_modes {
+        println!("    {:?}", present_mode);
+    }
+}
+
+fn extension_name(extension: &vk::ExtensionProperties) -> &CStr {
+    unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) }
+}
+
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+fn format_extent(extent: vk::Extent2D) -> String {
+    format!("{}x{}", extent.width, extent.height)
+}
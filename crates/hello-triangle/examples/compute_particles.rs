@@ -0,0 +1,241 @@
+use app::graphics::commands::CommandPool;
+use app::graphics::compute::{self, ComputeBinding, ComputeOutput, ComputePipeline};
+use app::graphics::graphics_errors::GraphicsError;
+use app::graphics::mapped_buffer::MappedBuffer;
+use app::graphics::memory::Allocator;
+use app::graphics::pod::Pod;
+use app::graphics::queue_ownership::{QueueSharing, QueueUse};
+use app::graphics::shaders::ShaderCompiler;
+use ash::{vk, Device, Entry, Instance};
+use shaderc::ShaderKind;
+use std::{ffi::CString, mem, slice};
+
+// Must match local_size_x in particles.comp
+const WORKGROUP_SIZE: u32 = 64;
+
+const PARTICLE_COUNT: usize = 1000;
+const STEPS: u32 = 120;
+const DT: f32 = 1.0 / 60.0;
+
+// Laid out to match the Particle struct in particles.comp, which std430 packs without padding
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+
+// Laid out to match the shader's push constant block
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Step {
+    dt: f32,
+    count: u32,
+}
+
+// An f32 and a u32, so there is no padding
+unsafe impl Pod for Step {}
+
+// Simulates particles falling and bouncing on the GPU without a window, then prints where a few of them ended up
+fn main() -> Result<(), GraphicsError> {
+    let entry = unsafe { Entry::new()? };
+    let instance = create_instance(&entry)?;
+
+    let result = simulate(&instance);
+
+    unsafe { instance.destroy_instance(None) };
+    result
+}
+
+fn simulate(instance: &Instance) -> Result<(), GraphicsError> {
+    let (physical_device, family_index) = pick_physical_device(instance)?;
+    let device = create_logical_device(instance, physical_device, family_index)?;
+
+    let result = run(instance, physical_device, &device, family_index);
+
+    unsafe { device.destroy_device(None) };
+    result
+}
+
+fn run(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &Device,
+    family_index: u32,
+) -> Result<(), GraphicsError> {
+    let queue = unsafe { device.get_device_queue(family_index, 0) };
+    let mut allocator = Allocator::new(
+        unsafe { instance.get_physical_device_memory_properties(physical_device) },
+        &unsafe { instance.get_physical_device_properties(physical_device) }.limits,
+    );
+
+    // Host visible so the starting state can be written and the result read without staging copies
+    let mut particles = match MappedBuffer::<Particle>::new(
+        device,
+        &allocator,
+        PARTICLE_COUNT,
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    ) {
+        Ok(particles) => particles,
+        Err(error) => {
+            allocator.destroy(device);
+            return Err(error);
+        }
+    };
+
+    // Particles start in a row at different heights, thrown sideways at different speeds
+    for (index, particle) in particles.as_mut_slice().iter_mut().enumerate() {
+        let t = index as f32 / PARTICLE_COUNT as f32;
+        *particle = Particle {
+            position: [t * 10.0, 1.0 + t * 4.0],
+            velocity: [1.0 - t * 2.0, 0.0],
+        };
+    }
+
+    let result = particles
+        .flush(device, 0..PARTICLE_COUNT)
+        .and_then(|()| dispatch_steps(device, queue, family_index, &particles))
+        .and_then(|()| {
+            particles.invalidate(device, 0..PARTICLE_COUNT)?;
+            for (index, particle) in particles
+                .as_slice()
+                .iter()
+                .enumerate()
+                .step_by(PARTICLE_COUNT / 10)
+            {
+                println!(
+                    "Particle {:>4}: position ({:>6.3}, {:>6.3}), velocity ({:>6.3}, {:>6.3})",
+                    index,
+                    particle.position[0],
+                    particle.position[1],
+                    particle.velocity[0],
+                    particle.velocity[1]
+                );
+            }
+            Ok(())
+        });
+
+    particles.destroy(device);
+    allocator.destroy(device);
+    result
+}
+
+// Runs STEPS dispatches of the simulation on queue, waiting for each before the next
+fn dispatch_steps(
+    device: &Device,
+    queue: vk::Queue,
+    family_index: u32,
+    particles: &MappedBuffer<Particle>,
+) -> Result<(), GraphicsError> {
+    let compiler = ShaderCompiler::new()?;
+    let code = compiler.compile_source(
+        include_str!("shaders/particles.comp"),
+        ShaderKind::Compute,
+        "particles.comp",
+        "particles",
+    )?;
+
+    let binding = ComputeBinding::StorageBuffer {
+        buffer: particles.handle(),
+        offset: 0,
+        range: vk::WHOLE_SIZE,
+    };
+    let mut pipeline = ComputePipeline::new(
+        device,
+        &code,
+        slice::from_ref(&binding),
+        mem::size_of::<Step>() as u32,
+    )?;
+
+    // Read by the host once the queue is done with it
+    let output = ComputeOutput::buffer(
+        &QueueSharing::exclusive(),
+        particles.handle(),
+        family_index,
+        QueueUse {
+            family: family_index,
+            stage: vk::PipelineStageFlags::HOST,
+            access: vk::AccessFlags::HOST_READ,
+        },
+    );
+
+    let step = Step {
+        dt: DT,
+        count: PARTICLE_COUNT as u32,
+    };
+    let group_count = [compute::group_count(step.count, WORKGROUP_SIZE), 1, 1];
+
+    let mut command_pool = match CommandPool::new(device, family_index, 0) {
+        Ok(command_pool) => command_pool,
+        Err(error) => {
+            pipeline.destroy(device);
+            return Err(error);
+        }
+    };
+
+    let result = (0..STEPS).try_for_each(|_| {
+        command_pool.submit_once(device, queue, |command_buffer| {
+            pipeline.dispatch(
+                device,
+                command_buffer,
+                group_count,
+                &step,
+                slice::from_ref(&output),
+            );
+            output.record_acquire(device, command_buffer);
+        })
+    });
+
+    command_pool.destroy(device);
+    pipeline.destroy(device);
+    result
+}
+
+fn create_instance(entry: &Entry) -> Result<Instance, GraphicsError> {
+    let application_name = CString::new("Compute Particles").unwrap();
+
+    let app_info = vk::ApplicationInfo::builder()
+        .application_name(&application_name)
+        .application_version(vk::make_api_version(0, 1, 0, 0))
+        .api_version(vk::make_api_version(0, 1, 0, 0));
+
+    let create_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+
+    Ok(unsafe { entry.create_instance(&create_info, None)? })
+}
+
+// Picks the first device that can compute, along with the family compute::find_compute_family prefers on it
+fn pick_physical_device(instance: &Instance) -> Result<(vk::PhysicalDevice, u32), GraphicsError> {
+    let physical_devices = unsafe { instance.enumerate_physical_devices()? };
+
+    physical_devices
+        .into_iter()
+        .find_map(|device| {
+            let queue_families =
+                unsafe { instance.get_physical_device_queue_family_properties(device) };
+            compute::find_compute_family(&queue_families).map(|index| (device, index as u32))
+        })
+        .ok_or(GraphicsError::InvalidGPU)
+}
+
+fn create_logical_device(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    family_index: u32,
+) -> Result<Device, GraphicsError> {
+    let queue_priorities = [1.0];
+
+    let queue_info = vk::DeviceQueueCreateInfo::builder()
+        .queue_family_index(family_index)
+        .queue_priorities(&queue_priorities);
+
+    let device_create_info =
+        vk::DeviceCreateInfo::builder().queue_create_infos(slice::from_ref(&queue_info));
+
+    unsafe {
+        instance
+            .create_device(physical_device, &device_create_info, None)
+            .map_err(GraphicsError::DeviceCreation)
+    }
+}
@@ -0,0 +1,29 @@
+use app::graphics::vulkan_base::{VulkanBase, VulkanSettings, WindowDimensions};
+use std::{env, error::Error};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+// Draws the triangle without a window and saves it as a PNG, to the path given as the first argument or
+// headless_triangle.png
+// The default uniforms are identity transforms, so the triangle is drawn straight in clip space
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("headless_triangle.png"));
+
+    let settings = VulkanSettings {
+        // Nothing is drawn after the first frame, so there is no point watching the sources
+        shader_hot_reload: false,
+        ..VulkanSettings::default()
+    };
+
+    let mut vulkan = VulkanBase::new_headless(WIDTH, HEIGHT, &settings)?;
+    vulkan.draw_frame(&WindowDimensions::new(WIDTH, HEIGHT))?;
+    let pixels = vulkan.read_pixels()?;
+
+    image::save_buffer(&path, &pixels, WIDTH, HEIGHT, image::ColorType::Rgba8)?;
+    println!("Saved {}x{} frame to {}", WIDTH, HEIGHT, path);
+
+    Ok(())
+}
@@ -0,0 +1,579 @@
+use cgmath::Vector3;
+
+// Number of buckets the surface area heuristic evaluates split candidates over
+const BIN_COUNT: usize = 12;
+
+// Nodes with this many primitives or fewer are never split
+const MAX_LEAF_SIZE: usize = 4;
+
+// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // Inverted box that any union or grow replaces entirely
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Vector3<f32>]) -> Aabb {
+        points
+            .iter()
+            .fold(Aabb::empty(), |bounds, point| bounds.grow(point))
+    }
+
+    pub fn grow(&self, point: &Vector3<f32>) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    // Slab test - returns the distance along the ray where it enters the box, if it does so before max_distance
+    // inverse_direction is 1 / direction per component, which callers can reuse across many boxes
+    pub fn intersect_ray(
+        &self,
+        origin: &Vector3<f32>,
+        inverse_direction: &Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = max_distance;
+
+        for axis in 0..3 {
+            // A ray parallel to the slab is inside it everywhere or nowhere, and testing it as usual could multiply 0 by
+            // the infinite inverse when the origin lies on a face, giving NaN
+            if inverse_direction[axis].is_infinite() {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+
+        if near <= far {
+            Some(near)
+        } else {
+            None
+        }
+    }
+}
+
+// Flattened BVH node - interior nodes store their two children next to each other
+#[derive(Clone, Copy, Debug)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    // Index of the left child (right child is first + 1) for interior nodes,
+    // or of the first entry in Bvh::primitive_indices for leaves
+    pub first: u32,
+    // Number of primitives in a leaf, 0 for interior nodes
+    pub count: u32,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+// Bounding volume hierarchy over arbitrary primitives, built with a binned surface area heuristic
+//
+// Nodes are stored in a flat array with the root at index 0, so the tree can be uploaded to the GPU as is.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitive_indices: Vec<u32>,
+}
+
+impl Bvh {
+    // Builds a BVH over primitives described only by their bounds (e.g. scene objects)
+    pub fn build(primitive_bounds: &[Aabb]) -> Bvh {
+        let mut primitive_indices: Vec<u32> = (0..primitive_bounds.len() as u32).collect();
+        let mut nodes = Vec::with_capacity(primitive_bounds.len() * 2);
+
+        if primitive_bounds.is_empty() {
+            return Bvh {
+                nodes,
+                primitive_indices,
+            };
+        }
+
+        let centroids: Vec<Vector3<f32>> = primitive_bounds.iter().map(Aabb::centroid).collect();
+
+        nodes.push(BvhNode {
+            bounds: Aabb::empty(),
+            first: 0,
+            count: primitive_bounds.len() as u32,
+        });
+
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let first = nodes[node_index].first as usize;
+            let count = nodes[node_index].count as usize;
+            let indices = &mut primitive_indices[first..first + count];
+
+            let bounds = indices.iter().fold(Aabb::empty(), |bounds, index| {
+                bounds.union(&primitive_bounds[*index as usize])
+            });
+            nodes[node_index].bounds = bounds;
+
+            if count <= MAX_LEAF_SIZE {
+                continue;
+            }
+
+            let left_count = match Bvh::partition(indices, &centroids, primitive_bounds, &bounds) {
+                Some(left_count) => left_count,
+                None => continue,
+            };
+
+            // Turns the node into an interior node whose children take over its primitive range
+            let left = nodes.len();
+            nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: first as u32,
+                count: left_count as u32,
+            });
+            nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: (first + left_count) as u32,
+                count: (count - left_count) as u32,
+            });
+
+            nodes[node_index].first = left as u32;
+            nodes[node_index].count = 0;
+
+            stack.push(left + 1);
+            stack.push(left);
+        }
+
+        Bvh {
+            nodes,
+            primitive_indices,
+        }
+    }
+
+    // Builds a BVH over the triangles of an indexed mesh - primitive i is the triangle made of indices[3 * i..3 * i + 3]
+    pub fn from_triangles(positions: &[Vector3<f32>], indices: &[u32]) -> Bvh {
+        let triangle_bounds: Vec<Aabb> = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                Aabb::empty()
+                    .grow(&positions[triangle[0] as usize])
+                    .grow(&positions[triangle[1] as usize])
+                    .grow(&positions[triangle[2] as usize])
+            })
+            .collect();
+
+        Bvh::build(&triangle_bounds)
+    }
+
+    pub fn nodes(&self) -> &[BvhNode] {
+        &self.nodes
+    }
+
+    // Primitive indices referenced by the leaves, reordered so every leaf owns a contiguous range
+    pub fn primitive_indices(&self) -> &[u32] {
+        &self.primitive_indices
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|root| root.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+
+    // Calls visit with every primitive in a leaf reachable through nodes whose bounds pass overlaps
+    // overlaps can be a frustum, box, or ray test - visit still has to test the primitive itself
+    pub fn query<O, V>(&self, mut overlaps: O, mut visit: V)
+    where
+        O: FnMut(&Aabb) -> bool,
+        V: FnMut(u32),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if !overlaps(&node.bounds) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let first = node.first as usize;
+                for index in &self.primitive_indices[first..first + node.count as usize] {
+                    visit(*index);
+                }
+            } else {
+                stack.push(node.first as usize + 1);
+                stack.push(node.first as usize);
+            }
+        }
+    }
+
+    // Splits indices in place along the best SAH bucket boundary, returning the size of the left half
+    // Returns None when keeping the node as a leaf is cheaper than any split
+    fn partition(
+        indices: &mut [u32],
+        centroids: &[Vector3<f32>],
+        primitive_bounds: &[Aabb],
+        node_bounds: &Aabb,
+    ) -> Option<usize> {
+        let centroid_bounds = indices.iter().fold(Aabb::empty(), |bounds, index| {
+            bounds.grow(&centroids[*index as usize])
+        });
+
+        // Splits along the axis where the centroids are most spread out
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        // All centroids coincide, so no split can separate them
+        if extent[axis] <= 0.0 {
+            return None;
+        }
+
+        let bin_of = |index: u32| {
+            let offset =
+                (centroids[index as usize][axis] - centroid_bounds.min[axis]) / extent[axis];
+            ((offset * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)
+        };
+
+        let mut bin_counts = [0usize; BIN_COUNT];
+        let mut bin_bounds = [Aabb::empty(); BIN_COUNT];
+
+        for index in indices.iter() {
+            let bin = bin_of(*index);
+            bin_counts[bin] += 1;
+            bin_bounds[bin] = bin_bounds[bin].union(&primitive_bounds[*index as usize]);
+        }
+
+        // Sweeps from the right to get the cost of everything past each boundary
+        let mut right_costs = [0.0f32; BIN_COUNT];
+        let mut right_bounds = Aabb::empty();
+        let mut right_count = 0;
+
+        for bin in (1..BIN_COUNT).rev() {
+            right_bounds = right_bounds.union(&bin_bounds[bin]);
+            right_count += bin_counts[bin];
+            right_costs[bin] = right_count as f32 * right_bounds.surface_area();
+        }
+
+        // Then sweeps from the left, splitting after best_bin
+        let mut best_bin = 0;
+        let mut best_cost = f32::INFINITY;
+        let mut left_bounds = Aabb::empty();
+        let mut left_count = 0;
+
+        for bin in 0..BIN_COUNT - 1 {
+            left_bounds = left_bounds.union(&bin_bounds[bin]);
+            left_count += bin_counts[bin];
+
+            let cost = left_count as f32 * left_bounds.surface_area() + right_costs[bin + 1];
+            if cost < best_cost {
+                best_cost = cost;
+                best_bin = bin;
+            }
+        }
+
+        let leaf_cost = indices.len() as f32 * node_bounds.surface_area();
+        if leaf_cost > 0.0 {
+            if best_cost >= leaf_cost {
+                return None;
+            }
+        } else {
+            // Degenerate nodes (e.g. collinear triangles) have no surface area to weigh splits by, and would otherwise
+            // all end up in one leaf, so they are split where half the centroids fall on each side instead
+            // The first and last bins both hold a centroid, so both halves are non-empty
+            let mut count = 0;
+            best_bin = BIN_COUNT - 2;
+            for (bin, bin_count) in bin_counts.iter().enumerate().take(BIN_COUNT - 1) {
+                count += bin_count;
+                if count * 2 >= indices.len() {
+                    best_bin = bin;
+                    break;
+                }
+            }
+        }
+
+        let mut left_count = 0;
+        for i in 0..indices.len() {
+            if bin_of(indices[i]) <= best_bin {
+                indices.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+
+        if left_count == 0 || left_count == indices.len() {
+            None
+        } else {
+            Some(left_count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit cube at (x, y, z)
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(
+            Vector3::new(x, y, z),
+            Vector3::new(x + 1.0, y + 1.0, z + 1.0),
+        )
+    }
+
+    fn contains(outer: &Aabb, inner: &Aabb) -> bool {
+        outer.min.x <= inner.min.x
+            && outer.min.y <= inner.min.y
+            && outer.min.z <= inner.min.z
+            && outer.max.x >= inner.max.x
+            && outer.max.y >= inner.max.y
+            && outer.max.z >= inner.max.z
+    }
+
+    // Checks every node's bounds hold its children or primitives, and that every primitive is in exactly one leaf
+    fn assert_valid(bvh: &Bvh, primitive_bounds: &[Aabb]) {
+        let mut seen = vec![0; primitive_bounds.len()];
+
+        for node in bvh.nodes() {
+            if node.is_leaf() {
+                let first = node.first as usize;
+                for index in &bvh.primitive_indices()[first..first + node.count as usize] {
+                    assert!(contains(&node.bounds, &primitive_bounds[*index as usize]));
+                    seen[*index as usize] += 1;
+                }
+            } else {
+                let left = &bvh.nodes()[node.first as usize];
+                let right = &bvh.nodes()[node.first as usize + 1];
+                assert!(contains(&node.bounds, &left.bounds));
+                assert!(contains(&node.bounds, &right.bounds));
+            }
+        }
+
+        assert!(seen.iter().all(|count| *count == 1));
+    }
+
+    fn inverse(direction: Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z)
+    }
+
+    #[test]
+    fn empty_input_builds_an_empty_tree() {
+        let bvh = Bvh::build(&[]);
+
+        assert!(bvh.nodes().is_empty());
+        assert!(bvh.bounds().is_empty());
+        bvh.query(|_| true, |_| panic!("Empty trees have no primitives!"));
+    }
+
+    #[test]
+    fn small_inputs_stay_a_single_leaf() {
+        let primitive_bounds = [unit_box(0.0, 0.0, 0.0), unit_box(5.0, 0.0, 0.0)];
+        let bvh = Bvh::build(&primitive_bounds);
+
+        assert_eq!(bvh.nodes().len(), 1);
+        assert_eq!(bvh.nodes()[0].count, 2);
+        assert_eq!(
+            bvh.bounds(),
+            Aabb::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(6.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn spread_out_primitives_are_split_into_valid_leaves() {
+        let primitive_bounds: Vec<Aabb> = (0..64)
+            .map(|i| {
+                unit_box(
+                    (i % 4) as f32 * 3.0,
+                    (i / 4 % 4) as f32 * 3.0,
+                    (i / 16) as f32 * 3.0,
+                )
+            })
+            .collect();
+        let bvh = Bvh::build(&primitive_bounds);
+
+        assert_valid(&bvh, &primitive_bounds);
+        assert!(bvh.nodes().len() > 1);
+        assert!(bvh
+            .nodes()
+            .iter()
+            .all(|node| node.count as usize <= MAX_LEAF_SIZE));
+    }
+
+    #[test]
+    fn coincident_primitives_stay_in_one_leaf() {
+        let primitive_bounds = vec![unit_box(0.0, 0.0, 0.0); 10];
+        let bvh = Bvh::build(&primitive_bounds);
+
+        assert_valid(&bvh, &primitive_bounds);
+        assert_eq!(bvh.nodes().len(), 1);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_still_split() {
+        // Collinear triangles along the x axis, whose bounds have no surface area
+        let positions: Vec<Vector3<f32>> =
+            (0..32).map(|i| Vector3::new(i as f32, 0.0, 0.0)).collect();
+        let indices: Vec<u32> = (0..30).flat_map(|i| vec![i, i + 1, i + 2]).collect();
+        let bvh = Bvh::from_triangles(&positions, &indices);
+
+        assert!(bvh.nodes().len() > 1);
+        assert!(bvh
+            .nodes()
+            .iter()
+            .all(|node| node.count as usize <= MAX_LEAF_SIZE));
+    }
+
+    #[test]
+    fn query_visits_only_overlapping_primitives() {
+        let primitive_bounds: Vec<Aabb> = (0..20)
+            .map(|i| unit_box(i as f32 * 2.0, 0.0, 0.0))
+            .collect();
+        let bvh = Bvh::build(&primitive_bounds);
+        let region = Aabb::new(Vector3::new(3.5, 0.0, 0.0), Vector3::new(8.5, 1.0, 1.0));
+
+        let mut visited = Vec::new();
+        bvh.query(
+            |bounds| bounds.overlaps(&region),
+            |index| {
+                if primitive_bounds[index as usize].overlaps(&region) {
+                    visited.push(index);
+                }
+            },
+        );
+        visited.sort_unstable();
+
+        assert_eq!(visited, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn rays_hit_boxes_in_front_of_them() {
+        let bounds = unit_box(2.0, 0.0, 0.0);
+        let origin = Vector3::new(0.0, 0.5, 0.5);
+        let direction = inverse(Vector3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(bounds.intersect_ray(&origin, &direction, 100.0), Some(2.0));
+    }
+
+    #[test]
+    fn rays_starting_inside_hit_at_zero() {
+        let bounds = unit_box(0.0, 0.0, 0.0);
+        let origin = Vector3::new(0.5, 0.5, 0.5);
+        let direction = inverse(Vector3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(bounds.intersect_ray(&origin, &direction, 100.0), Some(0.0));
+    }
+
+    #[test]
+    fn rays_miss_boxes_beside_behind_or_beyond_them() {
+        let bounds = unit_box(2.0, 0.0, 0.0);
+        let direction = inverse(Vector3::new(1.0, 0.0, 0.0));
+
+        // Beside
+        assert_eq!(
+            bounds.intersect_ray(&Vector3::new(0.0, 2.0, 0.5), &direction, 100.0),
+            None
+        );
+        // Behind
+        assert_eq!(
+            bounds.intersect_ray(&Vector3::new(4.0, 0.5, 0.5), &direction, 100.0),
+            None
+        );
+        // Beyond max_distance
+        assert_eq!(
+            bounds.intersect_ray(&Vector3::new(0.0, 0.5, 0.5), &direction, 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn rays_along_a_face_hit_without_nan() {
+        // The origin lies on the box's min y and z faces while the ray has no y or z direction, which makes the
+        // unguarded slab test compute 0 * infinity
+        let bounds = unit_box(2.0, 0.0, 0.0);
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+        let direction = inverse(Vector3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(bounds.intersect_ray(&origin, &direction, 100.0), Some(2.0));
+        assert_eq!(
+            bounds.intersect_ray(&Vector3::new(0.0, -0.1, 0.0), &direction, 100.0),
+            None
+        );
+    }
+}
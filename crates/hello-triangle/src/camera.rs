@@ -0,0 +1,341 @@
+use crate::graphics::uniforms;
+use crate::picking::Ray;
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3, VectorSpace};
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+// Pitch stops just short of straight up or down, where the view direction would line up with the up vector
+const MAX_PITCH: Rad<f32> = Rad(std::f32::consts::FRAC_PI_2 - 0.01);
+
+// Perspective camera looking along its yaw and pitch from position, with +y up
+// Zero yaw and pitch look down -z, positive yaw turns right and positive pitch looks up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    // Vertical field of view
+    pub fovy: Rad<f32>,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, yaw: Rad<f32>, pitch: Rad<f32>) -> Camera {
+        let mut camera = Camera {
+            position,
+            yaw,
+            ..Camera::default()
+        };
+        camera.set_pitch(pitch);
+        camera
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    // Clamped to just under a quarter turn either way
+    pub fn set_pitch(&mut self, pitch: Rad<f32>) {
+        self.pitch = Rad(pitch.0.clamp(-MAX_PITCH.0, MAX_PITCH.0));
+    }
+
+    // Unit vector the camera looks along
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        Vector3::new(cos_pitch * sin_yaw, sin_pitch, -cos_pitch * cos_yaw)
+    }
+
+    // Unit vector to the camera's right, kept level with the ground
+    pub fn right(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_yaw, 0.0, sin_yaw)
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(
+            self.position,
+            self.position + self.forward(),
+            Vector3::unit_y(),
+        )
+    }
+
+    // Projection for a viewport of the given width / height, ready for the uniform buffer
+    pub fn projection_matrix(&self, aspect: f32) -> Matrix4<f32> {
+        uniforms::perspective(self.fovy, aspect, self.near, self.far)
+    }
+
+    // World space ray from the camera through a point on a width x height viewport, measured in pixels from its top
+    // left, for picking what is drawn there
+    pub fn screen_ray(&self, x: f32, y: f32, width: f32, height: f32) -> Ray {
+        let half_height = (self.fovy.0 / 2.0).tan();
+        let half_width = half_height * width / height;
+
+        // -1 to 1 across the viewport, with y flipped so up is positive
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+
+        let forward = self.forward();
+        let right = self.right();
+        let up = right.cross(forward);
+        let direction = forward + right * (ndc_x * half_width) + up * (ndc_y * half_height);
+
+        Ray::new(self.position.to_vec(), direction.normalize())
+    }
+
+    // Blends from previous to this camera's position and orientation, for rendering between fixed updates
+    pub fn interpolate(&self, previous: &Camera, alpha: f32) -> Camera {
+        Camera {
+            position: Point3::from_vec(
+                previous
+                    .position
+                    .to_vec()
+                    .lerp(self.position.to_vec(), alpha),
+            ),
+            yaw: previous.yaw + (self.yaw - previous.yaw) * alpha,
+            pitch: previous.pitch + (self.pitch - previous.pitch) * alpha,
+            ..*self
+        }
+    }
+}
+
+impl Default for Camera {
+    // Two units back from the origin, looking at it
+    fn default() -> Camera {
+        Camera {
+            position: Point3::new(0.0, 0.0, 2.0),
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            fovy: Deg(45.0).into(),
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+}
+
+// Moves a camera from window input - WASD to move, Space and left Shift to rise and sink, and mouse movement while
+// the right button is held to look around
+pub struct CameraController {
+    // Units per second
+    pub speed: f32,
+    // Radians per unit of raw mouse motion
+    pub sensitivity: f32,
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    looking: bool,
+    // Mouse motion since the last update()
+    yaw_delta: f32,
+    pitch_delta: f32,
+}
+
+impl CameraController {
+    pub fn new(speed: f32, sensitivity: f32) -> CameraController {
+        CameraController {
+            speed,
+            sensitivity,
+            forward: false,
+            backward: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            looking: false,
+            yaw_delta: 0.0,
+            pitch_delta: 0.0,
+        }
+    }
+
+    // Tracks movement keys and the look button, returning true if the event was used
+    pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => self.handle_key(*key, *state),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.looking = *state == ElementState::Pressed;
+                true
+            }
+            // Keys released while unfocused never send a release, so they would stay held
+            WindowEvent::Focused(false) => {
+                *self = CameraController::new(self.speed, self.sensitivity);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Accumulates raw mouse motion while looking, which unlike cursor movement keeps going at the window edge
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            if self.looking {
+                self.yaw_delta += *x as f32 * self.sensitivity;
+                self.pitch_delta -= *y as f32 * self.sensitivity;
+            }
+        }
+    }
+
+    // Turns the camera by the mouse motion since the last update, and moves it for dt seconds of held keys
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        camera.yaw += Rad(self.yaw_delta);
+        camera.set_pitch(camera.pitch + Rad(self.pitch_delta));
+        self.yaw_delta = 0.0;
+        self.pitch_delta = 0.0;
+
+        // Moves along the ground rather than where the camera is looking, so looking down doesn't slow walking
+        let right = camera.right();
+        let forward = Vector3::unit_y().cross(right);
+        let direction = forward * axis(self.forward, self.backward)
+            + right * axis(self.right, self.left)
+            + Vector3::unit_y() * axis(self.up, self.down);
+
+        // Diagonal movement is no faster than straight movement
+        if direction.magnitude2() > 0.0 {
+            camera.position += direction.normalize() * self.speed * dt;
+        }
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        let held = match key {
+            VirtualKeyCode::W => &mut self.forward,
+            VirtualKeyCode::S => &mut self.backward,
+            VirtualKeyCode::A => &mut self.left,
+            VirtualKeyCode::D => &mut self.right,
+            VirtualKeyCode::Space => &mut self.up,
+            VirtualKeyCode::LShift => &mut self.down,
+            _ => return false,
+        };
+        *held = pressed;
+        true
+    }
+}
+
+// 1 when only positive is held, -1 when only negative is, and 0 otherwise
+fn axis(positive: bool, negative: bool) -> f32 {
+    (positive as i32 - negative as i32) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector4;
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    fn columns_close(a: Vector4<f32>, b: Vector4<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn default_camera_matches_looking_at_the_origin() {
+        let view = Camera::default().view_matrix();
+        let expected = Matrix4::look_at_rh(
+            Point3::new(0.0, 0.0, 2.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+        );
+        assert!(columns_close(view.x, expected.x));
+        assert!(columns_close(view.y, expected.y));
+        assert!(columns_close(view.z, expected.z));
+        assert!(columns_close(view.w, expected.w));
+    }
+
+    #[test]
+    fn positive_yaw_turns_right() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Deg(90.0).into(), Rad(0.0));
+        assert!(close(camera.forward(), Vector3::unit_x()));
+        assert!(close(camera.right(), Vector3::unit_z()));
+    }
+
+    #[test]
+    fn screen_ray_through_the_center_looks_forward() {
+        let camera = Camera::new(
+            Point3::new(1.0, 2.0, 3.0),
+            Deg(30.0).into(),
+            Deg(20.0).into(),
+        );
+        let ray = camera.screen_ray(400.0, 300.0, 800.0, 600.0);
+        assert!(close(ray.origin, Vector3::new(1.0, 2.0, 3.0)));
+        assert!(close(ray.direction, camera.forward()));
+    }
+
+    #[test]
+    fn screen_rays_project_back_to_their_pixel() {
+        let camera = Camera::new(
+            Point3::new(0.5, -1.0, 4.0),
+            Deg(-40.0).into(),
+            Deg(10.0).into(),
+        );
+        let (width, height) = (800.0, 600.0);
+        let clip = camera.projection_matrix(width / height) * camera.view_matrix();
+
+        for &(x, y) in &[(0.0, 0.0), (800.0, 600.0), (200.0, 450.0)] {
+            let point = camera.screen_ray(x, y, width, height).at(5.0);
+            let projected = clip * point.extend(1.0);
+            // Vulkan's normalized device coordinates have y pointing down, like the pixels
+            let ndc_x = projected.x / projected.w;
+            let ndc_y = projected.y / projected.w;
+            assert!((ndc_x - (2.0 * x / width - 1.0)).abs() < 1e-4);
+            assert!((ndc_y - (2.0 * y / height - 1.0)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn pitch_is_clamped_short_of_vertical() {
+        let mut camera = Camera::default();
+        camera.set_pitch(Deg(120.0).into());
+        assert_eq!(camera.pitch(), MAX_PITCH);
+        camera.set_pitch(Deg(-120.0).into());
+        assert_eq!(camera.pitch(), -MAX_PITCH);
+    }
+
+    #[test]
+    fn held_keys_move_along_the_ground() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Deg(-60.0).into());
+        let mut controller = CameraController::new(2.0, 0.01);
+        assert!(controller.handle_key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(!controller.handle_key(VirtualKeyCode::Q, ElementState::Pressed));
+
+        controller.update(&mut camera, 0.5);
+        assert!(close(
+            camera.position.to_vec(),
+            Vector3::new(0.0, 0.0, -1.0)
+        ));
+
+        controller.handle_key(VirtualKeyCode::W, ElementState::Released);
+        controller.update(&mut camera, 0.5);
+        assert!(close(
+            camera.position.to_vec(),
+            Vector3::new(0.0, 0.0, -1.0)
+        ));
+    }
+
+    #[test]
+    fn diagonal_movement_is_not_faster() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0));
+        let mut controller = CameraController::new(1.0, 0.01);
+        controller.handle_key(VirtualKeyCode::W, ElementState::Pressed);
+        controller.handle_key(VirtualKeyCode::D, ElementState::Pressed);
+
+        controller.update(&mut camera, 1.0);
+        assert!((camera.position.to_vec().magnitude() - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::graphics::vulkan_base::{GraphicsError, VulkanBase, VulkanSettings, WindowDimensions};
+use crate::monitor::{self, MonitorInfo};
+use crate::timing::{FixedTimestep, FrameLimiter};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
+};
+
+// Window, timing, and Vulkan options for an Engine
+#[derive(Clone, Debug)]
+pub struct EngineSettings {
+    pub title: String,
+    // Initial inner size of the window in logical pixels
+    pub width: u32,
+    pub height: u32,
+    // Rate the update callback runs at, regardless of frame rate
+    pub updates_per_second: u32,
+    pub vulkan: VulkanSettings,
+}
+
+impl Default for EngineSettings {
+    fn default() -> EngineSettings {
+        EngineSettings {
+            title: String::from("name of window"),
+            width: 800,
+            height: 600,
+            updates_per_second: 60,
+            vulkan: VulkanSettings::default(),
+        }
+    }
+}
+
+// Whether the platform only has a surface between Resumed and Suspended events, which winit sends only on Android and
+// iOS - everywhere else the window and Vulkan state are created at startup and live until exit
+const SURFACE_FOLLOWS_RESUME: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+// Frame the render callback is preparing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameInfo {
+    // Size of the area drawn to in pixels - the letterbox resolution if there is one, and otherwise the window's size,
+    // which is never zero as minimized windows are not rendered
+    pub width: u32,
+    pub height: u32,
+    // How far the frame is between the previous and latest update, from 0 to 1, for blending simulation states
+    pub alpha: f32,
+}
+
+impl FrameInfo {
+    // Width / height, as projection matrices take it
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
+
+// Runs the winit event loop for a window and the Vulkan state drawing to it, calling back into the application
+// to update at a fixed timestep and to prepare each frame
+// Takes care of creating the window at startup (or on the first resume on mobile), rebuilding Vulkan across suspends
+// and lost surfaces, recreating the swapchain on resize, skipping frames while minimized, and pacing frames to the
+// monitor
+pub struct Engine {
+    settings: EngineSettings,
+    // Only exists while the application is resumed, since the surface it presents to can be destroyed while
+    // suspended (e.g. on Android) - declared before window so it is dropped first
+    vulkan: Option<VulkanBase>,
+    // Created once the event loop starts, or on the first Resumed event on platforms with no surface before then
+    window: Option<Window>,
+    // Monitor the window was last seen on, used to pace frames to its refresh rate
+    current_monitor: Option<MonitorInfo>,
+}
+
+impl Engine {
+    // Creates the event loop for the engine - the window and Vulkan state wait for it to start running
+    pub fn new(settings: EngineSettings) -> (Engine, EventLoop<()>) {
+        let engine = Engine {
+            settings,
+            vulkan: None,
+            window: None,
+            current_monitor: None,
+        };
+
+        (engine, EventLoop::new())
+    }
+
+    // Runs the event loop until the window is closed or a graphics error leaves no way to carry on
+    // input sees every event before the engine handles it, update advances the simulation by a fixed step of dt
+    // seconds, and render sets up the frame (e.g. its uniforms) before the engine draws and presents it
+    // Each callback gets state, so they can share it without capturing it themselves
+    pub fn run<S, U, R, I>(
+        mut self,
+        event_loop: EventLoop<()>,
+        mut state: S,
+        mut update: U,
+        mut render: R,
+        mut input: I,
+    ) where
+        S: 'static,
+        U: FnMut(&mut S, f32) + 'static,
+        R: FnMut(&mut S, &mut VulkanBase, &FrameInfo) -> Result<(), GraphicsError> + 'static,
+        I: FnMut(&mut S, &Event<()>) + 'static,
+    {
+        let mut timestep = FixedTimestep::new(self.settings.updates_per_second);
+        // Caps rendering to the refresh rate of the active monitor, once the window exists
+        let mut frame_limiter = FrameLimiter::new(None);
+
+        event_loop.run(move |event, window_target, control_flow| {
+            // Continually runs the event loop while resumed, and sleeps until the next event while suspended
+            *control_flow = if self.is_resumed() {
+                ControlFlow::Poll
+            } else {
+                ControlFlow::Wait
+            };
+
+            input(&mut state, &event);
+
+            match event {
+                // Sent once before any other event, when desktop platforms can already create a surface
+                Event::NewEvents(StartCause::Init) if !SURFACE_FOLLOWS_RESUME => {
+                    *control_flow = ControlFlow::Poll;
+                    exit_on_error(self.resumed(window_target), control_flow);
+                    frame_limiter.set_refresh_rate(self.refresh_rate());
+                }
+                // Sent whenever a mobile app returns to the foreground
+                Event::Resumed if SURFACE_FOLLOWS_RESUME => {
+                    *control_flow = ControlFlow::Poll;
+                    exit_on_error(self.resumed(window_target), control_flow);
+                    frame_limiter.set_refresh_rate(self.refresh_rate());
+                }
+                Event::Suspended if SURFACE_FOLLOWS_RESUME => {
+                    *control_flow = ControlFlow::Wait;
+                    self.suspended();
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => exit_on_error(self.resized(size.width, size.height), control_flow),
+                // Retargets the frame limiter when the window is dragged onto another monitor
+                Event::WindowEvent {
+                    event: WindowEvent::Moved(_),
+                    ..
+                }
+                | Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { .. },
+                    ..
+                } if self.update_current_monitor() => {
+                    frame_limiter.set_refresh_rate(self.refresh_rate())
+                }
+                Event::MainEventsCleared if self.is_resumed() => {
+                    for _ in 0..timestep.tick() {
+                        update(&mut state, timestep.step_seconds());
+                    }
+
+                    exit_on_error(
+                        self.draw(&mut state, &mut render, timestep.alpha()),
+                        control_flow,
+                    );
+                    frame_limiter.wait();
+                }
+                _ => (),
+            }
+        });
+    }
+
+    // Creates the window the first time the engine starts or is resumed, and the Vulkan state whenever it is missing
+    fn resumed(&mut self, event_loop: &EventLoopWindowTarget<()>) -> Result<(), GraphicsError> {
+        if self.window.is_none() {
+            let window = WindowBuilder::new()
+                .with_title(&self.settings.title)
+                .with_inner_size(LogicalSize::new(self.settings.width, self.settings.height))
+                .build(event_loop)
+                .expect("Could not create a window!");
+
+            // Lists every monitor the window could be presented to
+            for monitor in monitor::available_monitors(&window) {
+                println!(
+                    "Monitor: {} ({}x{}, {} Hz)",
+                    monitor.name,
+                    monitor.size.width,
+                    monitor.size.height,
+                    monitor
+                        .refresh_rate
+                        .map_or_else(|| String::from("unknown"), |rate| rate.to_string())
+                );
+            }
+
+            self.current_monitor = monitor::current_monitor(&window);
+            self.window = Some(window);
+        }
+
+        if self.vulkan.is_none() {
+            let window = self
+                .window
+                .as_ref()
+                .expect("Window should exist once resumed!");
+
+            let size = window.inner_size();
+            let window_dimensions = WindowDimensions::new(size.width, size.height);
+            self.vulkan = Some(VulkanBase::new(
+                window,
+                &window_dimensions,
+                &self.settings.vulkan,
+            )?);
+        }
+
+        Ok(())
+    }
+
+    // Releases the Vulkan state along with the surface, keeping the window for the next resume
+    fn suspended(&mut self) {
+        self.vulkan = None;
+    }
+
+    fn is_resumed(&self) -> bool {
+        self.vulkan.is_some()
+    }
+
+    // Rebuilds the swapchain for the new window size
+    fn resized(&mut self, width: u32, height: u32) -> Result<(), GraphicsError> {
+        // A minimized window has no area to present to, so the old swapchain is kept until it is restored
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let window_dimensions = WindowDimensions::new(width, height);
+        let result = match &mut self.vulkan {
+            Some(vulkan) => vulkan.recreate_swapchain(&window_dimensions),
+            None => return Ok(()),
+        };
+
+        match result {
+            Err(GraphicsError::SurfaceLost) => self.recover_surface(&window_dimensions),
+            result => result,
+        }
+    }
+
+    // Replaces a lost surface, falling back to rebuilding all the Vulkan state if the new surface can't be used with
+    // the old device
+    fn recover_surface(
+        &mut self,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        let (window, vulkan) = match (&self.window, &mut self.vulkan) {
+            (Some(window), Some(vulkan)) => (window, vulkan),
+            _ => return Ok(()),
+        };
+
+        if let Err(error) = vulkan.recreate_surface(window, window_dimensions) {
+            eprintln!(
+                "Rebuilding Vulkan after failing to recreate the surface: {}",
+                error
+            );
+            self.vulkan = None;
+            self.vulkan = Some(VulkanBase::new(
+                window,
+                window_dimensions,
+                &self.settings.vulkan,
+            )?);
+        }
+
+        Ok(())
+    }
+
+    // Refresh rate of the monitor the window is on, None if unknown
+    fn refresh_rate(&self) -> Option<u16> {
+        self.current_monitor
+            .as_ref()
+            .and_then(|monitor| monitor.refresh_rate)
+    }
+
+    // Re-checks which monitor the window is on, returning true if it moved to a different one
+    fn update_current_monitor(&mut self) -> bool {
+        let current_monitor = match &self.window {
+            Some(window) => monitor::current_monitor(window),
+            None => return false,
+        };
+
+        if current_monitor == self.current_monitor {
+            return false;
+        }
+
+        self.current_monitor = current_monitor;
+        true
+    }
+
+    // Lets render set up the frame, then draws and presents it
+    fn draw<S, R>(&mut self, state: &mut S, render: &mut R, alpha: f32) -> Result<(), GraphicsError>
+    where
+        R: FnMut(&mut S, &mut VulkanBase, &FrameInfo) -> Result<(), GraphicsError>,
+    {
+        let (window, vulkan) = match (&self.window, &mut self.vulkan) {
+            (Some(window), Some(vulkan)) => (window, vulkan),
+            _ => return Ok(()),
+        };
+
+        // Nothing can be presented while minimized
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
+
+        // Broken shader edits keep the previous pipeline, so they are reported and drawing carries on
+        match vulkan.reload_shaders() {
+            Ok(_) => {}
+            Err(error @ GraphicsError::ShaderCompilation { .. })
+            | Err(error @ GraphicsError::ShaderLoading { .. }) => {
+                eprintln!("Shader reload failed: {}", error)
+            }
+            Err(error) => return Err(error),
+        }
+
+        let (width, height) = match vulkan.letterbox() {
+            Some(letterbox) => (letterbox.width, letterbox.height),
+            None => (size.width, size.height),
+        };
+        let frame = FrameInfo {
+            width,
+            height,
+            alpha,
+        };
+        render(state, vulkan, &frame)?;
+
+        let window_dimensions = WindowDimensions::new(size.width, size.height);
+        match vulkan.draw_frame(&window_dimensions) {
+            // The compositor stopped handing out images for now, so this frame is skipped and the next one tries again
+            Err(error @ GraphicsError::AcquireTimeout)
+            | Err(error @ GraphicsError::SwapchainOutOfDate) => {
+                eprintln!("Skipped frame: {}", error);
+                Ok(())
+            }
+            Err(GraphicsError::SurfaceLost) => self.recover_surface(&window_dimensions),
+            result => result,
+        }
+    }
+}
+
+// Reports a graphics failure and exits, as the application has no way to carry on without Vulkan
+fn exit_on_error(result: Result<(), GraphicsError>, control_flow: &mut ControlFlow) {
+    if let Err(error) = result {
+        eprintln!("Graphics error: {}", error);
+        *control_flow = ControlFlow::Exit;
+    }
+}
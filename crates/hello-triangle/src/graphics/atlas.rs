@@ -0,0 +1,280 @@
+// Region of an atlas handed out by AtlasPacker::allocate, in texels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasAllocation {
+    pub layer: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Normalized texture coordinates of an allocation, plus the array layer to sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub layer: u32,
+}
+
+// A row of allocations sharing the same top edge
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor_x: u32,
+}
+
+#[derive(Default)]
+struct Layer {
+    shelves: Vec<Shelf>,
+    // Top edge of the next shelf to be opened
+    next_y: u32,
+}
+
+// Shelf based rectangle packer for square texture atlases (e.g. glyph caches and sprite batches)
+//
+// When every layer is full the atlas first doubles in size up to max_size, and after that spills into a new array layer.
+// Allocations are stored in texels, so UVs must be recomputed (and the texture re-uploaded) after the size changes.
+pub struct AtlasPacker {
+    size: u32,
+    max_size: u32,
+    // Empty border kept around every allocation to stop filtering from bleeding between neighbours
+    padding: u32,
+    layers: Vec<Layer>,
+}
+
+impl AtlasPacker {
+    pub fn new(initial_size: u32, max_size: u32, padding: u32) -> AtlasPacker {
+        assert!(
+            initial_size > 0 && initial_size <= max_size,
+            "Atlas initial size must be non-zero and no bigger than its max size!"
+        );
+
+        AtlasPacker {
+            size: initial_size,
+            max_size,
+            padding,
+            layers: vec![Layer::default()],
+        }
+    }
+
+    // Width and height of every layer
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    // Reserves a width x height region, growing or adding layers as needed
+    // Returns None if the region can't fit even in an empty layer of max_size
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<AtlasAllocation> {
+        // Sizes near u32::MAX would overflow once padded, and could never fit anyway
+        let padding = self.padding.checked_mul(2)?;
+        let padded_width = width.checked_add(padding)?;
+        let padded_height = height.checked_add(padding)?;
+
+        if padded_width > self.max_size || padded_height > self.max_size {
+            return None;
+        }
+
+        loop {
+            for (index, layer) in self.layers.iter_mut().enumerate() {
+                if let Some((x, y)) =
+                    AtlasPacker::allocate_in_layer(layer, self.size, padded_width, padded_height)
+                {
+                    return Some(AtlasAllocation {
+                        layer: index as u32,
+                        x: x + self.padding,
+                        y: y + self.padding,
+                        width,
+                        height,
+                    });
+                }
+            }
+
+            // Existing allocations keep their texel positions when the atlas grows
+            if self.size < self.max_size {
+                self.size = self.size.saturating_mul(2).min(self.max_size);
+            } else {
+                self.layers.push(Layer::default());
+            }
+        }
+    }
+
+    // Frees every allocation, keeping the current size and layer count
+    pub fn clear(&mut self) {
+        for layer in self.layers.iter_mut() {
+            *layer = Layer::default();
+        }
+    }
+
+    // Converts an allocation into normalized texture coordinates for the current atlas size
+    pub fn uv_rect(&self, allocation: &AtlasAllocation) -> UvRect {
+        let size = self.size as f32;
+
+        UvRect {
+            min: [allocation.x as f32 / size, allocation.y as f32 / size],
+            max: [
+                (allocation.x as f32 + allocation.width as f32) / size,
+                (allocation.y as f32 + allocation.height as f32) / size,
+            ],
+            layer: allocation.layer,
+        }
+    }
+
+    // Maps a UV relative to the allocated image ([0, 1] across the image) into atlas UV space
+    pub fn remap_uv(&self, allocation: &AtlasAllocation, uv: [f32; 2]) -> [f32; 2] {
+        let rect = self.uv_rect(allocation);
+
+        [
+            rect.min[0] + (rect.max[0] - rect.min[0]) * uv[0],
+            rect.min[1] + (rect.max[1] - rect.min[1]) * uv[1],
+        ]
+    }
+
+    // Places a rectangle on the best fitting shelf of a layer, opening a new shelf if none fit
+    // Shelves never extend past size, so the remaining space is compared rather than summed to avoid overflow
+    fn allocate_in_layer(
+        layer: &mut Layer,
+        size: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<(u32, u32)> {
+        // Prefers the shelf wasting the least height
+        let best_shelf = layer
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && width <= size - shelf.cursor_x)
+            .min_by_key(|shelf| shelf.height - height);
+
+        if let Some(shelf) = best_shelf {
+            let x = shelf.cursor_x;
+            shelf.cursor_x += width;
+            return Some((x, shelf.y));
+        }
+
+        if height > size - layer.next_y || width > size {
+            return None;
+        }
+
+        let y = layer.next_y;
+        layer.shelves.push(Shelf {
+            y,
+            height,
+            cursor_x: width,
+        });
+        layer.next_y += height;
+
+        Some((0, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(layer: u32, x: u32, y: u32, width: u32, height: u32) -> AtlasAllocation {
+        AtlasAllocation {
+            layer,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn rectangles_fill_shelves_left_to_right() {
+        let mut packer = AtlasPacker::new(64, 64, 0);
+
+        assert_eq!(packer.allocate(16, 8), Some(allocation(0, 0, 0, 16, 8)));
+        assert_eq!(packer.allocate(16, 8), Some(allocation(0, 16, 0, 16, 8)));
+        // Too tall for the first shelf, so it opens a second one below it
+        assert_eq!(packer.allocate(16, 12), Some(allocation(0, 0, 8, 16, 12)));
+        // Shorter rectangles go on the shelf wasting the least height
+        assert_eq!(packer.allocate(16, 6), Some(allocation(0, 32, 0, 16, 6)));
+    }
+
+    #[test]
+    fn padding_surrounds_every_allocation() {
+        let mut packer = AtlasPacker::new(64, 64, 1);
+
+        assert_eq!(packer.allocate(8, 8), Some(allocation(0, 1, 1, 8, 8)));
+        assert_eq!(packer.allocate(8, 8), Some(allocation(0, 11, 1, 8, 8)));
+    }
+
+    #[test]
+    fn full_atlases_grow_up_to_max_size() {
+        let mut packer = AtlasPacker::new(16, 64, 0);
+
+        assert_eq!(packer.allocate(16, 16), Some(allocation(0, 0, 0, 16, 16)));
+        assert_eq!(packer.allocate(16, 16), Some(allocation(0, 16, 0, 16, 16)));
+        assert_eq!(packer.size(), 32);
+
+        packer.allocate(32, 32).unwrap();
+        packer.allocate(32, 32).unwrap();
+        assert_eq!(packer.size(), 64);
+        assert_eq!(packer.layer_count(), 1);
+    }
+
+    #[test]
+    fn full_atlases_at_max_size_spill_into_new_layers() {
+        let mut packer = AtlasPacker::new(32, 32, 0);
+
+        assert_eq!(packer.allocate(32, 32), Some(allocation(0, 0, 0, 32, 32)));
+        assert_eq!(packer.allocate(32, 32), Some(allocation(1, 0, 0, 32, 32)));
+        assert_eq!(packer.layer_count(), 2);
+        assert_eq!(packer.size(), 32);
+    }
+
+    #[test]
+    fn oversized_rectangles_are_rejected() {
+        let mut packer = AtlasPacker::new(32, 64, 2);
+
+        assert_eq!(packer.allocate(61, 8), None);
+        assert_eq!(packer.allocate(u32::MAX, 8), None);
+        assert_eq!(packer.allocate(8, u32::MAX - 1), None);
+        assert_eq!(packer.layer_count(), 1);
+        assert_eq!(packer.size(), 32);
+    }
+
+    #[test]
+    fn huge_padding_is_rejected_without_overflow() {
+        let mut packer = AtlasPacker::new(32, u32::MAX, u32::MAX / 2 + 1);
+
+        assert_eq!(packer.allocate(1, 1), None);
+    }
+
+    #[test]
+    fn clear_frees_space_but_keeps_the_size() {
+        let mut packer = AtlasPacker::new(16, 32, 0);
+        packer.allocate(16, 16).unwrap();
+        packer.allocate(16, 16).unwrap();
+
+        packer.clear();
+
+        assert_eq!(packer.size(), 32);
+        assert_eq!(packer.allocate(16, 16), Some(allocation(0, 0, 0, 16, 16)));
+    }
+
+    #[test]
+    fn uv_rect_normalizes_by_the_current_size() {
+        let packer = AtlasPacker::new(64, 64, 0);
+        let rect = packer.uv_rect(&allocation(1, 16, 32, 16, 8));
+
+        assert_eq!(rect.min, [0.25, 0.5]);
+        assert_eq!(rect.max, [0.5, 0.625]);
+        assert_eq!(rect.layer, 1);
+    }
+
+    #[test]
+    fn remap_uv_maps_image_corners_onto_the_allocation() {
+        let packer = AtlasPacker::new(64, 64, 0);
+        let region = allocation(0, 16, 32, 16, 8);
+
+        assert_eq!(packer.remap_uv(&region, [0.0, 0.0]), [0.25, 0.5]);
+        assert_eq!(packer.remap_uv(&region, [1.0, 1.0]), [0.5, 0.625]);
+        assert_eq!(packer.remap_uv(&region, [0.5, 0.5]), [0.375, 0.5625]);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::{Allocation, Allocator};
+use crate::graphics::queue_ownership::{OwnershipTransfer, QueueSharing, QueueUse};
+use crate::graphics::upload::Uploader;
+use ash::{vk, Device};
+use std::{mem, ptr, slice};
+
+// Vertex layout read by the vertex shader, with pos at location 0, color at location 1, and tex_coord at location 2
+// The normal is loaded with models but not read by the shaders yet, so it has no attribute description
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    pub color: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl Vertex {
+    // Vertices are read per vertex from binding 0
+    pub fn binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: mem::size_of::<Vertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }
+    }
+
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
+        [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: mem::size_of::<[f32; 3]>() as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 2,
+                binding: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: mem::size_of::<[f32; 6]>() as u32,
+            },
+        ]
+    }
+}
+
+// Integer types an index buffer can hold
+pub trait Index: Copy {
+    const INDEX_TYPE: vk::IndexType;
+}
+
+impl Index for u16 {
+    const INDEX_TYPE: vk::IndexType = vk::IndexType::UINT16;
+}
+
+impl Index for u32 {
+    const INDEX_TYPE: vk::IndexType = vk::IndexType::UINT32;
+}
+
+// Buffer along with the memory bound to it
+pub struct Buffer {
+    buffer: vk::Buffer,
+    // None once destroyed
+    memory: Option<Allocation>,
+    size: vk::DeviceSize,
+}
+
+impl Buffer {
+    // Creates a buffer of size bytes and binds it to memory with the given properties from allocator
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<Buffer, GraphicsError> {
+        Buffer::new_shared(
+            device,
+            allocator,
+            size,
+            usage,
+            properties,
+            &QueueSharing::exclusive(),
+        )
+    }
+
+    // Same as new, for a buffer used from the queue families in sharing
+    pub fn new_shared(
+        device: &Device,
+        allocator: &Allocator,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+        sharing: &QueueSharing,
+    ) -> Result<Buffer, GraphicsError> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(sharing.sharing_mode())
+            .queue_family_indices(sharing.queue_family_indices());
+
+        let buffer = unsafe { device.create_buffer(&buffer_info, None)? };
+
+        // The buffer is destroyed again if its memory cannot be allocated or bound, so nothing leaks on failure
+        match allocator.allocate_buffer(device, buffer, properties) {
+            Ok(memory) => Ok(Buffer {
+                buffer,
+                memory: Some(memory),
+                size,
+            }),
+            Err(error) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                Err(error)
+            }
+        }
+    }
+
+    // Creates a device local buffer holding data, copied in through a host visible staging buffer
+    // The copy is submitted through uploader, and has finished by the time this returns
+    pub fn new_device_local<T: Copy>(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        usage: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> Result<Buffer, GraphicsError> {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+
+        let mut staging_buffer = Buffer::new(
+            device,
+            allocator,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        staging_buffer.write(data);
+        let result = Buffer::copy_from_staging(device, allocator, uploader, usage, &staging_buffer);
+
+        // The upload is waited on for the copy, so the staging buffer is no longer in use either way
+        staging_buffer.destroy(device);
+
+        result
+    }
+
+    // Vertex buffer holding vertices, uploaded to device local memory
+    pub fn new_vertex_buffer(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        vertices: &[Vertex],
+    ) -> Result<Buffer, GraphicsError> {
+        Buffer::new_device_local(
+            device,
+            allocator,
+            uploader,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vertices,
+        )
+    }
+
+    // Index buffer holding indices, uploaded to device local memory
+    pub fn new_index_buffer<I: Index>(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        indices: &[I],
+    ) -> Result<IndexBuffer, GraphicsError> {
+        let buffer = Buffer::new_device_local(
+            device,
+            allocator,
+            uploader,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            indices,
+        )?;
+
+        Ok(IndexBuffer {
+            buffer,
+            index_type: I::INDEX_TYPE,
+            index_count: indices.len() as u32,
+        })
+    }
+
+    // Copies data to the start of the buffer, which must be host visible and coherent and at least as large as data
+    pub fn write<T: Copy>(&mut self, data: &[T]) {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+        assert!(size <= self.size, "Data does not fit in the buffer!");
+
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), self.mapped() as *mut T, data.len()) };
+    }
+
+    // Passes the contents of the whole buffer, which must be host visible and coherent, to read
+    pub fn map_read<R, F: FnOnce(&[u8]) -> R>(&self, read: F) -> R {
+        read(unsafe { slice::from_raw_parts(self.mapped(), self.size as usize) })
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    // Memory bound to the buffer, None once destroyed
+    pub fn allocation(&self) -> Option<&Allocation> {
+        self.memory.as_ref()
+    }
+
+    // Size in bytes that was requested, which may be less than the memory allocated for it
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    // Destroys the buffer and frees its memory - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe { device.destroy_buffer(self.buffer, None) };
+        self.buffer = vk::Buffer::null();
+        // Dropping the allocation hands it back to the allocator
+        self.memory = None;
+    }
+
+    // Start of the buffer in host memory, which must be host visible
+    fn mapped(&self) -> *mut u8 {
+        let mapped = self
+            .memory
+            .as_ref()
+            .map_or(ptr::null_mut(), Allocation::mapped);
+        assert!(!mapped.is_null(), "Buffer is not host visible!");
+        mapped
+    }
+
+    // Creates a device local buffer the size of staging_buffer and copies its contents in, handing it over to the
+    // graphics queue for its first use as usage
+    fn copy_from_staging(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        usage: vk::BufferUsageFlags,
+        staging_buffer: &Buffer,
+    ) -> Result<Buffer, GraphicsError> {
+        let mut buffer = Buffer::new_shared(
+            device,
+            allocator,
+            staging_buffer.size,
+            vk::BufferUsageFlags::TRANSFER_DST | usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            uploader.sharing(),
+        )?;
+
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size: staging_buffer.size,
+        };
+
+        let (stage, access) = first_use(usage);
+        let transfer = OwnershipTransfer::buffer(
+            uploader.sharing(),
+            buffer.buffer,
+            0,
+            vk::WHOLE_SIZE,
+            QueueUse {
+                family: uploader.transfer_family_index(),
+                stage: vk::PipelineStageFlags::TRANSFER,
+                access: vk::AccessFlags::TRANSFER_WRITE,
+            },
+            QueueUse {
+                family: uploader.graphics_family_index(),
+                stage,
+                access,
+            },
+        );
+
+        let result = uploader.submit(
+            device,
+            |command_buffer| {
+                unsafe {
+                    device.cmd_copy_buffer(
+                        command_buffer,
+                        staging_buffer.buffer,
+                        buffer.buffer,
+                        &[region],
+                    )
+                };
+                transfer.record_release(device, command_buffer);
+            },
+            |command_buffer| transfer.record_acquire(device, command_buffer),
+        );
+
+        match result {
+            Ok(()) => Ok(buffer),
+            Err(error) => {
+                buffer.destroy(device);
+                Err(error)
+            }
+        }
+    }
+}
+
+// Index buffer along with the type and number of indices it holds, as needed to bind and draw it
+pub struct IndexBuffer {
+    buffer: Buffer,
+    index_type: vk::IndexType,
+    index_count: u32,
+}
+
+impl IndexBuffer {
+    // Binds the buffer for the following cmd_draw_indexed calls
+    pub fn bind(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_index_buffer(command_buffer, self.buffer.handle(), 0, self.index_type)
+        };
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer.handle()
+    }
+
+    pub fn index_type(&self) -> vk::IndexType {
+        self.index_type
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+        self.index_count = 0;
+    }
+}
+
+// Stages and access a buffer with usage is first read with, for the barrier making an upload visible to it
+fn first_use(usage: vk::BufferUsageFlags) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    let shaders = vk::PipelineStageFlags::VERTEX_SHADER
+        | vk::PipelineStageFlags::FRAGMENT_SHADER
+        | vk::PipelineStageFlags::COMPUTE_SHADER;
+
+    let uses = [
+        (
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+        ),
+        (
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::INDEX_READ,
+        ),
+        (
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            shaders,
+            vk::AccessFlags::UNIFORM_READ,
+        ),
+        (
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            shaders,
+            vk::AccessFlags::SHADER_READ,
+        ),
+        (
+            vk::BufferUsageFlags::INDIRECT_BUFFER,
+            vk::PipelineStageFlags::DRAW_INDIRECT,
+            vk::AccessFlags::INDIRECT_COMMAND_READ,
+        ),
+    ]
+    .iter()
+    .filter(|(flag, _, _)| usage.contains(*flag))
+    .fold(
+        (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty()),
+        |(stages, access), (_, stage, flag_access)| (stages | *stage, access | *flag_access),
+    );
+
+    // Anything else could be read anywhere
+    if uses.0.is_empty() {
+        (
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ,
+        )
+    } else {
+        uses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_use_combines_every_usage() {
+        assert_eq!(
+            first_use(vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER),
+            (
+                vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ
+            )
+        );
+    }
+
+    #[test]
+    fn first_use_ignores_transfer_usage() {
+        assert_eq!(
+            first_use(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDIRECT_BUFFER),
+            (
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::INDIRECT_COMMAND_READ
+            )
+        );
+    }
+
+    #[test]
+    fn first_use_falls_back_to_any_read() {
+        assert_eq!(
+            first_use(vk::BufferUsageFlags::TRANSFER_SRC),
+            (
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_READ
+            )
+        );
+    }
+}
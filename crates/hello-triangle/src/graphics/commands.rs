@@ -0,0 +1,161 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use ash::{vk, Device};
+use std::slice;
+
+// Command pool on a queue family, with one primary command buffer per swapchain image for recording frames, and
+// temporary ones for setup work
+pub struct CommandPool {
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+impl CommandPool {
+    pub fn new(
+        device: &Device,
+        queue_family_index: u32,
+        buffer_count: usize,
+    ) -> Result<CommandPool, GraphicsError> {
+        // Buffers are re-recorded every frame, so each must be resettable on its own
+        let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
+            .queue_family_index(queue_family_index)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+
+        let command_pool = unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+
+        let mut pool = CommandPool {
+            command_pool,
+            command_buffers: Vec::new(),
+        };
+
+        pool.resize(device, buffer_count)?;
+        Ok(pool)
+    }
+
+    // Allocates or frees command buffers so there is one for each of buffer_count swapchain images
+    // The freed buffers must not be pending execution
+    pub fn resize(&mut self, device: &Device, buffer_count: usize) -> Result<(), GraphicsError> {
+        if buffer_count > self.command_buffers.len() {
+            let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(self.command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count((buffer_count - self.command_buffers.len()) as u32);
+
+            let command_buffers =
+                unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? };
+            self.command_buffers.extend(command_buffers);
+        } else if buffer_count < self.command_buffers.len() {
+            let command_buffers: Vec<vk::CommandBuffer> =
+                self.command_buffers.drain(buffer_count..).collect();
+            unsafe { device.free_command_buffers(self.command_pool, &command_buffers) };
+        }
+
+        Ok(())
+    }
+
+    // Resets and re-records the command buffer at index, with record filling in the commands
+    // The buffer must not be pending execution, so the previous submission using it has to have finished
+    pub fn record<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        device: &Device,
+        index: usize,
+        record: F,
+    ) -> Result<vk::CommandBuffer, GraphicsError> {
+        let command_buffer = self.command_buffers[index];
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        unsafe {
+            device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+            device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
+
+        record(command_buffer);
+
+        unsafe { device.end_command_buffer(command_buffer)? };
+
+        Ok(command_buffer)
+    }
+
+    // Records a temporary command buffer, submits it to queue, and waits for it to finish - meant for setup work
+    // such as uploads rather than anything done every frame
+    // Only this submission is waited on, so frames already queued keep running
+    pub fn submit_once<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        device: &Device,
+        queue: vk::Queue,
+        record: F,
+    ) -> Result<(), GraphicsError> {
+        let command_buffer = self.begin_once(device)?;
+        let fence = match unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) } {
+            Ok(fence) => fence,
+            Err(result) => {
+                self.free_once(device, command_buffer);
+                return Err(result.into());
+            }
+        };
+
+        let submit_info =
+            vk::SubmitInfo::builder().command_buffers(slice::from_ref(&command_buffer));
+
+        record(command_buffer);
+
+        let result = unsafe {
+            device
+                .end_command_buffer(command_buffer)
+                .and_then(|_| device.queue_submit(queue, &[*submit_info], fence))
+                .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX))
+        };
+
+        // Freed whether or not the submission succeeded, as nothing else refers to them
+        unsafe { device.destroy_fence(fence, None) };
+        self.free_once(device, command_buffer);
+
+        result.map_err(GraphicsError::from)
+    }
+
+    // Allocates a temporary command buffer and begins recording it for a single submission
+    // Once the submission has finished, it must be given back with free_once()
+    pub fn begin_once(&self, device: &Device) -> Result<vk::CommandBuffer, GraphicsError> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+
+        let command_buffer =
+            unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? }[0];
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        if let Err(result) =
+            unsafe { device.begin_command_buffer(command_buffer, &command_buffer_begin_info) }
+        {
+            self.free_once(device, command_buffer);
+            return Err(result.into());
+        }
+
+        Ok(command_buffer)
+    }
+
+    // Frees a command buffer from begin_once(), which must not be pending execution
+    pub fn free_once(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        unsafe { device.free_command_buffers(self.command_pool, slice::from_ref(&command_buffer)) };
+    }
+
+    pub fn handle(&self) -> vk::CommandPool {
+        self.command_pool
+    }
+
+    pub fn command_buffers(&self) -> &[vk::CommandBuffer] {
+        &self.command_buffers
+    }
+
+    // Destroys the pool, which frees its command buffers - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.command_buffers.clear();
+
+        unsafe { device.destroy_command_pool(self.command_pool, None) };
+        self.command_pool = vk::CommandPool::null();
+    }
+}
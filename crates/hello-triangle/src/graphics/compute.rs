@@ -0,0 +1,526 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::pod::Pod;
+use crate::graphics::push_constants;
+use crate::graphics::queue_ownership::{OwnershipTransfer, QueueSharing, QueueUse};
+use ash::{vk, Device};
+use std::{ffi::CString, mem, slice};
+
+// Queue family to dispatch compute work on, preferring one without graphics support so it can run alongside rendering
+// (async compute), and otherwise any that can compute, which includes the graphics family on every device that has one
+pub fn find_compute_family(queue_families: &[vk::QueueFamilyProperties]) -> Option<usize> {
+    let supports_compute = |family: &vk::QueueFamilyProperties| {
+        family.queue_count > 0 && family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+    };
+
+    queue_families
+        .iter()
+        .position(|family| {
+            supports_compute(family) && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+        })
+        .or_else(|| queue_families.iter().position(supports_compute))
+}
+
+// Number of workgroups of local_size invocations needed to cover invocations
+pub fn group_count(invocations: u32, local_size: u32) -> u32 {
+    invocations.div_ceil(local_size)
+}
+
+// Resource bound to a compute shader in set 0, at the binding matching its position in the list of bindings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComputeBinding {
+    // Range of a buffer with STORAGE_BUFFER usage, read and written as a buffer block
+    StorageBuffer {
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    },
+    // View of an image with STORAGE usage, which must be in GENERAL layout when dispatched
+    StorageImage(vk::ImageView),
+}
+
+impl ComputeBinding {
+    fn descriptor_type(&self) -> vk::DescriptorType {
+        match self {
+            ComputeBinding::StorageBuffer { .. } => vk::DescriptorType::STORAGE_BUFFER,
+            ComputeBinding::StorageImage(_) => vk::DescriptorType::STORAGE_IMAGE,
+        }
+    }
+}
+
+// Barriers for an output of one of the supported resource types
+#[derive(Clone, Copy, Debug)]
+enum OutputBarriers {
+    Buffer(OwnershipTransfer<vk::BufferMemoryBarrier>),
+    Image(OwnershipTransfer<vk::ImageMemoryBarrier>),
+}
+
+// Resource a dispatch writes that something else reads afterwards, such as a vertex buffer the graphics pass draws
+// or an image it samples
+// dispatch() releases it after writing, and record_acquire() must be recorded before the first read - right after
+// dispatch() when both run on the same queue, and otherwise on the reading queue, waiting on a semaphore the compute
+// submission signals
+#[derive(Clone, Copy, Debug)]
+pub struct ComputeOutput {
+    barriers: OutputBarriers,
+    // Stages that read the output, which the next dispatch writing it has to wait for
+    reader_stage: vk::PipelineStageFlags,
+}
+
+impl ComputeOutput {
+    // Whole of buffer, written by dispatches on compute_family and then read as reader describes
+    pub fn buffer(
+        sharing: &QueueSharing,
+        buffer: vk::Buffer,
+        compute_family: u32,
+        reader: QueueUse,
+    ) -> ComputeOutput {
+        ComputeOutput {
+            barriers: OutputBarriers::Buffer(OwnershipTransfer::buffer(
+                sharing,
+                buffer,
+                0,
+                vk::WHOLE_SIZE,
+                ComputeOutput::writer(compute_family),
+                reader,
+            )),
+            reader_stage: reader.stage,
+        }
+    }
+
+    // subresource_range of image, written by dispatches on compute_family and then read as reader describes
+    // The image stays in GENERAL layout, which storage writes need and sampling allows, so nothing has to move it back
+    // before the next dispatch
+    pub fn image(
+        sharing: &QueueSharing,
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+        compute_family: u32,
+        reader: QueueUse,
+    ) -> ComputeOutput {
+        ComputeOutput {
+            barriers: OutputBarriers::Image(OwnershipTransfer::image(
+                sharing,
+                image,
+                subresource_range,
+                [vk::ImageLayout::GENERAL, vk::ImageLayout::GENERAL],
+                ComputeOutput::writer(compute_family),
+                reader,
+            )),
+            reader_stage: reader.stage,
+        }
+    }
+
+    // Whether the output changes queue family, needing a semaphore between the dispatch and the reader
+    pub fn is_transfer(&self) -> bool {
+        match &self.barriers {
+            OutputBarriers::Buffer(transfer) => transfer.is_transfer(),
+            OutputBarriers::Image(transfer) => transfer.is_transfer(),
+        }
+    }
+
+    // Records the barrier making the dispatch's writes visible to the reader, before the reader's first use
+    pub fn record_acquire(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        match &self.barriers {
+            OutputBarriers::Buffer(transfer) => transfer.record_acquire(device, command_buffer),
+            OutputBarriers::Image(transfer) => transfer.record_acquire(device, command_buffer),
+        }
+    }
+
+    fn record_release(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        match &self.barriers {
+            OutputBarriers::Buffer(transfer) => transfer.record_release(device, command_buffer),
+            OutputBarriers::Image(transfer) => transfer.record_release(device, command_buffer),
+        }
+    }
+
+    fn writer(compute_family: u32) -> QueueUse {
+        QueueUse {
+            family: compute_family,
+            stage: vk::PipelineStageFlags::COMPUTE_SHADER,
+            access: vk::AccessFlags::SHADER_WRITE,
+        }
+    }
+}
+
+// Compute pipeline built from a SPIR-V compute shader, along with the descriptor set holding its bindings
+pub struct ComputePipeline {
+    shader_module: vk::ShaderModule,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    push_constant_size: u32,
+}
+
+impl ComputePipeline {
+    // Creates a pipeline running code's main function, with bindings in set 0 and push_constant_size bytes of push
+    // constants
+    pub fn new(
+        device: &Device,
+        code: &[u32],
+        bindings: &[ComputeBinding],
+        push_constant_size: u32,
+    ) -> Result<ComputePipeline, GraphicsError> {
+        let mut pipeline = ComputePipeline {
+            shader_module: vk::ShaderModule::null(),
+            descriptor_set_layout: vk::DescriptorSetLayout::null(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set: vk::DescriptorSet::null(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            push_constant_size,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        match pipeline.create(device, code, bindings) {
+            Ok(()) => Ok(pipeline),
+            Err(error) => {
+                pipeline.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    // Points the descriptor set at other resources, of the same types the pipeline was created with
+    // The descriptor set must not be in use by a pending dispatch
+    pub fn update_bindings(&self, device: &Device, bindings: &[ComputeBinding]) {
+        // Infos are built up front, as the writes point into them
+        let buffer_infos: Vec<vk::DescriptorBufferInfo> = bindings
+            .iter()
+            .map(|binding| match *binding {
+                ComputeBinding::StorageBuffer {
+                    buffer,
+                    offset,
+                    range,
+                } => vk::DescriptorBufferInfo {
+                    buffer,
+                    offset,
+                    range,
+                },
+                ComputeBinding::StorageImage(_) => vk::DescriptorBufferInfo::default(),
+            })
+            .collect();
+        let image_infos: Vec<vk::DescriptorImageInfo> = bindings
+            .iter()
+            .map(|binding| match *binding {
+                ComputeBinding::StorageImage(image_view) => vk::DescriptorImageInfo {
+                    sampler: vk::Sampler::null(),
+                    image_view,
+                    image_layout: vk::ImageLayout::GENERAL,
+                },
+                ComputeBinding::StorageBuffer { .. } => vk::DescriptorImageInfo::default(),
+            })
+            .collect();
+
+        let writes: Vec<vk::WriteDescriptorSet> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_set(self.descriptor_set)
+                    .dst_binding(index as u32)
+                    .descriptor_type(binding.descriptor_type());
+
+                match binding {
+                    ComputeBinding::StorageBuffer { .. } => write
+                        .buffer_info(slice::from_ref(&buffer_infos[index]))
+                        .build(),
+                    ComputeBinding::StorageImage(_) => write
+                        .image_info(slice::from_ref(&image_infos[index]))
+                        .build(),
+                }
+            })
+            .collect();
+
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    // Records a dispatch of group_count workgroups with push_constants, then releases each of outputs to its reader
+    // Outputs read on the same queue are waited for first, so the last reads of the previous dispatch's results finish
+    // before they are overwritten
+    pub fn dispatch<P: Pod>(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        group_count: [u32; 3],
+        push_constants: &P,
+        outputs: &[ComputeOutput],
+    ) {
+        let push_constant_size = mem::size_of::<P>();
+        assert!(
+            push_constant_size <= self.push_constant_size as usize,
+            "Push constants do not fit in the pipeline's push constant range!"
+        );
+
+        // Only an execution dependency is needed to stop writes overtaking earlier reads
+        let readers = outputs
+            .iter()
+            .filter(|output| !output.is_transfer())
+            .fold(vk::PipelineStageFlags::empty(), |stages, output| {
+                stages | output.reader_stage
+            });
+
+        unsafe {
+            if !readers.is_empty() {
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    readers,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[],
+                );
+            }
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.layout,
+                0,
+                slice::from_ref(&self.descriptor_set),
+                &[],
+            );
+
+            push_constants::cmd_push_constants(
+                device,
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constants,
+            );
+
+            device.cmd_dispatch(
+                command_buffer,
+                group_count[0],
+                group_count[1],
+                group_count[2],
+            );
+        }
+
+        for output in outputs {
+            output.record_release(device, command_buffer);
+        }
+    }
+
+    // Pipeline to bind with cmd_bind_pipeline at vk::PipelineBindPoint::COMPUTE
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    // Destroys the pipeline, its layouts, descriptor pool, and shader module - must be called before the device is
+    // destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            // Destroying the pool frees the descriptor set
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            device.destroy_shader_module(self.shader_module, None);
+        }
+        self.pipeline = vk::Pipeline::null();
+        self.layout = vk::PipelineLayout::null();
+        self.descriptor_set = vk::DescriptorSet::null();
+        self.descriptor_pool = vk::DescriptorPool::null();
+        self.descriptor_set_layout = vk::DescriptorSetLayout::null();
+        self.shader_module = vk::ShaderModule::null();
+    }
+
+    fn create(
+        &mut self,
+        device: &Device,
+        code: &[u32],
+        bindings: &[ComputeBinding],
+    ) -> Result<(), GraphicsError> {
+        let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(index as u32)
+                    .descriptor_type(binding.descriptor_type())
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .build()
+            })
+            .collect();
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        self.descriptor_set_layout =
+            unsafe { device.create_descriptor_set_layout(&layout_info, None)? };
+
+        // Pools can't be created empty, so a pipeline without bindings gets no pool or set
+        if !bindings.is_empty() {
+            let pool_sizes: Vec<vk::DescriptorPoolSize> = layout_bindings
+                .iter()
+                .map(|binding| vk::DescriptorPoolSize {
+                    ty: binding.descriptor_type,
+                    descriptor_count: 1,
+                })
+                .collect();
+
+            let pool_info = vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(1)
+                .pool_sizes(&pool_sizes);
+            self.descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None)? };
+
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(slice::from_ref(&self.descriptor_set_layout));
+            self.descriptor_set = unsafe { device.allocate_descriptor_sets(&allocate_info)? }[0];
+
+            self.update_bindings(device, bindings);
+        }
+
+        let shader_module_info = vk::ShaderModuleCreateInfo::builder().code(code);
+        self.shader_module = unsafe {
+            device
+                .create_shader_module(&shader_module_info, None)
+                .map_err(|result| GraphicsError::ShaderModuleCreation {
+                    name: "compute",
+                    result,
+                })?
+        };
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: self.push_constant_size,
+        };
+        let push_constant_ranges = if self.push_constant_size > 0 {
+            slice::from_ref(&push_constant_range)
+        } else {
+            &[]
+        };
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(slice::from_ref(&self.descriptor_set_layout))
+            .push_constant_ranges(push_constant_ranges);
+        self.layout = unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .map_err(GraphicsError::ComputePipelineCreation)?
+        };
+
+        let shader_entry_name = CString::new("main").unwrap();
+        let stage_info = vk::PipelineShaderStageCreateInfo::builder()
+            .module(self.shader_module)
+            .name(shader_entry_name.as_c_str())
+            .stage(vk::ShaderStageFlags::COMPUTE);
+
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(*stage_info)
+            .layout(self.layout);
+
+        self.pipeline = unsafe {
+            device
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    slice::from_ref(&pipeline_info),
+                    None,
+                )
+                .map_err(|(_, result)| GraphicsError::ComputePipelineCreation(result))?
+        }[0];
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::queue_ownership::SharingPolicy;
+
+    const GRAPHICS: u32 = 0;
+    const COMPUTE: u32 = 1;
+
+    fn families(flags: &[vk::QueueFlags]) -> Vec<vk::QueueFamilyProperties> {
+        flags
+            .iter()
+            .map(|&queue_flags| vk::QueueFamilyProperties {
+                queue_flags,
+                queue_count: 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn vertex_read(family: u32) -> QueueUse {
+        QueueUse {
+            family,
+            stage: vk::PipelineStageFlags::VERTEX_INPUT,
+            access: vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+        }
+    }
+
+    #[test]
+    fn prefers_async_compute_families() {
+        let families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+        ]);
+
+        assert_eq!(find_compute_family(&families), Some(2));
+    }
+
+    #[test]
+    fn falls_back_to_the_graphics_family() {
+        let families = families(&[
+            vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE,
+        ]);
+
+        assert_eq!(find_compute_family(&families), Some(1));
+        assert_eq!(
+            find_compute_family(&families[..1]),
+            None,
+            "Transfer only families can't compute"
+        );
+    }
+
+    #[test]
+    fn group_count_covers_every_invocation() {
+        assert_eq!(group_count(0, 64), 0);
+        assert_eq!(group_count(1, 64), 1);
+        assert_eq!(group_count(64, 64), 1);
+        assert_eq!(group_count(65, 64), 2);
+    }
+
+    #[test]
+    fn same_queue_outputs_are_waited_on_by_the_next_dispatch() {
+        let sharing = QueueSharing::new(SharingPolicy::Exclusive, &[GRAPHICS, COMPUTE]);
+
+        let same_queue = ComputeOutput::buffer(
+            &sharing,
+            vk::Buffer::null(),
+            GRAPHICS,
+            vertex_read(GRAPHICS),
+        );
+        assert!(!same_queue.is_transfer());
+        assert_eq!(
+            same_queue.reader_stage,
+            vk::PipelineStageFlags::VERTEX_INPUT
+        );
+
+        let async_compute =
+            ComputeOutput::buffer(&sharing, vk::Buffer::null(), COMPUTE, vertex_read(GRAPHICS));
+        assert!(async_compute.is_transfer());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use ash::{extensions::ext::DebugUtils, vk, Entry, Instance};
+use std::{
+    borrow::Cow,
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+};
+
+const BAD_ERROR: &str = "Something went incredibly wrong!";
+
+const VALIDATION_LAYER_NAME: &[u8] = b"VK_LAYER_KHRONOS_validation\0";
+
+pub fn validation_layer_name() -> &'static CStr {
+    CStr::from_bytes_with_nul(VALIDATION_LAYER_NAME).expect(BAD_ERROR)
+}
+
+// Checks if the Khronos validation layer is installed (it ships with the Vulkan SDK, not the driver)
+pub fn check_validation_layer_support(entry: &Entry) -> Result<bool, GraphicsError> {
+    let layers = entry.enumerate_instance_layer_properties()?;
+
+    Ok(layers.iter().any(|layer| {
+        let layer_name = unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) };
+        layer_name == validation_layer_name()
+    }))
+}
+
+// Settings for the messenger, also chained onto the instance create info to catch messages from instance creation
+pub fn messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+    vk::DebugUtilsMessengerCreateInfoEXT::builder()
+        .message_severity(
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        )
+        .message_type(
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        )
+        .pfn_user_callback(Some(vulkan_debug_callback))
+        .build()
+}
+
+// Routes validation and driver messages to the log, which Vulkan would otherwise drop silently
+pub struct DebugMessenger {
+    debug_utils: DebugUtils,
+    messenger: vk::DebugUtilsMessengerEXT,
+}
+
+impl DebugMessenger {
+    pub fn new(entry: &Entry, instance: &Instance) -> Result<DebugMessenger, GraphicsError> {
+        let debug_utils = DebugUtils::new(entry, instance);
+
+        let messenger =
+            unsafe { debug_utils.create_debug_utils_messenger(&messenger_create_info(), None)? };
+
+        Ok(DebugMessenger {
+            debug_utils,
+            messenger,
+        })
+    }
+
+    // Must be called before the instance is destroyed
+    pub fn destroy(&mut self) {
+        unsafe {
+            self.debug_utils
+                .destroy_debug_utils_messenger(self.messenger, None);
+        }
+    }
+}
+
+unsafe extern "system" fn vulkan_debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _user_data: *mut c_void,
+) -> vk::Bool32 {
+    let callback_data = &*callback_data;
+
+    let message_id_name = c_string_or_empty(callback_data.p_message_id_name);
+    let message = c_string_or_empty(callback_data.p_message);
+
+    let severity = if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        "ERROR"
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        "WARNING"
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        "INFO"
+    } else {
+        "VERBOSE"
+    };
+
+    eprintln!(
+        "[Vulkan {} {:?}] {}: {}",
+        severity, message_type, message_id_name, message
+    );
+
+    // Returning false lets the call that triggered the message carry on
+    vk::FALSE
+}
+
+unsafe fn c_string_or_empty<'a>(pointer: *const c_char) -> Cow<'a, str> {
+    if pointer.is_null() {
+        Cow::Borrowed("")
+    } else {
+        CStr::from_ptr(pointer).to_string_lossy()
+    }
+}
@@ -0,0 +1,165 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::{Allocation, Allocator};
+use ash::{vk, Device, Instance};
+
+// Depth formats in order of preference - D32 gives the most precision, and D24S8 is the fallback some GPUs need
+const DEPTH_FORMAT_CANDIDATES: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+// Depth image the render pass tests against, sized to the swapchain
+// A single image is shared by every frame in flight, which the render pass dependency keeps in order
+pub struct DepthBuffer {
+    image: vk::Image,
+    // Only held until destroy() drops it, which hands the memory back to the allocator
+    _memory: Option<Allocation>,
+    view: vk::ImageView,
+    format: vk::Format,
+    // Must match the color attachments it is used with
+    samples: vk::SampleCountFlags,
+    extent: vk::Extent2D,
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+    ) -> Result<DepthBuffer, GraphicsError> {
+        let mut depth_buffer = DepthBuffer {
+            image: vk::Image::null(),
+            _memory: None,
+            view: vk::ImageView::null(),
+            format,
+            samples,
+            extent,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        match depth_buffer.create(device, allocator) {
+            Ok(()) => Ok(depth_buffer),
+            Err(error) => {
+                depth_buffer.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    // Picks the first candidate format the physical device can use as an optimally tiled depth attachment
+    pub fn find_format(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<vk::Format, GraphicsError> {
+        DEPTH_FORMAT_CANDIDATES
+            .iter()
+            .copied()
+            .find(|format| {
+                let properties = unsafe {
+                    instance.get_physical_device_format_properties(physical_device, *format)
+                };
+                properties
+                    .optimal_tiling_features
+                    .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .ok_or(GraphicsError::NoDepthFormat)
+    }
+
+    // Replaces the image with one of the new extent, keeping the format and sample count
+    // The device must be idle, and framebuffers using the old view must be rebuilt afterwards
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        allocator: &Allocator,
+        extent: vk::Extent2D,
+    ) -> Result<(), GraphicsError> {
+        self.destroy(device);
+        self.extent = extent;
+
+        self.create(device, allocator)
+            .inspect_err(|_| self.destroy(device))
+    }
+
+    // View to attach to framebuffers
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Destroys the view and image and frees its memory - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self._memory = None;
+    }
+
+    fn create(&mut self, device: &Device, allocator: &Allocator) -> Result<(), GraphicsError> {
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.format)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(self.samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        self._memory = Some(allocator.allocate_image(
+            device,
+            self.image,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(self.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: DepthBuffer::aspect_mask(self.format),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        Ok(())
+    }
+
+    // Attachment views of combined depth stencil formats must cover both aspects
+    fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+        match format {
+            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            }
+            _ => vk::ImageAspectFlags::DEPTH,
+        }
+    }
+}
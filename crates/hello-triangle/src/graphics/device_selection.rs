@@ -0,0 +1,247 @@
+use ash::vk;
+use std::env;
+
+// Environment variable overriding VulkanSettings::device_selection, holding either a device index as listed in the
+// log or part of a device name
+pub const DEVICE_OVERRIDE_VARIABLE: &str = "HELLO_TRIANGLE_DEVICE";
+
+// How VulkanBase picks between the suitable physical devices
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceSelection {
+    // Highest scoring device
+    #[default]
+    Best,
+    // Device at this index in vkEnumeratePhysicalDevices order
+    Index(usize),
+    // First device whose name contains this, ignoring case
+    Name(String),
+}
+
+impl DeviceSelection {
+    // Reads the selection from DEVICE_OVERRIDE_VARIABLE, if it is set
+    pub fn from_env() -> Option<DeviceSelection> {
+        env::var(DEVICE_OVERRIDE_VARIABLE)
+            .ok()
+            .and_then(|value| DeviceSelection::parse(&value))
+    }
+
+    // Numbers select by index and anything else by name, with an empty value meaning no override
+    pub fn parse(value: &str) -> Option<DeviceSelection> {
+        let value = value.trim();
+
+        if value.is_empty() {
+            None
+        } else if let Ok(index) = value.parse() {
+            Some(DeviceSelection::Index(index))
+        } else {
+            Some(DeviceSelection::Name(value.to_string()))
+        }
+    }
+}
+
+// What a suitable physical device offers, gathered from its properties, features, and memory heaps
+#[derive(Clone, Debug)]
+pub struct DeviceCandidate {
+    // Position in vkEnumeratePhysicalDevices order
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub max_image_dimension_2d: u32,
+    // Total size of the device local heaps in bytes
+    pub device_local_memory: u64,
+    pub sampler_anisotropy: bool,
+    pub geometry_shader: bool,
+}
+
+// Ranks a device, mostly by type so a discrete GPU always beats an integrated one (which can report most of system
+// memory as device local), then by memory, supported features, and image size limits
+pub fn score(candidate: &DeviceCandidate) -> u64 {
+    let type_score = match candidate.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 100_000,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 10_000,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 1_000,
+        vk::PhysicalDeviceType::CPU => 100,
+        _ => 0,
+    };
+
+    // 100 points per GiB
+    let memory_score = candidate.device_local_memory * 100 / (1024 * 1024 * 1024);
+
+    let feature_score = [candidate.sampler_anisotropy, candidate.geometry_shader]
+        .iter()
+        .filter(|supported| **supported)
+        .count() as u64
+        * 50;
+
+    let image_score = u64::from(candidate.max_image_dimension_2d / 1024);
+
+    type_score + memory_score + feature_score + image_score
+}
+
+// Returns the position in candidates of the device to use, None only when there are no candidates
+// Falls back to the best device when an index or name matches none of them
+pub fn select(candidates: &[DeviceCandidate], selection: &DeviceSelection) -> Option<usize> {
+    let selected = match selection {
+        DeviceSelection::Best => None,
+        DeviceSelection::Index(index) => candidates
+            .iter()
+            .position(|candidate| candidate.index == *index),
+        DeviceSelection::Name(name) => {
+            let name = name.to_lowercase();
+            candidates
+                .iter()
+                .position(|candidate| candidate.name.to_lowercase().contains(&name))
+        }
+    };
+
+    if selected.is_none() && *selection != DeviceSelection::Best {
+        println!(
+            "No suitable GPU matches {:?}, picking the best one instead!",
+            selection
+        );
+    }
+
+    // max_by_key returns the last of equal scores, so the first is kept by comparing in reverse
+    selected.or_else(|| {
+        candidates
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, candidate)| score(candidate))
+            .map(|(position, _)| position)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn candidate(
+        index: usize,
+        name: &str,
+        device_type: vk::PhysicalDeviceType,
+        device_local_memory: u64,
+    ) -> DeviceCandidate {
+        DeviceCandidate {
+            index,
+            name: name.to_string(),
+            device_type,
+            max_image_dimension_2d: 16384,
+            device_local_memory,
+            sampler_anisotropy: true,
+            geometry_shader: true,
+        }
+    }
+
+    // A hybrid laptop, which lists the integrated GPU first and shares most of system memory with it
+    fn hybrid_laptop() -> Vec<DeviceCandidate> {
+        vec![
+            candidate(
+                0,
+                "Intel(R) UHD Graphics 630",
+                vk::PhysicalDeviceType::INTEGRATED_GPU,
+                24 * GIB,
+            ),
+            candidate(
+                1,
+                "NVIDIA GeForce RTX 2060",
+                vk::PhysicalDeviceType::DISCRETE_GPU,
+                6 * GIB,
+            ),
+            candidate(
+                2,
+                "llvmpipe (LLVM 12.0.0, 256 bits)",
+                vk::PhysicalDeviceType::CPU,
+                32 * GIB,
+            ),
+        ]
+    }
+
+    #[test]
+    fn parse_reads_indices_and_names() {
+        assert_eq!(DeviceSelection::parse("1"), Some(DeviceSelection::Index(1)));
+        assert_eq!(
+            DeviceSelection::parse(" nvidia "),
+            Some(DeviceSelection::Name(String::from("nvidia")))
+        );
+        assert_eq!(DeviceSelection::parse("  "), None);
+    }
+
+    #[test]
+    fn discrete_gpu_outscores_integrated_gpu_with_more_memory() {
+        let candidates = hybrid_laptop();
+
+        assert!(score(&candidates[1]) > score(&candidates[0]));
+        assert!(score(&candidates[0]) > score(&candidates[2]));
+    }
+
+    #[test]
+    fn features_and_memory_break_ties_within_a_type() {
+        let mut weaker = candidate(0, "A", vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB);
+        weaker.sampler_anisotropy = false;
+        let stronger = candidate(1, "B", vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB);
+        let bigger = candidate(2, "C", vk::PhysicalDeviceType::DISCRETE_GPU, 12 * GIB);
+
+        assert!(score(&stronger) > score(&weaker));
+        assert!(score(&bigger) > score(&stronger));
+    }
+
+    #[test]
+    fn best_picks_the_highest_score() {
+        assert_eq!(select(&hybrid_laptop(), &DeviceSelection::Best), Some(1));
+    }
+
+    #[test]
+    fn best_keeps_the_first_of_equal_scores() {
+        let candidates = vec![
+            candidate(0, "A", vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB),
+            candidate(1, "B", vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB),
+        ];
+
+        assert_eq!(select(&candidates, &DeviceSelection::Best), Some(0));
+    }
+
+    #[test]
+    fn overrides_select_by_index_and_name() {
+        let candidates = hybrid_laptop();
+
+        assert_eq!(select(&candidates, &DeviceSelection::Index(0)), Some(0));
+        assert_eq!(
+            select(
+                &candidates,
+                &DeviceSelection::Name(String::from("LLVMPIPE"))
+            ),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn index_refers_to_enumeration_order_not_position() {
+        // Device 1 was unsuitable, so it is missing from the candidates
+        let candidates = vec![
+            candidate(0, "A", vk::PhysicalDeviceType::INTEGRATED_GPU, 2 * GIB),
+            candidate(2, "C", vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB),
+        ];
+
+        assert_eq!(select(&candidates, &DeviceSelection::Index(2)), Some(1));
+    }
+
+    #[test]
+    fn unmatched_overrides_fall_back_to_best() {
+        let candidates = hybrid_laptop();
+
+        assert_eq!(select(&candidates, &DeviceSelection::Index(7)), Some(1));
+        assert_eq!(
+            select(&candidates, &DeviceSelection::Name(String::from("radeon"))),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn no_candidates_selects_nothing() {
+        assert_eq!(select(&[], &DeviceSelection::Best), None);
+        assert_eq!(select(&[], &DeviceSelection::Index(0)), None);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use ash::{vk, Device};
+
+// Semaphores and fences letting the CPU record up to frames_in_flight frames ahead of the GPU
+pub struct FrameSync {
+    // Signalled when the frame's swapchain image has been acquired
+    image_available: Vec<vk::Semaphore>,
+    // Signalled when the frame's commands have finished, which presentation waits on
+    render_finished: Vec<vk::Semaphore>,
+    // Signalled when the frame's submission has finished, so its resources can be reused
+    in_flight: Vec<vk::Fence>,
+    // Fence of the frame last submitted for each swapchain image, null if the image has not been used
+    images_in_flight: Vec<vk::Fence>,
+    current_frame: usize,
+}
+
+impl FrameSync {
+    // frames_in_flight must be at least 1
+    pub fn new(
+        device: &Device,
+        frames_in_flight: usize,
+        image_count: usize,
+    ) -> Result<FrameSync, GraphicsError> {
+        let mut frame_sync = FrameSync {
+            image_available: Vec::with_capacity(frames_in_flight),
+            render_finished: Vec::with_capacity(frames_in_flight),
+            in_flight: Vec::with_capacity(frames_in_flight),
+            images_in_flight: vec![vk::Fence::null(); image_count],
+            current_frame: 0,
+        };
+
+        // Whatever was created before a failure is in the lists, so it can be cleaned up as a whole
+        frame_sync
+            .create(device, frames_in_flight)
+            .inspect_err(|_| frame_sync.destroy(device))?;
+
+        Ok(frame_sync)
+    }
+
+    // Waits until the current frame's previous submission has finished, so its semaphores can be reused
+    pub fn wait_for_frame(&self, device: &Device) -> Result<(), GraphicsError> {
+        unsafe { device.wait_for_fences(&[self.in_flight[self.current_frame]], true, u64::MAX)? };
+        Ok(())
+    }
+
+    // Waits until any earlier frame rendering to image_index has finished, then marks the current frame as
+    // using it - the image's command buffer can be re-recorded afterwards
+    pub fn wait_for_image(
+        &mut self,
+        device: &Device,
+        image_index: u32,
+    ) -> Result<(), GraphicsError> {
+        let image_fence = self.images_in_flight[image_index as usize];
+        if image_fence != vk::Fence::null() {
+            unsafe { device.wait_for_fences(&[image_fence], true, u64::MAX)? };
+        }
+
+        self.images_in_flight[image_index as usize] = self.in_flight[self.current_frame];
+        Ok(())
+    }
+
+    // Unsignals the current frame's fence, immediately before the submission that will signal it again
+    pub fn reset_frame_fence(&self, device: &Device) -> Result<(), GraphicsError> {
+        unsafe { device.reset_fences(&[self.in_flight[self.current_frame]])? };
+        Ok(())
+    }
+
+    pub fn image_available(&self) -> vk::Semaphore {
+        self.image_available[self.current_frame]
+    }
+
+    pub fn render_finished(&self) -> vk::Semaphore {
+        self.render_finished[self.current_frame]
+    }
+
+    pub fn in_flight_fence(&self) -> vk::Fence {
+        self.in_flight[self.current_frame]
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn advance(&mut self) {
+        self.current_frame = (self.current_frame + 1) % self.in_flight.len();
+    }
+
+    // Forgets which frames used which swapchain images, after the swapchain is recreated with image_count images
+    // The device must be idle
+    pub fn reset_images(&mut self, image_count: usize) {
+        self.images_in_flight = vec![vk::Fence::null(); image_count];
+    }
+
+    // Replaces every semaphore with a fresh unsignalled one, for when a failed present left render_finished signalled
+    // with nothing going to wait on it
+    // The device must be idle
+    pub fn recreate_semaphores(&mut self, device: &Device) -> Result<(), GraphicsError> {
+        let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+
+        for semaphore in self
+            .image_available
+            .iter_mut()
+            .chain(self.render_finished.iter_mut())
+        {
+            unsafe {
+                device.destroy_semaphore(*semaphore, None);
+                // Null until created, so destroy() can still clean up if creating one fails
+                *semaphore = vk::Semaphore::null();
+                *semaphore = device.create_semaphore(&semaphore_create_info, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create(&mut self, device: &Device, frames_in_flight: usize) -> Result<(), GraphicsError> {
+        let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+        // Fences start signalled so waiting on them for the first frames returns immediately
+        let fence_create_info =
+            vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
+
+        for _ in 0..frames_in_flight {
+            unsafe {
+                self.image_available
+                    .push(device.create_semaphore(&semaphore_create_info, None)?);
+                self.render_finished
+                    .push(device.create_semaphore(&semaphore_create_info, None)?);
+                self.in_flight
+                    .push(device.create_fence(&fence_create_info, None)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Destroys the semaphores and fences - the device must be idle, and this must be called before it is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            for semaphore in self
+                .image_available
+                .drain(..)
+                .chain(self.render_finished.drain(..))
+            {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for fence in self.in_flight.drain(..) {
+                device.destroy_fence(fence, None);
+            }
+        }
+        self.images_in_flight.clear();
+    }
+}
@@ -1,7 +1,117 @@
+use ash::vk;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum GraphicsError {
     #[error("Invalid GPU")]
     InvalidGPU,
-}
\ No newline at end of file
+    #[error("Failed to load the Vulkan library: {0}")]
+    Loading(#[from] ash::LoadingError),
+    #[error("Failed to create the Vulkan instance: {0}")]
+    InstanceCreation(vk::Result),
+    #[error("Failed to load Vulkan instance functions: {}", .0.join(", "))]
+    InstanceLoading(Vec<&'static str>),
+    #[error("Failed to create the window surface: {0}")]
+    SurfaceCreation(vk::Result),
+    #[error("Failed to create the logical device: {0}")]
+    DeviceCreation(vk::Result),
+    #[error("Swapchain operation failed: {0}")]
+    Swapchain(vk::Result),
+    #[error("Timed out acquiring a swapchain image")]
+    AcquireTimeout,
+    #[error("The swapchain is out of date")]
+    SwapchainOutOfDate,
+    #[error("The window surface was lost")]
+    SurfaceLost,
+    #[error("The presentation queue family cannot present to the new surface")]
+    PresentUnsupported,
+    #[error("The surface supports no formats")]
+    NoSurfaceFormats,
+    #[error("Not available when rendering headless")]
+    Headless,
+    #[error("Frames can only be read back when rendering headless")]
+    ReadbackUnsupported,
+    #[error("Failed to read {name} shader: {source}")]
+    ShaderLoading {
+        name: &'static str,
+        source: std::io::Error,
+    },
+    #[error("Failed to start the shader compiler")]
+    ShaderCompilerCreation,
+    #[error("Failed to compile {name} shader:\n{log}")]
+    ShaderCompilation { name: &'static str, log: String },
+    #[error("Failed to reflect {name} shader: {source}")]
+    ShaderReflection {
+        name: &'static str,
+        source: shader_reflect::ReflectError,
+    },
+    #[error("The {name} shader reads push constant block {block} outside the declared ranges")]
+    UndeclaredPushConstants { name: &'static str, block: String },
+    #[error("A shader stage is in more than one push constant range")]
+    OverlappingPushConstantRanges,
+    #[error("Push constant offset {0} is not a multiple of 4")]
+    UnalignedPushConstantOffset(u32),
+    #[error("Push constant range of {size} bytes at offset {offset} runs past 128 bytes")]
+    PushConstantRangeTooLarge { offset: u32, size: u32 },
+    #[error("Failed to create {name} shader module: {result}")]
+    ShaderModuleCreation {
+        name: &'static str,
+        result: vk::Result,
+    },
+    #[error("No supported depth format")]
+    NoDepthFormat,
+    #[error("Failed to load texture: {0}")]
+    TextureLoading(#[from] image::ImageError),
+    #[error("Failed to read model: {0}")]
+    ModelReading(std::io::Error),
+    #[error("Invalid OBJ data on line {line}: {message}")]
+    ModelParsing { line: usize, message: &'static str },
+    #[error("Failed to create the graphics pipeline: {0}")]
+    PipelineCreation(vk::Result),
+    #[error("Failed to create the compute pipeline: {0}")]
+    ComputePipelineCreation(vk::Result),
+    #[error("No memory type with {0:?}")]
+    NoSuitableMemoryType(vk::MemoryPropertyFlags),
+    #[error("Out of memory: {0}")]
+    OutOfMemory(vk::Result),
+    #[error("Device lost")]
+    DeviceLost,
+    #[error("Vulkan call failed: {0}")]
+    Vulkan(vk::Result),
+}
+
+// Sorts results from calls without a more specific variant, so callers can tell memory exhaustion and device loss
+// apart from other failures
+impl From<vk::Result> for GraphicsError {
+    fn from(result: vk::Result) -> GraphicsError {
+        match result {
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                GraphicsError::OutOfMemory(result)
+            }
+            vk::Result::ERROR_DEVICE_LOST => GraphicsError::DeviceLost,
+            _ => GraphicsError::Vulkan(result),
+        }
+    }
+}
+
+// Entry::create_instance reports functions it could not load apart from a failed vkCreateInstance
+impl From<ash::InstanceError> for GraphicsError {
+    fn from(error: ash::InstanceError) -> GraphicsError {
+        match error {
+            ash::InstanceError::LoadError(functions) => GraphicsError::InstanceLoading(functions),
+            ash::InstanceError::VkError(result) => GraphicsError::InstanceCreation(result),
+        }
+    }
+}
+
+impl GraphicsError {
+    // Sorts results from acquiring and presenting, which the render loop recovers from differently
+    pub fn from_swapchain(result: vk::Result) -> GraphicsError {
+        match result {
+            vk::Result::TIMEOUT | vk::Result::NOT_READY => GraphicsError::AcquireTimeout,
+            vk::Result::ERROR_OUT_OF_DATE_KHR => GraphicsError::SwapchainOutOfDate,
+            vk::Result::ERROR_SURFACE_LOST_KHR => GraphicsError::SurfaceLost,
+            _ => GraphicsError::Swapchain(result),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use ash::vk;
+
+// Fixed internal resolution drawn centred in the swapchain, with bars filling the rest of the window
+// The viewport and scissor are narrowed to the letterboxed area, so drawing is unchanged however the window is sized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Letterbox {
+    // Internal resolution in pixels, whose aspect ratio is kept
+    pub width: u32,
+    pub height: u32,
+    // Only scales by whole multiples, keeping pixel art crisp - windows too small for 1x scale down to fit instead
+    pub integer_scale: bool,
+    // Color of the bars left around the letterboxed area
+    pub bar_color: [f32; 4],
+}
+
+impl Letterbox {
+    pub fn new(width: u32, height: u32) -> Letterbox {
+        Letterbox {
+            width,
+            height,
+            integer_scale: false,
+            bar_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    // Width / height of the internal resolution, for the projection to use in place of the window's
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    // Largest area with the internal aspect ratio that fits in extent, centred in it
+    pub fn fit(&self, extent: vk::Extent2D) -> vk::Rect2D {
+        assert!(
+            self.width > 0 && self.height > 0,
+            "Letterbox resolution must not be zero!"
+        );
+
+        let scale = (extent.width / self.width).min(extent.height / self.height);
+        let (width, height) = if self.integer_scale && scale >= 1 {
+            (self.width * scale, self.height * scale)
+        } else if extent.width as u64 * self.height as u64
+            > extent.height as u64 * self.width as u64
+        {
+            // The window is wider than the internal resolution, so bars go at the sides
+            (
+                scale_rounded(extent.height, self.width, self.height),
+                extent.height,
+            )
+        } else {
+            (
+                extent.width,
+                scale_rounded(extent.width, self.height, self.width),
+            )
+        };
+
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: ((extent.width - width) / 2) as i32,
+                y: ((extent.height - height) / 2) as i32,
+            },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+}
+
+// value * numerator / denominator, rounded to the nearest whole number
+fn scale_rounded(value: u32, numerator: u32, denominator: u32) -> u32 {
+    let denominator = denominator as u64;
+    ((value as u64 * numerator as u64 + denominator / 2) / denominator) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: extent(width, height),
+        }
+    }
+
+    #[test]
+    fn matching_aspect_ratio_fills_the_window() {
+        assert_eq!(
+            Letterbox::new(320, 180).fit(extent(1920, 1080)),
+            rect(0, 0, 1920, 1080)
+        );
+    }
+
+    #[test]
+    fn wider_windows_are_pillarboxed() {
+        assert_eq!(
+            Letterbox::new(640, 480).fit(extent(1920, 1080)),
+            rect(240, 0, 1440, 1080)
+        );
+    }
+
+    #[test]
+    fn taller_windows_are_letterboxed() {
+        assert_eq!(
+            Letterbox::new(1920, 1080).fit(extent(800, 600)),
+            rect(0, 75, 800, 450)
+        );
+    }
+
+    #[test]
+    fn integer_scale_uses_whole_multiples() {
+        let letterbox = Letterbox {
+            integer_scale: true,
+            ..Letterbox::new(320, 180)
+        };
+
+        assert_eq!(letterbox.fit(extent(1000, 700)), rect(20, 80, 960, 540));
+        assert_eq!(letterbox.fit(extent(320, 180)), rect(0, 0, 320, 180));
+    }
+
+    #[test]
+    fn integer_scale_shrinks_to_fit_small_windows() {
+        let letterbox = Letterbox {
+            integer_scale: true,
+            ..Letterbox::new(320, 180)
+        };
+
+        assert_eq!(letterbox.fit(extent(160, 120)), rect(0, 15, 160, 90));
+    }
+}
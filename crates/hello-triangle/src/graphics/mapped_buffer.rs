@@ -0,0 +1,195 @@
+use crate::graphics::buffers::Buffer;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::Allocator;
+use ash::{vk, Device};
+use std::{mem, ops::Range, ptr, slice};
+
+// Buffer of len values of T that can be read and written through slices, using the mapping the allocator keeps of
+// host visible memory
+// Memory that is not host coherent needs flush() after writing and invalidate() before reading, which do nothing for
+// coherent memory
+pub struct MappedBuffer<T: Copy> {
+    buffer: Buffer,
+    mapped: *mut T,
+    len: usize,
+    coherent: bool,
+    // Flushed and invalidated ranges are widened to multiples of this, from the physical device limits
+    non_coherent_atom_size: vk::DeviceSize,
+}
+
+impl<T: Copy> MappedBuffer<T> {
+    // Creates a buffer for len values of T, in memory with properties that must include HOST_VISIBLE
+    // Leaving out HOST_COHERENT allows host cached memory that is faster to read back, at the cost of flushing and
+    // invalidating by hand
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        len: usize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<MappedBuffer<T>, GraphicsError> {
+        assert!(
+            properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            "Mapped buffers must be host visible!"
+        );
+
+        let size = (mem::size_of::<T>() * len) as vk::DeviceSize;
+        let buffer = Buffer::new(device, allocator, size, usage, properties)?;
+        let allocation = buffer.allocation().unwrap();
+
+        // The memory type chosen may be coherent even when that was not asked for, in which case nothing needs flushing
+        let coherent = allocation
+            .property_flags()
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT);
+        let mapped = allocation.mapped() as *mut T;
+
+        Ok(MappedBuffer {
+            buffer,
+            mapped,
+            len,
+            coherent,
+            non_coherent_atom_size: allocator.non_coherent_atom_size(),
+        })
+    }
+
+    // Contents as last written by the host, or by the device once invalidate() has been called for them
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.mapped, self.len) }
+    }
+
+    // Contents for the host to write, which the device sees once flush() has been called for them
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.mapped, self.len) }
+    }
+
+    // Copies data to the values starting at offset and flushes them
+    pub fn write(
+        &mut self,
+        device: &Device,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), GraphicsError> {
+        let range = offset..offset + data.len();
+        self.as_mut_slice()[range.clone()].copy_from_slice(data);
+        self.flush(device, range)
+    }
+
+    // Makes host writes to the values in range available to the device
+    pub fn flush(&self, device: &Device, range: Range<usize>) -> Result<(), GraphicsError> {
+        if self.coherent || range.start == range.end {
+            return Ok(());
+        }
+
+        let memory_range = self.memory_range(range);
+        unsafe { device.flush_mapped_memory_ranges(slice::from_ref(&memory_range))? };
+        Ok(())
+    }
+
+    // Makes device writes to the values in range visible to the host, once the device work writing them has finished
+    pub fn invalidate(&self, device: &Device, range: Range<usize>) -> Result<(), GraphicsError> {
+        if self.coherent || range.start == range.end {
+            return Ok(());
+        }
+
+        let memory_range = self.memory_range(range);
+        unsafe { device.invalidate_mapped_memory_ranges(slice::from_ref(&memory_range))? };
+        Ok(())
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer.handle()
+    }
+
+    // Number of values of T the buffer holds
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Whether the memory is host coherent, making flush() and invalidate() unnecessary
+    pub fn is_coherent(&self) -> bool {
+        self.coherent
+    }
+
+    // Destroys the buffer - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+        self.mapped = ptr::null_mut();
+        self.len = 0;
+    }
+
+    // Byte range of the memory holding the values in range, widened to whole atoms
+    fn memory_range(&self, range: Range<usize>) -> vk::MappedMemoryRange {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "Range is outside the buffer!"
+        );
+
+        // Ranges are relative to the start of the memory block the buffer shares with others
+        let allocation = self
+            .buffer
+            .allocation()
+            .expect("Buffer has been destroyed!");
+        let value_size = mem::size_of::<T>() as vk::DeviceSize;
+        let (offset, size) = atom_aligned_range(
+            allocation.offset() + range.start as vk::DeviceSize * value_size,
+            (range.end - range.start) as vk::DeviceSize * value_size,
+            self.non_coherent_atom_size,
+            allocation.offset() + allocation.size(),
+        );
+
+        vk::MappedMemoryRange::builder()
+            .memory(allocation.memory())
+            .offset(offset)
+            .size(size)
+            .build()
+    }
+}
+
+// Widens size bytes at offset to whole multiples of atom_size, as flushing and invalidating non-coherent memory
+// requires, without running past allocation_end
+// Allocator keeps non-coherent allocations to whole atoms, so widening never reaches into another resource's memory
+fn atom_aligned_range(
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    atom_size: vk::DeviceSize,
+    allocation_end: vk::DeviceSize,
+) -> (vk::DeviceSize, vk::DeviceSize) {
+    let start = offset / atom_size * atom_size;
+    let end = (offset + size)
+        .next_multiple_of(atom_size)
+        .min(allocation_end);
+    (start, end - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_ranges_are_unchanged() {
+        assert_eq!(atom_aligned_range(0, 64, 64, 1024), (0, 64));
+        assert_eq!(atom_aligned_range(128, 256, 64, 1024), (128, 256));
+    }
+
+    #[test]
+    fn ranges_are_widened_to_whole_atoms() {
+        assert_eq!(atom_aligned_range(4, 8, 64, 1024), (0, 64));
+        assert_eq!(atom_aligned_range(60, 8, 64, 1024), (0, 128));
+        assert_eq!(atom_aligned_range(100, 200, 64, 1024), (64, 256));
+    }
+
+    #[test]
+    fn widened_ranges_stop_at_the_end_of_the_allocation() {
+        assert_eq!(atom_aligned_range(1000, 10, 64, 1010), (960, 50));
+        assert_eq!(atom_aligned_range(0, 1010, 256, 1010), (0, 1010));
+    }
+
+    #[test]
+    fn single_byte_atoms_keep_the_exact_range() {
+        assert_eq!(atom_aligned_range(3, 5, 1, 1024), (3, 5));
+    }
+}
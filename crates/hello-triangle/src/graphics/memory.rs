@@ -0,0 +1,410 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use ash::{vk, Device};
+use std::{cell::RefCell, ptr, rc::Rc};
+
+// Size of the device memory blocks resources are suballocated from, large enough that a scene needs a handful at most
+// rather than one per resource, which maxMemoryAllocationCount (as low as 4096) would run out of
+// Resources larger than this get a block of their own
+const BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+// How a resource lays out its memory, as linear and optimally tiled resources placed next to each other must be
+// bufferImageGranularity apart
+// Keeping them in separate blocks avoids the padding, and most devices report a granularity of 1 anyway
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    // Buffers, and images with LINEAR tiling
+    Linear,
+    // Images with OPTIMAL tiling
+    Optimal,
+}
+
+// Free ranges of a block, kept sorted by offset and merged with their neighbours when freed
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FreeList {
+    ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+}
+
+impl FreeList {
+    // All of size bytes free
+    fn new(size: vk::DeviceSize) -> FreeList {
+        FreeList {
+            ranges: vec![(0, size)],
+        }
+    }
+
+    // Offset of size bytes aligned to alignment, taken from the first free range they fit in
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, offset) =
+            self.ranges
+                .iter()
+                .enumerate()
+                .find_map(|(index, &(start, range_size))| {
+                    let offset = start.next_multiple_of(alignment);
+                    if offset + size <= start + range_size {
+                        Some((index, offset))
+                    } else {
+                        None
+                    }
+                })?;
+
+        // The padding before the allocation and whatever is left after it stay free
+        let (start, range_size) = self.ranges[index];
+        let end = start + range_size;
+        let mut remaining = Vec::with_capacity(2);
+        if offset > start {
+            remaining.push((start, offset - start));
+        }
+        if offset + size < end {
+            remaining.push((offset + size, end - offset - size));
+        }
+        self.ranges.splice(index..=index, remaining);
+
+        Some(offset)
+    }
+
+    // Returns size bytes at offset, which must have come from allocate()
+    fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self.ranges.partition_point(|&(start, _)| start < offset);
+        self.ranges.insert(index, (offset, size));
+
+        // Merges with the following range first, so index still points at the new one
+        if index + 1 < self.ranges.len() && offset + size == self.ranges[index + 1].0 {
+            self.ranges[index].1 += self.ranges[index + 1].1;
+            self.ranges.remove(index + 1);
+        }
+        if index > 0 {
+            let (previous_start, previous_size) = self.ranges[index - 1];
+            if previous_start + previous_size == offset {
+                self.ranges[index - 1].1 += self.ranges[index].1;
+                self.ranges.remove(index);
+            }
+        }
+    }
+
+    fn is_empty(&self, size: vk::DeviceSize) -> bool {
+        self.ranges == [(0, size)]
+    }
+}
+
+// Device memory allocation that resources are carved out of
+struct Block {
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+    kind: ResourceKind,
+    // Host visible blocks stay mapped for their whole lifetime, as memory can only be mapped once at a time and
+    // resources in the same block may all want to be
+    mapped: *mut u8,
+    free_list: FreeList,
+}
+
+// Suballocates buffer and image memory from large blocks of device memory, instead of allocating memory for each
+// resource
+// Empty blocks are kept around for the next resources rather than freed, so recreating resources (e.g. along with the
+// swapchain) reuses the same memory
+pub struct Allocator {
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    non_coherent_atom_size: vk::DeviceSize,
+    // Shared with every Allocation, which hands its range back when dropped
+    blocks: Rc<RefCell<Vec<Block>>>,
+}
+
+impl Allocator {
+    pub fn new(
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        limits: &vk::PhysicalDeviceLimits,
+    ) -> Allocator {
+        Allocator {
+            memory_properties,
+            non_coherent_atom_size: limits.non_coherent_atom_size,
+            blocks: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // Allocates memory meeting requirements from the first memory type with all of properties, for a resource
+    // laid out as kind
+    pub fn allocate(
+        &self,
+        device: &Device,
+        requirements: &vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+        kind: ResourceKind,
+    ) -> Result<Allocation, GraphicsError> {
+        let memory_type_index = find_memory_type(&self.memory_properties, requirements, properties)
+            .ok_or(GraphicsError::NoSuitableMemoryType(properties))?;
+        let property_flags =
+            self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+
+        // Flushes and invalidates of non-coherent memory are widened to whole atoms, so allocations are kept to whole
+        // atoms too, where widening can't reach into their neighbours
+        let (size, alignment) = if property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+            && !property_flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            (
+                requirements
+                    .size
+                    .next_multiple_of(self.non_coherent_atom_size),
+                requirements.alignment.max(self.non_coherent_atom_size),
+            )
+        } else {
+            (requirements.size, requirements.alignment)
+        };
+
+        let mut blocks = self.blocks.borrow_mut();
+
+        let existing = blocks.iter_mut().enumerate().find_map(|(index, block)| {
+            if block.memory_type_index == memory_type_index && block.kind == kind {
+                block
+                    .free_list
+                    .allocate(size, alignment)
+                    .map(|offset| (index, offset))
+            } else {
+                None
+            }
+        });
+
+        let (block_index, offset) = match existing {
+            Some(found) => found,
+            None => {
+                let mut block = Allocator::allocate_block(
+                    device,
+                    memory_type_index,
+                    property_flags,
+                    size.max(BLOCK_SIZE),
+                    kind,
+                )?;
+                // A new block starts at offset 0, which every alignment allows
+                let offset = block.free_list.allocate(size, alignment).unwrap();
+                blocks.push(block);
+                (blocks.len() - 1, offset)
+            }
+        };
+
+        let block = &blocks[block_index];
+        let mapped = if block.mapped.is_null() {
+            ptr::null_mut()
+        } else {
+            unsafe { block.mapped.add(offset as usize) }
+        };
+
+        Ok(Allocation {
+            memory: block.memory,
+            offset,
+            size,
+            mapped,
+            property_flags,
+            block_index,
+            blocks: Rc::clone(&self.blocks),
+        })
+    }
+
+    // Allocates memory with properties for buffer and binds it
+    pub fn allocate_buffer(
+        &self,
+        device: &Device,
+        buffer: vk::Buffer,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, GraphicsError> {
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = self.allocate(device, &requirements, properties, ResourceKind::Linear)?;
+
+        // The allocation is dropped on failure, which frees it
+        unsafe { device.bind_buffer_memory(buffer, allocation.memory, allocation.offset)? };
+        Ok(allocation)
+    }
+
+    // Allocates memory with properties for image, which must have OPTIMAL tiling, and binds it
+    pub fn allocate_image(
+        &self,
+        device: &Device,
+        image: vk::Image,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<Allocation, GraphicsError> {
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocation = self.allocate(device, &requirements, properties, ResourceKind::Optimal)?;
+
+        unsafe { device.bind_image_memory(image, allocation.memory, allocation.offset)? };
+        Ok(allocation)
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    // Granularity that flushed and invalidated ranges of non-coherent memory are widened to
+    pub fn non_coherent_atom_size(&self) -> vk::DeviceSize {
+        self.non_coherent_atom_size
+    }
+
+    // Number of device memory allocations made, which counts towards maxMemoryAllocationCount
+    pub fn block_count(&self) -> usize {
+        self.blocks.borrow().len()
+    }
+
+    // Frees every block - must be called before the device is destroyed, once the resources using them are
+    pub fn destroy(&mut self, device: &Device) {
+        for block in self.blocks.borrow_mut().drain(..) {
+            debug_assert!(
+                block.free_list.is_empty(block.size),
+                "Memory block freed while resources still use it!"
+            );
+            // Freeing mapped memory unmaps it
+            unsafe { device.free_memory(block.memory, None) };
+        }
+    }
+
+    fn allocate_block(
+        device: &Device,
+        memory_type_index: u32,
+        property_flags: vk::MemoryPropertyFlags,
+        size: vk::DeviceSize,
+        kind: ResourceKind,
+    ) -> Result<Block, GraphicsError> {
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index);
+
+        let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+
+        let mapped = if property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            let mapped = unsafe {
+                device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            };
+            match mapped {
+                Ok(mapped) => mapped as *mut u8,
+                Err(result) => {
+                    unsafe { device.free_memory(memory, None) };
+                    return Err(result.into());
+                }
+            }
+        } else {
+            ptr::null_mut()
+        };
+
+        Ok(Block {
+            memory,
+            size,
+            memory_type_index,
+            kind,
+            mapped,
+            free_list: FreeList::new(size),
+        })
+    }
+}
+
+// Range of a memory block bound to a buffer or image, which is handed back to the Allocator when dropped
+// The resource bound to it must be destroyed first, or at least no longer be in use by the device
+pub struct Allocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    mapped: *mut u8,
+    property_flags: vk::MemoryPropertyFlags,
+    block_index: usize,
+    blocks: Rc<RefCell<Vec<Block>>>,
+}
+
+impl Allocation {
+    // Memory block the allocation is part of, shared with other resources
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    // Offset of the allocation within memory()
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    // Bytes allocated, which may be more than the resource asked for
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    // Start of the allocation in host memory, or null if it is not host visible
+    pub fn mapped(&self) -> *mut u8 {
+        self.mapped
+    }
+
+    // Properties of the memory type chosen, which may include more than were asked for
+    pub fn property_flags(&self) -> vk::MemoryPropertyFlags {
+        self.property_flags
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        // Blocks are gone once the allocator is destroyed, leaving nothing to hand back
+        if let Some(block) = self.blocks.borrow_mut().get_mut(self.block_index) {
+            block.free_list.free(self.offset, self.size);
+        }
+    }
+}
+
+// Index of the memory type Allocator::allocate uses for requirements and properties, if there is one
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    requirements: &vk::MemoryRequirements,
+    properties: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .position(|(index, memory_type)| {
+            requirements.memory_type_bits & (1 << index) != 0
+                && memory_type.property_flags.contains(properties)
+        })
+        .map(|index| index as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_aligned_and_packed() {
+        let mut free_list = FreeList::new(1024);
+
+        assert_eq!(free_list.allocate(100, 1), Some(0));
+        assert_eq!(free_list.allocate(100, 256), Some(256));
+        assert_eq!(free_list.allocate(50, 1), Some(100), "Padding stays free");
+        assert_eq!(free_list.ranges, vec![(150, 106), (356, 668)]);
+    }
+
+    #[test]
+    fn full_lists_refuse_allocations() {
+        let mut free_list = FreeList::new(256);
+
+        assert_eq!(free_list.allocate(256, 1), Some(0));
+        assert_eq!(free_list.allocate(1, 1), None);
+        assert_eq!(free_list.allocate(512, 1), None);
+    }
+
+    #[test]
+    fn freed_ranges_merge_with_their_neighbours() {
+        let mut free_list = FreeList::new(300);
+        let offsets: Vec<vk::DeviceSize> = (0..3)
+            .map(|_| free_list.allocate(100, 1).unwrap())
+            .collect();
+
+        free_list.free(offsets[0], 100);
+        free_list.free(offsets[2], 100);
+        assert_eq!(free_list.ranges, vec![(0, 100), (200, 100)]);
+
+        free_list.free(offsets[1], 100);
+        assert!(free_list.is_empty(300));
+    }
+
+    #[test]
+    fn freed_ranges_are_reused() {
+        let mut free_list = FreeList::new(300);
+        let first = free_list.allocate(100, 1).unwrap();
+        free_list.allocate(100, 1).unwrap();
+
+        free_list.free(first, 100);
+        assert_eq!(free_list.allocate(64, 64), Some(0));
+    }
+}
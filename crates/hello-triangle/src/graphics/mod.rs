@@ -1,2 +1,31 @@
+// Types here hold Vulkan handles but no reference to the device that created them, so rather than freeing them on
+// drop, their owner calls destroy() on each before destroying the device
+
+pub mod atlas;
+pub mod buffers;
+pub mod commands;
+pub mod compute;
+pub mod debug;
+pub mod depth;
+pub mod device_selection;
+pub mod frame_sync;
 pub mod graphics_errors;
+pub mod letterbox;
+pub mod mapped_buffer;
+pub mod memory;
+pub mod model;
+pub mod msaa;
+pub mod offscreen;
+pub mod pipeline;
+pub mod pod;
+pub mod push_constants;
+pub mod queue_ownership;
+pub mod readback;
+pub mod render_pass;
+pub mod shaders;
+pub mod swapchain;
+pub mod swapchain_config;
+pub mod texture;
+pub mod uniforms;
+pub mod upload;
 pub mod vulkan_base;
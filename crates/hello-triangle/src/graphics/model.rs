@@ -0,0 +1,350 @@
+use crate::graphics::buffers::{Buffer, Index, IndexBuffer, Vertex};
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::Allocator;
+use crate::graphics::upload::Uploader;
+use ash::{vk, Device};
+use std::{collections::HashMap, fs, path::Path};
+
+// Color given to vertices of models without vertex colors, so the texture shows through untinted
+const DEFAULT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+// Indices of the position, texture coordinate, and normal making up an OBJ face corner, which identify a unique vertex
+type CornerKey = (usize, Option<usize>, Option<usize>);
+
+// Indexed triangle list loaded from a Wavefront OBJ file, with each unique corner stored as one vertex
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Model {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl Model {
+    pub fn from_file(path: &Path) -> Result<Model, GraphicsError> {
+        let source = fs::read_to_string(path).map_err(GraphicsError::ModelReading)?;
+        Model::parse(&source)
+    }
+
+    // Reads positions, texture coordinates, normals, and faces from OBJ source, fanning polygons out into triangles
+    // Vertex colors following positions are kept, and everything else (groups, materials, smoothing, lines) is
+    // ignored
+    pub fn parse(source: &str) -> Result<Model, GraphicsError> {
+        let mut positions: Vec<([f32; 3], [f32; 3])> = Vec::new();
+        let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+
+        let mut model = Model::default();
+        let mut unique_vertices: HashMap<CornerKey, u32> = HashMap::new();
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("v") => {
+                    let values = parse_floats(words, line_number)?;
+                    match values.len() {
+                        3 | 4 => positions.push(([values[0], values[1], values[2]], DEFAULT_COLOR)),
+                        6 => positions.push((
+                            [values[0], values[1], values[2]],
+                            [values[3], values[4], values[5]],
+                        )),
+                        _ => return Err(parse_error(line_number, "expected 3, 4, or 6 values")),
+                    }
+                }
+                Some("vt") => {
+                    let values = parse_floats(words, line_number)?;
+                    match values.len() {
+                        // OBJ puts v = 0 at the bottom of the image, and Vulkan at the top
+                        1..=3 => tex_coords.push([values[0], 1.0 - values.get(1).unwrap_or(&0.0)]),
+                        _ => return Err(parse_error(line_number, "expected 1 to 3 values")),
+                    }
+                }
+                Some("vn") => {
+                    let values = parse_floats(words, line_number)?;
+                    match values.len() {
+                        3 => normals.push([values[0], values[1], values[2]]),
+                        _ => return Err(parse_error(line_number, "expected 3 values")),
+                    }
+                }
+                Some("f") => {
+                    let corners = words
+                        .map(|corner| {
+                            parse_corner(
+                                corner,
+                                (positions.len(), tex_coords.len(), normals.len()),
+                                line_number,
+                            )
+                        })
+                        .collect::<Result<Vec<CornerKey>, GraphicsError>>()?;
+
+                    if corners.len() < 3 {
+                        return Err(parse_error(line_number, "faces need at least 3 corners"));
+                    }
+
+                    let mut corner_indices = Vec::with_capacity(corners.len());
+                    for corner in corners {
+                        let next_index = model.vertices.len() as u32;
+                        let index = *unique_vertices.entry(corner).or_insert(next_index);
+                        if index == next_index {
+                            let (position, color) = positions[corner.0];
+                            model.vertices.push(Vertex {
+                                pos: position,
+                                color,
+                                tex_coord: corner.1.map_or([0.0, 0.0], |index| tex_coords[index]),
+                                normal: corner.2.map_or([0.0, 0.0, 0.0], |index| normals[index]),
+                            });
+                        }
+                        corner_indices.push(index);
+                    }
+
+                    for edge in corner_indices[1..].windows(2) {
+                        model
+                            .indices
+                            .extend_from_slice(&[corner_indices[0], edge[0], edge[1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(model)
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    // Three indices into vertices() per triangle
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+}
+
+// Vertex and index buffers of something to draw, in device local memory
+pub struct Mesh {
+    vertex_buffer: Buffer,
+    index_buffer: IndexBuffer,
+}
+
+impl Mesh {
+    // Uploads vertices and indices through uploader, blocking until the upload has finished
+    pub fn new<I: Index>(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        vertices: &[Vertex],
+        indices: &[I],
+    ) -> Result<Mesh, GraphicsError> {
+        let mut vertex_buffer = Buffer::new_vertex_buffer(device, allocator, uploader, vertices)?;
+
+        match Buffer::new_index_buffer(device, allocator, uploader, indices) {
+            Ok(index_buffer) => Ok(Mesh {
+                vertex_buffer,
+                index_buffer,
+            }),
+            Err(error) => {
+                vertex_buffer.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn from_model(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        model: &Model,
+    ) -> Result<Mesh, GraphicsError> {
+        Mesh::new(
+            device,
+            allocator,
+            uploader,
+            model.vertices(),
+            model.indices(),
+        )
+    }
+
+    // Binds the buffers and draws every triangle, with a pipeline reading Vertex at binding 0 already bound
+    pub fn draw(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        self.index_buffer.bind(device, command_buffer);
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.handle()], &[0]);
+            device.cmd_draw_indexed(command_buffer, self.index_buffer.index_count(), 1, 0, 0, 0);
+        }
+    }
+
+    // Destroys both buffers - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.vertex_buffer.destroy(device);
+        self.index_buffer.destroy(device);
+    }
+}
+
+fn parse_floats<'a>(
+    words: impl Iterator<Item = &'a str>,
+    line_number: usize,
+) -> Result<Vec<f32>, GraphicsError> {
+    words
+        .map(|word| {
+            word.parse()
+                .map_err(|_| parse_error(line_number, "invalid number"))
+        })
+        .collect()
+}
+
+// Parses a face corner written as v, v/vt, v//vn, or v/vt/vn, given the number of (v, vt, vn) entries read so far
+fn parse_corner(
+    corner: &str,
+    counts: (usize, usize, usize),
+    line_number: usize,
+) -> Result<CornerKey, GraphicsError> {
+    let mut parts = corner.split('/');
+
+    let position = match parts.next() {
+        Some(part) if !part.is_empty() => resolve_index(part, counts.0, line_number)?,
+        _ => return Err(parse_error(line_number, "face corner without a position")),
+    };
+
+    let mut optional_index = |count| match parts.next() {
+        Some(part) if !part.is_empty() => resolve_index(part, count, line_number).map(Some),
+        _ => Ok(None),
+    };
+    let tex_coord = optional_index(counts.1)?;
+    let normal = optional_index(counts.2)?;
+
+    Ok((position, tex_coord, normal))
+}
+
+// Turns a 1 based OBJ index, or a negative one counting back from the last entry, into an index into count entries
+fn resolve_index(index: &str, count: usize, line_number: usize) -> Result<usize, GraphicsError> {
+    let index: i64 = index
+        .parse()
+        .map_err(|_| parse_error(line_number, "invalid index"))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(line_number, "index out of range"));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn parse_error(line: usize, message: &'static str) -> GraphicsError {
+    GraphicsError::ModelParsing { line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+# Unit square made of two triangles sharing an edge
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+f 1/1/1 3/3/1 4/4/1
+";
+
+    fn parse_error_line(source: &str) -> Option<usize> {
+        match Model::parse(source) {
+            Err(GraphicsError::ModelParsing { line, .. }) => Some(line),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn shared_corners_become_one_vertex() {
+        let model = Model::parse(QUAD).unwrap();
+
+        assert_eq!(model.vertices().len(), 4);
+        assert_eq!(model.indices(), &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(model.vertices()[2].pos, [1.0, 1.0, 0.0]);
+        assert_eq!(model.vertices()[2].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(model.vertices()[2].color, DEFAULT_COLOR);
+    }
+
+    #[test]
+    fn tex_coords_are_flipped_to_a_top_left_origin() {
+        let model = Model::parse(QUAD).unwrap();
+
+        assert_eq!(model.vertices()[0].tex_coord, [0.0, 1.0]);
+        assert_eq!(model.vertices()[2].tex_coord, [1.0, 0.0]);
+    }
+
+    #[test]
+    fn corners_differing_in_any_attribute_stay_separate() {
+        let model = Model::parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 1\nf 1/1 2/1 3/1\nf 1/2 2/1 3/1\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.vertices().len(), 4);
+        assert_eq!(model.indices(), &[0, 1, 2, 3, 1, 2]);
+    }
+
+    #[test]
+    fn polygons_are_fanned_into_triangles() {
+        let model =
+            Model::parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 1 0\nf 1 2 3 4 5\n").unwrap();
+
+        assert_eq!(model.indices(), &[0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn corner_formats_and_negative_indices() {
+        let model = Model::parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0 1 0 0\nvn 0 0 1\nvt 0.5 0.25\nf -3//1 2//-1 3/1/1\n",
+        )
+        .unwrap();
+
+        let vertices = model.vertices();
+        assert_eq!(vertices[0].pos, [0.0, 0.0, 0.0]);
+        assert_eq!(vertices[0].tex_coord, [0.0, 0.0]);
+        assert_eq!(vertices[1].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(vertices[2].color, [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[2].tex_coord, [0.5, 0.75]);
+    }
+
+    #[test]
+    fn ignores_unsupported_statements() {
+        let model = Model::parse(
+            "mtllib room.mtl\no room\ng walls\nusemtl wood\ns 1\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nl 1 2\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.indices().len(), 3);
+    }
+
+    #[test]
+    fn reports_the_line_of_invalid_data() {
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 0\n"), Some(2));
+        assert_eq!(parse_error_line("v 0 0 zero\n"), Some(1));
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 0 0\nf 1 2\n"), Some(3));
+        assert_eq!(
+            parse_error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"),
+            Some(4)
+        );
+        assert_eq!(
+            parse_error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"),
+            Some(4)
+        );
+        assert_eq!(
+            parse_error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2 3\n"),
+            Some(4)
+        );
+    }
+}
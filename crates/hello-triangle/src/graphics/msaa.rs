@@ -0,0 +1,243 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::{Allocation, Allocator};
+use ash::{vk, Device, Instance};
+
+// Sample counts MSAA can be set to, from most to fewest samples
+const SAMPLE_COUNTS: [(u32, vk::SampleCountFlags); 4] = [
+    (8, vk::SampleCountFlags::TYPE_8),
+    (4, vk::SampleCountFlags::TYPE_4),
+    (2, vk::SampleCountFlags::TYPE_2),
+    (1, vk::SampleCountFlags::TYPE_1),
+];
+
+// Sample counts usable for both the color and depth attachments of a framebuffer
+pub fn supported_sample_counts(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+    limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+}
+
+// Picks the highest supported sample count no greater than requested, falling back to a single sample, which every
+// device supports
+pub fn choose_sample_count(
+    requested: u32,
+    supported: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    SAMPLE_COUNTS
+        .iter()
+        .find(|(count, flag)| *count <= requested && supported.contains(*flag))
+        .map_or(vk::SampleCountFlags::TYPE_1, |(_, flag)| *flag)
+}
+
+// Number of samples a single sample count flag stands for, for reporting
+pub fn sample_count_value(samples: vk::SampleCountFlags) -> u32 {
+    samples.as_raw()
+}
+
+// Multisampled color image the render pass draws into and resolves to the swapchain image at the end
+// Its contents are never needed after the resolve, so it is a transient attachment shared by every frame
+pub struct ColorTarget {
+    image: vk::Image,
+    // Only held until destroy() drops it, which hands the memory back to the allocator
+    _memory: Option<Allocation>,
+    view: vk::ImageView,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    extent: vk::Extent2D,
+}
+
+impl ColorTarget {
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+    ) -> Result<ColorTarget, GraphicsError> {
+        let mut color_target = ColorTarget {
+            image: vk::Image::null(),
+            _memory: None,
+            view: vk::ImageView::null(),
+            format,
+            samples,
+            extent,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        match color_target.create(device, allocator) {
+            Ok(()) => Ok(color_target),
+            Err(error) => {
+                color_target.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    // Replaces the image with one matching a recreated swapchain, keeping the sample count
+    // The device must be idle, and framebuffers using the old view must be rebuilt afterwards
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        allocator: &Allocator,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Result<(), GraphicsError> {
+        self.destroy(device);
+        self.format = format;
+        self.extent = extent;
+
+        self.create(device, allocator)
+            .inspect_err(|_| self.destroy(device))
+    }
+
+    // View to attach to framebuffers
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Destroys the view and image and frees its memory - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self._memory = None;
+    }
+
+    fn create(&mut self, device: &Device, allocator: &Allocator) -> Result<(), GraphicsError> {
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.format)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(self.samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        self._memory = Some(allocator.allocate_image(
+            device,
+            self.image,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(self.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported(counts: &[vk::SampleCountFlags]) -> vk::SampleCountFlags {
+        counts
+            .iter()
+            .fold(vk::SampleCountFlags::empty(), |all, count| all | *count)
+    }
+
+    #[test]
+    fn picks_the_requested_count_when_supported() {
+        let supported = supported(&[
+            vk::SampleCountFlags::TYPE_1,
+            vk::SampleCountFlags::TYPE_2,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_8,
+        ]);
+
+        assert_eq!(
+            choose_sample_count(4, supported),
+            vk::SampleCountFlags::TYPE_4
+        );
+        assert_eq!(
+            choose_sample_count(8, supported),
+            vk::SampleCountFlags::TYPE_8
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_highest_supported_count_below_the_request() {
+        let supported = supported(&[
+            vk::SampleCountFlags::TYPE_1,
+            vk::SampleCountFlags::TYPE_2,
+            vk::SampleCountFlags::TYPE_4,
+        ]);
+
+        assert_eq!(
+            choose_sample_count(8, supported),
+            vk::SampleCountFlags::TYPE_4
+        );
+        // Counts between the supported ones round down
+        assert_eq!(
+            choose_sample_count(3, supported),
+            vk::SampleCountFlags::TYPE_2
+        );
+    }
+
+    #[test]
+    fn never_exceeds_the_request() {
+        let supported = supported(&[vk::SampleCountFlags::TYPE_1, vk::SampleCountFlags::TYPE_8]);
+
+        assert_eq!(
+            choose_sample_count(4, supported),
+            vk::SampleCountFlags::TYPE_1
+        );
+    }
+
+    #[test]
+    fn single_sample_disables_msaa() {
+        assert_eq!(
+            choose_sample_count(1, vk::SampleCountFlags::TYPE_8),
+            vk::SampleCountFlags::TYPE_1
+        );
+        assert_eq!(
+            choose_sample_count(0, vk::SampleCountFlags::empty()),
+            vk::SampleCountFlags::TYPE_1
+        );
+    }
+
+    #[test]
+    fn sample_count_value_matches_the_flag() {
+        assert_eq!(sample_count_value(vk::SampleCountFlags::TYPE_1), 1);
+        assert_eq!(sample_count_value(vk::SampleCountFlags::TYPE_8), 8);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::graphics::buffers::Buffer;
+use crate::graphics::commands::CommandPool;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::{Allocation, Allocator};
+use crate::graphics::readback::ImageRegion;
+use ash::{vk, Device};
+use std::slice;
+
+// Format headless frames are drawn in, read back as tightly packed RGBA8 with the same sRGB encoding a window shows
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+// Bytes per texel of OFFSCREEN_FORMAT
+const TEXEL_SIZE: vk::DeviceSize = 4;
+
+// Color image drawn into in place of a swapchain when rendering without a window
+// Frames leave it in TRANSFER_SRC_OPTIMAL, ready to be read back after each one
+pub struct OffscreenTarget {
+    image: vk::Image,
+    // Only held until destroy() drops it, which hands the memory back to the allocator
+    _memory: Option<Allocation>,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+}
+
+impl OffscreenTarget {
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        extent: vk::Extent2D,
+    ) -> Result<OffscreenTarget, GraphicsError> {
+        let mut target = OffscreenTarget {
+            image: vk::Image::null(),
+            _memory: None,
+            view: vk::ImageView::null(),
+            extent,
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        match target.create(device, allocator) {
+            Ok(()) => Ok(target),
+            Err(error) => {
+                target.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    // Replaces the image with one of a new size
+    // The device must be idle, and framebuffers using the old view must be rebuilt afterwards
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        allocator: &Allocator,
+        extent: vk::Extent2D,
+    ) -> Result<(), GraphicsError> {
+        self.destroy(device);
+        self.extent = extent;
+
+        self.create(device, allocator)
+            .inspect_err(|_| self.destroy(device))
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    // The view as the single image framebuffers are created for, standing in for the swapchain's image views
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        slice::from_ref(&self.view)
+    }
+
+    pub fn format(&self) -> vk::Format {
+        OFFSCREEN_FORMAT
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Copies the image back to the CPU as tightly packed RGBA8 rows from top to bottom, submitting the copy to queue
+    // and waiting for it to finish
+    // The image must have been drawn to, and the submission drawing it must have been made to queue beforehand
+    pub fn read_pixels(
+        &self,
+        device: &Device,
+        allocator: &Allocator,
+        command_pool: &CommandPool,
+        queue: vk::Queue,
+    ) -> Result<Vec<u8>, GraphicsError> {
+        let region = ImageRegion {
+            image: self.image,
+            subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            extent: vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+            texel_size: TEXEL_SIZE,
+        };
+
+        let mut staging_buffer = Buffer::new(
+            device,
+            allocator,
+            region.size(),
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let result = command_pool.submit_once(device, queue, |command_buffer| {
+            OffscreenTarget::record_copy(device, command_buffer, &region, staging_buffer.handle())
+        });
+
+        let pixels = result.map(|()| staging_buffer.map_read(|contents| contents.to_vec()));
+        staging_buffer.destroy(device);
+        pixels
+    }
+
+    // Destroys the view and image and frees its memory - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self._memory = None;
+    }
+
+    fn create(&mut self, device: &Device, allocator: &Allocator) -> Result<(), GraphicsError> {
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(OFFSCREEN_FORMAT)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        self._memory = Some(allocator.allocate_image(
+            device,
+            self.image,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(OFFSCREEN_FORMAT)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        Ok(())
+    }
+
+    // Records copying region to the start of buffer, between barriers waiting for the frame's writes and making the
+    // copy visible to the host
+    fn record_copy(
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        region: &ImageRegion,
+        buffer: vk::Buffer,
+    ) {
+        // The render pass already moved the image to TRANSFER_SRC_OPTIMAL, so this only orders the copy after the
+        // frame's writes, which happened in an earlier submission
+        let image_barrier = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(region.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: region.subresource.aspect_mask,
+                base_mip_level: region.subresource.mip_level,
+                level_count: 1,
+                base_array_layer: region.subresource.base_array_layer,
+                layer_count: region.subresource.layer_count,
+            });
+
+        // Zero row length and image height mean rows follow each other without padding
+        let copy = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: region.subresource,
+            image_offset: region.offset,
+            image_extent: region.extent,
+        };
+
+        let buffer_barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(region.size());
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                slice::from_ref(&image_barrier),
+            );
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                region.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                slice::from_ref(&copy),
+            );
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                slice::from_ref(&buffer_barrier),
+                &[],
+            );
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::graphics::buffers::Vertex;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::push_constants;
+use crate::graphics::shaders::ShaderCode;
+use crate::graphics::swapchain_config::OutputTransform;
+use ash::{vk, Device};
+use std::{ffi::CString, slice};
+
+// Fixed function state and shader inputs a GraphicsPipeline is built with, besides the render pass and layout
+pub struct PipelineSettings<'a> {
+    // Must match the render pass attachments
+    pub samples: vk::SampleCountFlags,
+    // Declared in the layout, e.g. from PushConstantRange::range(), and may not share stages
+    pub push_constant_ranges: &'a [vk::PushConstantRange],
+    // Encoding for the render target's surface format, given to the fragment shader as specialization constants
+    // 0 and 1 - shaders that don't declare them are unaffected
+    pub output_transform: OutputTransform,
+}
+
+// Graphics pipeline for the triangle, along with the shader modules and layout it was built from
+pub struct GraphicsPipeline {
+    shader_modules: Vec<vk::ShaderModule>,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl GraphicsPipeline {
+    // Creates shader modules, graphics pipeline layout, and graphics pipeline
+    // descriptor_set_layout describes set 0, which holds the uniform buffer and texture
+    // shaders can be the precompiled ones or freshly compiled sources, as the modules are created from them here
+    pub fn new(
+        device: &Device,
+        render_pass: &vk::RenderPass,
+        extent: &vk::Extent2D,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        shaders: &ShaderCode,
+        settings: &PipelineSettings,
+    ) -> Result<GraphicsPipeline, GraphicsError> {
+        if push_constants::stages_overlap(settings.push_constant_ranges) {
+            return Err(GraphicsError::OverlappingPushConstantRanges);
+        }
+
+        GraphicsPipeline::check_push_constants(
+            &shaders.vertex,
+            vk::ShaderStageFlags::VERTEX,
+            "vertex",
+            settings.push_constant_ranges,
+        )?;
+        GraphicsPipeline::check_push_constants(
+            &shaders.fragment,
+            vk::ShaderStageFlags::FRAGMENT,
+            "fragment",
+            settings.push_constant_ranges,
+        )?;
+
+        // Shader modules
+        let vertex_shader_module =
+            GraphicsPipeline::create_shader_module(device, &shaders.vertex, "vertex")?;
+        let fragment_shader_module =
+            GraphicsPipeline::create_shader_module(device, &shaders.fragment, "fragment")
+                .inspect_err(|_| unsafe {
+                    device.destroy_shader_module(vertex_shader_module, None)
+                })?;
+        let destroy_shader_modules = || unsafe {
+            device.destroy_shader_module(vertex_shader_module, None);
+            device.destroy_shader_module(fragment_shader_module, None);
+        };
+
+        let shader_entry_name = CString::new("main").unwrap();
+
+        let specialization_data = settings.output_transform.specialization_data();
+        let specialization_entries = [
+            vk::SpecializationMapEntry {
+                constant_id: 0,
+                offset: 0,
+                size: 4,
+            },
+            vk::SpecializationMapEntry {
+                constant_id: 1,
+                offset: 4,
+                size: 4,
+            },
+        ];
+        let specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&specialization_entries)
+            .data(&specialization_data);
+
+        let shader_stage_infos = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .module(vertex_shader_module)
+                .name(shader_entry_name.as_c_str())
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .module(fragment_shader_module)
+                .name(shader_entry_name.as_c_str())
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .specialization_info(&specialization_info)
+                .build(),
+        ];
+
+        // Vertices are read from a single vertex buffer bound at binding 0
+        let vertex_binding_description = Vertex::binding_description();
+        let vertex_attribute_descriptions = Vertex::attribute_descriptions();
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(slice::from_ref(&vertex_binding_description))
+            .vertex_attribute_descriptions(&vertex_attribute_descriptions);
+
+        let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        // Viewport and scissor are dynamic, so these only fill in the counts and the renderer must set both
+        // with cmd_set_viewport and cmd_set_scissor before drawing
+        let viewports = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        let scissors = vk::Rect2D::builder()
+            .offset(*vk::Offset2D::builder().x(0).y(0))
+            .extent(*extent);
+
+        let viewport_info = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice::from_ref(&viewports))
+            .scissors(slice::from_ref(&scissors));
+
+        let rasterization_info = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+
+        let multisample_info = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(settings.samples);
+
+        // Nearer fragments win, as the depth buffer is cleared to the far plane
+        let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false);
+
+        let alpha_blending_attachments = vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(false)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::all());
+
+        let color_blend_info = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op(vk::LogicOp::CLEAR)
+            .attachments(slice::from_ref(&alpha_blending_attachments));
+
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_info =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(slice::from_ref(&descriptor_set_layout))
+            .push_constant_ranges(settings.push_constant_ranges);
+        let layout = unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .map_err(GraphicsError::PipelineCreation)
+                .inspect_err(|_| destroy_shader_modules())?
+        };
+
+        let graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_infos)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly_info)
+            .viewport_state(&viewport_info)
+            .rasterization_state(&rasterization_info)
+            .multisample_state(&multisample_info)
+            .depth_stencil_state(&depth_stencil_info)
+            .color_blend_state(&color_blend_info)
+            .dynamic_state(&dynamic_info)
+            .layout(layout)
+            .render_pass(*render_pass);
+
+        let graphics_pipelines = unsafe {
+            device
+                .create_graphics_pipelines(
+                    vk::PipelineCache::null(),
+                    slice::from_ref(&graphics_pipeline_info),
+                    None,
+                )
+                .map_err(|(_, result)| GraphicsError::PipelineCreation(result))
+                .inspect_err(|_| {
+                    device.destroy_pipeline_layout(layout, None);
+                    destroy_shader_modules();
+                })?
+        };
+
+        Ok(GraphicsPipeline {
+            shader_modules: vec![vertex_shader_module, fragment_shader_module],
+            layout,
+            pipeline: graphics_pipelines[0],
+        })
+    }
+
+    // Pipeline to bind with cmd_bind_pipeline at vk::PipelineBindPoint::GRAPHICS
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    // Destroys the pipeline, its layout, and its shader modules - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            for shader_module in self.shader_modules.drain(..) {
+                device.destroy_shader_module(shader_module, None);
+            }
+        }
+        self.pipeline = vk::Pipeline::null();
+        self.layout = vk::PipelineLayout::null();
+    }
+
+    // Fails if code reads push constants outside the ranges declared for stage, which Vulkan leaves undefined
+    // Hot reloaded shaders can add push constants at any time, so this catches them before the pipeline is built
+    fn check_push_constants(
+        code: &[u32],
+        stage: vk::ShaderStageFlags,
+        name: &'static str,
+        ranges: &[vk::PushConstantRange],
+    ) -> Result<(), GraphicsError> {
+        let module = shader_reflect::reflect_words(code.to_vec())
+            .map_err(|source| GraphicsError::ShaderReflection { name, source })?;
+
+        match module
+            .push_constants
+            .iter()
+            .find(|block| !push_constants::is_declared(stage, block.offset, block.size, ranges))
+        {
+            Some(block) => Err(GraphicsError::UndeclaredPushConstants {
+                name,
+                block: block.name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Creates a shader module from shader code stored in a u32 vector
+    fn create_shader_module(
+        device: &Device,
+        code: &[u32],
+        name: &'static str,
+    ) -> Result<vk::ShaderModule, GraphicsError> {
+        let shader_module_create_info = vk::ShaderModuleCreateInfo::builder().code(code);
+
+        unsafe {
+            device
+                .create_shader_module(&shader_module_create_info, None)
+                .map_err(|result| GraphicsError::ShaderModuleCreation { name, result })
+        }
+    }
+}
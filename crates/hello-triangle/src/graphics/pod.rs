@@ -0,0 +1,50 @@
+use std::{mem, slice};
+
+/// Plain data that can be handed to the GPU as bytes, such as push constants
+///
+/// # Safety
+///
+/// Implementors must have no padding bytes, as reading padding is undefined behaviour. In practice that means
+/// #[repr(C)] structs of integers, floats, and arrays of them, laid out so no field needs padding before it.
+pub unsafe trait Pod: Copy + 'static {}
+
+// Zero sized, for pipelines without push constants
+unsafe impl Pod for () {}
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+// Bytes of value, e.g. for vkCmdPushConstants
+pub fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    // Pod rules out padding, so every byte is initialised
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Pair {
+        first: u32,
+        second: f32,
+    }
+
+    unsafe impl Pod for Pair {}
+
+    #[test]
+    fn structs_are_laid_out_field_by_field() {
+        let pair = Pair {
+            first: 1,
+            second: 2.0,
+        };
+
+        let mut expected = 1u32.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&2.0f32.to_ne_bytes());
+        assert_eq!(bytes_of(&pair), &expected[..]);
+        assert!(bytes_of(&()).is_empty());
+    }
+}
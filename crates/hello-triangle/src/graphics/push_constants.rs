@@ -0,0 +1,227 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::pod::{self, Pod};
+use ash::{vk, Device};
+use std::{marker::PhantomData, mem};
+
+// Push constant space every implementation provides (the guaranteed minimum of maxPushConstantsSize)
+pub const MAX_PUSH_CONSTANTS_SIZE: u32 = 128;
+
+// Size of T as push constants, which fails to compile for types that are not a whole number of 4 byte words or do
+// not fit in the guaranteed push constant space
+struct PushConstantSize<T>(PhantomData<T>);
+
+impl<T> PushConstantSize<T> {
+    const SIZE: u32 = {
+        let size = mem::size_of::<T>();
+        assert!(
+            size % 4 == 0,
+            "Push constants must be a multiple of 4 bytes!"
+        );
+        assert!(
+            size <= MAX_PUSH_CONSTANTS_SIZE as usize,
+            "Push constants do not fit in 128 bytes!"
+        );
+        size as u32
+    };
+}
+
+// Push constant range declared in a pipeline layout, holding values of T
+// Pushing through the range rather than by hand means a value can never be larger than what the layout declared
+#[derive(Debug)]
+pub struct PushConstantRange<T: Pod> {
+    stages: vk::ShaderStageFlags,
+    offset: u32,
+    _value: PhantomData<T>,
+}
+
+// Derived impls would require T: Clone, which the range does not hold a value of
+impl<T: Pod> Clone for PushConstantRange<T> {
+    fn clone(&self) -> PushConstantRange<T> {
+        *self
+    }
+}
+
+impl<T: Pod> Copy for PushConstantRange<T> {}
+
+impl<T: Pod> PushConstantRange<T> {
+    // Range for a T at offset bytes, visible to stages
+    // Fails if offset is not a multiple of 4 or the range runs past the guaranteed push constant space
+    pub fn new(
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+    ) -> Result<PushConstantRange<T>, GraphicsError> {
+        let size = PushConstantSize::<T>::SIZE;
+        if offset % 4 != 0 {
+            return Err(GraphicsError::UnalignedPushConstantOffset(offset));
+        }
+        if u64::from(offset) + u64::from(size) > u64::from(MAX_PUSH_CONSTANTS_SIZE) {
+            return Err(GraphicsError::PushConstantRangeTooLarge { offset, size });
+        }
+
+        Ok(PushConstantRange {
+            stages,
+            offset,
+            _value: PhantomData,
+        })
+    }
+
+    // Declaration to pass to the pipeline layout
+    pub fn range(&self) -> vk::PushConstantRange {
+        vk::PushConstantRange {
+            stage_flags: self.stages,
+            offset: self.offset,
+            size: PushConstantSize::<T>::SIZE,
+        }
+    }
+
+    // Records pushing value into the range, for draws and dispatches recorded after it with a layout declaring it
+    pub fn push(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        value: &T,
+    ) {
+        cmd_push_constants(
+            device,
+            command_buffer,
+            layout,
+            self.stages,
+            self.offset,
+            value,
+        );
+    }
+}
+
+// Records pushing value at offset bytes for stages
+// The size of T is checked at compile time, but not against the layout - PushConstantRange::push() checks both
+pub fn cmd_push_constants<T: Pod>(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    layout: vk::PipelineLayout,
+    stages: vk::ShaderStageFlags,
+    offset: u32,
+    value: &T,
+) {
+    let size = PushConstantSize::<T>::SIZE;
+    if size == 0 {
+        return;
+    }
+
+    unsafe {
+        device.cmd_push_constants(command_buffer, layout, stages, offset, pod::bytes_of(value))
+    };
+}
+
+// Whether a block of size bytes at offset, which a shader for stage reads, lies inside one of ranges declared for it
+pub fn is_declared(
+    stage: vk::ShaderStageFlags,
+    offset: u32,
+    size: u32,
+    ranges: &[vk::PushConstantRange],
+) -> bool {
+    ranges.iter().any(|range| {
+        range.stage_flags.contains(stage)
+            && range.offset <= offset
+            && u64::from(offset) + u64::from(size)
+                <= u64::from(range.offset) + u64::from(range.size)
+    })
+}
+
+// Whether any stage is in more than one of ranges, which pipeline layouts do not allow
+pub fn stages_overlap(ranges: &[vk::PushConstantRange]) -> bool {
+    let mut seen = vk::ShaderStageFlags::empty();
+    for range in ranges {
+        if seen.intersects(range.stage_flags) {
+            return true;
+        }
+        seen |= range.stage_flags;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_is_sized_for_the_value_type() {
+        let range = PushConstantRange::<[[f32; 4]; 4]>::new(vk::ShaderStageFlags::VERTEX, 0)
+            .unwrap()
+            .range();
+        assert_eq!(range.stage_flags, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(range.offset, 0);
+        assert_eq!(range.size, 64);
+
+        let range = PushConstantRange::<[u32; 2]>::new(vk::ShaderStageFlags::FRAGMENT, 64)
+            .unwrap()
+            .range();
+        assert_eq!(range.offset, 64);
+        assert_eq!(range.size, 8);
+    }
+
+    #[test]
+    fn unaligned_offsets_are_rejected() {
+        assert!(matches!(
+            PushConstantRange::<u32>::new(vk::ShaderStageFlags::VERTEX, 2),
+            Err(GraphicsError::UnalignedPushConstantOffset(2))
+        ));
+    }
+
+    #[test]
+    fn ranges_past_the_guaranteed_space_are_rejected() {
+        assert!(matches!(
+            PushConstantRange::<[f32; 16]>::new(vk::ShaderStageFlags::VERTEX, 96),
+            Err(GraphicsError::PushConstantRangeTooLarge {
+                offset: 96,
+                size: 64
+            })
+        ));
+        assert!(matches!(
+            PushConstantRange::<u32>::new(vk::ShaderStageFlags::VERTEX, u32::MAX - 3),
+            Err(GraphicsError::PushConstantRangeTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn overlapping_stages_are_detected() {
+        let vertex = PushConstantRange::<[f32; 16]>::new(vk::ShaderStageFlags::VERTEX, 0)
+            .unwrap()
+            .range();
+        let fragment = PushConstantRange::<[f32; 4]>::new(vk::ShaderStageFlags::FRAGMENT, 64)
+            .unwrap()
+            .range();
+        let both = PushConstantRange::<[f32; 4]>::new(
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            64,
+        )
+        .unwrap()
+        .range();
+
+        assert!(!stages_overlap(&[]));
+        assert!(!stages_overlap(&[vertex, fragment]));
+        assert!(stages_overlap(&[vertex, both]));
+    }
+
+    #[test]
+    fn blocks_must_lie_inside_a_range_for_their_stage() {
+        let vertex = PushConstantRange::<[f32; 16]>::new(vk::ShaderStageFlags::VERTEX, 0)
+            .unwrap()
+            .range();
+        let fragment = PushConstantRange::<[f32; 4]>::new(vk::ShaderStageFlags::FRAGMENT, 64)
+            .unwrap()
+            .range();
+        let ranges = [vertex, fragment];
+
+        assert!(is_declared(vk::ShaderStageFlags::VERTEX, 0, 64, &ranges));
+        assert!(is_declared(vk::ShaderStageFlags::FRAGMENT, 68, 8, &ranges));
+        assert!(!is_declared(vk::ShaderStageFlags::FRAGMENT, 0, 16, &ranges));
+        assert!(!is_declared(
+            vk::ShaderStageFlags::FRAGMENT,
+            72,
+            16,
+            &ranges
+        ));
+        assert!(!is_declared(vk::ShaderStageFlags::VERTEX, 0, 64, &[]));
+    }
+}
@@ -0,0 +1,401 @@
+use ash::{vk, Device};
+use std::slice;
+
+// How resources used from more than one queue family are shared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SharingPolicy {
+    // Resources belong to one family at a time and are moved with release and acquire barriers, which is fastest on
+    // most hardware
+    Exclusive,
+    // Resources can be used from every family at once with no transfers, which may disable compression on some GPUs
+    Concurrent,
+}
+
+// Sharing mode and queue families to create buffers and images with, so they match the barriers from
+// OwnershipTransfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueSharing {
+    // Distinct families, in the order first given
+    families: Vec<u32>,
+    concurrent: bool,
+}
+
+impl QueueSharing {
+    // Sharing between families, where repeats are ignored
+    // Vulkan only allows concurrent sharing between two or more distinct families, so a single family is always
+    // exclusive, which then needs no transfers anyway
+    pub fn new(policy: SharingPolicy, families: &[u32]) -> QueueSharing {
+        let mut distinct = Vec::with_capacity(families.len());
+        for &family in families {
+            if !distinct.contains(&family) {
+                distinct.push(family);
+            }
+        }
+
+        QueueSharing {
+            concurrent: policy == SharingPolicy::Concurrent && distinct.len() > 1,
+            families: distinct,
+        }
+    }
+
+    // Sharing for resources only ever used from one family
+    pub fn exclusive() -> QueueSharing {
+        QueueSharing {
+            families: Vec::new(),
+            concurrent: false,
+        }
+    }
+
+    pub fn sharing_mode(&self) -> vk::SharingMode {
+        if self.concurrent {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        }
+    }
+
+    // Families to pass along with sharing_mode(), which are only used for concurrent sharing
+    pub fn queue_family_indices(&self) -> &[u32] {
+        if self.concurrent {
+            &self.families
+        } else {
+            &[]
+        }
+    }
+
+    pub fn is_concurrent(&self) -> bool {
+        self.concurrent
+    }
+
+    // Whether moving a resource from src_family to dst_family takes a release and acquire
+    pub fn needs_transfer(&self, src_family: u32, dst_family: u32) -> bool {
+        !self.concurrent && src_family != dst_family
+    }
+}
+
+// A queue's use of a resource on one side of a transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueUse {
+    pub family: u32,
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+}
+
+// Barriers that can be recorded as part of an ownership transfer
+pub trait MemoryBarrier: Copy {
+    fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+    );
+}
+
+impl MemoryBarrier for vk::BufferMemoryBarrier {
+    fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+    ) {
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                slice::from_ref(self),
+                &[],
+            )
+        };
+    }
+}
+
+impl MemoryBarrier for vk::ImageMemoryBarrier {
+    fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+    ) {
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                slice::from_ref(self),
+            )
+        };
+    }
+}
+
+// Matching release and acquire barriers that hand a resource from one queue to another
+// record_release() goes at the end of the source queue's work and record_acquire() at the start of the destination
+// queue's, which must wait on a semaphore the source submission signals
+// When no ownership transfer is needed, because the families match or sharing is concurrent, the release does nothing
+// and the acquire is an ordinary barrier, so callers can record both either way
+#[derive(Clone, Copy, Debug)]
+pub struct OwnershipTransfer<B: MemoryBarrier> {
+    release: Option<B>,
+    acquire: B,
+    src_stage: vk::PipelineStageFlags,
+    dst_stage: vk::PipelineStageFlags,
+}
+
+impl OwnershipTransfer<vk::BufferMemoryBarrier> {
+    // Transfer of size bytes of buffer at offset
+    pub fn buffer(
+        sharing: &QueueSharing,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        from: QueueUse,
+        to: QueueUse,
+    ) -> OwnershipTransfer<vk::BufferMemoryBarrier> {
+        OwnershipTransfer::new(sharing, from, to, |families, src_access, dst_access| {
+            vk::BufferMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .src_queue_family_index(families.0)
+                .dst_queue_family_index(families.1)
+                .buffer(buffer)
+                .offset(offset)
+                .size(size)
+                .build()
+        })
+    }
+}
+
+impl OwnershipTransfer<vk::ImageMemoryBarrier> {
+    // Transfer of subresource_range of image, changing its layout from layouts[0] to layouts[1]
+    // Both halves must carry the same layout change, which happens once between them
+    pub fn image(
+        sharing: &QueueSharing,
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+        layouts: [vk::ImageLayout; 2],
+        from: QueueUse,
+        to: QueueUse,
+    ) -> OwnershipTransfer<vk::ImageMemoryBarrier> {
+        OwnershipTransfer::new(sharing, from, to, |families, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(layouts[0])
+                .new_layout(layouts[1])
+                .src_queue_family_index(families.0)
+                .dst_queue_family_index(families.1)
+                .image(image)
+                .subresource_range(subresource_range)
+                .build()
+        })
+    }
+}
+
+impl<B: MemoryBarrier> OwnershipTransfer<B> {
+    // Builds both halves with barrier, which takes the source and destination families and access masks
+    fn new<F: Fn((u32, u32), vk::AccessFlags, vk::AccessFlags) -> B>(
+        sharing: &QueueSharing,
+        from: QueueUse,
+        to: QueueUse,
+        barrier: F,
+    ) -> OwnershipTransfer<B> {
+        if !sharing.needs_transfer(from.family, to.family) {
+            return OwnershipTransfer {
+                release: None,
+                acquire: barrier(
+                    (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED),
+                    from.access,
+                    to.access,
+                ),
+                src_stage: from.stage,
+                dst_stage: to.stage,
+            };
+        }
+
+        // The release's destination access and the acquire's source access are ignored, as the semaphore between the
+        // queues already orders them
+        let families = (from.family, to.family);
+        OwnershipTransfer {
+            release: Some(barrier(families, from.access, vk::AccessFlags::empty())),
+            acquire: barrier(families, vk::AccessFlags::empty(), to.access),
+            src_stage: from.stage,
+            dst_stage: to.stage,
+        }
+    }
+
+    // Whether the resource actually changes owner, needing the release recorded on the source queue
+    pub fn is_transfer(&self) -> bool {
+        self.release.is_some()
+    }
+
+    // Records the release into a command buffer for the source queue, after its last use of the resource
+    pub fn record_release(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if let Some(release) = &self.release {
+            release.record(
+                device,
+                command_buffer,
+                self.src_stage,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            );
+        }
+    }
+
+    // Records the acquire into a command buffer for the destination queue, before its first use of the resource
+    pub fn record_acquire(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        let src_stage = if self.is_transfer() {
+            vk::PipelineStageFlags::TOP_OF_PIPE
+        } else {
+            self.src_stage
+        };
+
+        self.acquire
+            .record(device, command_buffer, src_stage, self.dst_stage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPHICS: u32 = 0;
+    const TRANSFER: u32 = 2;
+
+    fn copy_write(family: u32) -> QueueUse {
+        QueueUse {
+            family,
+            stage: vk::PipelineStageFlags::TRANSFER,
+            access: vk::AccessFlags::TRANSFER_WRITE,
+        }
+    }
+
+    fn vertex_read(family: u32) -> QueueUse {
+        QueueUse {
+            family,
+            stage: vk::PipelineStageFlags::VERTEX_INPUT,
+            access: vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+        }
+    }
+
+    fn buffer_transfer(
+        sharing: &QueueSharing,
+        from: QueueUse,
+        to: QueueUse,
+    ) -> OwnershipTransfer<vk::BufferMemoryBarrier> {
+        OwnershipTransfer::buffer(sharing, vk::Buffer::null(), 0, vk::WHOLE_SIZE, from, to)
+    }
+
+    #[test]
+    fn concurrent_sharing_needs_two_distinct_families() {
+        let single = QueueSharing::new(SharingPolicy::Concurrent, &[GRAPHICS, GRAPHICS]);
+        assert_eq!(single.sharing_mode(), vk::SharingMode::EXCLUSIVE);
+        assert!(single.queue_family_indices().is_empty());
+
+        let shared = QueueSharing::new(SharingPolicy::Concurrent, &[GRAPHICS, TRANSFER, GRAPHICS]);
+        assert_eq!(shared.sharing_mode(), vk::SharingMode::CONCURRENT);
+        assert_eq!(shared.queue_family_indices(), &[GRAPHICS, TRANSFER]);
+    }
+
+    #[test]
+    fn exclusive_sharing_lists_no_families() {
+        let sharing = QueueSharing::new(SharingPolicy::Exclusive, &[GRAPHICS, TRANSFER]);
+
+        assert_eq!(sharing.sharing_mode(), vk::SharingMode::EXCLUSIVE);
+        assert!(sharing.queue_family_indices().is_empty());
+        assert!(sharing.needs_transfer(TRANSFER, GRAPHICS));
+        assert!(!sharing.needs_transfer(GRAPHICS, GRAPHICS));
+    }
+
+    #[test]
+    fn exclusive_transfers_pair_release_and_acquire() {
+        let sharing = QueueSharing::new(SharingPolicy::Exclusive, &[GRAPHICS, TRANSFER]);
+        let transfer = buffer_transfer(&sharing, copy_write(TRANSFER), vertex_read(GRAPHICS));
+
+        let release = transfer.release.expect("Release should be recorded!");
+        assert_eq!(release.src_queue_family_index, TRANSFER);
+        assert_eq!(release.dst_queue_family_index, GRAPHICS);
+        assert_eq!(release.src_access_mask, vk::AccessFlags::TRANSFER_WRITE);
+        assert_eq!(release.dst_access_mask, vk::AccessFlags::empty());
+
+        let acquire = transfer.acquire;
+        assert_eq!(acquire.src_queue_family_index, TRANSFER);
+        assert_eq!(acquire.dst_queue_family_index, GRAPHICS);
+        assert_eq!(acquire.src_access_mask, vk::AccessFlags::empty());
+        assert_eq!(
+            acquire.dst_access_mask,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+        );
+    }
+
+    #[test]
+    fn concurrent_and_same_family_uses_need_only_a_barrier() {
+        let concurrent = QueueSharing::new(SharingPolicy::Concurrent, &[GRAPHICS, TRANSFER]);
+        let exclusive = QueueSharing::new(SharingPolicy::Exclusive, &[GRAPHICS, TRANSFER]);
+
+        for transfer in [
+            buffer_transfer(&concurrent, copy_write(TRANSFER), vertex_read(GRAPHICS)),
+            buffer_transfer(&exclusive, copy_write(GRAPHICS), vertex_read(GRAPHICS)),
+        ]
+        .iter()
+        {
+            assert!(!transfer.is_transfer());
+            assert_eq!(
+                transfer.acquire.src_queue_family_index,
+                vk::QUEUE_FAMILY_IGNORED
+            );
+            assert_eq!(
+                transfer.acquire.dst_queue_family_index,
+                vk::QUEUE_FAMILY_IGNORED
+            );
+            assert_eq!(
+                transfer.acquire.src_access_mask,
+                vk::AccessFlags::TRANSFER_WRITE
+            );
+            assert_eq!(
+                transfer.acquire.dst_access_mask,
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+            );
+        }
+    }
+
+    #[test]
+    fn image_transfers_repeat_the_layout_change() {
+        let sharing = QueueSharing::new(SharingPolicy::Exclusive, &[GRAPHICS, TRANSFER]);
+        let transfer = OwnershipTransfer::image(
+            &sharing,
+            vk::Image::null(),
+            vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            [
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ],
+            copy_write(TRANSFER),
+            QueueUse {
+                family: GRAPHICS,
+                stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                access: vk::AccessFlags::SHADER_READ,
+            },
+        );
+
+        for barrier in [transfer.release.unwrap(), transfer.acquire].iter() {
+            assert_eq!(barrier.old_layout, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            assert_eq!(
+                barrier.new_layout,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            );
+        }
+    }
+}
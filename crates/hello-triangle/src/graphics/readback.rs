@@ -0,0 +1,312 @@
+use crate::graphics::buffers::Buffer;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::Allocator;
+use ash::{vk, Device};
+use std::{mem, ops::Range, slice};
+
+// Offsets into a frame's staging buffer are kept to this alignment, which covers the 4 byte and texel size alignment
+// image copies need for all common color formats
+const READBACK_ALIGNMENT: vk::DeviceSize = 16;
+
+// Image region to copy back, in TRANSFER_SRC_OPTIMAL layout by the time the copy runs
+#[derive(Clone, Copy, Debug)]
+pub struct ImageRegion {
+    pub image: vk::Image,
+    pub subresource: vk::ImageSubresourceLayers,
+    pub offset: vk::Offset3D,
+    pub extent: vk::Extent3D,
+    // Bytes per texel of the image's format
+    pub texel_size: vk::DeviceSize,
+}
+
+impl ImageRegion {
+    // Bytes the region takes up when tightly packed
+    pub fn size(&self) -> vk::DeviceSize {
+        self.extent.width as vk::DeviceSize
+            * self.extent.height as vk::DeviceSize
+            * self.extent.depth as vk::DeviceSize
+            * self.subresource.layer_count as vk::DeviceSize
+            * self.texel_size
+    }
+}
+
+// Data copied back from the GPU, along with the tag it was scheduled with
+#[derive(Clone, Debug)]
+pub struct Readback<T> {
+    pub tag: T,
+    pub data: Vec<u8>,
+}
+
+// Copy recorded into a frame that has not been collected yet
+struct PendingReadback<T> {
+    tag: T,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+}
+
+// Staging buffer for one frame in flight, filled front to back as copies are scheduled
+struct ReadbackSlot<T> {
+    buffer: Buffer,
+    pending: Vec<PendingReadback<T>>,
+    used: vk::DeviceSize,
+}
+
+// Copies buffer and image data back to the CPU without stalling, using a host visible staging buffer for each frame in
+// flight
+// Copies are recorded into a frame's command buffer, and their results are collected once that frame's fence has been
+// waited on, which happens anyway before the frame is recorded again - results arrive frames_in_flight frames after
+// being scheduled, and nothing has to wait for the device to go idle
+pub struct ReadbackRing<T> {
+    slots: Vec<ReadbackSlot<T>>,
+}
+
+impl<T> ReadbackRing<T> {
+    // Creates a staging buffer of capacity bytes for each of frame_count frames in flight
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        frame_count: usize,
+        capacity: vk::DeviceSize,
+    ) -> Result<ReadbackRing<T>, GraphicsError> {
+        let mut ring = ReadbackRing {
+            slots: Vec::with_capacity(frame_count),
+        };
+
+        for _ in 0..frame_count {
+            let buffer = Buffer::new(
+                device,
+                allocator,
+                capacity,
+                vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+
+            match buffer {
+                Ok(buffer) => ring.slots.push(ReadbackSlot {
+                    buffer,
+                    pending: Vec::new(),
+                    used: 0,
+                }),
+                Err(error) => {
+                    ring.destroy(device);
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(ring)
+    }
+
+    // Number of frames between scheduling a readback and collecting its result
+    pub fn latency(&self) -> usize {
+        self.slots.len()
+    }
+
+    // Records a copy of range of source into frame's staging buffer, to be returned by collect() tagged with tag
+    // Writes to source must already be made available to transfer reads
+    // Returns false without recording anything when the frame's staging buffer is full
+    pub fn read_buffer(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        source: vk::Buffer,
+        range: Range<vk::DeviceSize>,
+        tag: T,
+    ) -> bool {
+        let size = range.end - range.start;
+        let slot = &mut self.slots[frame];
+        let offset = match reserve(slot.used, size, slot.buffer.size()) {
+            Some(offset) => offset,
+            None => return false,
+        };
+
+        let region = vk::BufferCopy {
+            src_offset: range.start,
+            dst_offset: offset,
+            size,
+        };
+
+        unsafe {
+            device.cmd_copy_buffer(
+                command_buffer,
+                source,
+                slot.buffer.handle(),
+                slice::from_ref(&region),
+            )
+        };
+
+        slot.push(device, command_buffer, tag, offset, size);
+        true
+    }
+
+    // Records a tightly packed copy of region into frame's staging buffer, to be returned by collect() tagged with tag
+    // Returns false without recording anything when the frame's staging buffer is full
+    pub fn read_image(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        region: &ImageRegion,
+        tag: T,
+    ) -> bool {
+        let size = region.size();
+        let slot = &mut self.slots[frame];
+        let offset = match reserve(slot.used, size, slot.buffer.size()) {
+            Some(offset) => offset,
+            None => return false,
+        };
+
+        // Zero row length and image height mean rows and layers follow each other without padding
+        let copy = vk::BufferImageCopy {
+            buffer_offset: offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: region.subresource,
+            image_offset: region.offset,
+            image_extent: region.extent,
+        };
+
+        unsafe {
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                region.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                slot.buffer.handle(),
+                slice::from_ref(&copy),
+            )
+        };
+
+        slot.push(device, command_buffer, tag, offset, size);
+        true
+    }
+
+    // Returns the readbacks scheduled the last time frame was recorded, in the order they were scheduled, and frees
+    // its staging buffer for new ones
+    // The fence of frame's previous submission must have been waited on
+    pub fn collect(&mut self, frame: usize) -> Vec<Readback<T>> {
+        let slot = &mut self.slots[frame];
+        slot.used = 0;
+
+        if slot.pending.is_empty() {
+            return Vec::new();
+        }
+
+        let pending = mem::take(&mut slot.pending);
+        slot.buffer.map_read(|contents| {
+            pending
+                .into_iter()
+                .map(|readback| {
+                    let start = readback.offset as usize;
+                    let end = start + readback.size as usize;
+                    Readback {
+                        tag: readback.tag,
+                        data: contents[start..end].to_vec(),
+                    }
+                })
+                .collect()
+        })
+    }
+
+    // Destroys every staging buffer, dropping uncollected readbacks - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        for slot in self.slots.iter_mut() {
+            slot.buffer.destroy(device);
+        }
+        self.slots.clear();
+    }
+}
+
+impl<T> ReadbackSlot<T> {
+    // Makes the copy just recorded visible to the host once the frame's fence signals, and remembers where it went
+    fn push(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        tag: T,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) {
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffer.handle())
+            .offset(offset)
+            .size(size);
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                slice::from_ref(&barrier),
+                &[],
+            )
+        };
+
+        self.pending.push(PendingReadback { tag, offset, size });
+        self.used = offset + size;
+    }
+}
+
+// Finds the offset for size bytes after the used part of a staging buffer of capacity bytes, or None if they don't fit
+fn reserve(
+    used: vk::DeviceSize,
+    size: vk::DeviceSize,
+    capacity: vk::DeviceSize,
+) -> Option<vk::DeviceSize> {
+    let offset = used.next_multiple_of(READBACK_ALIGNMENT);
+
+    match offset.checked_add(size) {
+        Some(end) if end <= capacity => Some(offset),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_aligns_offsets() {
+        assert_eq!(reserve(0, 4, 64), Some(0));
+        assert_eq!(reserve(4, 4, 64), Some(16));
+        assert_eq!(reserve(16, 4, 64), Some(16));
+        assert_eq!(reserve(17, 4, 64), Some(32));
+    }
+
+    #[test]
+    fn reserve_rejects_what_does_not_fit() {
+        assert_eq!(reserve(0, 64, 64), Some(0));
+        assert_eq!(reserve(0, 65, 64), None);
+        // Alignment padding counts against the capacity
+        assert_eq!(reserve(50, 4, 64), None);
+        assert_eq!(reserve(0, vk::DeviceSize::MAX, 64), None);
+    }
+
+    #[test]
+    fn image_region_size_is_tightly_packed() {
+        let region = ImageRegion {
+            image: vk::Image::null(),
+            subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 2,
+            },
+            offset: vk::Offset3D { x: 8, y: 8, z: 0 },
+            extent: vk::Extent3D {
+                width: 3,
+                height: 5,
+                depth: 1,
+            },
+            texel_size: 4,
+        };
+
+        assert_eq!(region.size(), 3 * 5 * 2 * 4);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::graphics::depth::DepthBuffer;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::msaa::ColorTarget;
+use ash::{vk, Device};
+use std::slice;
+
+// Render pass depth testing against a DepthBuffer, with a framebuffer for each swapchain image view
+// Without MSAA it draws straight into the swapchain, and otherwise it draws into a multisampled ColorTarget that is
+// resolved to the swapchain image at the end of the subpass
+// Headless rendering passes the OffscreenTarget's view in place of the swapchain's, leaving it ready to read back
+pub struct RenderPass {
+    render_pass: vk::RenderPass,
+    framebuffers: Vec<vk::Framebuffer>,
+    format: vk::Format,
+    depth_format: vk::Format,
+    samples: vk::SampleCountFlags,
+    // Layout the swapchain image is left in, PRESENT_SRC_KHR to present it or TRANSFER_SRC_OPTIMAL to read it back
+    final_layout: vk::ImageLayout,
+    extent: vk::Extent2D,
+}
+
+impl RenderPass {
+    // color_target must be given when the depth buffer is multisampled, with the same sample count
+    pub fn new(
+        device: &Device,
+        format: &vk::SurfaceFormatKHR,
+        final_layout: vk::ImageLayout,
+        image_views: &[vk::ImageView],
+        depth_buffer: &DepthBuffer,
+        color_target: Option<&ColorTarget>,
+    ) -> Result<RenderPass, GraphicsError> {
+        let samples = depth_buffer.samples();
+        let render_pass = RenderPass::create_render_pass(
+            device,
+            format,
+            final_layout,
+            depth_buffer.format(),
+            samples,
+        )?;
+        let framebuffers = RenderPass::create_framebuffers(
+            device,
+            render_pass,
+            image_views,
+            depth_buffer,
+            color_target,
+        )
+        .inspect_err(|_| unsafe { device.destroy_render_pass(render_pass, None) })?;
+
+        Ok(RenderPass {
+            render_pass,
+            framebuffers,
+            format: format.format,
+            depth_format: depth_buffer.format(),
+            samples,
+            final_layout,
+            extent: depth_buffer.extent(),
+        })
+    }
+
+    // Rebuilds the framebuffers for a recreated swapchain, depth buffer, and color target, and the render pass too if
+    // its format changed - the device must be idle, since the old framebuffers are destroyed
+    // Returns true when the render pass was replaced, meaning pipelines created for it must be rebuilt
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: &vk::SurfaceFormatKHR,
+        image_views: &[vk::ImageView],
+        depth_buffer: &DepthBuffer,
+        color_target: Option<&ColorTarget>,
+    ) -> Result<bool, GraphicsError> {
+        self.destroy_framebuffers(device);
+
+        let format_changed = format.format != self.format;
+        if format_changed {
+            unsafe { device.destroy_render_pass(self.render_pass, None) };
+            self.render_pass = RenderPass::create_render_pass(
+                device,
+                format,
+                self.final_layout,
+                self.depth_format,
+                self.samples,
+            )?;
+            self.format = format.format;
+        }
+
+        self.framebuffers = RenderPass::create_framebuffers(
+            device,
+            self.render_pass,
+            image_views,
+            depth_buffer,
+            color_target,
+        )?;
+        self.extent = depth_buffer.extent();
+
+        Ok(format_changed)
+    }
+
+    // Begins the render pass on the framebuffer for a given swapchain image, clearing it to clear_color and the depth
+    // buffer to the far plane
+    pub fn begin(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        clear_color: [f32; 4],
+    ) {
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffers[image_index as usize])
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.extent,
+            })
+            .clear_values(&clear_values);
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+    }
+
+    pub fn end(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        unsafe { device.cmd_end_render_pass(command_buffer) };
+    }
+
+    pub fn handle(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    // Framebuffers in the same order as the swapchain images
+    pub fn framebuffers(&self) -> &[vk::Framebuffer] {
+        &self.framebuffers
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Samples per pixel of the attachments drawn to, which pipelines for this render pass must rasterize with
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    // Destroys the framebuffers and render pass - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.destroy_framebuffers(device);
+
+        unsafe { device.destroy_render_pass(self.render_pass, None) };
+        self.render_pass = vk::RenderPass::null();
+    }
+
+    fn create_render_pass(
+        device: &Device,
+        format: &vk::SurfaceFormatKHR,
+        final_layout: vk::ImageLayout,
+        depth_format: vk::Format,
+        samples: vk::SampleCountFlags,
+    ) -> Result<vk::RenderPass, GraphicsError> {
+        let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+
+        // A multisampled color attachment is only read by the resolve, so like depth it is never stored
+        let (color_store_op, color_final_layout) = if multisampled {
+            (
+                vk::AttachmentStoreOp::DONT_CARE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            )
+        } else {
+            (vk::AttachmentStoreOp::STORE, final_layout)
+        };
+
+        let mut attachments = vec![
+            vk::AttachmentDescription::builder()
+                .format(format.format)
+                .samples(samples)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(color_store_op)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(color_final_layout)
+                .build(),
+            // Depth is only needed while drawing, so it is never stored
+            vk::AttachmentDescription::builder()
+                .format(depth_format)
+                .samples(samples)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .build(),
+        ];
+
+        // The swapchain image is written whole by the resolve, so its previous contents are not loaded
+        if multisampled {
+            attachments.push(
+                vk::AttachmentDescription::builder()
+                    .format(format.format)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .store_op(vk::AttachmentStoreOp::STORE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED)
+                    .final_layout(final_layout)
+                    .build(),
+            );
+        }
+
+        let color_attachment_references = vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let depth_attachment_reference = vk::AttachmentReference::builder()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+        let resolve_attachment_reference = vk::AttachmentReference::builder()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let mut subpasses = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(slice::from_ref(&color_attachment_references))
+            .depth_stencil_attachment(&depth_attachment_reference);
+
+        if multisampled {
+            subpasses =
+                subpasses.resolve_attachments(slice::from_ref(&resolve_attachment_reference));
+        }
+
+        // Holds the layout transition back until the image has been acquired, which the acquire semaphore
+        // only guarantees by the color attachment output stage
+        // The depth buffer and color target are shared between frames, so clearing them also waits for the previous
+        // frame's writes
+        let dependencies = vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            )
+            .src_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .dst_stage_mask(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            )
+            .dst_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            );
+
+        let render_pass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(slice::from_ref(&subpasses))
+            .dependencies(slice::from_ref(&dependencies));
+
+        Ok(unsafe { device.create_render_pass(&render_pass_info, None)? })
+    }
+
+    // Creates a framebuffer for each swapchain image view, all sharing the depth buffer and color target
+    // Attachments follow the render pass, with the swapchain image last as the resolve target when multisampling
+    // The framebuffers already created are destroyed if a later one fails, as the caller never gets them
+    fn create_framebuffers(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        image_views: &[vk::ImageView],
+        depth_buffer: &DepthBuffer,
+        color_target: Option<&ColorTarget>,
+    ) -> Result<Vec<vk::Framebuffer>, GraphicsError> {
+        let extent = depth_buffer.extent();
+        let mut framebuffers = Vec::with_capacity(image_views.len());
+
+        for image_view in image_views {
+            let attachments = match color_target {
+                Some(color_target) => vec![color_target.view(), depth_buffer.view(), *image_view],
+                None => vec![*image_view, depth_buffer.view()],
+            };
+            let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(&attachments)
+                .width(extent.width)
+                .height(extent.height)
+                .layers(1);
+
+            match unsafe { device.create_framebuffer(&framebuffer_create_info, None) } {
+                Ok(framebuffer) => framebuffers.push(framebuffer),
+                Err(result) => {
+                    for framebuffer in framebuffers {
+                        unsafe { device.destroy_framebuffer(framebuffer, None) };
+                    }
+                    return Err(result.into());
+                }
+            }
+        }
+
+        Ok(framebuffers)
+    }
+
+    fn destroy_framebuffers(&mut self, device: &Device) {
+        for framebuffer in self.framebuffers.drain(..) {
+            unsafe { device.destroy_framebuffer(framebuffer, None) };
+        }
+    }
+}
@@ -0,0 +1,547 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use ash::util;
+#[cfg(feature = "hot-reload")]
+use shaderc::{
+    CompileOptions, Compiler, IncludeType, OptimizationLevel, ResolvedInclude, ShaderKind,
+};
+#[cfg(feature = "hot-reload")]
+use std::{
+    fmt, fs,
+    time::{Duration, Instant, SystemTime},
+};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+// How often the shader sources are checked for changes, as checking every frame would stat the files hundreds of times
+// a second
+#[cfg(feature = "hot-reload")]
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// SPIR-V for each stage of the graphics pipeline
+#[derive(Clone, Debug)]
+pub struct ShaderCode {
+    pub vertex: Vec<u32>,
+    pub fragment: Vec<u32>,
+}
+
+impl ShaderCode {
+    // Reads the precompiled shaders, which are built from the .vert and .frag sources next to them with glslc
+    pub fn precompiled() -> Result<ShaderCode, GraphicsError> {
+        // Macro include_bytes! must know path names at compile time! If shaders are unknown, or for automation, a different solution is required.
+        let mut vertex_shader_file = Cursor::new(&include_bytes!("shaders/vertex.spv"));
+        let mut fragment_shader_file = Cursor::new(&include_bytes!("shaders/fragment.spv"));
+
+        let vertex = util::read_spv(&mut vertex_shader_file).map_err(|source| {
+            GraphicsError::ShaderLoading {
+                name: "vertex",
+                source,
+            }
+        })?;
+        let fragment = util::read_spv(&mut fragment_shader_file).map_err(|source| {
+            GraphicsError::ShaderLoading {
+                name: "fragment",
+                source,
+            }
+        })?;
+
+        Ok(ShaderCode { vertex, fragment })
+    }
+}
+
+// GLSL sources of the graphics pipeline's shaders
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderSources {
+    pub vertex: PathBuf,
+    pub fragment: PathBuf,
+    // Code shared between shaders, which #include <file> looks up and hot reloading watches along with the sources
+    pub include_directory: PathBuf,
+}
+
+impl ShaderSources {
+    // Sources in the crate's source tree, which only exist where the executable was built
+    pub fn source_tree() -> ShaderSources {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/graphics/shaders");
+        ShaderSources {
+            vertex: directory.join("vertex_shader.vert"),
+            fragment: directory.join("fragment_shader.frag"),
+            include_directory: directory.join("include"),
+        }
+    }
+}
+
+// shaderc runs the SPIR-V optimizer in release builds, while debug builds skip it to keep hot reloading quick and the
+// output readable in debuggers
+#[cfg(all(feature = "hot-reload", debug_assertions))]
+const OPTIMIZATION_LEVEL: OptimizationLevel = OptimizationLevel::Zero;
+#[cfg(all(feature = "hot-reload", not(debug_assertions)))]
+const OPTIMIZATION_LEVEL: OptimizationLevel = OptimizationLevel::Performance;
+
+// Size of a shader's SPIR-V before and after optimization, from ShaderCompiler::optimization_stats()
+#[cfg(feature = "hot-reload")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OptimizationStats {
+    pub unoptimized_bytes: usize,
+    pub optimized_bytes: usize,
+}
+
+#[cfg(feature = "hot-reload")]
+impl fmt::Display for OptimizationStats {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        // Signed, as optimizing for performance can unroll loops and inline functions into larger code
+        let change = if self.unoptimized_bytes == 0 {
+            0.0
+        } else {
+            (self.optimized_bytes as f64 / self.unoptimized_bytes as f64 - 1.0) * 100.0
+        };
+
+        write!(
+            formatter,
+            "{} bytes unoptimized, {} bytes optimized ({:+.1}%)",
+            self.unoptimized_bytes, self.optimized_bytes, change
+        )
+    }
+}
+
+// Compiles GLSL to SPIR-V for Vulkan with shaderc
+#[cfg(feature = "hot-reload")]
+pub struct ShaderCompiler {
+    compiler: Compiler,
+    options: CompileOptions<'static>,
+    // Kept to build options with other optimization levels for optimization_stats()
+    include_directories: Vec<PathBuf>,
+}
+
+#[cfg(feature = "hot-reload")]
+impl ShaderCompiler {
+    pub fn new() -> Result<ShaderCompiler, GraphicsError> {
+        ShaderCompiler::with_include_directories(Vec::new())
+    }
+
+    // Compiler looking up #include "file" next to the including file and then in include_directories, and
+    // #include <file> in include_directories alone, in the order given
+    pub fn with_include_directories(
+        include_directories: Vec<PathBuf>,
+    ) -> Result<ShaderCompiler, GraphicsError> {
+        let compiler = Compiler::new().ok_or(GraphicsError::ShaderCompilerCreation)?;
+        let options = ShaderCompiler::create_options(&include_directories, OPTIMIZATION_LEVEL)?;
+
+        Ok(ShaderCompiler {
+            compiler,
+            options,
+            include_directories,
+        })
+    }
+
+    // Reads and compiles the source at path as a shader of kind, with name identifying it in errors
+    // Failures to compile come back as GraphicsError::ShaderCompilation holding the compiler log, and warnings are
+    // printed
+    pub fn compile_file(
+        &self,
+        path: &Path,
+        kind: ShaderKind,
+        name: &'static str,
+    ) -> Result<Vec<u32>, GraphicsError> {
+        let source = fs::read_to_string(path)
+            .map_err(|source| GraphicsError::ShaderLoading { name, source })?;
+
+        self.compile_source(&source, kind, &path.to_string_lossy(), name)
+    }
+
+    // Same as compile_file, for source already in memory (e.g. from include_str!), with file_name standing in for its
+    // path in the compiler log and as the file #include "file" is looked up next to
+    pub fn compile_source(
+        &self,
+        source: &str,
+        kind: ShaderKind,
+        file_name: &str,
+        name: &'static str,
+    ) -> Result<Vec<u32>, GraphicsError> {
+        self.compile_with(source, kind, file_name, name, &self.options)
+    }
+
+    // Compiles source both without optimization and optimized for performance, whatever the build, to show what the
+    // optimizer does to it
+    pub fn optimization_stats(
+        &self,
+        source: &str,
+        kind: ShaderKind,
+        file_name: &str,
+        name: &'static str,
+    ) -> Result<OptimizationStats, GraphicsError> {
+        let size = |level: OptimizationLevel| -> Result<usize, GraphicsError> {
+            let options = ShaderCompiler::create_options(&self.include_directories, level)?;
+            let code = self.compile_with(source, kind, file_name, name, &options)?;
+            Ok(code.len() * 4)
+        };
+
+        Ok(OptimizationStats {
+            unoptimized_bytes: size(OptimizationLevel::Zero)?,
+            optimized_bytes: size(OptimizationLevel::Performance)?,
+        })
+    }
+
+    fn compile_with(
+        &self,
+        source: &str,
+        kind: ShaderKind,
+        file_name: &str,
+        name: &'static str,
+        options: &CompileOptions,
+    ) -> Result<Vec<u32>, GraphicsError> {
+        let artifact = self
+            .compiler
+            .compile_into_spirv(source, kind, file_name, "main", Some(options))
+            .map_err(|error| GraphicsError::ShaderCompilation {
+                name,
+                log: match error {
+                    shaderc::Error::CompilationError(_, log) => log,
+                    error => error.to_string(),
+                },
+            })?;
+
+        if artifact.get_num_warnings() > 0 {
+            println!("{}", artifact.get_warning_messages());
+        }
+
+        Ok(artifact.as_binary().to_vec())
+    }
+
+    // Compiles both stages of sources
+    pub fn compile(&self, sources: &ShaderSources) -> Result<ShaderCode, GraphicsError> {
+        Ok(ShaderCode {
+            vertex: self.compile_file(&sources.vertex, ShaderKind::Vertex, "vertex")?,
+            fragment: self.compile_file(&sources.fragment, ShaderKind::Fragment, "fragment")?,
+        })
+    }
+
+    fn create_options(
+        include_directories: &[PathBuf],
+        optimization_level: OptimizationLevel,
+    ) -> Result<CompileOptions<'static>, GraphicsError> {
+        let mut options = CompileOptions::new().ok_or(GraphicsError::ShaderCompilerCreation)?;
+        options.set_target_env(
+            shaderc::TargetEnv::Vulkan,
+            shaderc::EnvVersion::Vulkan1_0 as u32,
+        );
+        options.set_optimization_level(optimization_level);
+
+        let include_directories = include_directories.to_vec();
+        options.set_include_callback(move |requested, include_type, requesting_source, _depth| {
+            resolve_include(
+                requested,
+                include_type,
+                requesting_source,
+                &include_directories,
+            )
+        });
+
+        Ok(options)
+    }
+}
+
+// Notices when any of a set of files is modified, created, or deleted, by comparing modification times
+// Polling keeps it portable and dependency free, and a few files every POLL_INTERVAL costs next to nothing
+#[cfg(feature = "hot-reload")]
+pub struct ShaderWatcher {
+    // Each file along with its modification time when last checked, None if it could not be read
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    interval: Duration,
+    last_check: Instant,
+}
+
+#[cfg(feature = "hot-reload")]
+impl ShaderWatcher {
+    // Starts watching paths from their current state, checking them at most once per interval
+    pub fn new(paths: &[&Path], interval: Duration) -> ShaderWatcher {
+        ShaderWatcher {
+            files: paths
+                .iter()
+                .map(|path| (path.to_path_buf(), modified_time(path)))
+                .collect(),
+            interval,
+            last_check: Instant::now(),
+        }
+    }
+
+    // Returns true if any file changed since the last check, or false if nothing changed or interval has not passed
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < self.interval {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let mut changed = false;
+        for (path, modified) in self.files.iter_mut() {
+            let current = modified_time(path);
+            if current != *modified {
+                *modified = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+// Recompiles the graphics pipeline's shaders whenever their sources change
+#[cfg(feature = "hot-reload")]
+pub struct ShaderReload {
+    compiler: ShaderCompiler,
+    sources: ShaderSources,
+    watcher: ShaderWatcher,
+}
+
+#[cfg(feature = "hot-reload")]
+impl ShaderReload {
+    pub fn new(sources: ShaderSources) -> Result<ShaderReload, GraphicsError> {
+        // Every file in the include directory when reloading starts is watched, as any of them may be included
+        let mut paths = vec![sources.vertex.clone(), sources.fragment.clone()];
+        if let Ok(entries) = fs::read_dir(&sources.include_directory) {
+            paths.extend(entries.flatten().map(|entry| entry.path()));
+        }
+        let paths: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        let watcher = ShaderWatcher::new(&paths, POLL_INTERVAL);
+
+        Ok(ShaderReload {
+            compiler: ShaderCompiler::with_include_directories(vec![sources
+                .include_directory
+                .clone()])?,
+            sources,
+            watcher,
+        })
+    }
+
+    // Compiles the sources as they are now
+    pub fn compile(&self) -> Result<ShaderCode, GraphicsError> {
+        self.compiler.compile(&self.sources)
+    }
+
+    // Recompiled shaders if a source changed since the last call, or None if nothing changed
+    pub fn poll(&mut self) -> Option<Result<ShaderCode, GraphicsError>> {
+        if self.watcher.poll() {
+            Some(self.compile())
+        } else {
+            None
+        }
+    }
+}
+
+// Finds and reads the file an #include directive in requesting_source asks for, failing with the message for the
+// compiler log if there is none
+// The resolved name is the file's path, so includes inside it are looked up next to it in turn
+#[cfg(feature = "hot-reload")]
+fn resolve_include(
+    requested: &str,
+    include_type: IncludeType,
+    requesting_source: &str,
+    include_directories: &[PathBuf],
+) -> Result<ResolvedInclude, String> {
+    let next_to_source = match include_type {
+        IncludeType::Relative => Path::new(requesting_source).parent(),
+        IncludeType::Standard => None,
+    };
+
+    let path = next_to_source
+        .into_iter()
+        .chain(include_directories.iter().map(PathBuf::as_path))
+        .map(|directory| directory.join(requested))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("Could not find {} to include", requested))?;
+
+    let content = fs::read_to_string(&path)
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+
+    Ok(ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
+
+#[cfg(feature = "hot-reload")]
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(all(test, feature = "hot-reload"))]
+mod tests {
+    use super::*;
+    use std::{fs::File, process};
+
+    // File in the temp directory unique to this test run, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let path =
+                std::env::temp_dir().join(format!("shader-watcher-{}-{}", process::id(), name));
+            fs::write(&path, "void main() {}").unwrap();
+            TempFile(path)
+        }
+
+        // Moves the modification time forward, as file systems may not notice writes in quick succession
+        fn touch(&self, seconds: u64) {
+            let file = File::options().write(true).open(&self.0).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(seconds))
+                .unwrap();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    // Directory in the temp directory unique to this test run, removed along with its contents when dropped
+    struct TempDirectory(PathBuf);
+
+    impl TempDirectory {
+        fn new(name: &str) -> TempDirectory {
+            let path =
+                std::env::temp_dir().join(format!("shader-includes-{}-{}", process::id(), name));
+            fs::create_dir_all(path.join("include")).unwrap();
+            TempDirectory(path)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDirectory {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn relative_includes_are_found_next_to_the_including_file() {
+        let directory = TempDirectory::new("relative");
+        let shader = directory.write("shader.frag", "#include \"common.glsl\"");
+        let common = directory.write("common.glsl", "float common;");
+
+        let resolved = resolve_include(
+            "common.glsl",
+            IncludeType::Relative,
+            &shader.to_string_lossy(),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(resolved.resolved_name, common.to_string_lossy());
+        assert_eq!(resolved.content, "float common;");
+    }
+
+    #[test]
+    fn include_directories_are_searched_in_order() {
+        let directory = TempDirectory::new("standard");
+        let shader = directory.write("shader.frag", "#include <shared.glsl>");
+        directory.write("shared.glsl", "float next_to_source;");
+        let shared = directory.write("include/shared.glsl", "float shared;");
+        let include_directories = [directory.0.join("missing"), directory.0.join("include")];
+
+        // Standard includes skip the including file's directory
+        let resolved = resolve_include(
+            "shared.glsl",
+            IncludeType::Standard,
+            &shader.to_string_lossy(),
+            &include_directories,
+        )
+        .unwrap();
+        assert_eq!(resolved.resolved_name, shared.to_string_lossy());
+        assert_eq!(resolved.content, "float shared;");
+
+        // Relative includes fall back to the include directories when the file is not next to the including one
+        directory.write("include/fallback.glsl", "float fallback;");
+        let resolved = resolve_include(
+            "fallback.glsl",
+            IncludeType::Relative,
+            &shader.to_string_lossy(),
+            &include_directories,
+        )
+        .unwrap();
+        assert_eq!(resolved.content, "float fallback;");
+    }
+
+    #[test]
+    fn missing_includes_are_reported() {
+        let directory = TempDirectory::new("missing");
+        let shader = directory.write("shader.frag", "#include \"missing.glsl\"");
+
+        let error = resolve_include(
+            "missing.glsl",
+            IncludeType::Relative,
+            &shader.to_string_lossy(),
+            &[directory.0.join("include")],
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error, "Could not find missing.glsl to include");
+    }
+
+    #[test]
+    fn optimization_stats_show_the_change_in_size() {
+        let smaller = OptimizationStats {
+            unoptimized_bytes: 2000,
+            optimized_bytes: 1500,
+        };
+        assert_eq!(
+            smaller.to_string(),
+            "2000 bytes unoptimized, 1500 bytes optimized (-25.0%)"
+        );
+
+        let larger = OptimizationStats {
+            unoptimized_bytes: 1000,
+            optimized_bytes: 1100,
+        };
+        assert_eq!(
+            larger.to_string(),
+            "1000 bytes unoptimized, 1100 bytes optimized (+10.0%)"
+        );
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reported() {
+        let file = TempFile::new("unchanged");
+        let mut watcher = ShaderWatcher::new(&[&file.0], Duration::from_secs(0));
+
+        assert!(!watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn modified_files_are_reported_once() {
+        let vertex = TempFile::new("modified.vert");
+        let fragment = TempFile::new("modified.frag");
+        let mut watcher = ShaderWatcher::new(&[&vertex.0, &fragment.0], Duration::from_secs(0));
+
+        fragment.touch(10);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn deleted_and_recreated_files_are_reported() {
+        let file = TempFile::new("deleted");
+        let mut watcher = ShaderWatcher::new(&[&file.0], Duration::from_secs(0));
+
+        fs::remove_file(&file.0).unwrap();
+        assert!(watcher.poll());
+
+        fs::write(&file.0, "void main() {}").unwrap();
+        assert!(watcher.poll());
+    }
+
+    #[test]
+    fn changes_wait_for_the_interval() {
+        let file = TempFile::new("interval");
+        let mut watcher = ShaderWatcher::new(&[&file.0], Duration::from_secs(3600));
+
+        file.touch(10);
+        assert!(!watcher.poll());
+    }
+}
@@ -1,8 +1,16 @@
 #version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "include/output_transform.glsl"
+
+layout(binding = 1) uniform sampler2D texSampler;
 
 layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(fragColor, 1.0);
-}
\ No newline at end of file
+    vec3 color = fragColor * texture(texSampler, fragTexCoord).rgb;
+    outColor = vec4(encodeOutput(color), 1.0);
+}
@@ -0,0 +1,41 @@
+// Output transform for the swapchain's color space, from OutputTransform - colors are worked on as linear BT.709
+// until they are stored
+// 0 keeps BT.709 primaries, 1 converts to Display-P3, and 2 to BT.2020
+layout(constant_id = 0) const int OUTPUT_GAMUT = 0;
+// 0 stores linear values, 1 encodes with the sRGB curve, and 2 with the ST 2084 (PQ) curve
+layout(constant_id = 1) const int OUTPUT_TRANSFER = 0;
+
+// Column major conversions from linear BT.709
+const mat3 BT709_TO_DISPLAY_P3 = mat3(
+    0.8225, 0.0332, 0.0171,
+    0.1774, 0.9669, 0.0724,
+    0.0000, 0.0000, 0.9108
+);
+const mat3 BT709_TO_BT2020 = mat3(
+    0.6274, 0.0691, 0.0164,
+    0.3293, 0.9195, 0.0880,
+    0.0433, 0.0114, 0.8956
+);
+
+// PQ is absolute, so white is placed at the 203 nit reference white out of the curve's 10000
+const float PQ_WHITE = 203.0 / 10000.0;
+
+// Converts linear BT.709 color to what the swapchain's color space expects to be stored
+vec3 encodeOutput(vec3 color) {
+    if (OUTPUT_GAMUT == 1) {
+        color = BT709_TO_DISPLAY_P3 * color;
+    } else if (OUTPUT_GAMUT == 2) {
+        color = BT709_TO_BT2020 * color;
+    }
+
+    if (OUTPUT_TRANSFER == 1) {
+        vec3 positive = max(color, vec3(0.0));
+        color = mix(positive * 12.92, pow(positive, vec3(1.0 / 2.4)) * 1.055 - vec3(0.055),
+                    greaterThan(positive, vec3(0.0031308)));
+    } else if (OUTPUT_TRANSFER == 2) {
+        vec3 y = pow(max(color, vec3(0.0)) * PQ_WHITE, vec3(0.1593017578125));
+        color = pow((vec3(0.8359375) + y * 18.8515625) / (vec3(1.0) + y * 18.6875), vec3(78.84375));
+    }
+
+    return color;
+}
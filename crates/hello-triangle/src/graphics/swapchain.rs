@@ -0,0 +1,327 @@
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::swapchain_config;
+use crate::graphics::vulkan_base::WindowDimensions;
+use ash::{
+    extensions::khr::{Surface, Swapchain as SwapchainLoader},
+    vk, Device, Instance,
+};
+
+// A physical device's surface capabilities, formats, and presentation modes
+pub struct SwapchainSupportDetails {
+    pub capabilities: vk::SurfaceCapabilitiesKHR,
+    pub formats: Vec<vk::SurfaceFormatKHR>,
+    pub presentation_modes: Vec<vk::PresentModeKHR>,
+}
+
+impl SwapchainSupportDetails {
+    // Gets a given physical device's surface capabilities, formats, and presentation modes
+    pub fn query(
+        device: &vk::PhysicalDevice,
+        surface_khr: &vk::SurfaceKHR,
+        surface: &Surface,
+    ) -> Result<SwapchainSupportDetails, GraphicsError> {
+        let capabilities =
+            unsafe { surface.get_physical_device_surface_capabilities(*device, *surface_khr)? };
+
+        let formats =
+            unsafe { surface.get_physical_device_surface_formats(*device, *surface_khr)? };
+
+        let presentation_modes =
+            unsafe { surface.get_physical_device_surface_present_modes(*device, *surface_khr)? };
+
+        Ok(SwapchainSupportDetails {
+            capabilities,
+            formats,
+            presentation_modes,
+        })
+    }
+}
+
+// Swapchain and its image views, rebuilt in place whenever the surface changes size
+pub struct Swapchain {
+    loader: SwapchainLoader,
+    swapchain_khr: vk::SwapchainKHR,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    format: vk::SurfaceFormatKHR,
+    presentation_mode: vk::PresentModeKHR,
+    extent: vk::Extent2D,
+    // Ranked (format, color space) pairs to pick the image format from
+    surface_formats: Vec<vk::SurfaceFormatKHR>,
+    // Families sharing the images, empty when drawing and presenting use the same family
+    sharing_family_indices: Vec<u32>,
+}
+
+impl Swapchain {
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        surface_khr: &vk::SurfaceKHR,
+        swapchain_support_details: &SwapchainSupportDetails,
+        window: &WindowDimensions,
+        // Graphics and presentation queue families
+        queue_family_indices: [u32; 2],
+        surface_formats: &[vk::SurfaceFormatKHR],
+    ) -> Result<Swapchain, GraphicsError> {
+        // Images are shared between the families when drawing and presenting happen on different ones
+        let sharing_family_indices = if queue_family_indices[0] == queue_family_indices[1] {
+            Vec::new()
+        } else {
+            queue_family_indices.to_vec()
+        };
+
+        let mut swapchain = Swapchain {
+            loader: SwapchainLoader::new(instance, device),
+            swapchain_khr: vk::SwapchainKHR::null(),
+            images: Vec::new(),
+            image_views: Vec::new(),
+            format: vk::SurfaceFormatKHR::default(),
+            presentation_mode: vk::PresentModeKHR::FIFO,
+            extent: vk::Extent2D::default(),
+            surface_formats: surface_formats.to_vec(),
+            sharing_family_indices,
+        };
+
+        // create() sets swapchain_khr before getting the images and creating their views, so a later failure leaves a
+        // swapchain to destroy
+        swapchain
+            .create(device, surface_khr, swapchain_support_details, window)
+            .inspect_err(|_| swapchain.destroy(device))?;
+        Ok(swapchain)
+    }
+
+    // Rebuilds the swapchain for the surface's current capabilities, e.g. after a resize or ERROR_OUT_OF_DATE_KHR
+    // The device must be idle, since the old image views are destroyed
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        surface_khr: &vk::SurfaceKHR,
+        swapchain_support_details: &SwapchainSupportDetails,
+        window: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        self.destroy_image_views(device);
+
+        // Handing over the old swapchain lets the driver reuse its resources, after which it can be destroyed
+        let old_swapchain = self.swapchain_khr;
+        let result = self.create(device, surface_khr, swapchain_support_details, window);
+
+        // The old swapchain is retired either way, and is only still current if creating the new one failed
+        unsafe { self.loader.destroy_swapchain(old_swapchain, None) };
+        if self.swapchain_khr == old_swapchain {
+            self.swapchain_khr = vk::SwapchainKHR::null();
+        }
+
+        // The images may still be the old swapchain's, so none are handed out until a recreate succeeds
+        if result.is_err() {
+            self.images.clear();
+        }
+
+        result
+    }
+
+    // Acquires the next image to render to, signalling semaphore once it is ready
+    // Fails with AcquireTimeout if no image is available within timeout nanoseconds, and SwapchainOutOfDate when the
+    // swapchain must be recreated before rendering - neither signals the semaphore, so it can be used to try again
+    pub fn acquire_next_image(
+        &self,
+        semaphore: vk::Semaphore,
+        timeout: u64,
+    ) -> Result<u32, GraphicsError> {
+        let result = unsafe {
+            self.loader.acquire_next_image(
+                self.swapchain_khr,
+                timeout,
+                semaphore,
+                vk::Fence::null(),
+            )
+        };
+
+        match result {
+            // A suboptimal swapchain can still be presented to, so it is recreated after presenting instead
+            Ok((image_index, _suboptimal)) => Ok(image_index),
+            Err(result) => Err(GraphicsError::from_swapchain(result)),
+        }
+    }
+
+    // Queues an image for presentation once wait_semaphores are signalled
+    // Returns true when the swapchain is out of date or suboptimal and should be recreated
+    pub fn present(
+        &self,
+        queue: vk::Queue,
+        image_index: u32,
+        wait_semaphores: &[vk::Semaphore],
+    ) -> Result<bool, GraphicsError> {
+        let swapchains = [self.swapchain_khr];
+        let image_indices = [image_index];
+
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        match unsafe { self.loader.queue_present(queue, &present_info) } {
+            Ok(suboptimal) => Ok(suboptimal),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(true),
+            Err(result) => Err(GraphicsError::from_swapchain(result)),
+        }
+    }
+
+    // Destroys the image views and swapchain - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.destroy_image_views(device);
+
+        unsafe { self.loader.destroy_swapchain(self.swapchain_khr, None) };
+        self.swapchain_khr = vk::SwapchainKHR::null();
+        self.images.clear();
+    }
+
+    pub fn handle(&self) -> vk::SwapchainKHR {
+        self.swapchain_khr
+    }
+
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    // Format and color space of the swapchain images, which output passes must encode for
+    pub fn format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    // Position of format() in the requested surface formats, None if the surface supported none of them
+    pub fn format_rank(&self) -> Option<usize> {
+        self.surface_formats
+            .iter()
+            .position(|format| *format == self.format)
+    }
+
+    pub fn presentation_mode(&self) -> vk::PresentModeKHR {
+        self.presentation_mode
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Creates the swap chain after determining swap chain settings, replacing self.swapchain_khr
+    fn create(
+        &mut self,
+        device: &Device,
+        surface_khr: &vk::SurfaceKHR,
+        swapchain_support_details: &SwapchainSupportDetails,
+        window: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        let format = swapchain_config::choose_surface_format(
+            &swapchain_support_details.formats,
+            &self.surface_formats,
+        )?;
+
+        let presentation_mode =
+            swapchain_config::choose_present_mode(&swapchain_support_details.presentation_modes);
+
+        let extent = swapchain_config::choose_extent(
+            &swapchain_support_details.capabilities,
+            window.width(),
+            window.height(),
+        );
+
+        let image_count =
+            swapchain_config::choose_image_count(&swapchain_support_details.capabilities);
+
+        let sharing_mode = if self.sharing_family_indices.is_empty() {
+            vk::SharingMode::EXCLUSIVE
+        } else {
+            vk::SharingMode::CONCURRENT
+        };
+
+        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(*surface_khr)
+            .min_image_count(image_count)
+            .image_format(format.format)
+            .image_color_space(format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_sharing_mode(sharing_mode)
+            .queue_family_indices(&self.sharing_family_indices)
+            .pre_transform(swapchain_support_details.capabilities.current_transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .present_mode(presentation_mode)
+            .clipped(true)
+            .old_swapchain(self.swapchain_khr);
+
+        self.swapchain_khr = unsafe {
+            self.loader
+                .create_swapchain(&swapchain_create_info, None)
+                .map_err(GraphicsError::from_swapchain)?
+        };
+
+        // Retreives available swapchain images
+        self.images = unsafe {
+            self.loader
+                .get_swapchain_images(self.swapchain_khr)
+                .map_err(GraphicsError::Swapchain)?
+        };
+
+        // Creates and stores an image view for each swapchain image
+        self.image_views = Swapchain::create_image_views(device, &self.images, &format)?;
+
+        self.format = format;
+        self.presentation_mode = presentation_mode;
+        self.extent = extent;
+
+        Ok(())
+    }
+
+    // Creates an image view for each image in the swapchain
+    // The views already created are destroyed if a later one fails, as the caller never gets them
+    fn create_image_views(
+        device: &Device,
+        images: &[vk::Image],
+        format: &vk::SurfaceFormatKHR,
+    ) -> Result<Vec<vk::ImageView>, GraphicsError> {
+        let mut image_views = Vec::with_capacity(images.len());
+
+        for image in images {
+            let image_view_create_info = vk::ImageViewCreateInfo::builder()
+                .image(*image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format.format)
+                .components(vk::ComponentMapping {
+                    r: vk::ComponentSwizzle::IDENTITY,
+                    g: vk::ComponentSwizzle::IDENTITY,
+                    b: vk::ComponentSwizzle::IDENTITY,
+                    a: vk::ComponentSwizzle::IDENTITY,
+                })
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                });
+
+            match unsafe { device.create_image_view(&image_view_create_info, None) } {
+                Ok(image_view) => image_views.push(image_view),
+                Err(result) => {
+                    for image_view in image_views {
+                        unsafe { device.destroy_image_view(image_view, None) };
+                    }
+                    return Err(result.into());
+                }
+            }
+        }
+
+        Ok(image_views)
+    }
+
+    fn destroy_image_views(&mut self, device: &Device) {
+        for image_view in self.image_views.drain(..) {
+            unsafe { device.destroy_image_view(image_view, None) };
+        }
+    }
+}
@@ -0,0 +1,689 @@
+use super::graphics_errors::GraphicsError;
+use super::vulkan_base::ColorOutput;
+use ash::vk;
+use std::{convert::TryFrom, time::Duration};
+
+// Pure swapchain selection logic, kept free of any Vulkan handles so it can be tested against
+// capabilities reported by specific drivers without needing the hardware
+
+// Ranked (format, color space) pairs the crate asks for with each color output - 10 bit wide gamut formats come
+// ahead of 8 bit sRGB when wide gamut output is requested
+pub fn preferred_surface_formats(color_output: ColorOutput) -> Vec<vk::SurfaceFormatKHR> {
+    let srgb = [(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR)];
+    let wide_gamut = [
+        (
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+        ),
+        (
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+        ),
+        (
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        ),
+        (
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        ),
+        (
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+        (
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ),
+    ];
+
+    let preferences: &[_] = match color_output {
+        ColorOutput::Srgb => &[],
+        ColorOutput::WideGamut => &wide_gamut,
+    };
+
+    preferences
+        .iter()
+        .chain(srgb.iter())
+        .map(|&(format, color_space)| vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        })
+        .collect()
+}
+
+// Determines surface format, taking the first of preferences (ordered from most to least preferred) that the
+// surface supports, or the surface's first format if it supports none of them
+// Fails with NoSurfaceFormats when the surface reports no formats at all
+pub fn choose_surface_format(
+    formats: &[vk::SurfaceFormatKHR],
+    preferences: &[vk::SurfaceFormatKHR],
+) -> Result<vk::SurfaceFormatKHR, GraphicsError> {
+    preferences
+        .iter()
+        .find(|preference| formats.contains(preference))
+        .or_else(|| formats.first())
+        .copied()
+        .ok_or(GraphicsError::NoSurfaceFormats)
+}
+
+// Primaries the fragment shader converts its linear BT.709 output to, numbered as its OUTPUT_GAMUT constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputGamut {
+    Bt709 = 0,
+    DisplayP3 = 1,
+    Bt2020 = 2,
+}
+
+// Transfer function the fragment shader encodes its output with, numbered as its OUTPUT_TRANSFER constant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputTransfer {
+    // Stored as is, for linear color spaces and for sRGB formats, which encode on store
+    Linear = 0,
+    // The sRGB curve, which Display-P3 shares
+    Srgb = 1,
+    // The SMPTE ST 2084 perceptual quantizer used by HDR10
+    Pq = 2,
+}
+
+// Output encoding the fragment shader applies so its colors display correctly in a surface format, passed to it as
+// specialization constants
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputTransform {
+    pub gamut: OutputGamut,
+    pub transfer: OutputTransfer,
+}
+
+impl OutputTransform {
+    // Color spaces the crate doesn't know are treated as sRGB
+    pub fn for_surface_format(surface_format: vk::SurfaceFormatKHR) -> OutputTransform {
+        let (gamut, transfer) = match surface_format.color_space {
+            vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT => {
+                (OutputGamut::DisplayP3, OutputTransfer::Srgb)
+            }
+            vk::ColorSpaceKHR::DISPLAY_P3_LINEAR_EXT => {
+                (OutputGamut::DisplayP3, OutputTransfer::Linear)
+            }
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => (OutputGamut::Bt2020, OutputTransfer::Pq),
+            vk::ColorSpaceKHR::BT2020_LINEAR_EXT => (OutputGamut::Bt2020, OutputTransfer::Linear),
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => {
+                (OutputGamut::Bt709, OutputTransfer::Linear)
+            }
+            _ => (OutputGamut::Bt709, OutputTransfer::Srgb),
+        };
+
+        // Encoding again on top of an sRGB format would apply the curve twice
+        let encodes_on_store = matches!(
+            surface_format.format,
+            vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32
+        );
+        let transfer = if encodes_on_store && transfer == OutputTransfer::Srgb {
+            OutputTransfer::Linear
+        } else {
+            transfer
+        };
+
+        OutputTransform { gamut, transfer }
+    }
+
+    // Values for the shader's specialization constants 0 (OUTPUT_GAMUT) and 1 (OUTPUT_TRANSFER), as 32 bit ints
+    pub fn specialization_data(&self) -> [u8; 8] {
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&(self.gamut as i32).to_ne_bytes());
+        data[4..].copy_from_slice(&(self.transfer as i32).to_ne_bytes());
+        data
+    }
+}
+
+impl Default for OutputTransform {
+    // Linear BT.709 stored as is, which is right for the sRGB formats the crate prefers
+    fn default() -> OutputTransform {
+        OutputTransform {
+            gamut: OutputGamut::Bt709,
+            transfer: OutputTransfer::Linear,
+        }
+    }
+}
+
+// Chooses presentation mode - immediate is preferred for least latency (as opposed to VSync aka FIFO)
+pub fn choose_present_mode(presentation_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+    for presentation_mode in presentation_modes {
+        if *presentation_mode == vk::PresentModeKHR::IMMEDIATE {
+            return *presentation_mode;
+        }
+    }
+
+    // FIFO is the only mode every implementation is required to support
+    vk::PresentModeKHR::FIFO
+}
+
+// How long to wait for a swapchain image, and how often to try before giving up, so a compositor that stops
+// handing images back stalls a frame rather than the whole render loop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcquirePolicy {
+    // Longest a single acquire waits for an image
+    pub timeout: Duration,
+    // Acquires tried per frame, counting both timeouts and attempts after recreating an out of date swapchain
+    pub max_attempts: u32,
+}
+
+impl AcquirePolicy {
+    // Timeout in the nanoseconds acquire_next_image takes, saturating at u64::MAX which waits forever
+    pub fn timeout_nanos(&self) -> u64 {
+        u64::try_from(self.timeout.as_nanos()).unwrap_or(u64::MAX)
+    }
+}
+
+impl Default for AcquirePolicy {
+    fn default() -> AcquirePolicy {
+        AcquirePolicy {
+            timeout: Duration::from_secs(1),
+            max_attempts: 3,
+        }
+    }
+}
+
+// What to do after an acquire fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AcquireRecovery {
+    // Acquire again with the same swapchain
+    Retry,
+    // Recreate the swapchain, then acquire again
+    Recreate,
+    // Return the error to the caller
+    GiveUp,
+}
+
+// Decides how to recover from error after attempts failed acquires this frame
+// Only timeouts and out of date swapchains are worth another attempt - a lost surface needs a new one from the window
+pub fn acquire_recovery(
+    error: &GraphicsError,
+    attempts: u32,
+    policy: &AcquirePolicy,
+) -> AcquireRecovery {
+    if attempts >= policy.max_attempts {
+        return AcquireRecovery::GiveUp;
+    }
+
+    match error {
+        GraphicsError::AcquireTimeout => AcquireRecovery::Retry,
+        GraphicsError::SwapchainOutOfDate => AcquireRecovery::Recreate,
+        _ => AcquireRecovery::GiveUp,
+    }
+}
+
+// Creates an extent with the correct size
+pub fn choose_extent(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    window_width: u32,
+    window_height: u32,
+) -> vk::Extent2D {
+    // A current extent of u32::MAX means the surface size is decided by the swapchain (e.g. on Wayland)
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+
+    vk::Extent2D {
+        width: window_width
+            .max(capabilities.min_image_extent.width)
+            .min(capabilities.max_image_extent.width),
+        height: window_height
+            .max(capabilities.min_image_extent.height)
+            .min(capabilities.max_image_extent.height),
+    }
+}
+
+// Requests one image more than the minimum, so the driver never has to wait on us before acquiring
+pub fn choose_image_count(capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+    let image_count = capabilities.min_image_count + 1;
+
+    // A max image count of 0 means there is no upper limit
+    if capabilities.max_image_count > 0 && image_count > capabilities.max_image_count {
+        capabilities.max_image_count
+    } else {
+        image_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        }
+    }
+
+    fn capabilities(
+        current_extent: (u32, u32),
+        min_image_extent: (u32, u32),
+        max_image_extent: (u32, u32),
+        min_image_count: u32,
+        max_image_count: u32,
+    ) -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D {
+                width: current_extent.0,
+                height: current_extent.1,
+            },
+            min_image_extent: vk::Extent2D {
+                width: min_image_extent.0,
+                height: min_image_extent.1,
+            },
+            max_image_extent: vk::Extent2D {
+                width: max_image_extent.0,
+                height: max_image_extent.1,
+            },
+            min_image_count,
+            max_image_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn srgb_output_prefers_bgra8_srgb() {
+        let formats = [
+            surface_format(
+                vk::Format::B8G8R8A8_UNORM,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            ),
+            surface_format(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+            ),
+            surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+        ];
+
+        assert_eq!(
+            choose_surface_format(&formats, &preferred_surface_formats(ColorOutput::Srgb)).unwrap(),
+            formats[2]
+        );
+    }
+
+    #[test]
+    fn srgb_output_ignores_bgra8_srgb_in_other_color_spaces() {
+        // The matching format comes second, so only skipping it for its color space falls back to the first
+        let formats = [
+            surface_format(
+                vk::Format::R8G8B8A8_UNORM,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            ),
+            surface_format(
+                vk::Format::B8G8R8A8_SRGB,
+                vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            ),
+        ];
+
+        assert_eq!(
+            choose_surface_format(&formats, &preferred_surface_formats(ColorOutput::Srgb)).unwrap(),
+            formats[0]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_first_format() {
+        let formats = [
+            surface_format(
+                vk::Format::R8G8B8A8_UNORM,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            ),
+            surface_format(
+                vk::Format::B8G8R8A8_UNORM,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            ),
+        ];
+
+        assert_eq!(
+            choose_surface_format(&formats, &preferred_surface_formats(ColorOutput::Srgb)).unwrap(),
+            formats[0]
+        );
+        assert_eq!(
+            choose_surface_format(&formats, &preferred_surface_formats(ColorOutput::WideGamut))
+                .unwrap(),
+            formats[0]
+        );
+    }
+
+    #[test]
+    fn fails_without_formats() {
+        assert!(matches!(
+            choose_surface_format(&[], &preferred_surface_formats(ColorOutput::Srgb)),
+            Err(GraphicsError::NoSurfaceFormats)
+        ));
+    }
+
+    #[test]
+    fn wide_gamut_output_follows_preference_order() {
+        let display_p3 = surface_format(
+            vk::Format::A2R10G10B10_UNORM_PACK32,
+            vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+        );
+        let hdr10 = surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        );
+        let ten_bit_srgb = surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        );
+        let eight_bit_srgb =
+            surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+
+        assert_eq!(
+            choose_surface_format(
+                &[eight_bit_srgb, ten_bit_srgb, hdr10, display_p3],
+                &preferred_surface_formats(ColorOutput::WideGamut)
+            )
+            .unwrap(),
+            display_p3
+        );
+        assert_eq!(
+            choose_surface_format(
+                &[eight_bit_srgb, ten_bit_srgb, hdr10],
+                &preferred_surface_formats(ColorOutput::WideGamut)
+            )
+            .unwrap(),
+            hdr10
+        );
+        assert_eq!(
+            choose_surface_format(
+                &[eight_bit_srgb, ten_bit_srgb],
+                &preferred_surface_formats(ColorOutput::WideGamut)
+            )
+            .unwrap(),
+            ten_bit_srgb
+        );
+        assert_eq!(
+            choose_surface_format(
+                &[eight_bit_srgb],
+                &preferred_surface_formats(ColorOutput::WideGamut)
+            )
+            .unwrap(),
+            eight_bit_srgb
+        );
+    }
+
+    #[test]
+    fn srgb_output_never_picks_wide_gamut_formats() {
+        let formats = [
+            surface_format(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+            ),
+            surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+        ];
+
+        assert_eq!(
+            choose_surface_format(&formats, &preferred_surface_formats(ColorOutput::Srgb)).unwrap(),
+            formats[1]
+        );
+    }
+
+    #[test]
+    fn output_transform_matches_wide_gamut_color_spaces() {
+        let transform = |format, color_space| {
+            OutputTransform::for_surface_format(surface_format(format, color_space))
+        };
+
+        assert_eq!(
+            transform(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT
+            ),
+            OutputTransform {
+                gamut: OutputGamut::DisplayP3,
+                transfer: OutputTransfer::Srgb,
+            }
+        );
+        assert_eq!(
+            transform(
+                vk::Format::A2R10G10B10_UNORM_PACK32,
+                vk::ColorSpaceKHR::HDR10_ST2084_EXT
+            ),
+            OutputTransform {
+                gamut: OutputGamut::Bt2020,
+                transfer: OutputTransfer::Pq,
+            }
+        );
+        assert_eq!(
+            transform(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR
+            ),
+            OutputTransform {
+                gamut: OutputGamut::Bt709,
+                transfer: OutputTransfer::Srgb,
+            }
+        );
+    }
+
+    #[test]
+    fn output_transform_leaves_encoding_to_srgb_formats() {
+        assert_eq!(
+            OutputTransform::for_surface_format(surface_format(
+                vk::Format::B8G8R8A8_SRGB,
+                vk::ColorSpaceKHR::SRGB_NONLINEAR
+            )),
+            OutputTransform::default()
+        );
+        assert_eq!(
+            OutputTransform::for_surface_format(surface_format(
+                vk::Format::R8G8B8A8_SRGB,
+                vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT
+            )),
+            OutputTransform {
+                gamut: OutputGamut::DisplayP3,
+                transfer: OutputTransfer::Linear,
+            }
+        );
+    }
+
+    #[test]
+    fn output_transform_specialization_data_is_gamut_then_transfer() {
+        let transform = OutputTransform {
+            gamut: OutputGamut::Bt2020,
+            transfer: OutputTransfer::Srgb,
+        };
+
+        let data = transform.specialization_data();
+        assert_eq!(i32::from_ne_bytes([data[0], data[1], data[2], data[3]]), 2);
+        assert_eq!(i32::from_ne_bytes([data[4], data[5], data[6], data[7]]), 1);
+    }
+
+    #[test]
+    fn caller_preferences_override_the_default_order() {
+        let eight_bit_srgb =
+            surface_format(vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+        let eight_bit_unorm = surface_format(
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        );
+        let hdr10 = surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        );
+        let formats = [eight_bit_srgb, eight_bit_unorm];
+
+        assert_eq!(
+            choose_surface_format(&formats, &[hdr10, eight_bit_unorm, eight_bit_srgb]).unwrap(),
+            eight_bit_unorm
+        );
+        assert_eq!(
+            choose_surface_format(&formats, &[hdr10]).unwrap(),
+            eight_bit_srgb
+        );
+        assert_eq!(
+            choose_surface_format(&formats, &[]).unwrap(),
+            eight_bit_srgb
+        );
+    }
+
+    #[test]
+    fn present_mode_prefers_immediate() {
+        assert_eq!(
+            choose_present_mode(&[
+                vk::PresentModeKHR::FIFO,
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::IMMEDIATE,
+            ]),
+            vk::PresentModeKHR::IMMEDIATE
+        );
+    }
+
+    #[test]
+    fn present_mode_falls_back_to_fifo() {
+        assert_eq!(
+            choose_present_mode(&[vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO]),
+            vk::PresentModeKHR::FIFO
+        );
+        assert_eq!(
+            choose_present_mode(&[vk::PresentModeKHR::FIFO_RELAXED]),
+            vk::PresentModeKHR::FIFO
+        );
+        assert_eq!(choose_present_mode(&[]), vk::PresentModeKHR::FIFO);
+    }
+
+    #[test]
+    fn acquire_retries_timeouts_and_recreates_out_of_date_swapchains() {
+        let policy = AcquirePolicy::default();
+
+        assert_eq!(
+            acquire_recovery(&GraphicsError::AcquireTimeout, 1, &policy),
+            AcquireRecovery::Retry
+        );
+        assert_eq!(
+            acquire_recovery(&GraphicsError::SwapchainOutOfDate, 2, &policy),
+            AcquireRecovery::Recreate
+        );
+    }
+
+    #[test]
+    fn acquire_gives_up_after_max_attempts() {
+        let policy = AcquirePolicy {
+            timeout: Duration::from_millis(100),
+            max_attempts: 2,
+        };
+
+        assert_eq!(
+            acquire_recovery(&GraphicsError::AcquireTimeout, 2, &policy),
+            AcquireRecovery::GiveUp
+        );
+        assert_eq!(
+            acquire_recovery(&GraphicsError::SwapchainOutOfDate, 3, &policy),
+            AcquireRecovery::GiveUp
+        );
+    }
+
+    #[test]
+    fn acquire_gives_up_on_unrecoverable_errors() {
+        let policy = AcquirePolicy::default();
+
+        assert_eq!(
+            acquire_recovery(&GraphicsError::SurfaceLost, 1, &policy),
+            AcquireRecovery::GiveUp
+        );
+        assert_eq!(
+            acquire_recovery(&GraphicsError::DeviceLost, 1, &policy),
+            AcquireRecovery::GiveUp
+        );
+    }
+
+    #[test]
+    fn acquire_timeout_saturates_to_waiting_forever() {
+        let policy = AcquirePolicy {
+            timeout: Duration::from_secs(u64::MAX),
+            max_attempts: 1,
+        };
+
+        assert_eq!(policy.timeout_nanos(), u64::MAX);
+        assert_eq!(AcquirePolicy::default().timeout_nanos(), 1_000_000_000);
+    }
+
+    #[test]
+    fn extent_uses_current_extent_when_defined() {
+        let capabilities = capabilities((1280, 720), (1, 1), (4096, 4096), 2, 8);
+
+        assert_eq!(
+            choose_extent(&capabilities, 800, 600),
+            vk::Extent2D {
+                width: 1280,
+                height: 720,
+            }
+        );
+    }
+
+    #[test]
+    fn extent_uses_window_size_when_current_extent_is_undefined() {
+        let capabilities = capabilities((u32::MAX, u32::MAX), (1, 1), (4096, 4096), 2, 8);
+
+        assert_eq!(
+            choose_extent(&capabilities, 800, 600),
+            vk::Extent2D {
+                width: 800,
+                height: 600,
+            }
+        );
+    }
+
+    #[test]
+    fn extent_is_clamped_when_current_extent_is_undefined() {
+        let capabilities = capabilities((u32::MAX, u32::MAX), (64, 64), (1920, 1080), 2, 8);
+
+        assert_eq!(
+            choose_extent(&capabilities, 4000, 16),
+            vk::Extent2D {
+                width: 1920,
+                height: 64,
+            }
+        );
+        assert_eq!(
+            choose_extent(&capabilities, 0, 5000),
+            vk::Extent2D {
+                width: 64,
+                height: 1080,
+            }
+        );
+    }
+
+    #[test]
+    fn extent_can_be_zero_sized_when_minimized() {
+        // Windows reports a 0x0 current extent for minimized windows
+        let capabilities = capabilities((0, 0), (0, 0), (0, 0), 2, 8);
+
+        assert_eq!(
+            choose_extent(&capabilities, 800, 600),
+            vk::Extent2D {
+                width: 0,
+                height: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn image_count_is_one_more_than_minimum() {
+        assert_eq!(
+            choose_image_count(&capabilities((1, 1), (1, 1), (1, 1), 2, 8)),
+            3
+        );
+    }
+
+    #[test]
+    fn image_count_respects_maximum() {
+        assert_eq!(
+            choose_image_count(&capabilities((1, 1), (1, 1), (1, 1), 3, 3)),
+            3
+        );
+    }
+
+    #[test]
+    fn image_count_is_unbounded_when_maximum_is_zero() {
+        assert_eq!(
+            choose_image_count(&capabilities((1, 1), (1, 1), (1, 1), 4, 0)),
+            5
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use crate::graphics::buffers::Buffer;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::{Allocation, Allocator};
+use crate::graphics::queue_ownership::{OwnershipTransfer, QueueUse};
+use crate::graphics::upload::Uploader;
+use ash::{vk, Device, Instance};
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use std::{iter, ops::Range, path::Path, slice};
+
+// Color textures hold sRGB data, so sampling returns linear values for the shader to work with
+const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+// Sampled 2D image with a full mip chain, along with its memory, view, and sampler
+pub struct Texture {
+    image: vk::Image,
+    // Only held until destroy() drops it, which hands the memory back to the allocator
+    _memory: Option<Allocation>,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+}
+
+impl Texture {
+    // Loads a PNG or JPEG file and uploads it through uploader, blocking until the upload has finished
+    pub fn from_file(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        path: &Path,
+    ) -> Result<Texture, GraphicsError> {
+        let image = image::open(path)?;
+        let linear_blit = Texture::supports_linear_blit(instance, physical_device);
+        Texture::from_image(device, allocator, uploader, image, linear_blit)
+    }
+
+    // Same as from_file, for an encoded image already in memory (e.g. from include_bytes!)
+    pub fn from_memory(
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        bytes: &[u8],
+    ) -> Result<Texture, GraphicsError> {
+        let image = image::load_from_memory(bytes)?;
+        let linear_blit = Texture::supports_linear_blit(instance, physical_device);
+        Texture::from_image(device, allocator, uploader, image, linear_blit)
+    }
+
+    // Whether the mip chain can be generated on the GPU, which needs linearly filtered blits from and to the format
+    pub fn supports_linear_blit(instance: &Instance, physical_device: vk::PhysicalDevice) -> bool {
+        let properties = unsafe {
+            instance.get_physical_device_format_properties(physical_device, TEXTURE_FORMAT)
+        };
+
+        properties.optimal_tiling_features.contains(
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
+    // View to bind in a combined image sampler descriptor, in SHADER_READ_ONLY_OPTIMAL layout
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    // Size of mip level 0
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    // Destroys the sampler, view, and image and frees its memory - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        self.sampler = vk::Sampler::null();
+        self.view = vk::ImageView::null();
+        self.image = vk::Image::null();
+        self._memory = None;
+    }
+
+    fn from_image(
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        image: DynamicImage,
+        linear_blit: bool,
+    ) -> Result<Texture, GraphicsError> {
+        let pixels = image.to_rgba8();
+        let (width, height) = pixels.dimensions();
+
+        let mut texture = Texture {
+            image: vk::Image::null(),
+            _memory: None,
+            view: vk::ImageView::null(),
+            sampler: vk::Sampler::null(),
+            extent: vk::Extent2D { width, height },
+            mip_levels: mip_level_count(width, height),
+        };
+
+        // Destroying null handles does nothing, so whatever was created before a failure can be cleaned up as a whole
+        match texture.create(device, allocator, uploader, &pixels, linear_blit) {
+            Ok(()) => Ok(texture),
+            Err(error) => {
+                texture.destroy(device);
+                Err(error)
+            }
+        }
+    }
+
+    // Creates the image, uploads pixels into it along with the rest of the mip chain, then creates the view and
+    // sampler
+    fn create(
+        &mut self,
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        pixels: &RgbaImage,
+        linear_blit: bool,
+    ) -> Result<(), GraphicsError> {
+        // Each mip level is blitted from the one before, so the image is a transfer source as well
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(TEXTURE_FORMAT)
+            .extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            })
+            .mip_levels(self.mip_levels)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST
+                    | vk::ImageUsageFlags::SAMPLED,
+            )
+            .sharing_mode(uploader.sharing().sharing_mode())
+            .queue_family_indices(uploader.sharing().queue_family_indices())
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.image = unsafe { device.create_image(&image_info, None)? };
+
+        self._memory = Some(allocator.allocate_image(
+            device,
+            self.image,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?);
+
+        self.upload(device, allocator, uploader, pixels, linear_blit)?;
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(TEXTURE_FORMAT)
+            .subresource_range(Texture::subresource_range(0..self.mip_levels));
+
+        self.view = unsafe { device.create_image_view(&view_info, None)? };
+
+        // Trilinear filtering, blending between the two nearest mip levels
+        // Anisotropic filtering is left off, as it needs a device feature that is not enabled
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::REPEAT)
+            .address_mode_v(vk::SamplerAddressMode::REPEAT)
+            .address_mode_w(vk::SamplerAddressMode::REPEAT)
+            .anisotropy_enable(false)
+            .max_anisotropy(1.0)
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .compare_enable(false)
+            .min_lod(0.0)
+            .max_lod(self.mip_levels as f32);
+
+        self.sampler = unsafe { device.create_sampler(&sampler_info, None)? };
+
+        Ok(())
+    }
+
+    // Copies pixels into mip level 0 through a staging buffer and fills in the other levels, leaving every level ready
+    // to sample
+    // With linear_blit the levels are generated on the GPU, and otherwise they are downsampled on the CPU and uploaded
+    // along with level 0
+    fn upload(
+        &self,
+        device: &Device,
+        allocator: &Allocator,
+        uploader: &Uploader,
+        pixels: &RgbaImage,
+        linear_blit: bool,
+    ) -> Result<(), GraphicsError> {
+        let downsampled = if linear_blit {
+            Vec::new()
+        } else {
+            downsample(pixels, self.mip_levels)
+        };
+
+        // Levels are packed one after another, each region pointing at its own
+        let mut data = Vec::new();
+        let mut regions = Vec::new();
+        for (mip_level, level) in iter::once(pixels).chain(downsampled.iter()).enumerate() {
+            regions.push(
+                vk::BufferImageCopy::builder()
+                    .buffer_offset(data.len() as vk::DeviceSize)
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: mip_level as u32,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: level.width(),
+                        height: level.height(),
+                        depth: 1,
+                    })
+                    .build(),
+            );
+            data.extend_from_slice(level.as_raw());
+        }
+
+        let mut staging_buffer = Buffer::new(
+            device,
+            allocator,
+            data.len() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        // Blits need a graphics queue, so with linear_blit the image is handed over still being written to, and
+        // otherwise it is handed over ready to sample
+        let (layout, first_use) = if linear_blit {
+            (
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                QueueUse {
+                    family: uploader.graphics_family_index(),
+                    stage: vk::PipelineStageFlags::TRANSFER,
+                    access: vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE,
+                },
+            )
+        } else {
+            (
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                QueueUse {
+                    family: uploader.graphics_family_index(),
+                    stage: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    access: vk::AccessFlags::SHADER_READ,
+                },
+            )
+        };
+
+        let transfer = OwnershipTransfer::image(
+            uploader.sharing(),
+            self.image,
+            Texture::subresource_range(0..self.mip_levels),
+            [vk::ImageLayout::TRANSFER_DST_OPTIMAL, layout],
+            QueueUse {
+                family: uploader.transfer_family_index(),
+                stage: vk::PipelineStageFlags::TRANSFER,
+                access: vk::AccessFlags::TRANSFER_WRITE,
+            },
+            first_use,
+        );
+
+        staging_buffer.write(&data);
+        let result = uploader.submit(
+            device,
+            |command_buffer| {
+                // Previous contents are discarded, as every level is about to be overwritten
+                self.transition_layout(
+                    device,
+                    command_buffer,
+                    0..self.mip_levels,
+                    (
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ),
+                    (vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE),
+                    (
+                        vk::PipelineStageFlags::TOP_OF_PIPE,
+                        vk::PipelineStageFlags::TRANSFER,
+                    ),
+                );
+
+                unsafe {
+                    device.cmd_copy_buffer_to_image(
+                        command_buffer,
+                        staging_buffer.handle(),
+                        self.image,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &regions,
+                    )
+                };
+
+                transfer.record_release(device, command_buffer);
+            },
+            |command_buffer| {
+                transfer.record_acquire(device, command_buffer);
+
+                if linear_blit {
+                    self.generate_mipmaps(device, command_buffer);
+                }
+            },
+        );
+
+        // The upload is waited on, so the staging buffer is no longer in use either way
+        staging_buffer.destroy(device);
+
+        result
+    }
+
+    // Records blits filling each mip level from the one before, starting from level 0
+    // Every level must be in TRANSFER_DST_OPTIMAL, and each is moved to SHADER_READ_ONLY_OPTIMAL once it is no
+    // longer needed as a blit source
+    fn generate_mipmaps(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        let mut width = self.extent.width as i32;
+        let mut height = self.extent.height as i32;
+
+        for mip_level in 1..self.mip_levels {
+            let next_width = (width / 2).max(1);
+            let next_height = (height / 2).max(1);
+
+            self.transition_layout(
+                device,
+                command_buffer,
+                mip_level - 1..mip_level,
+                (
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ),
+                (
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::TRANSFER_READ,
+                ),
+                (
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                ),
+            );
+
+            let blit = vk::ImageBlit {
+                src_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: mip_level - 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                src_offsets: [
+                    vk::Offset3D { x: 0, y: 0, z: 0 },
+                    vk::Offset3D {
+                        x: width,
+                        y: height,
+                        z: 1,
+                    },
+                ],
+                dst_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                dst_offsets: [
+                    vk::Offset3D { x: 0, y: 0, z: 0 },
+                    vk::Offset3D {
+                        x: next_width,
+                        y: next_height,
+                        z: 1,
+                    },
+                ],
+            };
+
+            unsafe {
+                device.cmd_blit_image(
+                    command_buffer,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    slice::from_ref(&blit),
+                    vk::Filter::LINEAR,
+                )
+            };
+
+            self.transition_layout(
+                device,
+                command_buffer,
+                mip_level - 1..mip_level,
+                (
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ),
+                (vk::AccessFlags::TRANSFER_READ, vk::AccessFlags::SHADER_READ),
+                (
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                ),
+            );
+
+            width = next_width;
+            height = next_height;
+        }
+
+        // The last level is only ever blitted to
+        self.transition_layout(
+            device,
+            command_buffer,
+            self.mip_levels - 1..self.mip_levels,
+            (
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            ),
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+            ),
+        );
+    }
+
+    // Records a barrier moving mip_levels of the image between the (old, new) layouts, with the access masks and
+    // stages it waits on and blocks given as (source, destination) pairs
+    fn transition_layout(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        mip_levels: Range<u32>,
+        layouts: (vk::ImageLayout, vk::ImageLayout),
+        access_masks: (vk::AccessFlags, vk::AccessFlags),
+        stages: (vk::PipelineStageFlags, vk::PipelineStageFlags),
+    ) {
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(layouts.0)
+            .new_layout(layouts.1)
+            .src_access_mask(access_masks.0)
+            .dst_access_mask(access_masks.1)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(Texture::subresource_range(mip_levels));
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                stages.0,
+                stages.1,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                slice::from_ref(&barrier),
+            )
+        };
+    }
+
+    // The given mip levels of the texture's only layer
+    fn subresource_range(mip_levels: Range<u32>) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: mip_levels.start,
+            level_count: mip_levels.end - mip_levels.start,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+}
+
+// Number of levels in a full mip chain, halving the larger side until it reaches 1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Levels 1 and up of the mip chain for base, each half the size of the one before, for when the GPU can't blit them
+fn downsample(base: &RgbaImage, mip_levels: u32) -> Vec<RgbaImage> {
+    let mut levels: Vec<RgbaImage> = Vec::new();
+
+    for _ in 1..mip_levels {
+        let previous = levels.last().unwrap_or(base);
+        let level = image::imageops::resize(
+            previous,
+            (previous.width() / 2).max(1),
+            (previous.height() / 2).max(1),
+            FilterType::Triangle,
+        );
+        levels.push(level);
+    }
+
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_halves_the_larger_side_down_to_one() {
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_level_count(20, 512), 10);
+        assert_eq!(mip_level_count(1, 1), 1);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::graphics::buffers::Buffer;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::memory::Allocator;
+use crate::graphics::texture::Texture;
+use ash::{vk, Device};
+use cgmath::{Matrix4, PerspectiveFov, Rad, SquareMatrix};
+use std::{mem, slice};
+
+// Converts cgmath's OpenGL style clip space (y up, depth -1 to 1) to Vulkan's (y down, depth 0 to 1)
+#[rustfmt::skip]
+const OPENGL_TO_VULKAN: Matrix4<f32> = Matrix4::new(
+    1.0,  0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0,  0.0, 0.5, 0.0,
+    0.0,  0.0, 0.5, 1.0,
+);
+
+// Transforms read by the vertex shader from the uniform buffer at set 0, binding 0
+// Matrices are column major, matching both cgmath and the shader's default layout
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniformBufferObject {
+    pub model: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub proj: Matrix4<f32>,
+}
+
+// Identity transforms, which draw vertices straight into clip space
+impl Default for UniformBufferObject {
+    fn default() -> UniformBufferObject {
+        UniformBufferObject {
+            model: Matrix4::identity(),
+            view: Matrix4::identity(),
+            proj: Matrix4::identity(),
+        }
+    }
+}
+
+// Perspective projection for Vulkan's clip space, with fovy the vertical field of view and aspect width / height
+pub fn perspective(fovy: Rad<f32>, aspect: f32, near: f32, far: f32) -> Matrix4<f32> {
+    OPENGL_TO_VULKAN
+        * Matrix4::from(PerspectiveFov {
+            fovy,
+            aspect,
+            near,
+            far,
+        })
+}
+
+// A uniform buffer and descriptor set for each frame in flight, so a frame's transforms can be written while the
+// previous frames are still being drawn with theirs
+// Each set holds the uniform buffer at binding 0 and the texture sampled by the fragment shader at binding 1
+pub struct UniformBuffers {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    buffers: Vec<Buffer>,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+}
+
+impl UniformBuffers {
+    pub fn new(
+        device: &Device,
+        allocator: &Allocator,
+        frame_count: usize,
+        texture: &Texture,
+    ) -> Result<UniformBuffers, GraphicsError> {
+        let layout_bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        ];
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+
+        let descriptor_set_layout =
+            unsafe { device.create_descriptor_set_layout(&layout_info, None)? };
+
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: frame_count as u32,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: frame_count as u32,
+            },
+        ];
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(frame_count as u32);
+
+        let descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None)? };
+
+        let mut uniform_buffers = UniformBuffers {
+            descriptor_set_layout,
+            descriptor_pool,
+            buffers: Vec::with_capacity(frame_count),
+            descriptor_sets: Vec::new(),
+        };
+
+        // Written every frame, so kept in host visible memory rather than staged
+        for _ in 0..frame_count {
+            let buffer = Buffer::new(
+                device,
+                allocator,
+                mem::size_of::<UniformBufferObject>() as vk::DeviceSize,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+
+            match buffer {
+                Ok(buffer) => uniform_buffers.buffers.push(buffer),
+                Err(error) => {
+                    uniform_buffers.destroy(device);
+                    return Err(error);
+                }
+            }
+        }
+
+        let set_layouts = vec![descriptor_set_layout; frame_count];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(&set_layouts);
+
+        uniform_buffers.descriptor_sets =
+            match unsafe { device.allocate_descriptor_sets(&allocate_info) } {
+                Ok(descriptor_sets) => descriptor_sets,
+                Err(result) => {
+                    uniform_buffers.destroy(device);
+                    return Err(result.into());
+                }
+            };
+
+        // Every frame samples the same texture
+        let image_info = vk::DescriptorImageInfo::builder()
+            .sampler(texture.sampler())
+            .image_view(texture.view())
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        for (buffer, descriptor_set) in uniform_buffers
+            .buffers
+            .iter()
+            .zip(uniform_buffers.descriptor_sets.iter())
+        {
+            let buffer_info = vk::DescriptorBufferInfo::builder()
+                .buffer(buffer.handle())
+                .offset(0)
+                .range(buffer.size());
+
+            let writes = [
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(slice::from_ref(&buffer_info))
+                    .build(),
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(slice::from_ref(&image_info))
+                    .build(),
+            ];
+
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+
+        Ok(uniform_buffers)
+    }
+
+    // Layout of every descriptor set, for building pipeline layouts
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    // Descriptor set to bind at set 0 when drawing frame
+    pub fn descriptor_set(&self, frame: usize) -> vk::DescriptorSet {
+        self.descriptor_sets[frame]
+    }
+
+    // Writes the transforms for frame, which must not still be in use by the GPU
+    pub fn update(&mut self, frame: usize, ubo: &UniformBufferObject) {
+        self.buffers[frame].write(slice::from_ref(ubo));
+    }
+
+    // Destroys the buffers, pool, and layout - the pool frees the descriptor sets, so this must be called before the
+    // device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.destroy(device);
+        }
+        self.buffers.clear();
+        self.descriptor_sets.clear();
+
+        unsafe {
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        self.descriptor_pool = vk::DescriptorPool::null();
+        self.descriptor_set_layout = vk::DescriptorSetLayout::null();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector4};
+
+    fn project(point: [f32; 3]) -> Vector4<f32> {
+        let clip = perspective(Deg(90.0).into(), 1.0, 0.1, 10.0)
+            * Vector4::new(point[0], point[1], point[2], 1.0);
+        clip / clip.w
+    }
+
+    #[test]
+    fn uniform_buffer_object_matches_the_shader_block() {
+        // Three column major mat4s at offsets 0, 64, and 128
+        assert_eq!(mem::size_of::<UniformBufferObject>(), 192);
+    }
+
+    #[test]
+    fn perspective_maps_depth_to_zero_to_one() {
+        assert!((project([0.0, 0.0, -0.1]).z - 0.0).abs() < 1e-5);
+        assert!((project([0.0, 0.0, -10.0]).z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn perspective_flips_y_for_vulkan() {
+        // Up in view space is towards the top of the framebuffer, which is -y in Vulkan
+        assert!(project([0.0, 1.0, -2.0]).y < 0.0);
+        assert!(project([1.0, 0.0, -2.0]).x > 0.0);
+    }
+}
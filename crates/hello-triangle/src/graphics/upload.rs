@@ -0,0 +1,272 @@
+use crate::graphics::commands::CommandPool;
+use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::queue_ownership::QueueSharing;
+use ash::{vk, Device};
+use std::slice;
+
+// Queue family for uploads that can't draw, preferring one that only does transfers (usually backed by a DMA engine)
+// over one that can also compute
+pub fn find_transfer_family(queue_families: &[vk::QueueFamilyProperties]) -> Option<usize> {
+    let find = |excluded: vk::QueueFlags| {
+        queue_families.iter().position(|family| {
+            family.queue_count > 0
+                && family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !family.queue_flags.intersects(excluded)
+        })
+    };
+
+    find(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+        .or_else(|| find(vk::QueueFlags::GRAPHICS))
+}
+
+// Dedicated transfer queue along with a command pool for it
+struct TransferQueue {
+    family_index: u32,
+    queue: vk::Queue,
+    command_pool: CommandPool,
+}
+
+// Submits staging copies for buffers and textures, through a dedicated transfer queue when the device has one so they
+// run alongside rendering instead of in front of it
+// Copies are handed to the graphics queue with semaphores and queue family ownership transfers, and without a transfer
+// queue everything goes through the graphics queue instead
+pub struct Uploader {
+    graphics_family_index: u32,
+    graphics_queue: vk::Queue,
+    graphics_command_pool: CommandPool,
+    transfer: Option<TransferQueue>,
+    // Sharing uploaded resources are created with, which decides whether they need ownership transfers
+    sharing: QueueSharing,
+}
+
+impl Uploader {
+    // transfer holds the family index and queue of the dedicated transfer queue, if there is one
+    pub fn new(
+        device: &Device,
+        graphics_family_index: u32,
+        graphics_queue: vk::Queue,
+        transfer: Option<(u32, vk::Queue)>,
+        sharing: QueueSharing,
+    ) -> Result<Uploader, GraphicsError> {
+        let mut graphics_command_pool = CommandPool::new(device, graphics_family_index, 0)?;
+
+        let transfer = match transfer {
+            Some((family_index, queue)) => match CommandPool::new(device, family_index, 0) {
+                Ok(command_pool) => Some(TransferQueue {
+                    family_index,
+                    queue,
+                    command_pool,
+                }),
+                Err(error) => {
+                    graphics_command_pool.destroy(device);
+                    return Err(error);
+                }
+            },
+            None => None,
+        };
+
+        Ok(Uploader {
+            graphics_family_index,
+            graphics_queue,
+            graphics_command_pool,
+            transfer,
+            sharing,
+        })
+    }
+
+    // Family the copies recorded by submit() run on
+    pub fn transfer_family_index(&self) -> u32 {
+        self.transfer
+            .as_ref()
+            .map_or(self.graphics_family_index, |transfer| transfer.family_index)
+    }
+
+    // Family uploaded resources end up owned by
+    pub fn graphics_family_index(&self) -> u32 {
+        self.graphics_family_index
+    }
+
+    // Whether copies run on a dedicated transfer queue
+    pub fn has_transfer_queue(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    // Sharing to create uploaded buffers and images with, and to build their OwnershipTransfer barriers from
+    pub fn sharing(&self) -> &QueueSharing {
+        &self.sharing
+    }
+
+    // Records copy for the transfer queue and acquire for the graphics queue, submits them, and waits for both
+    // copy should end by releasing what it wrote and acquire should start by acquiring it, as OwnershipTransfer
+    // records, and acquire can then go on with work the transfer queue can't do, such as blits
+    // Only this upload is waited on, so frames already queued on the graphics queue keep running
+    pub fn submit<C, A>(&self, device: &Device, copy: C, acquire: A) -> Result<(), GraphicsError>
+    where
+        C: FnOnce(vk::CommandBuffer),
+        A: FnOnce(vk::CommandBuffer),
+    {
+        let transfer = match &self.transfer {
+            Some(transfer) => transfer,
+            // Both halves go in one command buffer, where the acquire is an ordinary barrier after the copies
+            None => {
+                return self.graphics_command_pool.submit_once(
+                    device,
+                    self.graphics_queue,
+                    |command_buffer| {
+                        copy(command_buffer);
+                        acquire(command_buffer);
+                    },
+                )
+            }
+        };
+
+        let semaphore =
+            unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)? };
+        let fence = match unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) } {
+            Ok(fence) => fence,
+            Err(result) => {
+                unsafe { device.destroy_semaphore(semaphore, None) };
+                return Err(result.into());
+            }
+        };
+
+        let mut command_buffers = (vk::CommandBuffer::null(), vk::CommandBuffer::null());
+        let result = self.submit_to_transfer_queue(
+            device,
+            transfer,
+            (semaphore, fence),
+            &mut command_buffers,
+            copy,
+            acquire,
+        );
+
+        // A failure part way through can leave the copy running, so it has to finish before anything is freed
+        if result.is_err() {
+            unsafe { device.queue_wait_idle(transfer.queue).ok() };
+        }
+
+        if command_buffers.0 != vk::CommandBuffer::null() {
+            transfer.command_pool.free_once(device, command_buffers.0);
+        }
+        if command_buffers.1 != vk::CommandBuffer::null() {
+            self.graphics_command_pool
+                .free_once(device, command_buffers.1);
+        }
+        unsafe {
+            device.destroy_fence(fence, None);
+            device.destroy_semaphore(semaphore, None);
+        }
+
+        result
+    }
+
+    // Destroys the command pools - must be called before the device is destroyed
+    pub fn destroy(&mut self, device: &Device) {
+        self.graphics_command_pool.destroy(device);
+        if let Some(transfer) = &mut self.transfer {
+            transfer.command_pool.destroy(device);
+        }
+    }
+
+    // Records and submits both halves of an upload, with the graphics submission waiting on semaphore for the copy and
+    // signalling fence once done
+    // The command buffers are stored in command_buffers as they are allocated, for submit() to free
+    fn submit_to_transfer_queue<C, A>(
+        &self,
+        device: &Device,
+        transfer: &TransferQueue,
+        (semaphore, fence): (vk::Semaphore, vk::Fence),
+        command_buffers: &mut (vk::CommandBuffer, vk::CommandBuffer),
+        copy: C,
+        acquire: A,
+    ) -> Result<(), GraphicsError>
+    where
+        C: FnOnce(vk::CommandBuffer),
+        A: FnOnce(vk::CommandBuffer),
+    {
+        command_buffers.0 = transfer.command_pool.begin_once(device)?;
+        copy(command_buffers.0);
+        unsafe { device.end_command_buffer(command_buffers.0)? };
+
+        command_buffers.1 = self.graphics_command_pool.begin_once(device)?;
+        acquire(command_buffers.1);
+        unsafe { device.end_command_buffer(command_buffers.1)? };
+
+        let transfer_submit_info = vk::SubmitInfo::builder()
+            .command_buffers(slice::from_ref(&command_buffers.0))
+            .signal_semaphores(slice::from_ref(&semaphore));
+
+        // The acquire barriers say which stages actually wait, so the semaphore can hold back everything
+        let wait_stage = vk::PipelineStageFlags::ALL_COMMANDS;
+        let graphics_submit_info = vk::SubmitInfo::builder()
+            .wait_semaphores(slice::from_ref(&semaphore))
+            .wait_dst_stage_mask(slice::from_ref(&wait_stage))
+            .command_buffers(slice::from_ref(&command_buffers.1));
+
+        unsafe {
+            device.queue_submit(transfer.queue, &[*transfer_submit_info], vk::Fence::null())?;
+            device.queue_submit(self.graphics_queue, &[*graphics_submit_info], fence)?;
+            device.wait_for_fences(&[fence], true, u64::MAX)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn families(flags: &[vk::QueueFlags]) -> Vec<vk::QueueFamilyProperties> {
+        flags
+            .iter()
+            .map(|&queue_flags| vk::QueueFamilyProperties {
+                queue_flags,
+                queue_count: 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prefers_transfer_only_families() {
+        let families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::TRANSFER | vk::QueueFlags::SPARSE_BINDING,
+        ]);
+
+        assert_eq!(find_transfer_family(&families), Some(2));
+    }
+
+    #[test]
+    fn falls_back_to_compute_families() {
+        let families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+        ]);
+
+        assert_eq!(find_transfer_family(&families), Some(1));
+    }
+
+    #[test]
+    fn graphics_families_are_never_used() {
+        let families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::TRANSFER,
+        ]);
+
+        assert_eq!(find_transfer_family(&families), None);
+    }
+
+    #[test]
+    fn families_without_queues_are_skipped() {
+        let mut families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::TRANSFER,
+        ]);
+        families[1].queue_count = 0;
+
+        assert_eq!(find_transfer_family(&families), None);
+    }
+}
@@ -1,31 +1,119 @@
 pub use crate::graphics::graphics_errors::GraphicsError;
+use crate::graphics::buffers::Vertex;
+use crate::graphics::commands::CommandPool;
+use crate::graphics::compute;
+use crate::graphics::debug::{self, DebugMessenger};
+use crate::graphics::depth::DepthBuffer;
+use crate::graphics::device_selection::{self, DeviceCandidate, DeviceSelection};
+use crate::graphics::frame_sync::FrameSync;
+use crate::graphics::letterbox::Letterbox;
+use crate::graphics::memory::Allocator;
+use crate::graphics::model::{Mesh, Model};
+use crate::graphics::msaa::{self, ColorTarget};
+use crate::graphics::offscreen::OffscreenTarget;
+use crate::graphics::pipeline::{GraphicsPipeline, PipelineSettings};
+use crate::graphics::queue_ownership::{QueueSharing, SharingPolicy};
+use crate::graphics::render_pass::RenderPass;
+use crate::graphics::shaders::ShaderCode;
+#[cfg(feature = "hot-reload")]
+use crate::graphics::shaders::{ShaderReload, ShaderSources};
+use crate::graphics::swapchain::{Swapchain, SwapchainSupportDetails};
+use crate::graphics::swapchain_config::{self, AcquirePolicy, AcquireRecovery, OutputTransform};
+use crate::graphics::texture::Texture;
+use crate::graphics::uniforms::{UniformBufferObject, UniformBuffers};
+use crate::graphics::upload::{self, Uploader};
 use ash::{
-    extensions::khr::{Surface, Swapchain},
-    util, vk, Device, Entry, Instance,
+    extensions::{
+        ext::DebugUtils,
+        khr::{Surface, Swapchain as SwapchainLoader},
+    },
+    vk, Device, Entry, Instance,
 };
 use std::{
     ffi::{CStr, CString},
-    io::Cursor,
-    vec::Vec,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
     slice,
+    vec::Vec,
 };
 use winit::window::Window;
 
-const BAD_ERROR: &str = "Something went incredibly wrong!";
+// Color the swapchain images are cleared to at the start of each frame
+const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// Triangle drawn when no model is configured, in model space with y up and red, green, and blue corners
+const TRIANGLE_VERTICES: [Vertex; 3] = [
+    Vertex {
+        pos: [0.0, 0.5, 0.0],
+        color: [1.0, 0.0, 0.0],
+        tex_coord: [0.5, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        pos: [0.5, -0.5, 0.0],
+        color: [0.0, 1.0, 0.0],
+        tex_coord: [1.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        pos: [-0.5, -0.5, 0.0],
+        color: [0.0, 0.0, 1.0],
+        tex_coord: [0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+    },
+];
+
+// Image sampled across the triangle, tinted by its vertex colors
+// Macro include_bytes! keeps it working wherever the executable is run from, like the shaders
+const TRIANGLE_TEXTURE: &[u8] = include_bytes!("textures/checkerboard.png");
+
+// Indices into TRIANGLE_VERTICES, so shapes sharing corners need not repeat vertices
+const TRIANGLE_INDICES: [u16; 3] = [0, 1, 2];
 
 pub struct VulkanBase {
-    _entry: Entry,
+    entry: Entry,
     instance: Instance,
+    // Only created when validation is enabled and the layer is installed
+    debug_messenger: Option<DebugMessenger>,
+    // Null when headless, along with surface being None
     surface_khr: vk::SurfaceKHR,
-    surface: Surface,
+    surface: Option<Surface>,
+    physical_device: vk::PhysicalDevice,
     device: Device,
-    swapchain_khr: vk::SwapchainKHR,
-    swapchain: Swapchain,
-    swapchain_image_views: Vec<vk::ImageView>,
-    render_pass: vk::RenderPass,
-    shader_modules: Vec<vk::ShaderModule>,
-    pipeline_layout: vk::PipelineLayout,
-    pipeline: vk::Pipeline,
+    // Memory for every buffer and image, freed once they all have been
+    allocator: Allocator,
+    target: RenderTarget,
+    depth_buffer: DepthBuffer,
+    // Only created when MSAA is on, as the multisampled image resolved into the swapchain
+    color_target: Option<ColorTarget>,
+    render_pass: RenderPass,
+    pipeline: GraphicsPipeline,
+    // Code the pipeline was built from, reused whenever it has to be rebuilt for a new render pass
+    shaders: ShaderCode,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    // Only present with hot reloading on and a working shader compiler
+    #[cfg(feature = "hot-reload")]
+    shader_reload: Option<ShaderReload>,
+    command_pool: CommandPool,
+    // Configured model, or the built in triangle
+    mesh: Mesh,
+    texture: Texture,
+    uniform_buffers: UniformBuffers,
+    // Transforms set by update_uniforms, written to the frame's uniform buffer once the GPU is done with it
+    uniforms: UniformBufferObject,
+    frame_sync: FrameSync,
+    graphics_family_index: u32,
+    queues: Vec<vk::Queue>,
+    present_family_index: u32,
+    present_queue: vk::Queue,
+    compute_family_index: u32,
+    compute_queue: vk::Queue,
+    // How resources are shared between the queue families in use, following VulkanSettings::queue_sharing
+    queue_sharing: QueueSharing,
+    // Uploads through the transfer queue when there is one
+    uploader: Uploader,
+    acquire_policy: AcquirePolicy,
+    letterbox: Option<Letterbox>,
 }
 
 pub struct WindowDimensions {
@@ -37,35 +125,277 @@ impl WindowDimensions {
     pub fn new(width: u32, height: u32) -> WindowDimensions {
         WindowDimensions { width, height }
     }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 }
 
-// Assumes graphics and presentation queue families are the same
-struct QueueFamilyIndices {
-    queue_family_index: u32,
+// Presets for VulkanSettings::surface_formats, expanded by swapchain_config::preferred_surface_formats()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorOutput {
+    // 8 bit sRGB
+    Srgb,
+    // 10 bit Display-P3 or BT.2020 where available, falling back to sRGB otherwise
+    WideGamut,
+}
+
+// Multisample anti-aliasing of the swapchain render pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MsaaSettings {
+    // Samples per pixel, usually 1, 2, 4, or 8 - the highest count the device supports up to this is used, and 1
+    // turns MSAA off
+    pub sample_count: u32,
+}
+
+impl Default for MsaaSettings {
+    fn default() -> MsaaSettings {
+        MsaaSettings { sample_count: 1 }
+    }
+}
+
+// Wavefront OBJ model to draw in place of the built in triangle, along with the PNG or JPEG texture it is sampled with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelFiles {
+    pub obj: PathBuf,
+    pub texture: PathBuf,
+}
+
+// Options chosen by the application when creating a VulkanBase
+#[derive(Clone, Debug)]
+pub struct VulkanSettings {
+    // Ranked (format, color space) pairs for the swapchain images, most preferred first
+    // The surface's first format is used when it supports none of them, and surface_format() reports what was chosen
+    pub surface_formats: Vec<vk::SurfaceFormatKHR>,
+    // Priority (0 to 1) of each queue to create in the graphics and presentation family - queue 0 is the main
+    // graphics queue, and any others can take upload or async compute work off it
+    // Requests beyond the family's queue count are dropped
+    pub queue_priorities: Vec<f32>,
+    // Enables VK_LAYER_KHRONOS_validation and prints its messages, if the layer is installed
+    pub validation: bool,
+    // Number of frames the CPU may record ahead of the GPU - more hides stalls at the cost of latency
+    pub frames_in_flight: usize,
+    // Physical device to use when several are suitable, overridden by the HELLO_TRIANGLE_DEVICE environment variable
+    pub device_selection: DeviceSelection,
+    pub msaa: MsaaSettings,
+    // Model to load at startup, with None drawing the built in triangle
+    pub model: Option<ModelFiles>,
+    // Compiles the GLSL shaders in the source tree at startup, and rebuilds the pipeline whenever they are saved
+    // The precompiled shaders are used instead while the sources are missing or fail to compile, and always without
+    // the hot-reload feature
+    pub shader_hot_reload: bool,
+    // Whether resources used from several queue families move between them with ownership transfers or are shared
+    // concurrently
+    pub queue_sharing: SharingPolicy,
+    // Push constant ranges declared in the pipeline layout, for shaders taking per-draw data such as model matrices
+    // The built in shaders use none, so this is only needed along with replacement shaders
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    // Draws at a fixed resolution centred in the window with bars around it, instead of filling the window
+    pub letterbox: Option<Letterbox>,
+    // Timeout and retries for acquiring swapchain images, which keep a stalled compositor from hanging draw_frame()
+    pub acquire_policy: AcquirePolicy,
+}
+
+impl Default for VulkanSettings {
+    fn default() -> VulkanSettings {
+        VulkanSettings {
+            surface_formats: swapchain_config::preferred_surface_formats(ColorOutput::Srgb),
+            queue_priorities: vec![1.0],
+            // Validation is slow, so it is only on by default in debug builds
+            validation: cfg!(debug_assertions),
+            frames_in_flight: 2,
+            device_selection: DeviceSelection::Best,
+            msaa: MsaaSettings::default(),
+            model: None,
+            // Like validation, this is only for development, where the sources are at hand
+            shader_hot_reload: cfg!(all(debug_assertions, feature = "hot-reload")),
+            queue_sharing: SharingPolicy::Exclusive,
+            push_constant_ranges: Vec::new(),
+            letterbox: None,
+            acquire_policy: AcquirePolicy::default(),
+        }
+    }
+}
+
+// What frames are drawn into - the window's swapchain, or a single offscreen image when headless
+enum RenderTarget {
+    Swapchain(Swapchain),
+    Offscreen(OffscreenTarget),
+}
+
+impl RenderTarget {
+    // The offscreen image has no surface to pick a color space from, so it reports the sRGB one its format encodes
+    fn format(&self) -> vk::SurfaceFormatKHR {
+        match self {
+            RenderTarget::Swapchain(swapchain) => swapchain.format(),
+            RenderTarget::Offscreen(target) => vk::SurfaceFormatKHR {
+                format: target.format(),
+                color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            },
+        }
+    }
+
+    fn extent(&self) -> vk::Extent2D {
+        match self {
+            RenderTarget::Swapchain(swapchain) => swapchain.extent(),
+            RenderTarget::Offscreen(target) => target.extent(),
+        }
+    }
+
+    // One view per image, each getting its own framebuffer and command buffer
+    fn image_views(&self) -> &[vk::ImageView] {
+        match self {
+            RenderTarget::Swapchain(swapchain) => swapchain.image_views(),
+            RenderTarget::Offscreen(target) => target.image_views(),
+        }
+    }
+
+    // Layout the render pass leaves the images in for whatever uses them next
+    fn final_layout(&self) -> vk::ImageLayout {
+        match self {
+            RenderTarget::Swapchain(_) => vk::ImageLayout::PRESENT_SRC_KHR,
+            RenderTarget::Offscreen(_) => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        }
+    }
+
+    fn destroy(&mut self, device: &Device) {
+        match self {
+            RenderTarget::Swapchain(swapchain) => swapchain.destroy(device),
+            RenderTarget::Offscreen(target) => target.destroy(device),
+        }
+    }
+}
+
+// Destroys what it holds with cleanup when dropped, unless into_inner() takes it back out first
+// VulkanBase::create() guards everything it creates, so an error partway through destroys whatever was created before
+// it, in reverse order as the guards go out of scope
+struct CleanupGuard<T, F: FnMut(&mut T)> {
+    value: Option<T>,
+    cleanup: F,
+}
+
+impl<T, F: FnMut(&mut T)> CleanupGuard<T, F> {
+    fn new(value: T, cleanup: F) -> CleanupGuard<T, F> {
+        CleanupGuard {
+            value: Some(value),
+            cleanup,
+        }
+    }
+
+    fn into_inner(mut self) -> T {
+        self.value
+            .take()
+            .expect("Guarded values are only taken out once!")
+    }
+}
+
+impl<T, F: FnMut(&mut T)> Deref for CleanupGuard<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+            .as_ref()
+            .expect("Guarded values are only taken out once!")
+    }
+}
+
+impl<T, F: FnMut(&mut T)> DerefMut for CleanupGuard<T, F> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+            .as_mut()
+            .expect("Guarded values are only taken out once!")
+    }
 }
 
-struct SwapchainSupportDetails {
-    capabilities: vk::SurfaceCapabilitiesKHR,
-    formats: Vec<vk::SurfaceFormatKHR>,
-    presentation_modes: Vec<vk::PresentModeKHR>,
+impl<T, F: FnMut(&mut T)> Drop for CleanupGuard<T, F> {
+    fn drop(&mut self) {
+        if let Some(value) = &mut self.value {
+            (self.cleanup)(value);
+        }
+    }
 }
 
-struct SwapchainDetails {
-    format: vk::SurfaceFormatKHR,
-    _presentation_mode: vk::PresentModeKHR,
-    extent: vk::Extent2D,
+// Graphics and presentation usually share a family, but some platforms only present from a separate one
+struct QueueFamilyIndices {
+    graphics_family_index: u32,
+    // Number of queues the graphics family supports
+    graphics_queue_count: u32,
+    present_family_index: u32,
+    // Family without graphics support to upload through, if the device has one
+    transfer_family_index: Option<u32>,
+    // Family to dispatch compute work on, which is the graphics family unless the device has async compute
+    compute_family_index: u32,
 }
 
 impl VulkanBase {
-    pub fn new(window: &Window, window_dimensions: &WindowDimensions) -> VulkanBase {
+    pub fn new(
+        window: &Window,
+        window_dimensions: &WindowDimensions,
+        settings: &VulkanSettings,
+    ) -> Result<VulkanBase, GraphicsError> {
+        VulkanBase::create(Some(window), window_dimensions, settings)
+    }
+
+    // Creates a VulkanBase without a window, drawing each frame into a width by height offscreen image that
+    // read_pixels() copies back to the CPU - for rendering in CI and comparing the output against reference images
+    // No surface or swapchain extensions are needed, so it also runs on devices and drivers unable to present
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        settings: &VulkanSettings,
+    ) -> Result<VulkanBase, GraphicsError> {
+        VulkanBase::create(None, &WindowDimensions::new(width, height), settings)
+    }
+
+    fn create(
+        window: Option<&Window>,
+        window_dimensions: &WindowDimensions,
+        settings: &VulkanSettings,
+    ) -> Result<VulkanBase, GraphicsError> {
         // Creates Entry and Instance
-        let (_entry, instance) = VulkanBase::create_instance(window);
+        // Everything created from here on is held in a CleanupGuard until the VulkanBase is built, so returning an
+        // error destroys it again
+        let (entry, instance, validation_enabled) = VulkanBase::create_instance(window, settings)?;
+        let instance = CleanupGuard::new(instance, |instance| unsafe {
+            instance.destroy_instance(None)
+        });
+
+        // Starts forwarding validation messages now that the instance exists
+        let debug_messenger = if validation_enabled {
+            Some(DebugMessenger::new(&entry, &instance)?)
+        } else {
+            None
+        };
+        let debug_messenger = CleanupGuard::new(debug_messenger, |debug_messenger| {
+            if let Some(debug_messenger) = debug_messenger {
+                debug_messenger.destroy();
+            }
+        });
 
-        // Creates vk::SurfaceKHR and Surface
-        let (surface_khr, surface) = VulkanBase::create_surface(&_entry, &instance, window);
+        // Creates vk::SurfaceKHR and Surface, unless headless
+        let (surface_khr, surface) = match window {
+            Some(window) => {
+                let (surface_khr, surface) = VulkanBase::create_surface(&entry, &instance, window)?;
+                (surface_khr, Some(surface))
+            }
+            None => (vk::SurfaceKHR::null(), None),
+        };
+        let surface = CleanupGuard::new(surface, move |surface| {
+            if let Some(surface) = surface {
+                unsafe { surface.destroy_surface(surface_khr, None) };
+            }
+        });
 
-        // Stores necessary device extensions
-        let device_extension_names_raw = [Swapchain::name().as_ptr()];
+        // Stores necessary device extensions, of which headless rendering needs none
+        let device_extension_names_raw = if surface.is_some() {
+            vec![SwapchainLoader::name().as_ptr()]
+        } else {
+            Vec::new()
+        };
 
         // Creates PhysicalDevice and stores queue family indices
         let (physical_device, queue_family_indices, swapchain_support_details) =
@@ -73,8 +403,13 @@ impl VulkanBase {
                 &instance,
                 &device_extension_names_raw,
                 &surface_khr,
-                &surface,
-            );
+                surface.as_ref(),
+                &settings.device_selection,
+            )?;
+
+        // Requests as many of the configured queues as the family supports, always at least one
+        let queue_priorities =
+            VulkanBase::choose_queue_priorities(&settings.queue_priorities, &queue_family_indices);
 
         // Creates Device
         let device = VulkanBase::create_logical_device(
@@ -82,62 +417,887 @@ impl VulkanBase {
             &physical_device,
             &device_extension_names_raw,
             &queue_family_indices,
-        );
+            &queue_priorities,
+        )?;
+        let device = CleanupGuard::new(device, |device| unsafe { device.destroy_device(None) });
+
+        // Creates a handle for each queue in the graphics queue family
+        let queues: Vec<vk::Queue> = (0..queue_priorities.len() as u32)
+            .map(|index| unsafe {
+                device.get_device_queue(queue_family_indices.graphics_family_index, index)
+            })
+            .collect();
 
-        // Creates vk::SwapchainKHR, Swapchain, and SwapchainDetails
-        let (swapchain_khr, swapchain, swapchain_details) = VulkanBase::create_swapchain(
-            &instance,
+        // Presents from the main graphics queue when the families are the same
+        let present_queue = if queue_family_indices.present_family_index
+            == queue_family_indices.graphics_family_index
+        {
+            queues[0]
+        } else {
+            unsafe { device.get_device_queue(queue_family_indices.present_family_index, 0) }
+        };
+
+        // Takes the first queue of the transfer family, which is requested on its own or as the present queue
+        let transfer_queue = queue_family_indices
+            .transfer_family_index
+            .map(|family_index| {
+                (family_index, unsafe {
+                    device.get_device_queue(family_index, 0)
+                })
+            });
+
+        // Dispatches from the main graphics queue when the families are the same
+        let compute_queue = if queue_family_indices.compute_family_index
+            == queue_family_indices.graphics_family_index
+        {
+            queues[0]
+        } else {
+            unsafe { device.get_device_queue(queue_family_indices.compute_family_index, 0) }
+        };
+
+        let mut sharing_families = vec![
+            queue_family_indices.graphics_family_index,
+            queue_family_indices.present_family_index,
+            queue_family_indices.compute_family_index,
+        ];
+        sharing_families.extend(queue_family_indices.transfer_family_index);
+        let queue_sharing = QueueSharing::new(settings.queue_sharing, &sharing_families);
+
+        // Uploads go through the transfer queue when there is one, handing everything over to the main graphics queue
+        let uploader = Uploader::new(
             &device,
-            window_dimensions,
-            &surface_khr,
-            &swapchain_support_details,
+            queue_family_indices.graphics_family_index,
+            queues[0],
+            transfer_queue,
+            queue_sharing.clone(),
+        )?;
+        let uploader = CleanupGuard::new(uploader, |uploader| uploader.destroy(&device));
+
+        // Uses the highest sample count up to the requested one that both color and depth attachments support
+        let samples = msaa::choose_sample_count(
+            settings.msaa.sample_count,
+            msaa::supported_sample_counts(&instance, physical_device),
+        );
+        if msaa::sample_count_value(samples) < settings.msaa.sample_count {
+            println!(
+                "Requested {}x MSAA but the device only supports {}x!",
+                settings.msaa.sample_count,
+                msaa::sample_count_value(samples)
+            );
+        }
+
+        // Suballocates buffers and images from a few large blocks of device memory
+        let allocator = Allocator::new(
+            unsafe { instance.get_physical_device_memory_properties(physical_device) },
+            &unsafe { instance.get_physical_device_properties(physical_device) }.limits,
         );
+        let allocator = CleanupGuard::new(allocator, |allocator| allocator.destroy(&device));
 
-        // Retreives available swapchain images
-        let swapchain_images = unsafe {
-            swapchain
-                .get_swapchain_images(swapchain_khr)
-                .expect(BAD_ERROR)
+        // Creates the swapchain and an image view for each of its images, or the image to draw into when headless
+        let target = match &swapchain_support_details {
+            Some(swapchain_support_details) => RenderTarget::Swapchain(Swapchain::new(
+                &instance,
+                &device,
+                &surface_khr,
+                swapchain_support_details,
+                window_dimensions,
+                [
+                    queue_family_indices.graphics_family_index,
+                    queue_family_indices.present_family_index,
+                ],
+                &settings.surface_formats,
+            )?),
+            None => RenderTarget::Offscreen(OffscreenTarget::new(
+                &device,
+                &allocator,
+                vk::Extent2D {
+                    width: window_dimensions.width(),
+                    height: window_dimensions.height(),
+                },
+            )?),
+        };
+        let target = CleanupGuard::new(target, |target| target.destroy(&device));
+
+        // Creates a depth buffer matching the swapchain
+        let depth_format = DepthBuffer::find_format(&instance, physical_device)?;
+        let depth_buffer =
+            DepthBuffer::new(&device, &allocator, depth_format, samples, target.extent())?;
+        let depth_buffer =
+            CleanupGuard::new(depth_buffer, |depth_buffer| depth_buffer.destroy(&device));
+
+        // Creates the multisampled color image to draw into when MSAA is on
+        let color_target = if samples == vk::SampleCountFlags::TYPE_1 {
+            None
+        } else {
+            Some(ColorTarget::new(
+                &device,
+                &allocator,
+                target.format().format,
+                samples,
+                target.extent(),
+            )?)
         };
+        let color_target = CleanupGuard::new(color_target, |color_target| {
+            if let Some(color_target) = color_target {
+                color_target.destroy(&device);
+            }
+        });
 
-        // Creates and stores an image view for each swapchain image
-        let swapchain_image_views =
-            VulkanBase::create_image_views(&device, &swapchain_images, &swapchain_details.format);
+        // Creates the render pass and a framebuffer for each swapchain image view
+        let render_pass = RenderPass::new(
+            &device,
+            &target.format(),
+            target.final_layout(),
+            target.image_views(),
+            &depth_buffer,
+            color_target.as_ref(),
+        )?;
+        let render_pass =
+            CleanupGuard::new(render_pass, |render_pass| render_pass.destroy(&device));
+
+        // Creates a command pool with a command buffer for each swapchain image
+        let command_pool = CommandPool::new(
+            &device,
+            queue_family_indices.graphics_family_index,
+            target.image_views().len(),
+        )?;
+        let command_pool =
+            CleanupGuard::new(command_pool, |command_pool| command_pool.destroy(&device));
+
+        // Uploads the model's texture, or the triangle's
+        let texture = match &settings.model {
+            Some(model_files) => Texture::from_file(
+                &instance,
+                physical_device,
+                &device,
+                &allocator,
+                &uploader,
+                &model_files.texture,
+            )?,
+            None => Texture::from_memory(
+                &instance,
+                physical_device,
+                &device,
+                &allocator,
+                &uploader,
+                TRIANGLE_TEXTURE,
+            )?,
+        };
+        let texture = CleanupGuard::new(texture, |texture| texture.destroy(&device));
 
-        // Creates a queue handle for the queue family (assumes both the graphics and presentation queue families are the same)
-        let _queue = unsafe { device.get_device_queue(queue_family_indices.queue_family_index, 0) };
+        // At least one frame is always in flight, and the uniform buffers and frame sync must agree on how many
+        let frames_in_flight = settings.frames_in_flight.max(1);
 
-        let render_pass = VulkanBase::create_render_pass(&device, &swapchain_details.format);
+        // Creates a uniform buffer and descriptor set for each frame in flight
+        let uniform_buffers = UniformBuffers::new(&device, &allocator, frames_in_flight, &texture)?;
+        let uniform_buffers = CleanupGuard::new(uniform_buffers, |uniform_buffers| {
+            uniform_buffers.destroy(&device)
+        });
 
-        // Creates shader modules, pipeline layout, and pipeline
-        let (shader_modules, pipeline_layout, pipeline) =
-            VulkanBase::create_graphics_pipeline(&device, &render_pass, &swapchain_details.extent);
+        // Compiles the shader sources when hot reloading, or reads the precompiled shaders
+        #[cfg(feature = "hot-reload")]
+        let (shaders, shader_reload) = VulkanBase::load_shaders(settings.shader_hot_reload)?;
+        #[cfg(not(feature = "hot-reload"))]
+        let shaders = VulkanBase::load_shaders(settings.shader_hot_reload)?;
 
-        VulkanBase {
-            _entry,
+        // Creates shader modules, pipeline layout, and pipeline
+        let pipeline = GraphicsPipeline::new(
+            &device,
+            &render_pass.handle(),
+            &target.extent(),
+            uniform_buffers.layout(),
+            &shaders,
+            &PipelineSettings {
+                samples: render_pass.samples(),
+                push_constant_ranges: &settings.push_constant_ranges,
+                output_transform: OutputTransform::for_surface_format(target.format()),
+            },
+        )?;
+        let pipeline = CleanupGuard::new(pipeline, |pipeline| pipeline.destroy(&device));
+
+        // Uploads the model, or the triangle, to device local vertex and index buffers
+        let mesh = match &settings.model {
+            Some(model_files) => Mesh::from_model(
+                &device,
+                &allocator,
+                &uploader,
+                &Model::from_file(&model_files.obj)?,
+            )?,
+            None => Mesh::new(
+                &device,
+                &allocator,
+                &uploader,
+                &TRIANGLE_VERTICES,
+                &TRIANGLE_INDICES,
+            )?,
+        };
+        let mesh = CleanupGuard::new(mesh, |mesh| mesh.destroy(&device));
+
+        // Creates the semaphores and fences for each frame in flight
+        let frame_sync = FrameSync::new(&device, frames_in_flight, target.image_views().len())?;
+        let frame_sync = CleanupGuard::new(frame_sync, |frame_sync| frame_sync.destroy(&device));
+
+        // Every fallible step is above, so from here the guards hand everything over to the VulkanBase, which
+        // destroys it on drop - no ? may come between these and building it
+        // Each guard's cleanup borrows device, so they are taken apart in reverse order of creation
+        let frame_sync = frame_sync.into_inner();
+        let mesh = mesh.into_inner();
+        let pipeline = pipeline.into_inner();
+        let uniform_buffers = uniform_buffers.into_inner();
+        let texture = texture.into_inner();
+        let command_pool = command_pool.into_inner();
+        let render_pass = render_pass.into_inner();
+        let color_target = color_target.into_inner();
+        let depth_buffer = depth_buffer.into_inner();
+        let target = target.into_inner();
+        let allocator = allocator.into_inner();
+        let uploader = uploader.into_inner();
+        let device = device.into_inner();
+        let surface = surface.into_inner();
+        let debug_messenger = debug_messenger.into_inner();
+        let instance = instance.into_inner();
+
+        Ok(VulkanBase {
+            entry,
             instance,
+            debug_messenger,
             surface_khr,
             surface,
+            physical_device,
             device,
-            swapchain_khr,
-            swapchain,
-            swapchain_image_views,
+            allocator,
+            target,
+            depth_buffer,
+            color_target,
             render_pass,
-            shader_modules,
-            pipeline_layout,
             pipeline,
+            shaders,
+            push_constant_ranges: settings.push_constant_ranges.clone(),
+            #[cfg(feature = "hot-reload")]
+            shader_reload,
+            command_pool,
+            mesh,
+            texture,
+            uniform_buffers,
+            uniforms: UniformBufferObject::default(),
+            frame_sync,
+            graphics_family_index: queue_family_indices.graphics_family_index,
+            queues,
+            present_family_index: queue_family_indices.present_family_index,
+            present_queue,
+            compute_family_index: queue_family_indices.compute_family_index,
+            compute_queue,
+            queue_sharing,
+            uploader,
+            acquire_policy: settings.acquire_policy,
+            letterbox: settings.letterbox,
+        })
+    }
+
+    // Fixed resolution drawn to instead of the whole window, if any
+    pub fn letterbox(&self) -> Option<&Letterbox> {
+        self.letterbox.as_ref()
+    }
+
+    // Format and color space of the swapchain images, which output passes must encode for
+    // Picked from VulkanSettings::surface_formats, so it can differ from the most preferred pair - headless rendering
+    // always uses offscreen::OFFSCREEN_FORMAT instead
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.target.format()
+    }
+
+    // None when headless
+    pub fn swapchain(&self) -> Option<&Swapchain> {
+        match &self.target {
+            RenderTarget::Swapchain(swapchain) => Some(swapchain),
+            RenderTarget::Offscreen(_) => None,
         }
     }
 
-    // Creates an ash Instance, which is a light wrapper around a vk::Instance
-    fn create_instance(window: &Window) -> (Entry, Instance) {
-        // Specifies extensions
-        let surface_extensions =
-            ash_window::enumerate_required_extensions(window).expect("Unsupported platform!");
-        let extension_names_raw = surface_extensions
+    // Image drawn into when headless, None when drawing to a window
+    pub fn offscreen_target(&self) -> Option<&OffscreenTarget> {
+        match &self.target {
+            RenderTarget::Swapchain(_) => None,
+            RenderTarget::Offscreen(target) => Some(target),
+        }
+    }
+
+    // Render pass and swapchain framebuffers, for command recording code to begin and end
+    pub fn render_pass(&self) -> &RenderPass {
+        &self.render_pass
+    }
+
+    // Triangle pipeline, which is rebuilt along with the swapchain so its handles should not be kept across resizes
+    pub fn pipeline(&self) -> &GraphicsPipeline {
+        &self.pipeline
+    }
+
+    // Sets the transforms used from the next draw_frame on
+    pub fn update_uniforms(&mut self, ubo: &UniformBufferObject) {
+        self.uniforms = *ubo;
+    }
+
+    // Records the command buffer for a swapchain image, returning it ready to submit
+    // The render pass is begun with the triangle pipeline and the current frame's uniforms bound and the viewport and
+    // scissor covering the swapchain, or the letterboxed area of it, and record issues the draws - the previous
+    // submission for image_index must have finished
+    pub fn record_frame<F: FnOnce(&Device, vk::CommandBuffer)>(
+        &self,
+        image_index: u32,
+        record: F,
+    ) -> Result<vk::CommandBuffer, GraphicsError> {
+        let extent = self.target.extent();
+        let scissor = match &self.letterbox {
+            Some(letterbox) => letterbox.fit(extent),
+            None => vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+        };
+
+        self.command_pool
+            .record(&self.device, image_index as usize, |command_buffer| {
+                // The render pass clears the whole image, so with a letterbox that clear draws the bars and the
+                // area inside them is cleared again to the background
+                match &self.letterbox {
+                    Some(letterbox) => {
+                        self.render_pass.begin(
+                            &self.device,
+                            command_buffer,
+                            image_index,
+                            letterbox.bar_color,
+                        );
+
+                        let clear_attachment = vk::ClearAttachment {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            color_attachment: 0,
+                            clear_value: vk::ClearValue {
+                                color: vk::ClearColorValue {
+                                    float32: CLEAR_COLOR,
+                                },
+                            },
+                        };
+                        let clear_rect = vk::ClearRect {
+                            rect: scissor,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        };
+                        unsafe {
+                            self.device.cmd_clear_attachments(
+                                command_buffer,
+                                slice::from_ref(&clear_attachment),
+                                slice::from_ref(&clear_rect),
+                            )
+                        };
+                    }
+                    None => self.render_pass.begin(
+                        &self.device,
+                        command_buffer,
+                        image_index,
+                        CLEAR_COLOR,
+                    ),
+                }
+
+                let viewport = vk::Viewport {
+                    x: scissor.offset.x as f32,
+                    y: scissor.offset.y as f32,
+                    width: scissor.extent.width as f32,
+                    height: scissor.extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                };
+
+                let descriptor_set = self
+                    .uniform_buffers
+                    .descriptor_set(self.frame_sync.current_frame());
+
+                unsafe {
+                    self.device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline.handle(),
+                    );
+                    self.device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline.layout(),
+                        0,
+                        &[descriptor_set],
+                        &[],
+                    );
+                    self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                    self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                }
+
+                record(&self.device, command_buffer);
+
+                self.render_pass.end(&self.device, command_buffer);
+            })
+    }
+
+    // Records the command buffer for a swapchain image to draw the model, or the triangle when none was configured
+    pub fn record_mesh(&self, image_index: u32) -> Result<vk::CommandBuffer, GraphicsError> {
+        self.record_frame(image_index, |device, command_buffer| {
+            self.mesh.draw(device, command_buffer)
+        })
+    }
+
+    // Draws and presents the mesh, recreating the swapchain for window_dimensions if it is out of date
+    // Blocks while frames_in_flight frames are already queued on the GPU
+    // Fails with AcquireTimeout or SwapchainOutOfDate when the acquire policy runs out of attempts, and SurfaceLost
+    // when the VulkanBase has to be recreated for a new surface
+    // When headless the mesh is drawn into the offscreen image instead, and window_dimensions is not used
+    pub fn draw_frame(
+        &mut self,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        let image_index = self.begin_frame(window_dimensions)?;
+
+        // The frame's fence has signalled, so its uniform buffer is free to overwrite
+        self.uniform_buffers
+            .update(self.frame_sync.current_frame(), &self.uniforms);
+
+        let command_buffer = self.record_mesh(image_index)?;
+
+        self.end_frame(image_index, command_buffer, window_dimensions)
+    }
+
+    // Waits until the next frame's resources are free and acquires the image to draw into, returning its index
+    fn begin_frame(&mut self, window_dimensions: &WindowDimensions) -> Result<u32, GraphicsError> {
+        self.frame_sync.wait_for_frame(&self.device)?;
+
+        // Every headless frame draws into the one image with the one command buffer
+        let image_index = match self.target {
+            RenderTarget::Swapchain(_) => {
+                // Nothing is submitted if acquiring fails, so the frame's fence is left signalled for the next attempt
+                let image_available = self.frame_sync.image_available();
+                self.acquire_next_image(image_available, window_dimensions)?
+            }
+            RenderTarget::Offscreen(_) => 0,
+        };
+
+        self.frame_sync.wait_for_image(&self.device, image_index)?;
+        Ok(image_index)
+    }
+
+    // Submits the frame's command buffer, then presents it and moves on to the next frame
+    fn end_frame(
+        &mut self,
+        image_index: u32,
+        command_buffer: vk::CommandBuffer,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        // There is nothing to acquire or present when headless, so the submission waits on and signals nothing
+        let presenting = self.swapchain().is_some();
+        let wait_semaphores = [self.frame_sync.image_available()];
+        // Only writing to the image has to wait for it to be acquired
+        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let signal_semaphores = [self.frame_sync.render_finished()];
+
+        let mut submit_info =
+            vk::SubmitInfo::builder().command_buffers(slice::from_ref(&command_buffer));
+        if presenting {
+            submit_info = submit_info
+                .wait_semaphores(&wait_semaphores)
+                .wait_dst_stage_mask(&wait_stages)
+                .signal_semaphores(&signal_semaphores);
+        }
+
+        self.frame_sync.reset_frame_fence(&self.device)?;
+
+        unsafe {
+            self.device.queue_submit(
+                self.queues[0],
+                slice::from_ref(&submit_info),
+                self.frame_sync.in_flight_fence(),
+            )?
+        };
+
+        // Suboptimal swapchains are still presented to, then recreated for the next frame
+        let recreate = match self.swapchain() {
+            Some(swapchain) => {
+                swapchain.present(self.present_queue, image_index, &signal_semaphores)?
+            }
+            None => false,
+        };
+
+        self.frame_sync.advance();
+
+        if recreate {
+            self.recreate_swapchain(window_dimensions)?;
+        }
+
+        Ok(())
+    }
+
+    // Copies the frame last drawn headless back to the CPU as tightly packed RGBA8 rows from top to bottom, waiting
+    // for it to finish drawing - draw_frame() must have been called at least once
+    // Fails with ReadbackUnsupported when drawing to a window, whose swapchain images can't be read back
+    pub fn read_pixels(&self) -> Result<Vec<u8>, GraphicsError> {
+        match &self.target {
+            RenderTarget::Swapchain(_) => Err(GraphicsError::ReadbackUnsupported),
+            // The copy is submitted after the frame on the same queue, so it waits for the frame on the GPU
+            RenderTarget::Offscreen(target) => target.read_pixels(
+                &self.device,
+                &self.allocator,
+                &self.command_pool,
+                self.queues[0],
+            ),
+        }
+    }
+
+    // Acquires the next swapchain image, retrying and recreating the swapchain as far as the acquire policy allows
+    fn acquire_next_image(
+        &mut self,
+        semaphore: vk::Semaphore,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<u32, GraphicsError> {
+        let mut attempts = 0;
+        loop {
+            let error = match self
+                .swapchain()
+                .ok_or(GraphicsError::Headless)?
+                .acquire_next_image(semaphore, self.acquire_policy.timeout_nanos())
+            {
+                Ok(image_index) => return Ok(image_index),
+                Err(error) => error,
+            };
+
+            attempts += 1;
+            match swapchain_config::acquire_recovery(&error, attempts, &self.acquire_policy) {
+                AcquireRecovery::Retry => {}
+                AcquireRecovery::Recreate => self.recreate_swapchain(window_dimensions)?,
+                AcquireRecovery::GiveUp => return Err(error),
+            }
+        }
+    }
+
+    // Rebuilds the swapchain for a new window size, along with the depth buffer, color target, and framebuffers that
+    // depend on it and the render pass and pipeline if its format changed - called on resize, or when acquiring or
+    // presenting reports the swapchain is out of date
+    // When headless the offscreen image is resized to window_dimensions instead
+    pub fn recreate_swapchain(
+        &mut self,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        unsafe { self.device.device_wait_idle()? };
+
+        match &mut self.target {
+            RenderTarget::Swapchain(swapchain) => {
+                let surface = self
+                    .surface
+                    .as_ref()
+                    .expect("Swapchains are only created along with a surface!");
+                let swapchain_support_details = SwapchainSupportDetails::query(
+                    &self.physical_device,
+                    &self.surface_khr,
+                    surface,
+                )?;
+
+                swapchain.recreate(
+                    &self.device,
+                    &self.surface_khr,
+                    &swapchain_support_details,
+                    window_dimensions,
+                )?;
+            }
+            RenderTarget::Offscreen(target) => target.recreate(
+                &self.device,
+                &self.allocator,
+                vk::Extent2D {
+                    width: window_dimensions.width(),
+                    height: window_dimensions.height(),
+                },
+            )?,
+        }
+
+        self.depth_buffer
+            .recreate(&self.device, &self.allocator, self.target.extent())?;
+
+        if let Some(color_target) = &mut self.color_target {
+            color_target.recreate(
+                &self.device,
+                &self.allocator,
+                self.target.format().format,
+                self.target.extent(),
+            )?;
+        }
+
+        let render_pass_recreated = self.render_pass.recreate(
+            &self.device,
+            &self.target.format(),
+            self.target.image_views(),
+            &self.depth_buffer,
+            self.color_target.as_ref(),
+        )?;
+
+        // The image count can change along with the swapchain
+        let image_count = self.target.image_views().len();
+        self.command_pool.resize(&self.device, image_count)?;
+        self.frame_sync.reset_images(image_count);
+
+        // Viewport and scissor are dynamic, so the pipeline only has to follow the render pass
+        if render_pass_recreated {
+            self.pipeline.destroy(&self.device);
+            self.pipeline = GraphicsPipeline::new(
+                &self.device,
+                &self.render_pass.handle(),
+                &self.target.extent(),
+                self.uniform_buffers.layout(),
+                &self.shaders,
+                &self.pipeline_settings(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Replaces a lost surface with a new one for window, then rebuilds the swapchain and everything sized to it
+    // Some drivers lose the surface when displays are reconfigured, which draw_frame() reports as SurfaceLost
+    // Failing to create the new surface, or finding the present family cannot use it, leaves the old surface and
+    // swapchain in place; failing to rebuild the swapchain after that leaves the VulkanBase without a usable one, and
+    // it has to be dropped and created again
+    // Fails with Headless without touching anything when there was never a surface
+    pub fn recreate_surface(
+        &mut self,
+        window: &Window,
+        window_dimensions: &WindowDimensions,
+    ) -> Result<(), GraphicsError> {
+        let surface = match &self.surface {
+            Some(surface) => surface,
+            None => return Err(GraphicsError::Headless),
+        };
+
+        unsafe { self.device.device_wait_idle()? };
+
+        // A present that failed with the surface may have left semaphores signalled that nothing will wait on
+        self.frame_sync.recreate_semaphores(&self.device)?;
+
+        let surface_khr = unsafe {
+            ash_window::create_surface(&self.entry, &self.instance, window, None)
+                .map_err(GraphicsError::SurfaceCreation)?
+        };
+
+        // Queues were picked for the old surface, so the new one is only usable if the same family can present to it
+        let present_supported = unsafe {
+            surface.get_physical_device_surface_support(
+                self.physical_device,
+                self.present_family_index,
+                surface_khr,
+            )
+        }
+        .inspect_err(|_| unsafe { surface.destroy_surface(surface_khr, None) })?;
+        if !present_supported {
+            unsafe { surface.destroy_surface(surface_khr, None) };
+            return Err(GraphicsError::PresentUnsupported);
+        }
+
+        // The swapchain belongs to the old surface, so both go before the new surface takes its place
+        self.target.destroy(&self.device);
+        unsafe { surface.destroy_surface(self.surface_khr, None) };
+        self.surface_khr = surface_khr;
+
+        self.recreate_swapchain(window_dimensions)
+    }
+
+    // Rebuilds the pipeline if hot reloading is on and a shader source changed, returning whether it was rebuilt
+    // Errors from compiling the sources or building the new pipeline leave the old pipeline in place, so the
+    // application can report them and keep drawing until the sources are fixed
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_shaders(&mut self) -> Result<bool, GraphicsError> {
+        let shaders = match self.shader_reload.as_mut().and_then(ShaderReload::poll) {
+            Some(shaders) => shaders?,
+            None => return Ok(false),
+        };
+
+        unsafe { self.device.device_wait_idle()? };
+
+        let pipeline = GraphicsPipeline::new(
+            &self.device,
+            &self.render_pass.handle(),
+            &self.target.extent(),
+            self.uniform_buffers.layout(),
+            &shaders,
+            &self.pipeline_settings(),
+        )?;
+
+        self.pipeline.destroy(&self.device);
+        self.pipeline = pipeline;
+        self.shaders = shaders;
+
+        Ok(true)
+    }
+
+    // Without the hot-reload feature the shaders never change after startup
+    #[cfg(not(feature = "hot-reload"))]
+    pub fn reload_shaders(&mut self) -> Result<bool, GraphicsError> {
+        Ok(false)
+    }
+
+    // Settings to rebuild the pipeline with, for the current render pass and target format
+    fn pipeline_settings(&self) -> PipelineSettings<'_> {
+        PipelineSettings {
+            samples: self.render_pass.samples(),
+            push_constant_ranges: &self.push_constant_ranges,
+            output_transform: OutputTransform::for_surface_format(self.target.format()),
+        }
+    }
+
+    // Family shared by every queue in queues()
+    pub fn graphics_family_index(&self) -> u32 {
+        self.graphics_family_index
+    }
+
+    // Queues created from VulkanSettings::queue_priorities, in the same order - index 0 is the main graphics queue
+    pub fn queues(&self) -> &[vk::Queue] {
+        &self.queues
+    }
+
+    pub fn present_family_index(&self) -> u32 {
+        self.present_family_index
+    }
+
+    // Queue to present swapchain images from, which is queues()[0] when the families are the same
+    pub fn present_queue(&self) -> vk::Queue {
+        self.present_queue
+    }
+
+    pub fn compute_family_index(&self) -> u32 {
+        self.compute_family_index
+    }
+
+    // Queue to dispatch compute work on, which is queues()[0] when the families are the same
+    // Results read by the graphics pass need a ComputeOutput acquire, and a semaphore when the queues differ
+    pub fn compute_queue(&self) -> vk::Queue {
+        self.compute_queue
+    }
+
+    // Sharing to create resources used across queue families with, and to build OwnershipTransfer barriers from
+    pub fn queue_sharing(&self) -> &QueueSharing {
+        &self.queue_sharing
+    }
+
+    // Family of the dedicated transfer queue, None if uploads go through the graphics queue
+    pub fn transfer_family_index(&self) -> Option<u32> {
+        if self.uploader.has_transfer_queue() {
+            Some(self.uploader.transfer_family_index())
+        } else {
+            None
+        }
+    }
+
+    // Uploads buffers and textures for use on the graphics queue
+    pub fn uploader(&self) -> &Uploader {
+        &self.uploader
+    }
+
+    // Shaders to build the pipeline from, along with the reloader when hot_reload is on
+    // Hot reloading falls back to the precompiled shaders rather than failing, as they are always available
+    #[cfg(feature = "hot-reload")]
+    fn load_shaders(hot_reload: bool) -> Result<(ShaderCode, Option<ShaderReload>), GraphicsError> {
+        if !hot_reload {
+            return Ok((ShaderCode::precompiled()?, None));
+        }
+
+        let shader_reload = match ShaderReload::new(ShaderSources::source_tree()) {
+            Ok(shader_reload) => shader_reload,
+            Err(error) => {
+                println!("{}, so shader hot reloading is off!", error);
+                return Ok((ShaderCode::precompiled()?, None));
+            }
+        };
+
+        match shader_reload.compile() {
+            Ok(shaders) => Ok((shaders, Some(shader_reload))),
+            Err(error) => {
+                println!(
+                    "{}\nUsing the precompiled shaders until the sources compile!",
+                    error
+                );
+                Ok((ShaderCode::precompiled()?, Some(shader_reload)))
+            }
+        }
+    }
+
+    // Without the hot-reload feature there is no shader compiler, so the precompiled shaders are always used
+    #[cfg(not(feature = "hot-reload"))]
+    fn load_shaders(hot_reload: bool) -> Result<ShaderCode, GraphicsError> {
+        if hot_reload {
+            println!("Built without the hot-reload feature, so shader hot reloading is off!");
+        }
+        ShaderCode::precompiled()
+    }
+
+    // Trims the requested priorities to what the queue family supports
+    fn choose_queue_priorities(requested: &[f32], indices: &QueueFamilyIndices) -> Vec<f32> {
+        if requested.len() > indices.graphics_queue_count as usize {
+            println!(
+                "Requested {} queues but the queue family only has {}!",
+                requested.len(),
+                indices.graphics_queue_count
+            );
+        }
+
+        let mut priorities: Vec<f32> = requested
             .iter()
-            .map(|ext| ext.as_ptr())
-            .collect::<Vec<_>>();
+            .take(indices.graphics_queue_count as usize)
+            .map(|priority| priority.clamp(0.0, 1.0))
+            .collect();
+
+        if priorities.is_empty() {
+            priorities.push(1.0);
+        }
+
+        priorities
+    }
+
+    // Creates an ash Instance, which is a light wrapper around a vk::Instance
+    // Also returns whether the validation layer was enabled
+    fn create_instance(
+        window: Option<&Window>,
+        settings: &VulkanSettings,
+    ) -> Result<(Entry, Instance, bool), GraphicsError> {
+        // Creats weird wrapper type for accessing cpp vulkan dynamic library
+        let entry = unsafe { Entry::new()? };
+
+        // Specifies extensions, leaving out the surface ones when headless
+        let mut extension_names_raw = match window {
+            Some(window) => ash_window::enumerate_required_extensions(window)
+                .map_err(GraphicsError::SurfaceCreation)?
+                .iter()
+                .map(|ext| ext.as_ptr())
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+
+        // Color spaces other than sRGB are only reported by surfaces once this extension is enabled
+        if window.is_some()
+            && settings
+                .surface_formats
+                .iter()
+                .any(|format| format.color_space != vk::ColorSpaceKHR::SRGB_NONLINEAR)
+            && VulkanBase::check_instance_extension_support(
+                &entry,
+                vk::ExtSwapchainColorspaceFn::name(),
+            )?
+        {
+            extension_names_raw.push(vk::ExtSwapchainColorspaceFn::name().as_ptr());
+        }
+
+        // Validation needs the layer from the Vulkan SDK, and debug utils to report back through
+        let validation_enabled = settings.validation
+            && debug::check_validation_layer_support(&entry)?
+            && VulkanBase::check_instance_extension_support(&entry, DebugUtils::name())?;
+
+        if settings.validation && !validation_enabled {
+            println!("Validation was requested but VK_LAYER_KHRONOS_validation is not installed!");
+        }
+
+        let mut layer_names_raw = Vec::new();
+        if validation_enabled {
+            layer_names_raw.push(debug::validation_layer_name().as_ptr());
+            extension_names_raw.push(DebugUtils::name().as_ptr());
+        }
 
         // Loads names into CStrings
         let application_name = CString::new("Hello Triangle").unwrap();
@@ -152,17 +1312,36 @@ impl VulkanBase {
             .api_version(vk::make_api_version(0, 1, 0, 0));
 
         // Creates instance info
-        let create_info = vk::InstanceCreateInfo::builder()
+        let mut create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
+            .enabled_layer_names(&layer_names_raw)
             .enabled_extension_names(&extension_names_raw);
 
-        // Creats weird wrapper type for accessing cpp vulkan dynamic library
-        let entry = unsafe { Entry::new().expect(BAD_ERROR) };
+        // The messenger only exists after the instance does, so this covers vkCreateInstance and vkDestroyInstance
+        let mut debug_create_info = debug::messenger_create_info();
+        if validation_enabled {
+            create_info = create_info.push_next(&mut debug_create_info);
+        }
 
         // Creates ash instance
-        let instance = unsafe { entry.create_instance(&create_info, None).expect(BAD_ERROR) };
+        let instance = unsafe { entry.create_instance(&create_info, None)? };
+
+        Ok((entry, instance, validation_enabled))
+    }
+
+    // Checks if the Vulkan implementation supports a given instance extension
+    fn check_instance_extension_support(
+        entry: &Entry,
+        extension: &CStr,
+    ) -> Result<bool, GraphicsError> {
+        let instance_extensions = entry.enumerate_instance_extension_properties()?;
+
+        Ok(instance_extensions.iter().any(|instance_extension| {
+            let instance_extension_name =
+                unsafe { CStr::from_ptr(instance_extension.extension_name.as_ptr()) };
 
-        (entry, instance)
+            instance_extension_name == extension
+        }))
     }
 
     // Creates a window surface
@@ -170,66 +1349,149 @@ impl VulkanBase {
         entry: &Entry,
         instance: &Instance,
         window: &Window,
-    ) -> (vk::SurfaceKHR, Surface) {
+    ) -> Result<(vk::SurfaceKHR, Surface), GraphicsError> {
         // Creates a Vulkan KHR object and then a light ash wrapper (both are required)
         let surface_khr = unsafe {
             ash_window::create_surface(entry, instance, window, None)
-                .expect("Unsupported platform!")
+                .map_err(GraphicsError::SurfaceCreation)?
         };
         let surface = Surface::new(entry, instance);
-        (surface_khr, surface)
+        Ok((surface_khr, surface))
     }
 
-    // Picks the first valid physical device
-    // TODO: pick best one instead of first one
+    // Scores every valid physical device and picks one according to selection, logging each device along the way
     fn pick_physical_device(
         instance: &Instance,
         extensions: &[*const i8],
         surface_khr: &vk::SurfaceKHR,
-        surface: &Surface,
-    ) -> (
-        vk::PhysicalDevice,
-        QueueFamilyIndices,
-        SwapchainSupportDetails,
-    ) {
-        let physical_devices = unsafe { instance.enumerate_physical_devices().expect(BAD_ERROR) };
-
-        for device in physical_devices {
-            if let Some((queue_family_indices, swapchain_support_details)) =
-                VulkanBase::is_device_suitable(instance, &device, extensions, surface_khr, surface)
-            {
-                return (device, queue_family_indices, swapchain_support_details);
+        surface: Option<&Surface>,
+        selection: &DeviceSelection,
+    ) -> Result<
+        (
+            vk::PhysicalDevice,
+            QueueFamilyIndices,
+            Option<SwapchainSupportDetails>,
+        ),
+        GraphicsError,
+    > {
+        let physical_devices = unsafe { instance.enumerate_physical_devices()? };
+
+        let mut suitable_devices = Vec::new();
+        let mut candidates = Vec::new();
+
+        for (index, device) in physical_devices.into_iter().enumerate() {
+            let candidate = VulkanBase::describe_physical_device(instance, &device, index);
+
+            let suitability = VulkanBase::is_device_suitable(
+                instance,
+                &device,
+                extensions,
+                surface_khr,
+                surface,
+            )?;
+
+            match suitability {
+                Some((queue_family_indices, swapchain_support_details)) => {
+                    println!(
+                        "GPU {}: {} ({:?}) - score {}",
+                        index,
+                        candidate.name,
+                        candidate.device_type,
+                        device_selection::score(&candidate)
+                    );
+
+                    suitable_devices.push((
+                        device,
+                        queue_family_indices,
+                        swapchain_support_details,
+                    ));
+                    candidates.push(candidate);
+                }
+                None => println!(
+                    "GPU {}: {} ({:?}) - unsuitable",
+                    index, candidate.name, candidate.device_type
+                ),
             }
         }
 
-        panic!("No valid GPU!");
+        // The environment variable wins so a device can be forced without rebuilding
+        let selection = DeviceSelection::from_env().unwrap_or_else(|| selection.clone());
+
+        let position =
+            device_selection::select(&candidates, &selection).ok_or(GraphicsError::InvalidGPU)?;
+        println!(
+            "Using GPU {}: {}",
+            candidates[position].index, candidates[position].name
+        );
+
+        Ok(suitable_devices.swap_remove(position))
+    }
+
+    // Gathers what a physical device offers for device_selection::score
+    fn describe_physical_device(
+        instance: &Instance,
+        device: &vk::PhysicalDevice,
+        index: usize,
+    ) -> DeviceCandidate {
+        let properties = unsafe { instance.get_physical_device_properties(*device) };
+        let features = unsafe { instance.get_physical_device_features(*device) };
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(*device) };
+
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+
+        let device_local_memory = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+
+        DeviceCandidate {
+            index,
+            name,
+            device_type: properties.device_type,
+            max_image_dimension_2d: properties.limits.max_image_dimension2_d,
+            device_local_memory,
+            sampler_anisotropy: features.sampler_anisotropy == vk::TRUE,
+            geometry_shader: features.geometry_shader == vk::TRUE,
+        }
     }
 
     // Checks whether a given physical device is valid, and if it is returns the queue family indices of that device
+    // Swapchain support is only needed with a surface, so headless rendering gets None in its place
     fn is_device_suitable(
         instance: &Instance,
         device: &vk::PhysicalDevice,
         required_extensions: &[*const i8],
         surface_khr: &vk::SurfaceKHR,
-        surface: &Surface,
-    ) -> Option<(QueueFamilyIndices, SwapchainSupportDetails)> {
-        if !VulkanBase::check_device_extension_support(instance, device, required_extensions) {
-            return None;
+        surface: Option<&Surface>,
+    ) -> Result<Option<(QueueFamilyIndices, Option<SwapchainSupportDetails>)>, GraphicsError> {
+        if !VulkanBase::check_device_extension_support(instance, device, required_extensions)? {
+            return Ok(None);
         }
 
-        let swapchain_support_details =
-            VulkanBase::query_swapchain_support_details(device, surface_khr, surface);
+        let swapchain_support_details = match surface {
+            Some(surface) => {
+                let swapchain_support_details =
+                    SwapchainSupportDetails::query(device, surface_khr, surface)?;
 
-        if swapchain_support_details.formats.is_empty()
-            | swapchain_support_details.presentation_modes.is_empty()
-        {
-            return None;
-        }
+                if swapchain_support_details.formats.is_empty()
+                    | swapchain_support_details.presentation_modes.is_empty()
+                {
+                    return Ok(None);
+                }
+
+                Some(swapchain_support_details)
+            }
+            None => None,
+        };
 
         let queue_family_indices =
-            VulkanBase::find_queue_families(instance, device, surface_khr, surface)?;
+            VulkanBase::find_queue_families(instance, device, surface_khr, surface);
 
-        Some((queue_family_indices, swapchain_support_details))
+        Ok(queue_family_indices.map(|indices| (indices, swapchain_support_details)))
     }
 
     // Checks if a given physical device supports given device extensions
@@ -237,15 +1499,11 @@ impl VulkanBase {
         instance: &Instance,
         device: &vk::PhysicalDevice,
         required_extensions: &[*const i8],
-    ) -> bool {
-        let device_extensions = unsafe {
-            instance
-                .enumerate_device_extension_properties(*device)
-                .expect(BAD_ERROR)
-        };
+    ) -> Result<bool, GraphicsError> {
+        let device_extensions = unsafe { instance.enumerate_device_extension_properties(*device)? };
 
         // Unsure if this is faster than using a hashset - device_extensions has length 122 on my system
-        required_extensions.iter().all(|required_extension| {
+        Ok(required_extensions.iter().all(|required_extension| {
             let required_extension_name = unsafe { CStr::from_ptr(*required_extension) };
 
             device_extensions.iter().any(|device_extension| {
@@ -254,67 +1512,56 @@ impl VulkanBase {
 
                 required_extension_name == device_extension_name
             })
-        })
+        }))
     }
 
-    // Finds the queue families of a given physical device
+    // Finds the queue families of a given physical device, preferring one family that can both draw and present
+    // Nothing is presented when headless, so every family counts as able to and presentation shares the graphics family
     fn find_queue_families(
         instance: &Instance,
         device: &vk::PhysicalDevice,
         surface_khr: &vk::SurfaceKHR,
-        surface: &Surface,
+        surface: Option<&Surface>,
     ) -> Option<QueueFamilyIndices> {
         let queue_families =
             unsafe { instance.get_physical_device_queue_family_properties(*device) };
 
-        for (index, queue_family) in queue_families.iter().enumerate() {
-            // Checks for graphics queue family
-            if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                // Checks for presentation queue family
-                && unsafe {
-                    surface
-                        .get_physical_device_surface_support(*device, index as u32, *surface_khr)
-                        .unwrap()
-                }
-            {
-                // Assumes both queue families are the same
-                return Some(QueueFamilyIndices {
-                    queue_family_index: index as u32,
-                });
-            }
-        }
-        None
-    }
-
-    // Gets a given physical device's surface capabilities, formats, and presentation modes
-    fn query_swapchain_support_details(
-        device: &vk::PhysicalDevice,
-        surface_khr: &vk::SurfaceKHR,
-        surface: &Surface,
-    ) -> SwapchainSupportDetails {
-        let capabilities = unsafe {
-            surface
-                .get_physical_device_surface_capabilities(*device, *surface_khr)
-                .expect(BAD_ERROR)
+        let supports_graphics = |index: usize| {
+            queue_families[index]
+                .queue_flags
+                .contains(vk::QueueFlags::GRAPHICS)
         };
-
-        let formats = unsafe {
-            surface
-                .get_physical_device_surface_formats(*device, *surface_khr)
-                .expect(BAD_ERROR)
+        // A family whose support can't be queried is treated as unable to present
+        let supports_present = |index: usize| match surface {
+            Some(surface) => unsafe {
+                surface
+                    .get_physical_device_surface_support(*device, index as u32, *surface_khr)
+                    .unwrap_or(false)
+            },
+            None => true,
         };
 
-        let presentation_modes = unsafe {
-            surface
-                .get_physical_device_surface_present_modes(*device, *surface_khr)
-                .expect(BAD_ERROR)
+        let shared_family_index = (0..queue_families.len())
+            .find(|index| supports_graphics(*index) && supports_present(*index));
+
+        // Falls back to separate families
+        let (graphics_family_index, present_family_index) = match shared_family_index {
+            Some(index) => (index, index),
+            None => (
+                (0..queue_families.len()).find(|index| supports_graphics(*index))?,
+                (0..queue_families.len()).find(|index| supports_present(*index))?,
+            ),
         };
 
-        SwapchainSupportDetails {
-            capabilities,
-            formats,
-            presentation_modes,
-        }
+        Some(QueueFamilyIndices {
+            graphics_family_index: graphics_family_index as u32,
+            graphics_queue_count: queue_families[graphics_family_index].queue_count,
+            present_family_index: present_family_index as u32,
+            transfer_family_index: upload::find_transfer_family(&queue_families)
+                .map(|index| index as u32),
+            compute_family_index: compute::find_compute_family(&queue_families)
+                .map_or(graphics_family_index as u32, |index| index as u32),
+        })
     }
 
     // Creates the logical device based on necessary queue families
@@ -323,319 +1570,47 @@ impl VulkanBase {
         physical_device: &vk::PhysicalDevice,
         extensions: &[*const i8],
         indices: &QueueFamilyIndices,
-    ) -> Device {
-        let queue_priorities = [1.0];
+        queue_priorities: &[f32],
+    ) -> Result<Device, GraphicsError> {
+        // The present, transfer, and compute queues each take the first queue of their family
+        let single_queue_priorities = [1.0];
+
+        let mut queue_infos = vec![vk::DeviceQueueCreateInfo::builder()
+            .queue_family_index(indices.graphics_family_index)
+            .queue_priorities(queue_priorities)];
+        let mut requested_families = vec![indices.graphics_family_index];
+
+        // Each family can only appear once, so queues are only requested for families not already requested
+        let other_families = [
+            Some(indices.present_family_index),
+            indices.transfer_family_index,
+            Some(indices.compute_family_index),
+        ];
+        for &family_index in other_families.iter().flatten() {
+            if !requested_families.contains(&family_index) {
+                requested_families.push(family_index);
+                queue_infos.push(
+                    vk::DeviceQueueCreateInfo::builder()
+                        .queue_family_index(family_index)
+                        .queue_priorities(&single_queue_priorities),
+                );
+            }
+        }
 
-        let queue_info = vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(indices.queue_family_index)
-            .queue_priorities(&queue_priorities);
+        // Builds the queue infos, which is safe as the priorities they point to outlive device creation
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = queue_infos
+            .into_iter()
+            .map(|queue_info| queue_info.build())
+            .collect();
 
-        // Wraps reference to queue_info in a slice in order to preserve lifetime information
-        // Bad alternative is to build() the queue_info
         let device_create_info = vk::DeviceCreateInfo::builder()
-            .queue_create_infos(slice::from_ref(&queue_info))
+            .queue_create_infos(&queue_infos)
             .enabled_extension_names(extensions);
 
         unsafe {
             instance
                 .create_device(*physical_device, &device_create_info, None)
-                .expect(BAD_ERROR)
-        }
-    }
-
-    // Creates the swap chain after determining swap chain settings
-    fn create_swapchain(
-        instance: &Instance,
-        device: &Device,
-        window: &WindowDimensions,
-        surface: &vk::SurfaceKHR,
-        swapchain_support_details: &SwapchainSupportDetails,
-    ) -> (vk::SwapchainKHR, Swapchain, SwapchainDetails) {
-        let format = VulkanBase::choose_swap_surface_format(&swapchain_support_details.formats);
-
-        let presentation_mode = VulkanBase::choose_swap_surface_presentation_mode(
-            &swapchain_support_details.presentation_modes,
-        );
-
-        let extent =
-            VulkanBase::choose_swap_extent(window, &swapchain_support_details.capabilities);
-
-        let mut image_count = swapchain_support_details.capabilities.min_image_count;
-
-        // Implements double buffering
-        if swapchain_support_details.capabilities.max_image_count
-            != swapchain_support_details.capabilities.min_image_count
-        {
-            image_count += 1;
-        }
-
-        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-            .surface(*surface)
-            .min_image_count(image_count)
-            .image_format(format.format)
-            .image_color_space(format.color_space)
-            .image_extent(extent)
-            .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .pre_transform(swapchain_support_details.capabilities.current_transform)
-            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(presentation_mode)
-            .clipped(true)
-            .old_swapchain(vk::SwapchainKHR::null());
-
-        let swapchain = Swapchain::new(instance, device);
-        let swapchain_khr = unsafe {
-            swapchain
-                .create_swapchain(&swapchain_create_info, None)
-                .expect(BAD_ERROR)
-        };
-
-        let swapchain_details = SwapchainDetails {
-            format,
-            _presentation_mode: presentation_mode,
-            extent,
-        };
-
-        (swapchain_khr, swapchain, swapchain_details)
-    }
-
-    // Determines surface format
-    fn choose_swap_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
-        for format in formats {
-            if format.format == vk::Format::B8G8R8A8_SRGB
-                && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-            {
-                return *format;
-            }
-        }
-
-        *formats.get(0).expect("No available surface formats!")
-    }
-
-    // Chooses presentation mode - immediate is preferred for least latency (as opposed to VSync aka FIFO)
-    fn choose_swap_surface_presentation_mode(
-        presentation_modes: &[vk::PresentModeKHR],
-    ) -> vk::PresentModeKHR {
-        for presentation_mode in presentation_modes {
-            if *presentation_mode == vk::PresentModeKHR::IMMEDIATE {
-                return *presentation_mode;
-            }
-        }
-
-        vk::PresentModeKHR::FIFO
-    }
-
-    // Creates an extent with the correct size
-    fn choose_swap_extent(
-        window: &WindowDimensions,
-        capabilities: &vk::SurfaceCapabilitiesKHR,
-    ) -> vk::Extent2D {
-        if capabilities.current_extent.width != std::u32::MAX {
-            return capabilities.current_extent;
-        }
-
-        vk::Extent2D {
-            width: window.width,
-            height: window.height,
-        }
-    }
-
-    // Creates an image view for each image in the swapchain
-    fn create_image_views(
-        device: &Device,
-        images: &[vk::Image],
-        format: &vk::SurfaceFormatKHR,
-    ) -> Vec<vk::ImageView> {
-        images
-            .iter()
-            .map(|image| {
-                let image_view_create_info = vk::ImageViewCreateInfo::builder()
-                    .image(*image)
-                    .view_type(vk::ImageViewType::TYPE_2D)
-                    .format(format.format)
-                    .components(vk::ComponentMapping {
-                        r: vk::ComponentSwizzle::IDENTITY,
-                        g: vk::ComponentSwizzle::IDENTITY,
-                        b: vk::ComponentSwizzle::IDENTITY,
-                        a: vk::ComponentSwizzle::IDENTITY,
-                    })
-                    .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    });
-                unsafe {
-                    device
-                        .create_image_view(&image_view_create_info, None)
-                        .expect(BAD_ERROR)
-                }
-            })
-            .collect()
-    }
-
-    fn create_render_pass(device: &Device, format: &vk::SurfaceFormatKHR) -> vk::RenderPass {
-        let color_attachments = vk::AttachmentDescription::builder()
-            .format(format.format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
-
-        let color_attachment_references = vk::AttachmentReference::builder()
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-
-        let subpasses = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(slice::from_ref(&color_attachment_references));
-
-        let render_pass_info = vk::RenderPassCreateInfo::builder()
-            .attachments(slice::from_ref(&color_attachments))
-            .subpasses(slice::from_ref(&subpasses));
-
-        unsafe {
-            device
-                .create_render_pass(&render_pass_info, None)
-                .expect(BAD_ERROR)
-        }
-    }
-
-    // Creates shader modules, graphics pipeline layout, and graphics pipeline
-    fn create_graphics_pipeline(
-        device: &Device,
-        render_pass: &vk::RenderPass,
-        extent: &vk::Extent2D,
-    ) -> (Vec<vk::ShaderModule>, vk::PipelineLayout, vk::Pipeline) {
-        let (vertex_code, fragment_code) = VulkanBase::read_shaders();
-
-        // Shader modules
-        let vertex_shader_module = VulkanBase::create_shader_module(device, &vertex_code);
-        let fragment_shader_module = VulkanBase::create_shader_module(device, &fragment_code);
-
-        let shader_entry_name = CString::new("main").unwrap();
-
-        let shader_stage_infos = [
-            vk::PipelineShaderStageCreateInfo::builder()
-                .module(vertex_shader_module)
-                .name(shader_entry_name.as_c_str())
-                .stage(vk::ShaderStageFlags::VERTEX)
-                .build(),
-            vk::PipelineShaderStageCreateInfo::builder()
-                .module(fragment_shader_module)
-                .name(shader_entry_name.as_c_str())
-                .stage(vk::ShaderStageFlags::FRAGMENT)
-                .build(),
-        ];
-
-        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(&[])
-            .vertex_attribute_descriptions(&[]);
-
-        let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
-
-        // In reality viewport and scissors should be set during render pass dynamically, rather than before,
-        // in order to prevent having to recreate the pipeline everytime the window is resized
-        let viewports = vk::Viewport::builder()
-            .x(0.0)
-            .y(0.0)
-            .width(extent.width as f32)
-            .height(extent.height as f32)
-            .min_depth(0.0)
-            .max_depth(1.0);
-
-        let scissors = vk::Rect2D::builder()
-            .offset(*vk::Offset2D::builder().x(0).y(0))
-            .extent(*extent);
-
-        let viewport_info = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(slice::from_ref(&viewports))
-            .scissors(slice::from_ref(&scissors));
-
-        let rasterization_info = vk::PipelineRasterizationStateCreateInfo::builder()
-            .polygon_mode(vk::PolygonMode::FILL)
-            .line_width(1.0)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
-
-        let multisample_info = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-        let alpha_blending_attachments = vk::PipelineColorBlendAttachmentState::builder()
-            .blend_enable(false)
-            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-            .color_blend_op(vk::BlendOp::ADD)
-            .src_alpha_blend_factor(vk::BlendFactor::ONE)
-            .dst_color_blend_factor(vk::BlendFactor::ZERO)
-            .alpha_blend_op(vk::BlendOp::ADD)
-            .color_write_mask(vk::ColorComponentFlags::all());
-
-        let color_blend_info = vk::PipelineColorBlendStateCreateInfo::builder()
-            .logic_op(vk::LogicOp::CLEAR)
-            .attachments(slice::from_ref(&alpha_blending_attachments));
-
-        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-        let dynamic_info =
-            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
-
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default();
-        let pipeline_layout = unsafe {
-            device
-                .create_pipeline_layout(&pipeline_layout_info, None)
-                .expect(BAD_ERROR)
-        };
-
-        let graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(&shader_stage_infos)
-            .vertex_input_state(&vertex_input_info)
-            .input_assembly_state(&input_assembly_info)
-            .viewport_state(&viewport_info)
-            .rasterization_state(&rasterization_info)
-            .multisample_state(&multisample_info)
-            .color_blend_state(&color_blend_info)
-            .dynamic_state(&dynamic_info)
-            .layout(pipeline_layout)
-            .render_pass(*render_pass);
-
-        let graphics_pipelines = unsafe {
-            device
-                .create_graphics_pipelines(vk::PipelineCache::null(), slice::from_ref(&graphics_pipeline_info), None)
-                .expect(BAD_ERROR)
-        };
-
-        (
-            vec![vertex_shader_module, fragment_shader_module],
-            pipeline_layout,
-            graphics_pipelines[0],
-        )
-    }
-
-    // Reads shader code from shader files (should be changed to take a shader file path as an argument in production code... I think)
-    fn read_shaders() -> (Vec<u32>, Vec<u32>) {
-        // Reads precompiled shaders
-        // Macro include_bytes! must know path names at compile time! If shaders are unknown, or for automation, a different solution is required.
-        let mut vertex_shader_file = Cursor::new(&include_bytes!("shaders/vertex.spv"));
-        let mut fragment_shader_file = Cursor::new(&include_bytes!("shaders/fragment.spv"));
-
-        let vertex_code =
-            util::read_spv(&mut vertex_shader_file).expect("Failed to read vertex shader file");
-        let fragment_code =
-            util::read_spv(&mut fragment_shader_file).expect("Failed to read fragment shader file");
-
-        (vertex_code, fragment_code)
-    }
-
-    // Creates a shader module from shader code stored in a u32 vector
-    fn create_shader_module(device: &Device, code: &[u32]) -> vk::ShaderModule {
-        let shader_module_create_info = vk::ShaderModuleCreateInfo::builder().code(code);
-
-        unsafe {
-            device
-                .create_shader_module(&shader_module_create_info, None)
-                .expect(BAD_ERROR)
+                .map_err(GraphicsError::DeviceCreation)
         }
     }
 }
@@ -643,20 +1618,35 @@ impl VulkanBase {
 impl Drop for VulkanBase {
     fn drop(&mut self) {
         println!("Cleaning up VulkanBase!");
+        // Frames may still be in flight - if the device was lost there is nothing left to wait for
+        unsafe { self.device.device_wait_idle().ok() };
+        self.frame_sync.destroy(&self.device);
+        self.command_pool.destroy(&self.device);
+        self.uploader.destroy(&self.device);
+        self.mesh.destroy(&self.device);
+        self.pipeline.destroy(&self.device);
+        self.uniform_buffers.destroy(&self.device);
+        self.texture.destroy(&self.device);
+        // Framebuffers reference the swapchain image views, depth buffer, and color target, so they go first
+        self.render_pass.destroy(&self.device);
+        if let Some(color_target) = &mut self.color_target {
+            color_target.destroy(&self.device);
+        }
+        self.depth_buffer.destroy(&self.device);
+        // The offscreen image's memory comes from the allocator, so the target goes before it
+        self.target.destroy(&self.device);
+        self.allocator.destroy(&self.device);
+        // The surface must go after the swapchain and device
         unsafe {
-            self.device.destroy_pipeline(self.pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            for shader_module in self.shader_modules.iter() {
-                self.device.destroy_shader_module(*shader_module, None);
-            }
-            for image_view in self.swapchain_image_views.iter() {
-                self.device.destroy_image_view(*image_view, None);
-            }
-            self.swapchain.destroy_swapchain(self.swapchain_khr, None);
             self.device.destroy_device(None);
-            self.surface.destroy_surface(self.surface_khr, None);
+            if let Some(surface) = &self.surface {
+                surface.destroy_surface(self.surface_khr, None);
+            }
+        }
+        if let Some(debug_messenger) = &mut self.debug_messenger {
+            debug_messenger.destroy();
+        }
+        unsafe {
             self.instance.destroy_instance(None);
         }
         println!("Cleaned up VulkanBase!");
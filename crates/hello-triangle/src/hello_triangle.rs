@@ -1,50 +1,116 @@
-use app::graphics::vulkan_base::{VulkanBase, WindowDimensions};
-use winit::{
-    dpi::LogicalSize,
-    event::{Event, WindowEvent},
-    event_loop::ControlFlow,
-    event_loop::EventLoop,
-    window::{Window, WindowBuilder},
-};
+use app::camera::{Camera, CameraController};
+use app::engine::{Engine, EngineSettings, FrameInfo};
+use app::graphics::uniforms::UniformBufferObject;
+use app::graphics::vulkan_base::{GraphicsError, ModelFiles, VulkanBase, VulkanSettings};
+use app::scene::{NodeId, Scene, Transform};
+use cgmath::{Deg, Quaternion, Rad, Rotation3};
+use std::env;
+use winit::event::Event;
 
+// How fast the triangle spins around the z axis
+const ROTATION_SPEED: Deg<f32> = Deg(90.0);
+// How fast the camera moves in units per second, and turns in radians per unit of mouse motion
+const CAMERA_SPEED: f32 = 2.0;
+const MOUSE_SENSITIVITY: f32 = 0.003;
+
+// Simulation state the engine calls back into - the window and Vulkan state belong to the engine
 pub struct TriangleApplication {
-    _window: Window,
-    _vulkan_type: VulkanBase,
+    // Triangle's angle after the last two fixed updates, interpolated between when rendering
+    previous_rotation: Rad<f32>,
+    rotation: Rad<f32>,
+    // Camera after the last two fixed updates, interpolated between like the rotation
+    previous_camera: Camera,
+    camera: Camera,
+    camera_controller: CameraController,
+    // Holds the triangle (or model) as a single node, whose world transform is the model matrix
+    scene: Scene,
+    triangle: NodeId,
 }
 
 impl TriangleApplication {
-    // Creates the window and event loop for the application
-    pub fn new() -> (Self, EventLoop<()>) {
-        let event_loop = EventLoop::new();
+    pub fn new() -> TriangleApplication {
+        let mut scene = Scene::new();
+        let triangle = scene.add_node("triangle", Transform::default(), None);
 
-        // Stores window dimensions
-        let width = 800;
-        let height = 600;
+        TriangleApplication {
+            previous_rotation: Rad(0.0),
+            rotation: Rad(0.0),
+            previous_camera: Camera::default(),
+            camera: Camera::default(),
+            camera_controller: CameraController::new(CAMERA_SPEED, MOUSE_SENSITIVITY),
+            scene,
+            triangle,
+        }
+    }
 
-        // Creates a window using a WindowBuilder
-        let mut builder = WindowBuilder::new();
-        builder = builder
-            .with_title("name of window")
-            .with_inner_size(LogicalSize::new(width, height));
-        let _window = builder
-            .build(&event_loop)
-            .expect("Could not create a window!");
+    // Settings for the engine, with the model named on the command line if any
+    fn settings() -> EngineSettings {
+        // Run as `hello-triangle <model.obj> <texture.png>` to draw a model instead of the triangle
+        let mut args = env::args_os().skip(1);
+        let model = match (args.next(), args.next()) {
+            (Some(obj), Some(texture)) => Some(ModelFiles {
+                obj: obj.into(),
+                texture: texture.into(),
+            }),
+            _ => None,
+        };
 
-        // Stores window information for use in VulkanBase
-        let window_dimensions = WindowDimensions::new(width, height);
+        EngineSettings {
+            vulkan: VulkanSettings {
+                model,
+                ..VulkanSettings::default()
+            },
+            ..EngineSettings::default()
+        }
+    }
 
-        // Creates a VulkanType holding all the vulkan data
-        let _vulkan_type = VulkanBase::new(&_window, &window_dimensions);
+    // Passes input on to the camera controller
+    fn input(&mut self, event: &Event<()>) {
+        match event {
+            Event::WindowEvent { event, .. } => {
+                self.camera_controller.handle_window_event(event);
+            }
+            Event::DeviceEvent { event, .. } => self.camera_controller.handle_device_event(event),
+            _ => (),
+        }
+    }
 
-        let app = TriangleApplication {
-            _window,
-            _vulkan_type,
-        };
+    // Advances the simulation by one fixed step of dt seconds
+    fn fixed_update(&mut self, dt: f32) {
+        self.previous_rotation = self.rotation;
+        self.rotation += Rad::from(ROTATION_SPEED) * dt;
 
-        (app, event_loop)
+        self.previous_camera = self.camera;
+        self.camera_controller.update(&mut self.camera, dt);
     }
 
-    fn example_function(&self) {}
+    // Sets the transforms for the frame - alpha blends between the previous and current simulation states
+    fn render(
+        &mut self,
+        vulkan_type: &mut VulkanBase,
+        frame: &FrameInfo,
+    ) -> Result<(), GraphicsError> {
+        let rotation =
+            self.previous_rotation + (self.rotation - self.previous_rotation) * frame.alpha;
+        let camera = self.camera.interpolate(&self.previous_camera, frame.alpha);
+
+        self.scene.set_local_transform(
+            self.triangle,
+            Transform {
+                rotation: Quaternion::from_angle_z(rotation),
+                ..Transform::default()
+            },
+        );
+        self.scene.update_world_transforms();
+
+        vulkan_type.update_uniforms(&UniformBufferObject {
+            model: *self.scene.world_transform(self.triangle),
+            view: camera.view_matrix(),
+            proj: camera.projection_matrix(frame.aspect()),
+        });
+
+        Ok(())
+    }
 }
 
 impl Drop for TriangleApplication {
@@ -54,24 +120,13 @@ impl Drop for TriangleApplication {
     }
 }
 
-pub fn run(app: TriangleApplication, event_loop: EventLoop<()>) {
-    event_loop.run(move |event, _, control_flow| {
-        // Continually runs the event loop
-        *control_flow = ControlFlow::Poll;
-
-        match event {
-            // Checks for close requested
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => {
-                app.example_function(); // TEST FUNCTION CALL - TO BE REMOVED
-                println!("Close button was pressed");
-                *control_flow = ControlFlow::Exit;
-            }
-            // Updates application
-            Event::MainEventsCleared => {}
-            _ => (),
-        }
-    });
+pub fn run(app: TriangleApplication) {
+    let (engine, event_loop) = Engine::new(TriangleApplication::settings());
+    engine.run(
+        event_loop,
+        app,
+        TriangleApplication::fixed_update,
+        TriangleApplication::render,
+        TriangleApplication::input,
+    );
 }
@@ -1 +1,8 @@
+pub mod bvh;
+pub mod camera;
+pub mod engine;
 pub mod graphics;
+pub mod monitor;
+pub mod picking;
+pub mod scene;
+pub mod timing;
@@ -3,6 +3,5 @@ mod hello_triangle;
 use hello_triangle::TriangleApplication;
 
 fn main() {
-    let (app, io) = TriangleApplication::new();
-    hello_triangle::run(app, io);
-}
\ No newline at end of file
+    hello_triangle::run(TriangleApplication::new());
+}
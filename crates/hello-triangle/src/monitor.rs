@@ -0,0 +1,56 @@
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+    window::Window,
+};
+
+// Snapshot of a monitor the window can be presented on
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorInfo {
+    pub name: String,
+    pub size: PhysicalSize<u32>,
+    pub position: PhysicalPosition<i32>,
+    pub scale_factor: f64,
+    // Estimated refresh rate in Hz, None if the platform reports no video modes
+    pub refresh_rate: Option<u16>,
+}
+
+impl MonitorInfo {
+    pub fn from_handle(monitor: &MonitorHandle) -> MonitorInfo {
+        let size = monitor.size();
+
+        // winit only exposes the refresh rates of video modes, so the fastest mode at the monitor's current
+        // resolution is taken as its refresh rate (falling back to the fastest mode at any resolution)
+        let refresh_rate = monitor
+            .video_modes()
+            .filter(|mode| mode.size() == size)
+            .map(|mode| mode.refresh_rate())
+            .max()
+            .or_else(|| monitor.video_modes().map(|mode| mode.refresh_rate()).max());
+
+        MonitorInfo {
+            name: monitor
+                .name()
+                .unwrap_or_else(|| String::from("Unknown monitor")),
+            size,
+            position: monitor.position(),
+            scale_factor: monitor.scale_factor(),
+            refresh_rate,
+        }
+    }
+}
+
+// Lists every monitor available to the window
+pub fn available_monitors(window: &Window) -> Vec<MonitorInfo> {
+    window
+        .available_monitors()
+        .map(|monitor| MonitorInfo::from_handle(&monitor))
+        .collect()
+}
+
+// The monitor the window is currently on, if the platform can tell
+pub fn current_monitor(window: &Window) -> Option<MonitorInfo> {
+    window
+        .current_monitor()
+        .map(|monitor| MonitorInfo::from_handle(&monitor))
+}
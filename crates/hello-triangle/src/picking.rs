@@ -0,0 +1,336 @@
+use crate::bvh::Bvh;
+use crate::graphics::model::Model;
+use crate::scene::{NodeId, Scene};
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+use std::cell::Cell;
+
+// Rays closer than this to parallel with a triangle's plane, or hits closer than this to the origin, are ignored
+const EPSILON: f32 = 1e-6;
+
+// Half line from origin along direction
+// direction need not be unit length, as distances along the ray are measured in multiples of it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray { origin, direction }
+    }
+
+    // Point distance along the ray
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // The same ray in the space matrix maps into, e.g. from world space into an object's with the inverse of its world
+    // transform
+    // direction is transformed without renormalizing, so a distance along either ray reaches the same point
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Ray {
+        Ray {
+            origin: (matrix * self.origin.extend(1.0)).truncate(),
+            direction: (matrix * self.direction.extend(0.0)).truncate(),
+        }
+    }
+}
+
+// Möller-Trumbore test against a triangle, from either side - returns the distance along ray to the hit, if any
+pub fn intersect_triangle(ray: &Ray, triangle: [Vector3<f32>; 3]) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    // Barycentric coordinates of the hit, which must both lie inside the triangle along with 1 - u - v
+    let to_origin = ray.origin - triangle[0];
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(edge1);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inverse_determinant;
+    if distance > EPSILON {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+// Closest triangle a ray hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub distance: f32,
+    // Index of the triangle, made of indices[3 * triangle..3 * triangle + 3] of the mesh
+    pub triangle: u32,
+}
+
+// CPU copy of a mesh's triangles along with a BVH over them, for picking against the mesh drawn on the GPU
+pub struct PickMesh {
+    positions: Vec<Vector3<f32>>,
+    indices: Vec<u32>,
+    bvh: Bvh,
+}
+
+impl PickMesh {
+    // Mesh whose triangles are made of indices[3 * i..3 * i + 3] into positions
+    pub fn new(positions: Vec<Vector3<f32>>, indices: Vec<u32>) -> PickMesh {
+        let bvh = Bvh::from_triangles(&positions, &indices);
+        PickMesh {
+            positions,
+            indices,
+            bvh,
+        }
+    }
+
+    // Triangles of a loaded OBJ model, matching the mesh Mesh::from_model() uploads
+    pub fn from_model(model: &Model) -> PickMesh {
+        let positions = model
+            .vertices()
+            .iter()
+            .map(|vertex| Vector3::from(vertex.pos))
+            .collect();
+        PickMesh::new(positions, model.indices().to_vec())
+    }
+
+    // Closest triangle ray hits before max_distance, in the mesh's own space
+    pub fn intersect(&self, ray: &Ray, max_distance: f32) -> Option<Hit> {
+        // Per component, where a zero gives an infinity the slab test handles
+        let inverse_direction = Vector3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+
+        // Shared by both closures, so nodes further away than the closest hit so far are skipped
+        let closest = Cell::new(max_distance);
+        let mut hit = None;
+
+        self.bvh.query(
+            |bounds| {
+                bounds
+                    .intersect_ray(&ray.origin, &inverse_direction, closest.get())
+                    .is_some()
+            },
+            |triangle| {
+                if let Some(distance) = intersect_triangle(ray, self.triangle(triangle)) {
+                    if distance < closest.get() {
+                        closest.set(distance);
+                        hit = Some(Hit { distance, triangle });
+                    }
+                }
+            },
+        );
+
+        hit
+    }
+
+    fn triangle(&self, triangle: u32) -> [Vector3<f32>; 3] {
+        let first = triangle as usize * 3;
+        [
+            self.positions[self.indices[first] as usize],
+            self.positions[self.indices[first + 1] as usize],
+            self.positions[self.indices[first + 2] as usize],
+        ]
+    }
+}
+
+// Closest hit of a world space ray against meshes placed at scene nodes, along with the node it landed on
+// World transforms must be up to date, and nodes scaled to nothing (which have no inverse) can't be hit
+// Distances are along the world space ray, however the nodes are scaled
+pub fn pick<'a, I>(scene: &Scene, objects: I, ray: &Ray, max_distance: f32) -> Option<(NodeId, Hit)>
+where
+    I: IntoIterator<Item = (NodeId, &'a PickMesh)>,
+{
+    let mut closest: Option<(NodeId, Hit)> = None;
+
+    for (id, mesh) in objects {
+        let world_to_object = match scene.world_transform(id).invert() {
+            Some(world_to_object) => world_to_object,
+            None => continue,
+        };
+
+        let limit = closest.map_or(max_distance, |(_, hit)| hit.distance);
+        if let Some(hit) = mesh.intersect(&ray.transform(&world_to_object), limit) {
+            closest = Some((id, hit));
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Transform;
+
+    // Unit square in the z = 0 plane, made of two triangles
+    fn square() -> PickMesh {
+        PickMesh::new(
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 1.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        )
+    }
+
+    // Looking down -z at x, y from z = 5
+    fn ray_down(x: f32, y: f32) -> Ray {
+        Ray::new(Vector3::new(x, y, 5.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn triangles_are_hit_from_either_side() {
+        let triangle = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+
+        assert_eq!(
+            intersect_triangle(&ray_down(0.25, 0.25), triangle),
+            Some(5.0)
+        );
+        let from_below = Ray::new(Vector3::new(0.25, 0.25, -2.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersect_triangle(&from_below, triangle), Some(2.0));
+    }
+
+    #[test]
+    fn misses_and_triangles_behind_the_ray_are_not_hit() {
+        let triangle = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+
+        assert_eq!(intersect_triangle(&ray_down(0.75, 0.75), triangle), None);
+        let away = Ray::new(Vector3::new(0.25, 0.25, 5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersect_triangle(&away, triangle), None);
+        let parallel = Ray::new(Vector3::new(-1.0, 0.25, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(intersect_triangle(&parallel, triangle), None);
+    }
+
+    #[test]
+    fn meshes_report_the_triangle_hit() {
+        let mesh = square();
+
+        assert_eq!(
+            mesh.intersect(&ray_down(0.75, 0.25), f32::INFINITY),
+            Some(Hit {
+                distance: 5.0,
+                triangle: 0
+            })
+        );
+        assert_eq!(
+            mesh.intersect(&ray_down(0.25, 0.75), f32::INFINITY)
+                .map(|hit| hit.triangle),
+            Some(1)
+        );
+        assert_eq!(mesh.intersect(&ray_down(1.5, 0.5), f32::INFINITY), None);
+        assert_eq!(mesh.intersect(&ray_down(0.5, 0.25), 4.0), None);
+    }
+
+    #[test]
+    fn the_closest_of_many_triangles_is_hit() {
+        // Stack of squares at z = 0, -1, -2, ... enough for the BVH to split them up
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for layer in 0..32 {
+            let z = -(layer as f32);
+            let first = positions.len() as u32;
+            positions.extend_from_slice(&[
+                Vector3::new(0.0, 0.0, z),
+                Vector3::new(1.0, 0.0, z),
+                Vector3::new(1.0, 1.0, z),
+                Vector3::new(0.0, 1.0, z),
+            ]);
+            indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+        let mesh = PickMesh::new(positions, indices);
+
+        let hit = mesh
+            .intersect(&ray_down(0.75, 0.25), f32::INFINITY)
+            .unwrap();
+        assert_eq!(hit.distance, 5.0);
+        assert_eq!(hit.triangle, 0);
+
+        let from_behind = Ray::new(Vector3::new(0.75, 0.25, -40.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersect(&from_behind, f32::INFINITY).unwrap();
+        assert_eq!(hit.distance, 9.0);
+        assert_eq!(hit.triangle, 62);
+    }
+
+    #[test]
+    fn models_are_picked_by_their_positions() {
+        let model = Model::parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let mesh = PickMesh::from_model(&model);
+
+        assert!(mesh.intersect(&ray_down(0.5, 0.5), f32::INFINITY).is_some());
+        assert!(mesh
+            .intersect(&ray_down(-0.5, 0.5), f32::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn scenes_are_picked_in_world_space() {
+        let mesh = square();
+        let mut scene = Scene::new();
+        let near = scene.add_node(
+            "near",
+            Transform {
+                translation: Vector3::new(10.0, 0.0, 1.0),
+                scale: Vector3::new(2.0, 2.0, 2.0),
+                ..Transform::default()
+            },
+            None,
+        );
+        let far = scene.add_node(
+            "far",
+            Transform {
+                translation: Vector3::new(10.0, 0.0, -1.0),
+                ..Transform::default()
+            },
+            None,
+        );
+        let flat = scene.add_node(
+            "flat",
+            Transform {
+                scale: Vector3::new(0.0, 1.0, 1.0),
+                ..Transform::default()
+            },
+            None,
+        );
+        scene.update_world_transforms();
+        let objects = [(far, &mesh), (near, &mesh), (flat, &mesh)];
+
+        // The near square covers [10, 12] x [0, 2] after scaling, and the far one [10, 11] x [0, 1]
+        let (id, hit) = pick(&scene, objects.iter().copied(), &ray_down(10.5, 0.5), 100.0).unwrap();
+        assert_eq!(id, near);
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+
+        let (id, hit) = pick(&scene, objects.iter().copied(), &ray_down(10.5, 0.5), 5.0).unwrap();
+        assert_eq!(id, near);
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+
+        assert_eq!(
+            pick(&scene, objects.iter().copied(), &ray_down(11.5, 1.5), 100.0).map(|(id, _)| id),
+            Some(near)
+        );
+        assert!(pick(&scene, objects.iter().copied(), &ray_down(0.5, 0.5), 100.0).is_none());
+    }
+}
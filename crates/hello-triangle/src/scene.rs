@@ -0,0 +1,360 @@
+use cgmath::{Matrix4, One, Quaternion, SquareMatrix, Vector3};
+
+// Handle to a node stored in a Scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+// Translation, rotation, and scale of a node relative to its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Transform {
+    pub fn new(
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) -> Transform {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Builds the matrix applying scale, then rotation, then translation
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Quaternion::one(),
+            Vector3::new(1.0, 1.0, 1.0),
+        )
+    }
+}
+
+struct Node {
+    name: String,
+    local: Transform,
+    world: Matrix4<f32>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // Set when the local transform or parent changed since the last update_world_transforms()
+    dirty: bool,
+}
+
+// Hierarchy of nodes whose world transforms are derived from their ancestors' local transforms
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    // Adds a node as a child of parent, or as a root if parent is None
+    pub fn add_node(&mut self, name: &str, local: Transform, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.nodes.push(Node {
+            name: name.to_string(),
+            local,
+            world: Matrix4::identity(),
+            parent,
+            children: Vec::new(),
+            dirty: true,
+        });
+
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+
+        id
+    }
+
+    // Moves a node (and its subtree) under a new parent, or to the root level if parent is None
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        if let Some(parent) = parent {
+            assert!(
+                !self.is_ancestor_or_self(id, parent),
+                "A node can't be parented to itself or one of its descendants!"
+            );
+        }
+
+        // Detaches the node from its current parent
+        match self.nodes[id.0].parent {
+            Some(old_parent) => self.nodes[old_parent.0]
+                .children
+                .retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+
+        let node = &mut self.nodes[id.0];
+        node.parent = parent;
+        node.dirty = true;
+    }
+
+    pub fn set_local_transform(&mut self, id: NodeId, local: Transform) {
+        let node = &mut self.nodes[id.0];
+        node.local = local;
+        node.dirty = true;
+    }
+
+    pub fn local_transform(&self, id: NodeId) -> &Transform {
+        &self.nodes[id.0].local
+    }
+
+    // Only up to date after update_world_transforms() has been called
+    pub fn world_transform(&self, id: NodeId) -> &Matrix4<f32> {
+        &self.nodes[id.0].world
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.nodes[id.0].name
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    // Finds the first node with the given name
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .map(NodeId)
+    }
+
+    // Recomputes world transforms, skipping subtrees where neither a node nor its ancestors changed
+    pub fn update_world_transforms(&mut self) {
+        // Depth first traversal, carrying whether an ancestor's world transform changed
+        let mut stack: Vec<(NodeId, bool)> = self.roots.iter().map(|root| (*root, false)).collect();
+
+        while let Some((id, parent_changed)) = stack.pop() {
+            let changed = parent_changed || self.nodes[id.0].dirty;
+
+            if changed {
+                let parent_world = match self.nodes[id.0].parent {
+                    Some(parent) => self.nodes[parent.0].world,
+                    None => Matrix4::identity(),
+                };
+
+                let node = &mut self.nodes[id.0];
+                node.world = parent_world * node.local.to_matrix();
+                node.dirty = false;
+            }
+
+            stack.extend(
+                self.nodes[id.0]
+                    .children
+                    .iter()
+                    .map(|child| (*child, changed)),
+            );
+        }
+    }
+
+    // Checks whether ancestor is node itself or lies on the path from node to its root
+    fn is_ancestor_or_self(&self, ancestor: NodeId, node: NodeId) -> bool {
+        let mut current = Some(node);
+
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.nodes[id.0].parent;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, InnerSpace, Rotation3, Vector4};
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vector3::new(x, y, z),
+            ..Transform::default()
+        }
+    }
+
+    // Where a node's world transform puts its local origin
+    fn world_origin(scene: &Scene, id: NodeId) -> Vector3<f32> {
+        (*scene.world_transform(id) * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate()
+    }
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn nodes_are_added_under_their_parent() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", Transform::default(), None);
+        let child = scene.add_node("child", Transform::default(), Some(root));
+
+        assert_eq!(scene.roots(), &[root]);
+        assert_eq!(scene.children(root), &[child]);
+        assert_eq!(scene.parent(child), Some(root));
+        assert_eq!(scene.find("child"), Some(child));
+        assert_eq!(scene.find("missing"), None);
+        assert_eq!(scene.name(child), "child");
+    }
+
+    #[test]
+    fn world_transforms_apply_the_parent_after_the_child() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(
+            "parent",
+            Transform::new(
+                Vector3::new(1.0, 0.0, 0.0),
+                Quaternion::from_angle_z(Deg(90.0)),
+                Vector3::new(2.0, 2.0, 2.0),
+            ),
+            None,
+        );
+        let child = scene.add_node("child", translation(1.0, 0.0, 0.0), Some(parent));
+
+        scene.update_world_transforms();
+
+        assert!(close(
+            world_origin(&scene, parent),
+            Vector3::new(1.0, 0.0, 0.0)
+        ));
+        // Scaled to 2 along x, rotated onto y, then moved by the parent's translation
+        assert!(close(
+            world_origin(&scene, child),
+            Vector3::new(1.0, 2.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn parent_changes_reach_unchanged_descendants() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", Transform::default(), None);
+        let middle = scene.add_node("middle", translation(0.0, 1.0, 0.0), Some(root));
+        let leaf = scene.add_node("leaf", translation(0.0, 0.0, 1.0), Some(middle));
+        scene.update_world_transforms();
+
+        scene.set_local_transform(root, translation(5.0, 0.0, 0.0));
+        scene.update_world_transforms();
+
+        assert!(close(
+            world_origin(&scene, middle),
+            Vector3::new(5.0, 1.0, 0.0)
+        ));
+        assert!(close(
+            world_origin(&scene, leaf),
+            Vector3::new(5.0, 1.0, 1.0)
+        ));
+        assert_eq!(scene.local_transform(leaf), &translation(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reparenting_moves_the_subtree() {
+        let mut scene = Scene::new();
+        let first = scene.add_node("first", translation(1.0, 0.0, 0.0), None);
+        let second = scene.add_node("second", translation(0.0, 10.0, 0.0), None);
+        let node = scene.add_node("node", translation(0.0, 0.0, 1.0), Some(first));
+        let child = scene.add_node("child", translation(0.0, 0.0, 1.0), Some(node));
+        scene.update_world_transforms();
+
+        scene.set_parent(node, Some(second));
+        scene.update_world_transforms();
+
+        assert!(scene.children(first).is_empty());
+        assert_eq!(scene.children(second), &[node]);
+        assert_eq!(scene.parent(node), Some(second));
+        assert_eq!(scene.children(node), &[child]);
+        assert!(close(
+            world_origin(&scene, node),
+            Vector3::new(0.0, 10.0, 1.0)
+        ));
+        assert!(close(
+            world_origin(&scene, child),
+            Vector3::new(0.0, 10.0, 2.0)
+        ));
+    }
+
+    #[test]
+    fn reparenting_to_none_makes_a_root() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", translation(1.0, 0.0, 0.0), None);
+        let node = scene.add_node("node", translation(0.0, 1.0, 0.0), Some(root));
+
+        scene.set_parent(node, None);
+        scene.update_world_transforms();
+
+        assert_eq!(scene.roots(), &[root, node]);
+        assert!(scene.children(root).is_empty());
+        assert_eq!(scene.parent(node), None);
+        assert!(close(
+            world_origin(&scene, node),
+            Vector3::new(0.0, 1.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn ancestors_are_found_up_to_the_root() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", Transform::default(), None);
+        let middle = scene.add_node("middle", Transform::default(), Some(root));
+        let leaf = scene.add_node("leaf", Transform::default(), Some(middle));
+        let other = scene.add_node("other", Transform::default(), None);
+
+        assert!(scene.is_ancestor_or_self(root, leaf));
+        assert!(scene.is_ancestor_or_self(middle, leaf));
+        assert!(scene.is_ancestor_or_self(leaf, leaf));
+        assert!(!scene.is_ancestor_or_self(leaf, root));
+        assert!(!scene.is_ancestor_or_self(other, leaf));
+    }
+
+    #[test]
+    #[should_panic(expected = "A node can't be parented to itself or one of its descendants!")]
+    fn parenting_to_a_descendant_is_rejected() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", Transform::default(), None);
+        let middle = scene.add_node("middle", Transform::default(), Some(root));
+        let leaf = scene.add_node("leaf", Transform::default(), Some(middle));
+
+        scene.set_parent(root, Some(leaf));
+    }
+
+    #[test]
+    #[should_panic(expected = "A node can't be parented to itself or one of its descendants!")]
+    fn parenting_to_itself_is_rejected() {
+        let mut scene = Scene::new();
+        let node = scene.add_node("node", Transform::default(), None);
+
+        scene.set_parent(node, Some(node));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+// Splits real elapsed time into fixed size simulation steps, so updates are frame rate independent
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    last_time: Instant,
+    // Caps the steps taken per tick so a long stall (e.g. dragging the window) can't make the simulation spiral
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    // updates_per_second of 0 is treated as 1, as a simulation that never updates has no step length
+    pub fn new(updates_per_second: u32) -> FixedTimestep {
+        FixedTimestep {
+            step: Duration::from_secs(1) / updates_per_second.max(1),
+            accumulator: Duration::from_secs(0),
+            last_time: Instant::now(),
+            max_steps: 8,
+        }
+    }
+
+    // Length of one fixed step in seconds, to be passed to the update callback
+    pub fn step_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    // Adds the real time elapsed since the last tick, returning how many fixed updates should run now
+    pub fn tick(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last_time;
+        self.last_time = now;
+
+        self.advance(elapsed)
+    }
+
+    // How far (0 to 1) real time has progressed into the next step, for interpolating between the last two states
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    // Adds elapsed to the accumulator and takes as many steps as fit, separate from tick() so tests control time
+    fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+
+            // Drops whatever time is left rather than trying to catch up
+            if steps == self.max_steps {
+                self.accumulator = Duration::from_secs(0);
+                break;
+            }
+        }
+
+        steps
+    }
+}
+
+// Sleeps between frames to cap the frame rate, e.g. to the refresh rate of the monitor being presented to
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    last_frame: Instant,
+}
+
+impl FrameLimiter {
+    // A refresh rate of None leaves the frame rate uncapped
+    pub fn new(refresh_rate: Option<u16>) -> FrameLimiter {
+        let mut limiter = FrameLimiter {
+            frame_time: None,
+            last_frame: Instant::now(),
+        };
+        limiter.set_refresh_rate(refresh_rate);
+        limiter
+    }
+
+    pub fn set_refresh_rate(&mut self, refresh_rate: Option<u16>) {
+        self.frame_time = refresh_rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| Duration::from_secs(1) / rate as u32);
+    }
+
+    // Blocks until a full frame time has passed since the previous frame
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+
+        match self.frame_time {
+            Some(frame_time) if now < self.last_frame + frame_time => {
+                let deadline = self.last_frame + frame_time;
+                thread::sleep(deadline - now);
+                // Advancing by exactly one frame keeps the average rate on target despite sleep overshoot
+                self.last_frame = deadline;
+            }
+            _ => self.last_frame = now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_updates_per_second_is_clamped() {
+        let timestep = FixedTimestep::new(0);
+
+        assert_eq!(timestep.step_seconds(), 1.0);
+    }
+
+    #[test]
+    fn steps_are_taken_once_enough_time_builds_up() {
+        let mut timestep = FixedTimestep::new(10);
+
+        assert_eq!(timestep.advance(Duration::from_millis(50)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(60)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(200)), 2);
+    }
+
+    #[test]
+    fn alpha_is_the_fraction_of_a_step_left_over() {
+        let mut timestep = FixedTimestep::new(10);
+
+        assert_eq!(timestep.alpha(), 0.0);
+        timestep.advance(Duration::from_millis(125));
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_stalls_are_capped_and_dropped() {
+        let mut timestep = FixedTimestep::new(10);
+
+        assert_eq!(timestep.advance(Duration::from_secs(10)), 8);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(Duration::from_millis(50)), 0);
+    }
+
+    #[test]
+    fn uncapped_limiters_do_not_wait() {
+        for refresh_rate in [None, Some(0)].iter() {
+            let mut limiter = FrameLimiter::new(*refresh_rate);
+            assert_eq!(limiter.frame_time, None);
+
+            let start = Instant::now();
+            limiter.wait();
+            limiter.wait();
+            assert!(start.elapsed() < Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn capped_limiters_wait_out_each_frame() {
+        let mut limiter = FrameLimiter::new(Some(100));
+        assert_eq!(limiter.frame_time, Some(Duration::from_millis(10)));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.wait();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn refresh_rate_can_be_changed() {
+        let mut limiter = FrameLimiter::new(Some(60));
+
+        limiter.set_refresh_rate(Some(120));
+        assert_eq!(limiter.frame_time, Some(Duration::from_secs(1) / 120));
+
+        limiter.set_refresh_rate(None);
+        assert_eq!(limiter.frame_time, None);
+    }
+}
@@ -0,0 +1,55 @@
+use app::graphics::graphics_errors::GraphicsError;
+use app::graphics::vulkan_base::{VulkanBase, VulkanSettings, WindowDimensions};
+use golden::{GoldenTest, Image};
+
+// Same size as the headless_triangle example, so its output can be compared by eye
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+// Headless VulkanBase, or None when there is no Vulkan device to run on, in which case the test is skipped
+// Failures past creating the instance and device still fail the test, as they point at the renderer itself
+fn headless_base() -> Option<VulkanBase> {
+    let settings = VulkanSettings {
+        // Only one frame is drawn, so there is no point watching the sources
+        shader_hot_reload: false,
+        ..VulkanSettings::default()
+    };
+
+    match VulkanBase::new_headless(WIDTH, HEIGHT, &settings) {
+        Ok(vulkan) => Some(vulkan),
+        Err(error) => match error {
+            GraphicsError::Loading(_)
+            | GraphicsError::InstanceCreation(_)
+            | GraphicsError::InstanceLoading(_)
+            | GraphicsError::InvalidGPU
+            | GraphicsError::DeviceCreation(_) => {
+                eprintln!("Skipping, as no Vulkan device is available: {}", error);
+                None
+            }
+            _ => panic!("Could not set up Vulkan: {}", error),
+        },
+    }
+}
+
+// Draws one frame into the offscreen image and compares it against tests/golden/headless_triangle.png
+// Ignored until that reference is checked in - write it on a machine with a Vulkan device with
+// UPDATE_GOLDEN=1 cargo test -p hello-triangle --test golden -- --ignored
+// and check the PNG matches the headless_triangle example before committing it
+#[test]
+#[ignore]
+fn headless_triangle_matches_golden_image() {
+    let golden = GoldenTest::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"));
+    let mut vulkan = match headless_base() {
+        Some(vulkan) => vulkan,
+        None => return,
+    };
+
+    vulkan
+        .draw_frame(&WindowDimensions::new(WIDTH, HEIGHT))
+        .expect("Could not draw the frame!");
+    let pixels = vulkan
+        .read_pixels()
+        .expect("Could not read the frame back!");
+
+    golden.check("headless_triangle", &Image::new(WIDTH, HEIGHT, pixels));
+}
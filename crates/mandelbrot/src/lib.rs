@@ -0,0 +1,46 @@
+pub mod mandelbrot;
+
+use compute_context::compute_errors::ComputeError;
+use mandelbrot::{MandelbrotRenderer, View};
+use std::{env, fs::File, io::BufWriter};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+// Renders the Mandelbrot set without a window and saves it to the path given as the first argument
+pub fn run() -> Result<(), ComputeError> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("mandelbrot.png"));
+
+    let mut renderer = MandelbrotRenderer::new(WIDTH, HEIGHT)?;
+
+    // Frames the whole set, which spans roughly [-2.5, 1] on the real axis
+    let view = View {
+        center: [-0.75, 0.0],
+        scale: 3.5 / WIDTH as f32,
+        max_iterations: 512,
+    };
+
+    let pixels = renderer.render(&view)?;
+
+    save_png(&path, WIDTH, HEIGHT, &pixels);
+    println!("Saved {}x{} image to {}", WIDTH, HEIGHT, path);
+    Ok(())
+}
+
+// Writes tightly packed RGBA8 pixels to a PNG file
+fn save_png(path: &str, width: u32, height: u32, pixels: &[u8]) {
+    let file = File::create(path).expect("Could not create the output file!");
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder
+        .write_header()
+        .expect("Could not write the PNG header!");
+    writer
+        .write_image_data(pixels)
+        .expect("Could not write the PNG image data!");
+}
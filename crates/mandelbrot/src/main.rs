@@ -0,0 +1,5 @@
+use compute_context::compute_errors::ComputeError;
+
+fn main() -> Result<(), ComputeError> {
+    mandelbrot::run()
+}
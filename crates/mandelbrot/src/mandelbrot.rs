@@ -0,0 +1,137 @@
+use ash::vk;
+use compute_context::barrier::{self, Access};
+use compute_context::commands::CommandPool;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::readback::Readback;
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use compute_context::storage_image::StorageImage;
+use std::slice;
+
+// Must match local_size_x and local_size_y in mandelbrot.comp
+const WORKGROUP_SIZE: u32 = 16;
+
+// Tightly packed so the pixels can be handed straight to the PNG encoder
+const IMAGE_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const BYTES_PER_PIXEL: vk::DeviceSize = 4;
+
+// Region of the complex plane to render, laid out to match the shader's push constant block
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    pub center: [f32; 2],
+    pub scale: f32,
+    pub max_iterations: u32,
+}
+
+// Four 4 byte fields, so there is no padding and any bit pattern is valid
+unsafe impl Pod for View {}
+
+// Renders the set with a compute shader on a headless context, reading each image back into host memory
+pub struct MandelbrotRenderer {
+    image: StorageImage,
+    pipeline: ComputePipeline<View>,
+    readback: Readback,
+    command_pool: CommandPool,
+    context: ComputeContext,
+}
+
+impl MandelbrotRenderer {
+    pub fn new(width: u32, height: u32) -> Result<MandelbrotRenderer, ComputeError> {
+        let context = ComputeContext::new_headless("Mandelbrot")?;
+        let device = context.device();
+
+        let mut image = StorageImage::new(
+            &context,
+            IMAGE_FORMAT,
+            vk::Extent2D { width, height },
+            vk::ImageUsageFlags::TRANSFER_SRC,
+        )?;
+
+        let mut pipeline = MandelbrotRenderer::create_pipeline(&context, &image)
+            .inspect_err(|_| image.destroy(device))?;
+
+        let mut readback = Readback::new(&context, &image, BYTES_PER_PIXEL).inspect_err(|_| {
+            pipeline.destroy(device);
+            image.destroy(device);
+        })?;
+
+        let command_pool =
+            CommandPool::new(device, context.queue_family_index(), 0).inspect_err(|_| {
+                readback.destroy(device);
+                pipeline.destroy(device);
+                image.destroy(device);
+            })?;
+
+        Ok(MandelbrotRenderer {
+            image,
+            pipeline,
+            readback,
+            command_pool,
+            context,
+        })
+    }
+
+    // Renders view and returns the image as tightly packed RGBA8 rows from top to bottom, waiting for the GPU
+    pub fn render(&mut self, view: &View) -> Result<Vec<u8>, ComputeError> {
+        let device = self.context.device();
+        let extent = self.image.extent();
+        let group_count = [
+            pipeline::group_count(extent.width, WORKGROUP_SIZE),
+            pipeline::group_count(extent.height, WORKGROUP_SIZE),
+            1,
+        ];
+
+        let image = &self.image;
+        let pipeline = &self.pipeline;
+        let readback = &self.readback;
+        self.command_pool
+            .submit_once(device, self.context.queue(), |command_buffer| {
+                // The whole image is overwritten, so its previous contents are discarded on the way to GENERAL
+                barrier::record_image_barrier(
+                    device,
+                    command_buffer,
+                    image.image(),
+                    [vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL],
+                    Access::NONE,
+                    Access::COMPUTE_WRITE,
+                );
+                pipeline.dispatch(device, command_buffer, group_count, view);
+                readback.record_copy(device, command_buffer, Access::COMPUTE_WRITE);
+            })?;
+
+        // submit_once() waited for the copy, so it can be read straight away
+        self.readback.read(device)
+    }
+
+    fn create_pipeline(
+        context: &ComputeContext,
+        image: &StorageImage,
+    ) -> Result<ComputePipeline<View>, ComputeError> {
+        let code = ShaderCompiler::new()?.compile(
+            include_str!("shaders/mandelbrot.comp"),
+            ShaderKind::Compute,
+            "mandelbrot.comp",
+            "mandelbrot",
+        )?;
+
+        ComputePipeline::new(
+            context.device(),
+            &code,
+            slice::from_ref(&Binding::StorageImage(image.view())),
+        )
+    }
+}
+
+impl Drop for MandelbrotRenderer {
+    fn drop(&mut self) {
+        // Every render waits for its submission, so nothing is still using these
+        let device = self.context.device();
+        self.command_pool.destroy(device);
+        self.readback.destroy(device);
+        self.pipeline.destroy(device);
+        self.image.destroy(device);
+    }
+}
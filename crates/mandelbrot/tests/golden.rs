@@ -0,0 +1,70 @@
+use compute_context::compute_errors::ComputeError;
+use golden::{GoldenTest, Image};
+use mandelbrot::mandelbrot::{MandelbrotRenderer, View};
+
+// Kept small so the whole suite renders in well under a second
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+// Named views whose renders are compared against tests/golden/<name>.png
+fn scenes() -> Vec<(&'static str, View)> {
+    vec![
+        (
+            "full_set",
+            View {
+                center: [-0.75, 0.0],
+                scale: 3.0 / WIDTH as f32,
+                max_iterations: 256,
+            },
+        ),
+        (
+            "seahorse_valley",
+            View {
+                center: [-0.745, 0.11],
+                scale: 0.02 / WIDTH as f32,
+                max_iterations: 1024,
+            },
+        ),
+        (
+            "elephant_valley",
+            View {
+                center: [0.28, 0.008],
+                scale: 0.02 / WIDTH as f32,
+                max_iterations: 1024,
+            },
+        ),
+    ]
+}
+
+// Renderer, or None when there is no Vulkan device to run on, in which case the test is skipped
+// Failures past creating the instance and device still fail the test, as they point at the renderer itself
+fn renderer() -> Option<MandelbrotRenderer> {
+    match MandelbrotRenderer::new(WIDTH, HEIGHT) {
+        Ok(renderer) => Some(renderer),
+        Err(error) => match error {
+            ComputeError::Loading(_)
+            | ComputeError::InstanceCreation(_)
+            | ComputeError::InstanceLoading(_)
+            | ComputeError::NoSuitableDevice
+            | ComputeError::DeviceCreation(_) => {
+                eprintln!("Skipping, as no Vulkan device is available: {}", error);
+                None
+            }
+            _ => panic!("Could not set up the renderer: {}", error),
+        },
+    }
+}
+
+#[test]
+fn mandelbrot_matches_golden_images() {
+    let golden = GoldenTest::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"));
+    let mut renderer = match renderer() {
+        Some(renderer) => renderer,
+        None => return,
+    };
+
+    for (name, view) in scenes() {
+        let pixels = renderer.render(&view).expect("Could not render the view!");
+        golden.check(name, &Image::new(WIDTH, HEIGHT, pixels));
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::{self, Write};
+
+// First four bytes of every OpenEXR file
+const MAGIC: u32 = 20_000_630;
+
+// Single part scanline file, with no flags set
+const VERSION: u32 = 2;
+
+// Pixel type of 32 bit float channels
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+// Channels are stored in alphabetical order, which the format requires of the channel list
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+// Writes width * height linear RGB pixels, in rows from top to bottom, as an uncompressed scanline OpenEXR image
+// Every row is its own chunk, which is how uncompressed files are laid out
+pub fn write_rgb<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[[f32; 3]],
+) -> io::Result<()> {
+    assert!(width > 0 && height > 0, "EXR images must not be empty!");
+    assert_eq!(
+        pixels.len(),
+        width as usize * height as usize,
+        "Pixel count does not match the image size!"
+    );
+
+    let header = header(width, height);
+    writer.write_all(&header)?;
+
+    // Each chunk is its row's y coordinate and data size, followed by the row's samples one channel at a time
+    let row_size = width as usize * CHANNELS.len() * 4;
+    let chunk_size = (8 + row_size) as u64;
+    let offset_table_size = height as u64 * 8;
+    for row in 0..height as u64 {
+        let offset = header.len() as u64 + offset_table_size + row * chunk_size;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+
+    let mut chunk = Vec::with_capacity(8 + row_size);
+    for (y, row) in pixels.chunks(width as usize).enumerate() {
+        chunk.clear();
+        chunk.extend_from_slice(&(y as i32).to_le_bytes());
+        chunk.extend_from_slice(&(row_size as i32).to_le_bytes());
+
+        // B, G and R are channels 2, 1 and 0 of each pixel
+        for channel in (0..CHANNELS.len()).rev() {
+            for pixel in row {
+                chunk.extend_from_slice(&pixel[channel].to_le_bytes());
+            }
+        }
+
+        writer.write_all(&chunk)?;
+    }
+
+    Ok(())
+}
+
+// Magic number, version and the attributes every scanline file must have, ending with the null byte after the last
+fn header(width: u32, height: u32) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut channels = Vec::new();
+    for name in CHANNELS.iter() {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // pLinear and three reserved bytes
+        channels.extend_from_slice(&[0; 4]);
+        // x and y sampling
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    // Inclusive pixel bounds, as xMin, yMin, xMax, yMax
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|value: &i32| value.to_le_bytes().to_vec())
+        .collect();
+
+    write_attribute(&mut header, "channels", "chlist", &channels);
+    write_attribute(&mut header, "compression", "compression", &[0]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    // Increasing y, so rows are stored from top to bottom
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    header
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn read_u64(bytes: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+    }
+
+    fn read_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_f32(bytes: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    // Finds an attribute by name and returns its value
+    fn attribute<'a>(header: &'a [u8], name: &str) -> &'a [u8] {
+        let mut at = 8;
+        while header[at] != 0 {
+            let name_end = at + header[at..].iter().position(|byte| *byte == 0).unwrap();
+            let kind_end = name_end
+                + 1
+                + header[name_end + 1..]
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap();
+            let size = read_i32(header, kind_end + 1) as usize;
+            let value = &header[kind_end + 5..kind_end + 5 + size];
+
+            if &header[at..name_end] == name.as_bytes() {
+                return value;
+            }
+            at = kind_end + 5 + size;
+        }
+        panic!("Missing attribute {}", name);
+    }
+
+    #[test]
+    fn headers_start_with_the_magic_number_and_version() {
+        let header = header(4, 3);
+
+        assert_eq!(&header[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        assert_eq!(&header[4..8], &[2, 0, 0, 0]);
+        assert_eq!(header.last(), Some(&0));
+    }
+
+    #[test]
+    fn headers_hold_the_image_bounds_and_sorted_channels() {
+        let header = header(4, 3);
+
+        let window = attribute(&header, "dataWindow");
+        assert_eq!(
+            (0..4).map(|i| read_i32(window, i * 4)).collect::<Vec<_>>(),
+            vec![0, 0, 3, 2]
+        );
+        assert_eq!(attribute(&header, "displayWindow"), window);
+        assert_eq!(attribute(&header, "compression"), &[0]);
+
+        let channels = attribute(&header, "channels");
+        assert_eq!(channels.len(), 3 * 18 + 1);
+        for (i, name) in [b'B', b'G', b'R'].iter().enumerate() {
+            let channel = &channels[i * 18..(i + 1) * 18];
+            assert_eq!(&channel[..2], &[*name, 0]);
+            assert_eq!(read_i32(channel, 2), PIXEL_TYPE_FLOAT);
+        }
+    }
+
+    #[test]
+    fn offsets_point_at_each_row() {
+        let pixels: Vec<[f32; 3]> = (0..6)
+            .map(|i| [i as f32, i as f32 + 0.25, i as f32 + 0.5])
+            .collect();
+        let mut file = Vec::new();
+        write_rgb(&mut file, 2, 3, &pixels).unwrap();
+
+        let header_size = header(2, 3).len();
+        let row_size = 2 * 3 * 4;
+        assert_eq!(file.len(), header_size + 3 * 8 + 3 * (8 + row_size));
+
+        for row in 0..3 {
+            let offset = read_u64(&file, header_size + row * 8) as usize;
+            assert_eq!(read_i32(&file, offset), row as i32);
+            assert_eq!(read_i32(&file, offset + 4), row_size as i32);
+        }
+    }
+
+    #[test]
+    fn rows_are_stored_one_channel_at_a_time() {
+        let pixels = [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+        let mut file = Vec::new();
+        write_rgb(&mut file, 2, 1, &pixels).unwrap();
+
+        let data = header(2, 1).len() + 8 + 8;
+        let samples: Vec<f32> = (0..6).map(|i| read_f32(&file, data + i * 4)).collect();
+
+        // Blue, then green, then red, each for both pixels
+        assert_eq!(samples, vec![3.0, 6.0, 2.0, 5.0, 1.0, 4.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pixel count does not match the image size!")]
+    fn pixel_counts_must_match_the_size() {
+        write_rgb(&mut Vec::new(), 2, 2, &[[0.0; 3]; 3]).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+mod exr;
+mod tracer;
+
+use compute_context::compute_errors::ComputeError;
+use std::{env, fs::File, io::BufWriter};
+use tracer::{PathTracer, HEIGHT, WIDTH};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// Traces a scene progressively into a window, saving the average of every sample taken to the EXR file given as the
+// first argument when the window is closed
+pub fn run() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("path-tracer.exr"));
+
+    let event_loop = EventLoop::new();
+
+    // Creates a window using a WindowBuilder
+    let window = WindowBuilder::new()
+        .with_title("Path Tracer")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)
+        .expect("Could not create a window!");
+
+    // Creates all the vulkan state, including the storage images the samples are summed and averaged in
+    let mut tracer = match PathTracer::new(&window) {
+        Ok(tracer) => tracer,
+        Err(error) => {
+            eprintln!("Vulkan error: {}", error);
+            return;
+        }
+    };
+
+    event_loop.run(move |event, _, control_flow| {
+        // Continually runs the event loop - presentation is paced by FIFO
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                println!("Close button was pressed");
+                match tracer.capture() {
+                    Ok(pixels) => {
+                        save_exr(&path, &pixels);
+                        println!(
+                            "Saved {}x{} image with {} samples per pixel to {}",
+                            WIDTH,
+                            HEIGHT,
+                            tracer.samples(),
+                            path
+                        );
+                    }
+                    Err(error) => eprintln!("Vulkan error: {}", error),
+                }
+                *control_flow = ControlFlow::Exit;
+            }
+            // A minimized window has a zero sized surface, so the old swapchain is kept until it is restored
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } if size.width > 0 && size.height > 0 => {
+                exit_on_error(tracer.resize(size.width, size.height), control_flow)
+            }
+            // Adds a sample and presents the image so far
+            Event::MainEventsCleared => {
+                let size = window.inner_size();
+
+                if size.width > 0 && size.height > 0 {
+                    exit_on_error(tracer.draw(), control_flow);
+                }
+            }
+            _ => (),
+        }
+    })
+}
+
+// Reports a Vulkan failure and exits, as tracing can't carry on without Vulkan
+fn exit_on_error(result: Result<(), ComputeError>, control_flow: &mut ControlFlow) {
+    if let Err(error) = result {
+        eprintln!("Vulkan error: {}", error);
+        *control_flow = ControlFlow::Exit;
+    }
+}
+
+// Writes linear RGB pixels to an EXR file
+fn save_exr(path: &str, pixels: &[[f32; 3]]) {
+    let file = File::create(path).expect("Could not create the output file!");
+
+    exr::write_rgb(&mut BufWriter::new(file), WIDTH, HEIGHT, pixels)
+        .expect("Could not write the EXR image!");
+}
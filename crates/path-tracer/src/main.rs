@@ -0,0 +1,3 @@
+fn main() {
+    path_tracer::run();
+}
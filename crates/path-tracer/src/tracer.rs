@@ -0,0 +1,341 @@
+use ash::{vk, Device};
+use compute_context::barrier::{self, Access};
+use compute_context::blit::{self, Letterbox};
+use compute_context::commands::CommandPool;
+use compute_context::compute_errors::ComputeError;
+use compute_context::context::ComputeContext;
+use compute_context::pipeline::{self, Binding, ComputePipeline};
+use compute_context::pod::Pod;
+use compute_context::presenter::{Frame, Presenter};
+use compute_context::readback::Readback;
+use compute_context::shaders::{ShaderCompiler, ShaderKind};
+use compute_context::storage_image::StorageImage;
+use std::convert::TryInto;
+use winit::window::Window;
+
+// Resolution traced at, letterboxed into the window so resizing doesn't throw the accumulated samples away
+pub const WIDTH: u32 = 960;
+pub const HEIGHT: u32 = 540;
+
+// Must match local_size_x and local_size_y in path_trace.comp
+const WORKGROUP_SIZE: u32 = 16;
+
+// Summed samples need the range and precision of 32 bit floats
+const ACCUMULATION_FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+const ACCUMULATION_TEXEL_SIZE: vk::DeviceSize = 16;
+
+// Linear average blitted to the window, in a format every device can store to and blit from
+const DISPLAY_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+// Tracing stops adding samples once this many have been taken per pixel, as the noise left is no longer visible
+const MAX_SAMPLES: u32 = 4096;
+
+const MAX_BOUNCES: u32 = 8;
+
+// Laid out to match the shader's push constant block
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Sample {
+    sample_index: u32,
+    max_bounces: u32,
+}
+
+// Two u32s, so there is no padding and any bit pattern is valid
+unsafe impl Pod for Sample {}
+
+// Window's Vulkan state and the image being traced with it
+pub struct PathTracer {
+    tracer: Tracer,
+    presenter: Presenter,
+    context: ComputeContext,
+}
+
+impl PathTracer {
+    pub fn new(window: &Window) -> Result<PathTracer, ComputeError> {
+        let size = window.inner_size();
+        let context = ComputeContext::new(window, "Path Tracer")?;
+        let mut presenter = Presenter::new(&context, size.width, size.height)?;
+        let tracer = Tracer::new(&context).inspect_err(|_| presenter.destroy(context.device()))?;
+
+        Ok(PathTracer {
+            tracer,
+            presenter,
+            context,
+        })
+    }
+
+    // Samples taken per pixel so far
+    pub fn samples(&self) -> u32 {
+        self.tracer.samples
+    }
+
+    // Rebuilds the swapchain for the new window size
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), ComputeError> {
+        self.presenter.resize(&self.context, width, height)
+    }
+
+    // Adds a sample per pixel, until MAX_SAMPLES have been taken, and presents the average so far
+    pub fn draw(&mut self) -> Result<(), ComputeError> {
+        let tracer = &mut self.tracer;
+
+        self.presenter
+            .draw(&self.context, |device, command_buffer, frame| {
+                if tracer.samples < MAX_SAMPLES {
+                    tracer.record_sample(device, command_buffer);
+                }
+                tracer.record_blit(device, command_buffer, frame);
+            })
+    }
+
+    // Reads back the average of the samples so far, as linear RGB rows from top to bottom, waiting for the GPU
+    pub fn capture(&mut self) -> Result<Vec<[f32; 3]>, ComputeError> {
+        // Frames in flight may still be adding samples
+        unsafe { self.context.device().device_wait_idle()? };
+        self.tracer.capture(&self.context)
+    }
+}
+
+impl Drop for PathTracer {
+    fn drop(&mut self) {
+        // Frames may still be tracing - if the device was lost there is nothing left to wait for
+        let device = self.context.device();
+        unsafe { device.device_wait_idle().ok() };
+        self.tracer.destroy(device);
+        self.presenter.destroy(device);
+    }
+}
+
+// Progressive path tracer adding one sample per pixel each frame to a running sum
+// Samples and blits are recorded into each frame's command buffer on the one queue, so frames in flight are kept apart
+// by barriers within the queue rather than by waiting on the CPU
+struct Tracer {
+    accumulation: StorageImage,
+    display: StorageImage,
+    pipeline: ComputePipeline<Sample>,
+    command_pool: CommandPool,
+    samples: u32,
+    letterbox: Letterbox,
+}
+
+impl Tracer {
+    fn new(context: &ComputeContext) -> Result<Tracer, ComputeError> {
+        let device = context.device();
+        let extent = vk::Extent2D {
+            width: WIDTH,
+            height: HEIGHT,
+        };
+
+        // Read back for the EXR and blitted to the window respectively
+        let mut accumulation = StorageImage::new(
+            context,
+            ACCUMULATION_FORMAT,
+            extent,
+            vk::ImageUsageFlags::TRANSFER_SRC,
+        )?;
+        let mut display = StorageImage::new(
+            context,
+            DISPLAY_FORMAT,
+            extent,
+            vk::ImageUsageFlags::TRANSFER_SRC,
+        )
+        .inspect_err(|_| accumulation.destroy(device))?;
+
+        let mut pipeline =
+            Tracer::create_pipeline(device, &accumulation, &display).inspect_err(|_| {
+                display.destroy(device);
+                accumulation.destroy(device);
+            })?;
+
+        let command_pool =
+            CommandPool::new(device, context.queue_family_index(), 0).inspect_err(|_| {
+                pipeline.destroy(device);
+                display.destroy(device);
+                accumulation.destroy(device);
+            })?;
+
+        let mut tracer = Tracer {
+            accumulation,
+            display,
+            pipeline,
+            command_pool,
+            samples: 0,
+            letterbox: Letterbox::new(WIDTH, HEIGHT),
+        };
+
+        tracer
+            .move_to_general(context)
+            .inspect_err(|_| tracer.destroy(device))?;
+
+        Ok(tracer)
+    }
+
+    // Records a dispatch adding the next sample to every pixel and refreshing the displayed average
+    fn record_sample(&mut self, device: &Device, command_buffer: vk::CommandBuffer) {
+        // The last sample's sum is read again, and the last blit must finish reading the average before it is
+        // overwritten
+        barrier::record_memory_barrier(
+            device,
+            command_buffer,
+            Access::COMPUTE_WRITE.and(Access::new(
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::empty(),
+            )),
+            Access::COMPUTE_READ.and(Access::COMPUTE_WRITE),
+        );
+
+        let push_constants = Sample {
+            sample_index: self.samples,
+            max_bounces: MAX_BOUNCES,
+        };
+        self.pipeline.dispatch(
+            device,
+            command_buffer,
+            [
+                pipeline::group_count(WIDTH, WORKGROUP_SIZE),
+                pipeline::group_count(HEIGHT, WORKGROUP_SIZE),
+                1,
+            ],
+            &push_constants,
+        );
+
+        // The average is blitted to the frame next
+        barrier::record_memory_barrier(
+            device,
+            command_buffer,
+            Access::COMPUTE_WRITE,
+            Access::TRANSFER_READ,
+        );
+
+        self.samples += 1;
+    }
+
+    // Records clearing the frame's image and blitting the average onto the letterboxed area
+    fn record_blit(&self, device: &Device, command_buffer: vk::CommandBuffer, frame: &Frame) {
+        blit::record_letterboxed_blit(
+            device,
+            command_buffer,
+            self.display.image(),
+            self.display.extent(),
+            frame,
+            &self.letterbox,
+            vk::Filter::LINEAR,
+        );
+    }
+
+    // Copies the running sum back and divides it by the number of samples, with no frames in flight
+    fn capture(&self, context: &ComputeContext) -> Result<Vec<[f32; 3]>, ComputeError> {
+        let device = context.device();
+        let mut readback = Readback::new(context, &self.accumulation, ACCUMULATION_TEXEL_SIZE)?;
+
+        let texels = self
+            .command_pool
+            .submit_once(device, context.queue(), |command_buffer| {
+                readback.record_copy(device, command_buffer, Access::COMPUTE_WRITE)
+            })
+            .and_then(|()| readback.read(device));
+        readback.destroy(device);
+
+        Ok(average(&texels?, self.samples))
+    }
+
+    // Destroys the pipeline, images, and command pool - must be called before the context is dropped
+    fn destroy(&mut self, device: &Device) {
+        self.command_pool.destroy(device);
+        self.pipeline.destroy(device);
+        self.display.destroy(device);
+        self.accumulation.destroy(device);
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        accumulation: &StorageImage,
+        display: &StorageImage,
+    ) -> Result<ComputePipeline<Sample>, ComputeError> {
+        let code = ShaderCompiler::new()?.compile(
+            include_str!("shaders/path_trace.comp"),
+            ShaderKind::Compute,
+            "path_trace.comp",
+            "path trace",
+        )?;
+
+        ComputePipeline::new(
+            device,
+            &code,
+            &[
+                Binding::StorageImage(accumulation.view()),
+                Binding::StorageImage(display.view()),
+            ],
+        )
+    }
+
+    // Moves both images to GENERAL, where they stay for good
+    // Their contents start undefined, which the first sample ignores
+    fn move_to_general(&self, context: &ComputeContext) -> Result<(), ComputeError> {
+        let device = context.device();
+
+        self.command_pool
+            .submit_once(device, context.queue(), |command_buffer| {
+                for image in [&self.accumulation, &self.display].iter() {
+                    barrier::record_image_barrier(
+                        device,
+                        command_buffer,
+                        image.image(),
+                        [vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL],
+                        Access::NONE,
+                        Access::COMPUTE_READ.and(Access::COMPUTE_WRITE),
+                    );
+                }
+            })
+    }
+}
+
+// Divides tightly packed RGBA32F sums by the number of samples taken, dropping alpha
+// The sums are undefined until the first sample, so with none taken the image comes out black
+fn average(texels: &[u8], samples: u32) -> Vec<[f32; 3]> {
+    let texel_size = ACCUMULATION_TEXEL_SIZE as usize;
+    if samples == 0 {
+        return vec![[0.0; 3]; texels.len() / texel_size];
+    }
+
+    let scale = 1.0 / samples as f32;
+    texels
+        .chunks_exact(texel_size)
+        .map(|texel| {
+            let channel = |i: usize| {
+                let bytes = texel[i * 4..i * 4 + 4].try_into().unwrap();
+                f32::from_ne_bytes(bytes) * scale
+            };
+            [channel(0), channel(1), channel(2)]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texel(rgba: [f32; 4]) -> Vec<u8> {
+        rgba.iter()
+            .flat_map(|value| value.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn sums_are_divided_by_the_sample_count() {
+        let mut texels = texel([4.0, 8.0, 2.0, 1.0]);
+        texels.extend(texel([0.0, 1.0, 3.0, 1.0]));
+
+        assert_eq!(
+            average(&texels, 4),
+            vec![[1.0, 2.0, 0.5], [0.0, 0.25, 0.75]]
+        );
+    }
+
+    #[test]
+    fn untraced_images_are_black() {
+        assert_eq!(
+            average(&texel([5.0, 5.0, 5.0, 1.0]), 0),
+            vec![[0.0, 0.0, 0.0]]
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+const MAGIC_NUMBER: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// Deepest nesting of arrays, structs, vectors, and matrices followed when sizing and naming types, far beyond what
+// real shaders use, so malformed modules with types containing themselves fail instead of overflowing the stack
+const MAX_TYPE_DEPTH: u32 = 64;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Decorations
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_OUTPUT: u32 = 3;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReflectError {
+    #[error("SPIR-V must be a whole number of 32 bit words, got {0} bytes")]
+    UnalignedLength(usize),
+    #[error("SPIR-V is too short to contain a header")]
+    MissingHeader,
+    #[error("Not SPIR-V - bad magic number {0:#010x}")]
+    BadMagicNumber(u32),
+    #[error("Instruction at word {0} runs past the end of the module")]
+    TruncatedInstruction(usize),
+    #[error("Type %{0} is nested too deeply or contains itself")]
+    TypeTooDeep(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Vertex,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+    Other(u32),
+}
+
+impl Stage {
+    fn from_execution_model(execution_model: u32) -> Stage {
+        match execution_model {
+            0 => Stage::Vertex,
+            1 => Stage::TessellationControl,
+            2 => Stage::TessellationEvaluation,
+            3 => Stage::Geometry,
+            4 => Stage::Fragment,
+            5 => Stage::Compute,
+            other => Stage::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DescriptorType {
+    Sampler,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+// A stage input or output, either at a location or a built-in such as gl_Position
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceVariable {
+    pub name: String,
+    pub location: Option<u32>,
+    // Names of the built-ins, more than one for blocks like gl_PerVertex
+    pub built_ins: Vec<String>,
+    pub type_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: Stage,
+    // Only set for compute shaders
+    pub local_size: Option<[u32; 3]>,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    // Array length, 0 for runtime sized arrays
+    pub count: u32,
+    pub name: String,
+    pub type_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushConstantRange {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+// Everything a pipeline layout needs to know about a SPIR-V module
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    pub version: (u32, u32),
+    pub entry_points: Vec<EntryPoint>,
+    // Sorted by set, then binding
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constants: Vec<PushConstantRange>,
+}
+
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+struct Variable {
+    id: u32,
+    type_id: u32,
+    storage_class: u32,
+}
+
+struct RawEntryPoint {
+    id: u32,
+    name: String,
+    stage: Stage,
+    interface: Vec<u32>,
+}
+
+// Result ids and their decorations, gathered in a single pass before anything is resolved
+#[derive(Default)]
+struct Parser {
+    names: HashMap<u32, String>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    types: HashMap<u32, Type>,
+    // Only 32 bit integer constants matter, as array lengths
+    constants: HashMap<u32, u32>,
+    variables: Vec<Variable>,
+    entry_points: Vec<RawEntryPoint>,
+    local_sizes: HashMap<u32, [u32; 3]>,
+}
+
+// Parses SPIR-V bytes (of either endianness) into its entry points and resource interface
+pub fn reflect(bytes: &[u8]) -> Result<Module, ReflectError> {
+    if bytes.len() % 4 != 0 {
+        return Err(ReflectError::UnalignedLength(bytes.len()));
+    }
+
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+
+    reflect_words(words)
+}
+
+// Same as reflect, for SPIR-V already split into words as Vulkan takes it, e.g. from ash::util::read_spv
+pub fn reflect_words(mut words: Vec<u32>) -> Result<Module, ReflectError> {
+    if words.len() < HEADER_WORDS {
+        return Err(ReflectError::MissingHeader);
+    }
+
+    if words[0] == MAGIC_NUMBER.swap_bytes() {
+        for word in words.iter_mut() {
+            *word = word.swap_bytes();
+        }
+    }
+
+    if words[0] != MAGIC_NUMBER {
+        return Err(ReflectError::BadMagicNumber(words[0]));
+    }
+
+    let version = ((words[1] >> 16) & 0xff, (words[1] >> 8) & 0xff);
+
+    let mut parser = Parser::default();
+    let mut index = HEADER_WORDS;
+
+    while index < words.len() {
+        let word_count = (words[index] >> 16) as usize;
+        let opcode = words[index] & 0xffff;
+
+        if word_count == 0 || index + word_count > words.len() {
+            return Err(ReflectError::TruncatedInstruction(index));
+        }
+
+        parser.parse_instruction(opcode, &words[index + 1..index + word_count]);
+        index += word_count;
+    }
+
+    parser.into_module(version)
+}
+
+impl Parser {
+    fn parse_instruction(&mut self, opcode: u32, operands: &[u32]) {
+        // Operands missing from malformed instructions read as 0 rather than indexing out of bounds
+        let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+
+        match opcode {
+            OP_NAME if !operands.is_empty() => {
+                self.names.insert(operands[0], parse_string(&operands[1..]));
+            }
+            OP_ENTRY_POINT if operands.len() >= 3 => {
+                let name = parse_string(&operands[2..]);
+                // The interface ids follow the nul terminated name
+                let name_words = name.len() / 4 + 1;
+
+                self.entry_points.push(RawEntryPoint {
+                    id: operands[1],
+                    name,
+                    stage: Stage::from_execution_model(operands[0]),
+                    interface: operands[(2 + name_words).min(operands.len())..].to_vec(),
+                });
+            }
+            OP_EXECUTION_MODE if operand(1) == EXECUTION_MODE_LOCAL_SIZE => {
+                self.local_sizes
+                    .insert(operand(0), [operand(2), operand(3), operand(4)]);
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(0), Type::Bool);
+            }
+            OP_TYPE_INT => {
+                self.types.insert(
+                    operand(0),
+                    Type::Int {
+                        width: operand(1),
+                        signed: operand(2) == 1,
+                    },
+                );
+            }
+            OP_TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0), Type::Float { width: operand(1) });
+            }
+            OP_TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0),
+                    Type::Vector {
+                        component: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0),
+                    Type::Matrix {
+                        column: operand(1),
+                        count: operand(2),
+                    },
+                );
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0),
+                    Type::Image {
+                        dim: operand(2),
+                        sampled: operand(6),
+                    },
+                );
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0), Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0), Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                // The length is the id of a constant, which always precedes the array type
+                let length = self.constants.get(&operand(2)).copied().unwrap_or(0);
+
+                self.types.insert(
+                    operand(0),
+                    Type::Array {
+                        element: operand(1),
+                        length,
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(
+                    operand(0),
+                    Type::RuntimeArray {
+                        element: operand(1),
+                    },
+                );
+            }
+            OP_TYPE_STRUCT if !operands.is_empty() => {
+                self.types.insert(
+                    operands[0],
+                    Type::Struct {
+                        members: operands[1..].to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(
+                    operand(0),
+                    Type::Pointer {
+                        pointee: operand(2),
+                    },
+                );
+            }
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+            }
+            OP_VARIABLE => {
+                self.variables.push(Variable {
+                    id: operand(1),
+                    type_id: operand(0),
+                    storage_class: operand(2),
+                });
+            }
+            OP_DECORATE if operands.len() >= 2 => {
+                self.decorations
+                    .insert((operands[0], operands[1]), operand(2));
+            }
+            OP_MEMBER_DECORATE if operands.len() >= 3 => {
+                self.member_decorations
+                    .insert((operands[0], operands[1], operands[2]), operand(3));
+            }
+            _ => (),
+        }
+    }
+
+    fn into_module(self, version: (u32, u32)) -> Result<Module, ReflectError> {
+        let entry_points = self
+            .entry_points
+            .iter()
+            .map(|entry_point| EntryPoint {
+                name: entry_point.name.clone(),
+                stage: entry_point.stage,
+                local_size: self.local_sizes.get(&entry_point.id).copied(),
+                inputs: self.interface_variables(&entry_point.interface, STORAGE_CLASS_INPUT),
+                outputs: self.interface_variables(&entry_point.interface, STORAGE_CLASS_OUTPUT),
+            })
+            .collect();
+
+        let mut descriptor_bindings: Vec<DescriptorBinding> = self
+            .variables
+            .iter()
+            .filter_map(|variable| self.descriptor_binding(variable))
+            .collect();
+        descriptor_bindings.sort_by_key(|binding| (binding.set, binding.binding));
+
+        let push_constants = self
+            .variables
+            .iter()
+            .filter(|variable| variable.storage_class == STORAGE_CLASS_PUSH_CONSTANT)
+            .map(|variable| self.push_constant_range(variable))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Module {
+            version,
+            entry_points,
+            descriptor_bindings,
+            push_constants,
+        })
+    }
+
+    fn interface_variables(&self, interface: &[u32], storage_class: u32) -> Vec<InterfaceVariable> {
+        let mut variables: Vec<InterfaceVariable> = self
+            .variables
+            .iter()
+            .filter(|variable| {
+                variable.storage_class == storage_class && interface.contains(&variable.id)
+            })
+            .map(|variable| {
+                let type_id = self.pointee(variable.type_id);
+
+                let built_ins = match self.decorations.get(&(variable.id, DECORATION_BUILT_IN)) {
+                    Some(built_in) => vec![built_in_name(*built_in)],
+                    // Blocks such as gl_PerVertex decorate their members instead
+                    None => match self.types.get(&type_id) {
+                        Some(Type::Struct { members }) => (0..members.len() as u32)
+                            .filter_map(|member| {
+                                self.member_decorations
+                                    .get(&(type_id, member, DECORATION_BUILT_IN))
+                            })
+                            .map(|built_in| built_in_name(*built_in))
+                            .collect(),
+                        _ => Vec::new(),
+                    },
+                };
+
+                InterfaceVariable {
+                    name: self.name(variable.id),
+                    location: self
+                        .decorations
+                        .get(&(variable.id, DECORATION_LOCATION))
+                        .copied(),
+                    built_ins,
+                    type_name: self.type_name(type_id),
+                }
+            })
+            .collect();
+
+        // Locations first in order, then built-ins
+        variables.sort_by_key(|variable| variable.location.unwrap_or(u32::MAX));
+        variables
+    }
+
+    fn descriptor_binding(&self, variable: &Variable) -> Option<DescriptorBinding> {
+        let set = *self
+            .decorations
+            .get(&(variable.id, DECORATION_DESCRIPTOR_SET))?;
+        let binding = *self.decorations.get(&(variable.id, DECORATION_BINDING))?;
+
+        // Arrays of descriptors are described by their element type
+        let type_id = self.pointee(variable.type_id);
+        let (element_id, count) = match self.types.get(&type_id)? {
+            Type::Array { element, length } => (*element, *length),
+            Type::RuntimeArray { element } => (*element, 0),
+            _ => (type_id, 1),
+        };
+
+        let descriptor_type = match (variable.storage_class, self.types.get(&element_id)?) {
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::Sampler) => DescriptorType::Sampler,
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::SampledImage) => {
+                DescriptorType::CombinedImageSampler
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, Type::Image { dim, sampled }) => {
+                // Sampled is 1 for images used with a sampler and 2 for storage images
+                match (*dim, *sampled) {
+                    (DIM_SUBPASS_DATA, _) => DescriptorType::InputAttachment,
+                    (DIM_BUFFER, 2) => DescriptorType::StorageTexelBuffer,
+                    (DIM_BUFFER, _) => DescriptorType::UniformTexelBuffer,
+                    (_, 2) => DescriptorType::StorageImage,
+                    _ => DescriptorType::SampledImage,
+                }
+            }
+            // Before SPIR-V 1.3 storage buffers are Uniform blocks decorated with BufferBlock
+            (STORAGE_CLASS_UNIFORM, _)
+                if self
+                    .decorations
+                    .contains_key(&(element_id, DECORATION_BUFFER_BLOCK)) =>
+            {
+                DescriptorType::StorageBuffer
+            }
+            (STORAGE_CLASS_UNIFORM, _) => DescriptorType::UniformBuffer,
+            (STORAGE_CLASS_STORAGE_BUFFER, _) => DescriptorType::StorageBuffer,
+            _ => return None,
+        };
+
+        Some(DescriptorBinding {
+            set,
+            binding,
+            descriptor_type,
+            count,
+            name: self.name(variable.id),
+            type_name: self.type_name(element_id),
+        })
+    }
+
+    fn push_constant_range(&self, variable: &Variable) -> Result<PushConstantRange, ReflectError> {
+        let type_id = self.pointee(variable.type_id);
+
+        // Blocks that only use later members still start at their first member's offset
+        let (offset, size) = match self.types.get(&type_id) {
+            Some(Type::Struct { members }) => {
+                let offsets = (0..members.len() as u32).map(|member| {
+                    self.member_decorations
+                        .get(&(type_id, member, DECORATION_OFFSET))
+                        .copied()
+                        .unwrap_or(0)
+                });
+
+                let start = offsets.clone().min().unwrap_or(0);
+                let end = self.type_size(type_id, 0)?;
+                (start, end.saturating_sub(start))
+            }
+            _ => (0, self.type_size(type_id, 0)?),
+        };
+
+        // Push constant blocks are usually unnamed instances, so the block's type name is more useful
+        let name = match self.names.get(&variable.id) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => self.name(type_id),
+        };
+
+        Ok(PushConstantRange { name, offset, size })
+    }
+
+    // Size in bytes of a type laid out with explicit offsets and strides, as in buffer and push constant blocks
+    // depth is how many types enclose this one, and sizes wrap rather than overflow for nonsensical lengths
+    fn type_size(&self, type_id: u32, depth: u32) -> Result<u32, ReflectError> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(ReflectError::TypeTooDeep(type_id));
+        }
+
+        let size = match self.types.get(&type_id) {
+            Some(Type::Bool) => 4,
+            Some(Type::Int { width, .. }) | Some(Type::Float { width }) => width / 8,
+            Some(Type::Vector { component, count }) => {
+                self.type_size(*component, depth + 1)?.wrapping_mul(*count)
+            }
+            Some(Type::Matrix { column, count }) => {
+                self.type_size(*column, depth + 1)?.wrapping_mul(*count)
+            }
+            Some(Type::Array { element, length }) => {
+                let stride = match self.decorations.get(&(type_id, DECORATION_ARRAY_STRIDE)) {
+                    Some(stride) => *stride,
+                    None => self.type_size(*element, depth + 1)?,
+                };
+                stride.wrapping_mul(*length)
+            }
+            Some(Type::Struct { members }) => members
+                .iter()
+                .enumerate()
+                .map(|(member, member_type)| {
+                    let member = member as u32;
+                    let offset = self
+                        .member_decorations
+                        .get(&(type_id, member, DECORATION_OFFSET))
+                        .copied()
+                        .unwrap_or(0);
+
+                    // Matrix members carry their column stride on the struct rather than the type
+                    let size = match (
+                        self.types.get(member_type),
+                        self.member_decorations
+                            .get(&(type_id, member, DECORATION_MATRIX_STRIDE)),
+                    ) {
+                        (Some(Type::Matrix { count, .. }), Some(stride)) => {
+                            count.wrapping_mul(*stride)
+                        }
+                        _ => self.type_size(*member_type, depth + 1)?,
+                    };
+
+                    Ok(offset.wrapping_add(size))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        };
+
+        Ok(size)
+    }
+
+    // GLSL style name of a type, e.g. vec3, mat4, or the block name for structs
+    fn type_name(&self, type_id: u32) -> String {
+        self.nested_type_name(type_id, 0)
+    }
+
+    // type_name for a type enclosed by depth others, falling back to the bare id past MAX_TYPE_DEPTH as names are
+    // only for display
+    fn nested_type_name(&self, type_id: u32, depth: u32) -> String {
+        if depth > MAX_TYPE_DEPTH {
+            return format!("%{}", type_id);
+        }
+
+        match self.types.get(&type_id) {
+            Some(Type::Bool) => String::from("bool"),
+            Some(Type::Int { width: 32, signed }) => {
+                String::from(if *signed { "int" } else { "uint" })
+            }
+            Some(Type::Int { width, signed }) => {
+                format!("{}int{}", if *signed { "" } else { "u" }, width)
+            }
+            Some(Type::Float { width: 32 }) => String::from("float"),
+            Some(Type::Float { width: 64 }) => String::from("double"),
+            Some(Type::Float { width }) => format!("float{}", width),
+            Some(Type::Vector { component, count }) => {
+                let prefix = match self.types.get(component) {
+                    Some(Type::Bool) => "b",
+                    Some(Type::Int { signed: true, .. }) => "i",
+                    Some(Type::Int { signed: false, .. }) => "u",
+                    Some(Type::Float { width: 64 }) => "d",
+                    _ => "",
+                };
+                format!("{}vec{}", prefix, count)
+            }
+            Some(Type::Matrix { column, count }) => match self.types.get(column) {
+                Some(Type::Vector { count: rows, .. }) if rows == count => format!("mat{}", count),
+                Some(Type::Vector { count: rows, .. }) => format!("mat{}x{}", count, rows),
+                _ => format!("mat{}", count),
+            },
+            Some(Type::Image { .. }) => String::from("image"),
+            Some(Type::Sampler) => String::from("sampler"),
+            Some(Type::SampledImage) => String::from("sampled image"),
+            Some(Type::Array { element, length }) => {
+                format!("{}[{}]", self.nested_type_name(*element, depth + 1), length)
+            }
+            Some(Type::RuntimeArray { element }) => {
+                format!("{}[]", self.nested_type_name(*element, depth + 1))
+            }
+            Some(Type::Struct { .. }) => self.name(type_id),
+            Some(Type::Pointer { pointee }) => self.nested_type_name(*pointee, depth + 1),
+            None => format!("%{}", type_id),
+        }
+    }
+
+    fn pointee(&self, type_id: u32) -> u32 {
+        match self.types.get(&type_id) {
+            Some(Type::Pointer { pointee }) => *pointee,
+            _ => type_id,
+        }
+    }
+
+    // Debug name of an id, or its SPIR-V id if the module was stripped
+    fn name(&self, id: u32) -> String {
+        match self.names.get(&id) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => format!("%{}", id),
+        }
+    }
+}
+
+// Decodes a nul terminated UTF-8 literal packed into words
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|byte| *byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn built_in_name(built_in: u32) -> String {
+    let name = match built_in {
+        0 => "Position",
+        1 => "PointSize",
+        3 => "ClipDistance",
+        4 => "CullDistance",
+        5 => "VertexId",
+        6 => "InstanceId",
+        7 => "PrimitiveId",
+        9 => "Layer",
+        10 => "ViewportIndex",
+        15 => "FragCoord",
+        16 => "PointCoord",
+        17 => "FrontFacing",
+        18 => "SampleId",
+        19 => "SamplePosition",
+        20 => "SampleMask",
+        22 => "FragDepth",
+        24 => "NumWorkgroups",
+        25 => "WorkgroupSize",
+        26 => "WorkgroupId",
+        27 => "LocalInvocationId",
+        28 => "GlobalInvocationId",
+        29 => "LocalInvocationIndex",
+        42 => "VertexIndex",
+        43 => "InstanceIndex",
+        other => return format!("BuiltIn({})", other),
+    };
+
+    String::from(name)
+}
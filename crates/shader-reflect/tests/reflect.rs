@@ -0,0 +1,190 @@
+use shader_reflect::{
+    reflect, reflect_words, DescriptorBinding, DescriptorType, ReflectError, Stage,
+};
+use std::fs;
+
+const SHADER_DIR: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../hello-triangle/src/graphics/shaders"
+);
+
+fn read_shader(name: &str) -> Vec<u8> {
+    fs::read(format!("{}/{}", SHADER_DIR, name)).expect("Could not read the shader!")
+}
+
+#[test]
+fn reflects_vertex_shader_interface() {
+    let module = reflect(&read_shader("vertex.spv")).unwrap();
+
+    assert_eq!(module.entry_points.len(), 1);
+    let entry_point = &module.entry_points[0];
+    assert_eq!(entry_point.name, "main");
+    assert_eq!(entry_point.stage, Stage::Vertex);
+    assert_eq!(entry_point.local_size, None);
+
+    let inputs: Vec<_> = entry_point
+        .inputs
+        .iter()
+        .map(|input| {
+            (
+                input.name.as_str(),
+                input.location,
+                input.type_name.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        inputs,
+        vec![
+            ("inPosition", Some(0), "vec3"),
+            ("inColor", Some(1), "vec3"),
+            ("inTexCoord", Some(2), "vec2")
+        ]
+    );
+
+    let frag_color = &entry_point.outputs[0];
+    assert_eq!(frag_color.name, "fragColor");
+    assert_eq!(frag_color.location, Some(0));
+    assert_eq!(frag_color.type_name, "vec3");
+
+    let frag_tex_coord = &entry_point.outputs[1];
+    assert_eq!(frag_tex_coord.name, "fragTexCoord");
+    assert_eq!(frag_tex_coord.location, Some(1));
+    assert_eq!(frag_tex_coord.type_name, "vec2");
+
+    // gl_PerVertex is reported as a single block of built-ins
+    assert!(entry_point.outputs[2]
+        .built_ins
+        .contains(&String::from("Position")));
+
+    assert_eq!(
+        module.descriptor_bindings,
+        vec![DescriptorBinding {
+            set: 0,
+            binding: 0,
+            descriptor_type: DescriptorType::UniformBuffer,
+            count: 1,
+            name: String::from("ubo"),
+            type_name: String::from("UniformBufferObject"),
+        }]
+    );
+    assert!(module.push_constants.is_empty());
+}
+
+#[test]
+fn vertex_outputs_match_fragment_inputs() {
+    let vertex = reflect(&read_shader("vertex.spv")).unwrap();
+    let fragment = reflect(&read_shader("fragment.spv")).unwrap();
+
+    let outputs: Vec<_> = vertex.entry_points[0]
+        .outputs
+        .iter()
+        .filter(|output| output.location.is_some())
+        .map(|output| (output.location, output.type_name.clone()))
+        .collect();
+    let inputs: Vec<_> = fragment.entry_points[0]
+        .inputs
+        .iter()
+        .map(|input| (input.location, input.type_name.clone()))
+        .collect();
+
+    assert_eq!(fragment.entry_points[0].stage, Stage::Fragment);
+    assert_eq!(outputs, inputs);
+}
+
+#[test]
+fn reflects_fragment_shader_sampler() {
+    let module = reflect(&read_shader("fragment.spv")).unwrap();
+
+    assert_eq!(module.descriptor_bindings.len(), 1);
+    let sampler = &module.descriptor_bindings[0];
+    assert_eq!((sampler.set, sampler.binding), (0, 1));
+    assert_eq!(
+        sampler.descriptor_type,
+        DescriptorType::CombinedImageSampler
+    );
+    assert_eq!(sampler.name, "texSampler");
+}
+
+#[test]
+fn reads_big_endian_modules() {
+    let little_endian = read_shader("fragment.spv");
+    let big_endian: Vec<u8> = little_endian
+        .chunks_exact(4)
+        .flat_map(|word| vec![word[3], word[2], word[1], word[0]])
+        .collect();
+
+    assert_eq!(reflect(&big_endian), reflect(&little_endian));
+}
+
+#[test]
+fn rejects_invalid_modules() {
+    let mut shader = read_shader("fragment.spv");
+
+    assert_eq!(
+        reflect(&shader[..shader.len() - 2]),
+        Err(ReflectError::UnalignedLength(shader.len() - 2))
+    );
+    assert_eq!(reflect(&shader[..12]), Err(ReflectError::MissingHeader));
+
+    // Claims the final instruction is one word longer than what is left of the module
+    let mut truncated = shader.clone();
+    let last_instruction = last_instruction_word(&truncated);
+    let word_count_byte = last_instruction * 4 + 2;
+    truncated[word_count_byte] += 1;
+    assert_eq!(
+        reflect(&truncated),
+        Err(ReflectError::TruncatedInstruction(last_instruction))
+    );
+
+    shader[0] = 0;
+    assert_eq!(
+        reflect(&shader),
+        Err(ReflectError::BadMagicNumber(0x0723_0200))
+    );
+}
+
+#[test]
+fn rejects_types_containing_themselves() {
+    // A push constant block whose only member is the block itself, which no compiler emits but a corrupted module
+    // can contain
+    let words = vec![
+        0x0723_0203,
+        0x0001_0000,
+        0,
+        4,
+        0,
+        // OpTypeStruct %1 with member %1
+        (3 << 16) | 30,
+        1,
+        1,
+        // OpTypePointer %2 PushConstant %1
+        (4 << 16) | 32,
+        2,
+        9,
+        1,
+        // OpVariable %2 %3 PushConstant
+        (4 << 16) | 59,
+        2,
+        3,
+        9,
+    ];
+
+    assert_eq!(reflect_words(words), Err(ReflectError::TypeTooDeep(1)));
+}
+
+// Word offset of the final instruction in a module
+fn last_instruction_word(shader: &[u8]) -> usize {
+    let words: Vec<u32> = shader
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+
+    let mut index = 5;
+    let mut last = index;
+    while index < words.len() {
+        last = index;
+        index += (words[index] >> 16) as usize;
+    }
+    last
+}
@@ -0,0 +1,117 @@
+use shader_reflect::Module;
+use std::{env, fs, process};
+
+// Prints the interface of every SPIR-V file given on the command line
+pub fn run() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("Usage: spv-inspect <file.spv>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+
+    for path in &paths {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+
+        match shader_reflect::reflect(&bytes) {
+            Ok(module) => print_module(path, &module),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn print_module(path: &str, module: &Module) {
+    println!(
+        "{} (SPIR-V {}.{})",
+        path, module.version.0, module.version.1
+    );
+
+    for entry_point in &module.entry_points {
+        println!();
+        println!(
+            "  Entry point {} ({:?})",
+            entry_point.name, entry_point.stage
+        );
+
+        if let Some([x, y, z]) = entry_point.local_size {
+            println!("    Local size: {}x{}x{}", x, y, z);
+        }
+
+        for (label, variables) in &[
+            ("Inputs", &entry_point.inputs),
+            ("Outputs", &entry_point.outputs),
+        ] {
+            if variables.is_empty() {
+                continue;
+            }
+
+            println!("    {}:", label);
+            for variable in variables.iter() {
+                let slot = match variable.location {
+                    Some(location) => format!("location {}", location),
+                    None => format!("built-in {}", variable.built_ins.join(", ")),
+                };
+                println!(
+                    "      {:<28} {:<10} {}",
+                    slot, variable.type_name, variable.name
+                );
+            }
+        }
+    }
+
+    if !module.descriptor_bindings.is_empty() {
+        println!();
+        println!("  Descriptor bindings:");
+        println!(
+            "    {:<4} {:<8} {:<22} {:<6} Name",
+            "Set", "Binding", "Type", "Count"
+        );
+
+        for binding in &module.descriptor_bindings {
+            let count = if binding.count == 0 {
+                String::from("[]")
+            } else {
+                binding.count.to_string()
+            };
+
+            println!(
+                "    {:<4} {:<8} {:<22} {:<6} {} ({})",
+                binding.set,
+                binding.binding,
+                format!("{:?}", binding.descriptor_type),
+                count,
+                binding.name,
+                binding.type_name
+            );
+        }
+    }
+
+    if !module.push_constants.is_empty() {
+        println!();
+        println!("  Push constants:");
+        for range in &module.push_constants {
+            println!(
+                "    {} - offset {}, size {} bytes",
+                range.name, range.offset, range.size
+            );
+        }
+    }
+
+    println!();
+}
@@ -0,0 +1,3 @@
+fn main() {
+    spv_inspect::run();
+}